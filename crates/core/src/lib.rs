@@ -1,2 +1,4 @@
+pub mod nvd;
+pub mod search;
 pub mod task;
 pub mod vuln_db;
@@ -0,0 +1,580 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// One CVSS base metric as published by NVD or a CNA.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CvssMetric {
+    /// `2.0`, `3.0`, `3.1` or `4.0`.
+    pub version: String,
+    pub vector: String,
+    pub base_score: f64,
+    /// `CRITICAL`, `HIGH`, `MEDIUM`, `LOW` or `NONE`.
+    pub base_severity: Option<String>,
+    /// Who scored it, e.g. `nvd@nist.gov`.
+    pub source: Option<String>,
+    /// NVD's own score rather than a secondary one from a CNA.
+    pub primary: bool,
+}
+
+/// The fields of a CPE 2.3 name that matching uses. `*` is ANY and `-` is
+/// NA, as in the formatted string.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cpe {
+    /// `a` (application), `o` (operating system) or `h` (hardware).
+    pub part: String,
+    pub vendor: String,
+    pub product: String,
+    pub version: String,
+    pub update: String,
+}
+
+impl Cpe {
+    /// Parses a formatted string such as
+    /// `cpe:2.3:a:busybox:busybox:1.24.1:*:*:*:*:*:*:*`. Escaped colons
+    /// (`\:`) stay part of their field; the names are lowercased.
+    pub fn parse(name: &str) -> Option<Self> {
+        let rest = name.strip_prefix("cpe:2.3:")?;
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => field.extend(chars.next()),
+                ':' => fields.push(std::mem::take(&mut field)),
+                c => field.push(c),
+            }
+        }
+        fields.push(field);
+        let mut fields = fields.into_iter().map(|f| f.to_lowercase());
+        let part = fields.next()?;
+        let vendor = fields.next()?;
+        let product = fields.next()?;
+        Some(Self {
+            part,
+            vendor,
+            product,
+            version: fields.next().unwrap_or_else(|| "*".to_string()),
+            update: fields.next().unwrap_or_else(|| "*".to_string()),
+        })
+    }
+}
+
+/// One vulnerable CPE range from a CVE's configurations.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CpeMatch {
+    pub cve: String,
+    pub criteria: Cpe,
+    pub version_start_including: Option<String>,
+    pub version_start_excluding: Option<String>,
+    pub version_end_including: Option<String>,
+    pub version_end_excluding: Option<String>,
+}
+
+impl CpeMatch {
+    /// Whether `version` of this product falls in the range. A criteria
+    /// with a concrete version matches only that version; ANY uses the
+    /// start and end bounds, and with none of them covers every version.
+    pub fn contains(&self, version: &str) -> bool {
+        let version = version.trim();
+        match self.criteria.version.as_str() {
+            "-" => false,
+            "*" | "" => {
+                let bound = |b: &Option<String>, ok: fn(Ordering) -> bool| {
+                    b.as_deref()
+                        .is_none_or(|b| ok(compare_versions(version, b)))
+                };
+                bound(&self.version_start_including, |o| o != Ordering::Less)
+                    && bound(&self.version_start_excluding, |o| o == Ordering::Greater)
+                    && bound(&self.version_end_including, |o| o != Ordering::Greater)
+                    && bound(&self.version_end_excluding, |o| o == Ordering::Less)
+            }
+            exact => {
+                let exact = match self.criteria.update.as_str() {
+                    "*" | "-" | "" => exact.to_string(),
+                    update => format!("{}{}", exact, update),
+                };
+                compare_versions(version, &exact) == Ordering::Equal
+            }
+        }
+    }
+}
+
+/// A CVE as read from a feed, before it is merged into the database.
+#[derive(Debug, Clone)]
+pub struct NvdCve {
+    pub id: String,
+    pub description: String,
+    pub published: Option<String>,
+    pub last_modified: Option<String>,
+    /// NVD marks withdrawn CVEs `Rejected`; importing one removes it.
+    pub rejected: bool,
+    pub cwes: Vec<String>,
+    pub cvss: Vec<CvssMetric>,
+    pub references: Vec<String>,
+    /// A reference is tagged `Exploit` or CISA lists it as exploited.
+    pub exploit_available: bool,
+    pub matches: Vec<CpeMatch>,
+}
+
+impl NvdCve {
+    /// The metric severity is taken from, see `preferred_metric`.
+    pub fn preferred_metric(&self) -> Option<&CvssMetric> {
+        preferred_metric(&self.cvss)
+    }
+
+    /// `vendor product` of every vulnerable CPE, without duplicates.
+    pub fn affected_products(&self) -> Vec<String> {
+        let mut products: Vec<String> = Vec::new();
+        for m in &self.matches {
+            let product = format!("{} {}", m.criteria.vendor, m.criteria.product);
+            if !products.contains(&product) {
+                products.push(product);
+            }
+        }
+        products
+    }
+}
+
+/// Any v3 or v4 score before a v2 one, then NVD's own score before a CNA's,
+/// then the newest version: 4.0 before 3.1 before 3.0.
+pub fn preferred_metric(metrics: &[CvssMetric]) -> Option<&CvssMetric> {
+    metrics.iter().max_by_key(|m| {
+        (
+            compare_versions(&m.version, "3.0") != Ordering::Less,
+            m.primary,
+            &m.version,
+        )
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct FeedFile {
+    format: Option<String>,
+    version: Option<String>,
+    #[serde(default)]
+    vulnerabilities: Vec<FeedItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FeedItem {
+    cve: CveItem,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CveItem {
+    id: String,
+    published: Option<String>,
+    last_modified: Option<String>,
+    vuln_status: Option<String>,
+    #[serde(default)]
+    descriptions: Vec<LangString>,
+    #[serde(default)]
+    metrics: Metrics,
+    #[serde(default)]
+    weaknesses: Vec<Weakness>,
+    #[serde(default)]
+    configurations: Vec<Configuration>,
+    #[serde(default)]
+    references: Vec<Reference>,
+    cisa_exploit_add: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LangString {
+    lang: String,
+    value: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Metrics {
+    #[serde(rename = "cvssMetricV40", default)]
+    v40: Vec<MetricItem>,
+    #[serde(rename = "cvssMetricV31", default)]
+    v31: Vec<MetricItem>,
+    #[serde(rename = "cvssMetricV30", default)]
+    v30: Vec<MetricItem>,
+    /// Only used when a CVE was never scored with v3 or later.
+    #[serde(rename = "cvssMetricV2", default)]
+    v2: Vec<MetricItem>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MetricItem {
+    source: Option<String>,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    /// v2 metrics carry the severity here rather than in `cvss_data`.
+    base_severity: Option<String>,
+    cvss_data: CvssData,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CvssData {
+    version: String,
+    vector_string: String,
+    base_score: f64,
+    base_severity: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Weakness {
+    #[serde(default)]
+    description: Vec<LangString>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Configuration {
+    #[serde(default)]
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Node {
+    #[serde(default)]
+    negate: bool,
+    #[serde(default)]
+    cpe_match: Vec<CpeMatchDef>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CpeMatchDef {
+    vulnerable: bool,
+    criteria: String,
+    version_start_including: Option<String>,
+    version_start_excluding: Option<String>,
+    version_end_including: Option<String>,
+    version_end_excluding: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Reference {
+    url: String,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+/// Reads an NVD 2.0 CVE feed (`nvdcve-2.0-*.json`, optionally gzipped) or
+/// a saved CVE API 2.0 response, which has the same layout.
+pub fn read_feed(path: &Path) -> Result<Vec<NvdCve>> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("failed to read NVD feed {}", path.display()))?;
+    let is_gzip = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gz"));
+    let reader: Box<dyn Read> = if is_gzip {
+        Box::new(flate2::read::GzDecoder::new(BufReader::new(file)))
+    } else {
+        Box::new(BufReader::new(file))
+    };
+    parse_feed(reader).with_context(|| format!("invalid NVD feed {}", path.display()))
+}
+
+pub fn parse_feed(reader: impl Read) -> Result<Vec<NvdCve>> {
+    let feed: FeedFile = serde_json::from_reader(reader)?;
+    if let Some(format) = &feed.format {
+        if format != "NVD_CVE" {
+            bail!("feed format is {}, expected NVD_CVE", format);
+        }
+    }
+    if let Some(version) = &feed.version {
+        if !version.starts_with("2.") {
+            bail!(
+                "feed is NVD schema {}, only 2.x feeds are supported",
+                version
+            );
+        }
+    }
+    Ok(feed
+        .vulnerabilities
+        .into_iter()
+        .map(|i| convert(i.cve))
+        .collect())
+}
+
+fn convert(cve: CveItem) -> NvdCve {
+    let english = |strings: &[LangString]| {
+        strings
+            .iter()
+            .find(|s| s.lang == "en")
+            .or_else(|| strings.first())
+            .map(|s| s.value.trim().to_string())
+    };
+    let mut cwes: Vec<String> = Vec::new();
+    for weakness in &cve.weaknesses {
+        for d in &weakness.description {
+            if d.value.starts_with("CWE-") && !cwes.contains(&d.value) {
+                cwes.push(d.value.clone());
+            }
+        }
+    }
+    let cvss = cve
+        .metrics
+        .v40
+        .iter()
+        .chain(&cve.metrics.v31)
+        .chain(&cve.metrics.v30)
+        .chain(&cve.metrics.v2)
+        .map(|m| CvssMetric {
+            version: m.cvss_data.version.clone(),
+            vector: m.cvss_data.vector_string.clone(),
+            base_score: m.cvss_data.base_score,
+            base_severity: m
+                .cvss_data
+                .base_severity
+                .clone()
+                .or_else(|| m.base_severity.clone()),
+            source: m.source.clone(),
+            primary: m.kind.as_deref() == Some("Primary"),
+        })
+        .collect();
+    // Only vulnerable CPEs are indexed. The platform half of an AND
+    // configuration (e.g. the board a firmware runs on) is not required
+    // for a match.
+    let matches = cve
+        .configurations
+        .iter()
+        .flat_map(|c| &c.nodes)
+        .filter(|n| !n.negate)
+        .flat_map(|n| &n.cpe_match)
+        .filter(|m| m.vulnerable)
+        .filter_map(|m| {
+            Some(CpeMatch {
+                cve: cve.id.clone(),
+                criteria: Cpe::parse(&m.criteria)?,
+                version_start_including: m.version_start_including.clone(),
+                version_start_excluding: m.version_start_excluding.clone(),
+                version_end_including: m.version_end_including.clone(),
+                version_end_excluding: m.version_end_excluding.clone(),
+            })
+        })
+        .collect();
+
+    NvdCve {
+        description: english(&cve.descriptions).unwrap_or_default(),
+        published: cve.published,
+        last_modified: cve.last_modified,
+        rejected: cve.vuln_status.as_deref() == Some("Rejected"),
+        cwes,
+        cvss,
+        exploit_available: cve.cisa_exploit_add.is_some()
+            || cve
+                .references
+                .iter()
+                .any(|r| r.tags.iter().any(|t| t == "Exploit")),
+        references: cve.references.into_iter().map(|r| r.url).collect(),
+        matches,
+        id: cve.id,
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Segment<'a> {
+    Alpha(&'a str),
+    Number(u64),
+}
+
+fn segments(version: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut rest = version;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_alphanumeric()) {
+        rest = &rest[start..];
+        let digits = rest.starts_with(|c: char| c.is_ascii_digit());
+        let end = rest
+            .find(|c: char| !c.is_ascii_alphanumeric() || c.is_ascii_digit() != digits)
+            .unwrap_or(rest.len());
+        let (token, tail) = rest.split_at(end);
+        segments.push(if digits {
+            Segment::Number(token.parse().unwrap_or(u64::MAX))
+        } else {
+            Segment::Alpha(token)
+        });
+        rest = tail;
+    }
+    segments
+}
+
+impl Segment<'_> {
+    fn is_pre_release(&self) -> bool {
+        matches!(self, Segment::Alpha(tag) if PRE_RELEASE_TAGS
+            .iter()
+            .any(|t| tag.eq_ignore_ascii_case(t)))
+    }
+}
+
+const PRE_RELEASE_TAGS: &[&str] = &["alpha", "beta", "rc", "pre", "dev"];
+
+/// Orders versions segment by segment: `1.0.9 < 1.0.10`, and OpenSSL
+/// letter releases `1.0.1 < 1.0.1f < 1.0.1zd`. A missing segment counts as
+/// 0 against a number, so `4.8` equals `4.8.0`. Against a letter it is
+/// newer if that is a pre-release tag (`4.5.0-beta2 < 4.5.0-rc1 < 4.5.0`)
+/// and older otherwise.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (a, b) = (segments(a), segments(b));
+    for i in 0..a.len().max(b.len()) {
+        let ordering = match (a.get(i), b.get(i)) {
+            (Some(x), Some(y)) => x.cmp(y),
+            (Some(Segment::Number(n)), None) => n.cmp(&0),
+            (None, Some(Segment::Number(n))) => 0.cmp(n),
+            (Some(x @ Segment::Alpha(_)), None) if x.is_pre_release() => Ordering::Less,
+            (None, Some(y @ Segment::Alpha(_))) if y.is_pre_release() => Ordering::Greater,
+            (Some(Segment::Alpha(_)), None) => Ordering::Greater,
+            (None, Some(Segment::Alpha(_))) => Ordering::Less,
+            (None, None) => Ordering::Equal,
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vuln_db::{VulnSeverity, VulnerabilityDatabase};
+
+    const FEED: &str = r#"{
+        "format": "NVD_CVE",
+        "version": "2.0",
+        "vulnerabilities": [{
+            "cve": {
+                "id": "CVE-2018-1000517",
+                "descriptions": [{"lang": "en", "value": "BusyBox wget contains a buffer overflow."}],
+                "metrics": {
+                    "cvssMetricV30": [{
+                        "source": "nvd@nist.gov",
+                        "type": "Primary",
+                        "cvssData": {
+                            "version": "3.0",
+                            "vectorString": "CVSS:3.0/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H",
+                            "baseScore": 9.8,
+                            "baseSeverity": "CRITICAL"
+                        }
+                    }],
+                    "cvssMetricV2": [{
+                        "source": "nvd@nist.gov",
+                        "type": "Primary",
+                        "baseSeverity": "HIGH",
+                        "cvssData": {
+                            "version": "2.0",
+                            "vectorString": "AV:N/AC:L/Au:N/C:P/I:P/A:P",
+                            "baseScore": 7.5
+                        }
+                    }]
+                },
+                "configurations": [{"nodes": [{"cpeMatch": [{
+                    "vulnerable": true,
+                    "criteria": "cpe:2.3:a:busybox:busybox:*:*:*:*:*:*:*:*",
+                    "versionEndExcluding": "1.29.0"
+                }]}]}]
+            }
+        }, {
+            "cve": {
+                "id": "CVE-2011-5325",
+                "descriptions": [{"lang": "en", "value": "BusyBox tar directory traversal."}],
+                "metrics": {
+                    "cvssMetricV2": [{
+                        "source": "nvd@nist.gov",
+                        "type": "Primary",
+                        "baseSeverity": "MEDIUM",
+                        "cvssData": {
+                            "version": "2.0",
+                            "vectorString": "AV:N/AC:L/Au:N/C:N/I:P/A:N",
+                            "baseScore": 5.0
+                        }
+                    }]
+                }
+            }
+        }]
+    }"#;
+
+    #[test]
+    fn v3_0_metrics_take_precedence_over_v2() {
+        let cves = parse_feed(FEED.as_bytes()).unwrap();
+        let metric = cves[0].preferred_metric().unwrap();
+        assert_eq!(metric.version, "3.0");
+        assert_eq!(metric.base_score, 9.8);
+
+        let v2_only = cves[1].preferred_metric().unwrap();
+        assert_eq!(v2_only.version, "2.0");
+        assert_eq!(v2_only.base_severity.as_deref(), Some("MEDIUM"));
+
+        let mut db = VulnerabilityDatabase::new();
+        db.import_nvd_cves(cves);
+        let entry = db.get_vulnerability("CVE-2018-1000517").unwrap();
+        assert_eq!(entry.severity, VulnSeverity::Critical);
+        assert_eq!(entry.cvss.len(), 2);
+        let entry = db.get_vulnerability("CVE-2011-5325").unwrap();
+        assert_eq!(entry.severity, VulnSeverity::Medium);
+    }
+
+    #[test]
+    fn newest_cvss_version_is_preferred() {
+        let metric = |version: &str, primary| CvssMetric {
+            version: version.to_string(),
+            vector: String::new(),
+            base_score: 0.0,
+            base_severity: None,
+            source: None,
+            primary,
+        };
+        let metrics = [
+            metric("2.0", true),
+            metric("3.0", false),
+            metric("3.1", false),
+        ];
+        assert_eq!(preferred_metric(&metrics).unwrap().version, "3.1");
+        let metrics = [
+            metric("4.0", false),
+            metric("3.1", true),
+            metric("3.0", true),
+        ];
+        assert_eq!(preferred_metric(&metrics).unwrap().version, "3.1");
+    }
+
+    #[test]
+    fn pre_releases_sort_before_the_release() {
+        let ordered = [
+            "1.0.1",
+            "1.0.1f",
+            "1.0.9",
+            "1.0.10",
+            "4.5.0-alpha",
+            "4.5.0-beta2",
+            "4.5.0-rc1",
+            "4.5.0",
+            "4.5.0.1",
+            "4.5.1-pre",
+            "4.5.1",
+        ];
+        for pair in ordered.windows(2) {
+            assert_eq!(
+                compare_versions(pair[0], pair[1]),
+                Ordering::Less,
+                "{pair:?}"
+            );
+            assert_eq!(
+                compare_versions(pair[1], pair[0]),
+                Ordering::Greater,
+                "{pair:?}"
+            );
+        }
+        assert_eq!(compare_versions("4.8", "4.8.0"), Ordering::Equal);
+        assert_eq!(compare_versions("4.5.0-rc1", "4.5.0rc1"), Ordering::Equal);
+
+        let mut db = VulnerabilityDatabase::new();
+        db.import_nvd_cves(parse_feed(FEED.as_bytes()).unwrap());
+        let found = |version| db.lookup("busybox", version).len();
+        assert_eq!(found("1.29.0-rc1"), 1);
+        assert_eq!(found("1.28.4"), 1);
+        assert_eq!(found("1.29.0"), 0);
+    }
+}
@@ -0,0 +1,784 @@
+use crate::vuln_db::{VulnSeverity, Vulnerability};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+
+/// BM25 term-frequency saturation and length normalisation.
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// Searchable parts of an entry. A query term without a field prefix
+/// searches all of them, with a match in a shorter, more specific field
+/// ranking higher.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+    /// Entry id, CVE and CWE.
+    Id,
+    Name,
+    Description,
+    Affected,
+    Tags,
+}
+
+const FIELDS: [Field; 5] = [
+    Field::Id,
+    Field::Name,
+    Field::Description,
+    Field::Affected,
+    Field::Tags,
+];
+
+impl Field {
+    /// The prefix used in queries, e.g. `affected:px4`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "id" | "cve" | "cwe" => Some(Self::Id),
+            "name" | "title" => Some(Self::Name),
+            "description" | "desc" => Some(Self::Description),
+            "affected" | "system" | "product" => Some(Self::Affected),
+            "tag" | "tags" => Some(Self::Tags),
+            _ => None,
+        }
+    }
+
+    fn weight(self) -> f64 {
+        match self {
+            Self::Id => 4.0,
+            Self::Name => 3.0,
+            Self::Tags => 2.5,
+            Self::Affected => 2.0,
+            Self::Description => 1.0,
+        }
+    }
+
+    fn slot(self) -> usize {
+        self as usize
+    }
+}
+
+/// What to search for and how to narrow it down.
+#[derive(Debug, Clone, Default)]
+pub struct SearchQuery {
+    /// Terms with `AND` (also implied between terms), `OR`, `NOT` or a
+    /// leading `-`, parentheses, `"quoted phrases"`, `prefix*` and field
+    /// prefixes such as `cve:`, `name:`, `affected:` and `tag:`. Empty
+    /// matches everything.
+    pub text: String,
+    /// Keep only these severities; empty keeps all.
+    pub severities: Vec<VulnSeverity>,
+    pub exploit_available: Option<bool>,
+    /// Keep only entries affecting this vendor, case-insensitively.
+    pub vendor: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl SearchQuery {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_severity(mut self, severity: VulnSeverity) -> Self {
+        self.severities.push(severity);
+        self
+    }
+
+    pub fn with_exploit_available(mut self, exploit_available: bool) -> Self {
+        self.exploit_available = Some(exploit_available);
+        self
+    }
+
+    pub fn with_vendor(mut self, vendor: impl Into<String>) -> Self {
+        self.vendor = Some(vendor.into());
+        self
+    }
+
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchHit<'a> {
+    pub vulnerability: &'a Vulnerability,
+    pub score: f64,
+}
+
+/// Counts over every entry the text matched, before the severity, exploit
+/// and vendor filters, so the other values of a facet stay visible.
+#[derive(Debug, Clone, Default)]
+pub struct Facets {
+    pub severity: BTreeMap<VulnSeverity, usize>,
+    pub exploit_available: usize,
+    pub no_exploit: usize,
+    /// Most common first.
+    pub vendors: Vec<(String, usize)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResults<'a> {
+    /// Best match first, after filters and `limit`.
+    pub hits: Vec<SearchHit<'a>>,
+    /// Matches after filters, before `limit`.
+    pub total: usize,
+    pub facets: Facets,
+}
+
+#[derive(Debug)]
+struct Posting {
+    doc: u32,
+    field: Field,
+    positions: Vec<u32>,
+}
+
+/// An inverted index over the entries of a `VulnerabilityDatabase`.
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    ids: Vec<String>,
+    severities: Vec<VulnSeverity>,
+    exploits: Vec<bool>,
+    vendors: Vec<Vec<String>>,
+    /// Token count of each field of each entry.
+    lengths: Vec<[u32; 5]>,
+    average_lengths: [f64; 5],
+    /// Postings sorted by entry, so a term's entry count is cheap.
+    terms: BTreeMap<String, Vec<Posting>>,
+}
+
+impl SearchIndex {
+    /// Indexes `entries` with the vendors each one affects.
+    pub fn build<'a>(entries: impl IntoIterator<Item = (&'a Vulnerability, Vec<String>)>) -> Self {
+        let mut index = Self::default();
+        for (vuln, vendors) in entries {
+            let doc = index.ids.len() as u32;
+            let mut lengths = [0u32; 5];
+            for field in FIELDS {
+                let tokens = field_tokens(vuln, field);
+                lengths[field.slot()] = tokens.len() as u32;
+                let mut positions: HashMap<String, Vec<u32>> = HashMap::new();
+                for (position, token) in tokens.into_iter().enumerate() {
+                    positions.entry(token).or_default().push(position as u32);
+                }
+                for (token, positions) in positions {
+                    index.terms.entry(token).or_default().push(Posting {
+                        doc,
+                        field,
+                        positions,
+                    });
+                }
+            }
+            index.ids.push(vuln.id.clone());
+            index.severities.push(vuln.severity.clone());
+            index.exploits.push(vuln.exploit_available);
+            index
+                .vendors
+                .push(vendors.into_iter().map(|v| v.to_lowercase()).collect());
+            index.lengths.push(lengths);
+        }
+        let count = index.ids.len().max(1) as f64;
+        for field in FIELDS {
+            let total: u64 = index
+                .lengths
+                .iter()
+                .map(|l| u64::from(l[field.slot()]))
+                .sum();
+            index.average_lengths[field.slot()] = (total as f64 / count).max(1.0);
+        }
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Ids of the matching entries with their scores, filtered and ranked,
+    /// plus facet counts over the unfiltered matches.
+    pub fn search(&self, query: &SearchQuery) -> (Vec<(&str, f64)>, usize, Facets) {
+        let matched = match parse(&query.text) {
+            Some(expr) => self.eval(&expr),
+            None => self.all(),
+        };
+
+        let mut facets = Facets::default();
+        let mut vendor_counts: HashMap<&str, usize> = HashMap::new();
+        for &doc in matched.keys() {
+            let doc = doc as usize;
+            *facets
+                .severity
+                .entry(self.severities[doc].clone())
+                .or_default() += 1;
+            if self.exploits[doc] {
+                facets.exploit_available += 1;
+            } else {
+                facets.no_exploit += 1;
+            }
+            for vendor in &self.vendors[doc] {
+                *vendor_counts.entry(vendor).or_default() += 1;
+            }
+        }
+        facets.vendors = vendor_counts
+            .into_iter()
+            .map(|(v, n)| (v.to_string(), n))
+            .collect();
+        facets
+            .vendors
+            .sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let vendor = query.vendor.as_ref().map(|v| v.to_lowercase());
+        let mut hits: Vec<(u32, f64)> = matched
+            .into_iter()
+            .filter(|(doc, _)| {
+                let doc = *doc as usize;
+                (query.severities.is_empty() || query.severities.contains(&self.severities[doc]))
+                    && query
+                        .exploit_available
+                        .is_none_or(|e| self.exploits[doc] == e)
+                    && vendor
+                        .as_ref()
+                        .is_none_or(|v| self.vendors[doc].contains(v))
+            })
+            .collect();
+        hits.sort_by(|(a, sa), (b, sb)| {
+            sb.total_cmp(sa)
+                .then_with(|| self.severities[*b as usize].cmp(&self.severities[*a as usize]))
+                .then_with(|| self.ids[*a as usize].cmp(&self.ids[*b as usize]))
+        });
+        let total = hits.len();
+        hits.truncate(query.limit.unwrap_or(usize::MAX));
+        let hits = hits
+            .into_iter()
+            .map(|(doc, score)| (self.ids[doc as usize].as_str(), score))
+            .collect();
+        (hits, total, facets)
+    }
+
+    fn all(&self) -> HashMap<u32, f64> {
+        (0..self.ids.len() as u32).map(|doc| (doc, 0.0)).collect()
+    }
+
+    fn eval(&self, expr: &Expr) -> HashMap<u32, f64> {
+        match expr {
+            Expr::Term {
+                field,
+                text,
+                prefix,
+            } => {
+                let mut scores = HashMap::new();
+                let postings: Vec<&Vec<Posting>> = if *prefix {
+                    self.terms
+                        .range::<str, _>((Bound::Included(text.as_str()), Bound::Unbounded))
+                        .take_while(|(term, _)| term.starts_with(text.as_str()))
+                        .map(|(_, postings)| postings)
+                        .collect()
+                } else {
+                    self.terms.get(text).into_iter().collect()
+                };
+                for postings in postings {
+                    let idf = self.idf(postings);
+                    for posting in postings {
+                        if field.is_none_or(|f| f == posting.field) {
+                            *scores.entry(posting.doc).or_default() +=
+                                self.bm25(posting, posting.positions.len(), idf);
+                        }
+                    }
+                }
+                scores
+            }
+            Expr::Phrase { field, terms } => self.phrase(*field, terms),
+            Expr::And(parts) => {
+                let (negative, positive): (Vec<&Expr>, Vec<&Expr>) =
+                    parts.iter().partition(|p| matches!(p, Expr::Not(_)));
+                let mut scores = match positive.split_first() {
+                    Some((first, rest)) => {
+                        let mut scores = self.eval(first);
+                        for part in rest {
+                            let other = self.eval(part);
+                            scores.retain(|doc, _| other.contains_key(doc));
+                            for (doc, score) in scores.iter_mut() {
+                                *score += other[doc];
+                            }
+                        }
+                        scores
+                    }
+                    None => self.all(),
+                };
+                for part in negative {
+                    if let Expr::Not(inner) = part {
+                        let excluded = self.eval(inner);
+                        scores.retain(|doc, _| !excluded.contains_key(doc));
+                    }
+                }
+                scores
+            }
+            Expr::Or(parts) => {
+                let mut scores: HashMap<u32, f64> = HashMap::new();
+                for part in parts {
+                    for (doc, score) in self.eval(part) {
+                        *scores.entry(doc).or_default() += score;
+                    }
+                }
+                scores
+            }
+            Expr::Not(inner) => {
+                let excluded = self.eval(inner);
+                let mut scores = self.all();
+                scores.retain(|doc, _| !excluded.contains_key(doc));
+                scores
+            }
+        }
+    }
+
+    /// Entries where `terms` appear next to each other in one field.
+    fn phrase(&self, field: Option<Field>, terms: &[String]) -> HashMap<u32, f64> {
+        let mut scores = HashMap::new();
+        let lists: Option<Vec<&Vec<Posting>>> = terms.iter().map(|t| self.terms.get(t)).collect();
+        let Some(lists) = lists else {
+            return scores;
+        };
+        let idfs: Vec<f64> = lists.iter().map(|p| self.idf(p)).collect();
+        let by_doc: Vec<HashMap<(u32, Field), &Posting>> = lists
+            .iter()
+            .map(|postings| postings.iter().map(|p| ((p.doc, p.field), p)).collect())
+            .collect();
+        for first in lists[0] {
+            if field.is_some_and(|f| f != first.field) {
+                continue;
+            }
+            let key = (first.doc, first.field);
+            let Some(rest) = by_doc[1..]
+                .iter()
+                .map(|m| m.get(&key).copied())
+                .collect::<Option<Vec<&Posting>>>()
+            else {
+                continue;
+            };
+            let occurrences = first
+                .positions
+                .iter()
+                .filter(|&&start| {
+                    rest.iter()
+                        .enumerate()
+                        .all(|(i, p)| p.positions.contains(&(start + i as u32 + 1)))
+                })
+                .count();
+            if occurrences > 0 {
+                let score: f64 = idfs
+                    .iter()
+                    .map(|idf| self.bm25(first, occurrences, *idf))
+                    .sum();
+                *scores.entry(first.doc).or_default() += score;
+            }
+        }
+        scores
+    }
+
+    fn idf(&self, postings: &[Posting]) -> f64 {
+        let mut docs = postings.iter().map(|p| p.doc).collect::<Vec<_>>();
+        docs.dedup();
+        let n = self.ids.len() as f64;
+        let df = docs.len() as f64;
+        (1.0 + (n - df + 0.5) / (df + 0.5)).ln()
+    }
+
+    fn bm25(&self, posting: &Posting, frequency: usize, idf: f64) -> f64 {
+        let slot = posting.field.slot();
+        let length = f64::from(self.lengths[posting.doc as usize][slot]);
+        let tf = frequency as f64;
+        let norm = K1 * (1.0 - B + B * length / self.average_lengths[slot]);
+        posting.field.weight() * idf * tf * (K1 + 1.0) / (tf + norm)
+    }
+}
+
+fn field_tokens(vuln: &Vulnerability, field: Field) -> Vec<String> {
+    let join = |parts: &[&str]| tokenize(&parts.join(" "));
+    match field {
+        Field::Id => {
+            let mut parts = vec![vuln.id.as_str()];
+            parts.extend(vuln.cve.as_deref().filter(|c| *c != vuln.id));
+            parts.extend(vuln.cwe.as_deref());
+            join(&parts)
+        }
+        Field::Name => tokenize(&vuln.name),
+        Field::Description => tokenize(&vuln.description),
+        Field::Affected => join(
+            &vuln
+                .affected_systems
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>(),
+        ),
+        Field::Tags => join(&vuln.tags.iter().map(String::as_str).collect::<Vec<_>>()),
+    }
+}
+
+/// Lowercase alphanumeric runs; `CVE-2014-0160` becomes `cve`, `2014`,
+/// `0160`, and `px4_drone_autopilot` becomes `px4`, `drone`, `autopilot`.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Term {
+        field: Option<Field>,
+        text: String,
+        prefix: bool,
+    },
+    Phrase {
+        field: Option<Field>,
+        terms: Vec<String>,
+    },
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Word {
+        field: Option<Field>,
+        text: String,
+        quoted: bool,
+    },
+}
+
+fn lex(query: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    let quoted = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+        chars.next();
+        let mut text = String::new();
+        for c in chars.by_ref() {
+            if c == '"' {
+                break;
+            }
+            text.push(c);
+        }
+        text
+    };
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => tokens.push(Token::Word {
+                field: None,
+                text: quoted(&mut chars),
+                quoted: true,
+            }),
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ')' | '"') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                if let Some(rest) = word.strip_prefix('-').filter(|r| !r.is_empty()) {
+                    tokens.push(Token::Not);
+                    word = rest.to_string();
+                }
+                match word.as_str() {
+                    "AND" | "&&" => tokens.push(Token::And),
+                    "OR" | "||" => tokens.push(Token::Or),
+                    "NOT" => tokens.push(Token::Not),
+                    _ => {
+                        let field = word
+                            .split_once(':')
+                            .and_then(|(name, _)| Field::parse(name));
+                        let text = match field {
+                            Some(_) => word.split_once(':').map_or("", |(_, t)| t).to_string(),
+                            None => word,
+                        };
+                        if text.is_empty() && chars.peek() == Some(&'"') {
+                            tokens.push(Token::Word {
+                                field,
+                                text: quoted(&mut chars),
+                                quoted: true,
+                            });
+                        } else {
+                            tokens.push(Token::Word {
+                                field,
+                                text,
+                                quoted: false,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+    tokens
+}
+
+/// Parses a query. Misplaced operators and unbalanced parentheses are
+/// ignored rather than rejected, since queries are typed interactively.
+/// `None` if the query has no terms.
+fn parse(query: &str) -> Option<Expr> {
+    let tokens = lex(query);
+    let mut pos = 0;
+    let mut expr = None;
+    // A stray `)` ends `parse_or` early; carry on after it.
+    while pos < tokens.len() {
+        let next = parse_or(&tokens, &mut pos);
+        expr = match (expr, next) {
+            (Some(a), Some(b)) => Some(Expr::And(vec![a, b])),
+            (a, b) => a.or(b),
+        };
+        pos += 1;
+    }
+    expr
+}
+
+fn parse_or(tokens: &[Token], pos: &mut usize) -> Option<Expr> {
+    let mut parts: Vec<Expr> = parse_and(tokens, pos).into_iter().collect();
+    while tokens.get(*pos) == Some(&Token::Or) {
+        *pos += 1;
+        parts.extend(parse_and(tokens, pos));
+    }
+    match parts.len() {
+        0 => None,
+        1 => parts.pop(),
+        _ => Some(Expr::Or(parts)),
+    }
+}
+
+fn parse_and(tokens: &[Token], pos: &mut usize) -> Option<Expr> {
+    let mut parts = Vec::new();
+    loop {
+        match tokens.get(*pos) {
+            None | Some(Token::Or) | Some(Token::Close) => break,
+            Some(Token::And) => *pos += 1,
+            Some(_) => parts.extend(parse_unary(tokens, pos)),
+        }
+    }
+    match parts.len() {
+        0 => None,
+        1 => parts.pop(),
+        _ => Some(Expr::And(parts)),
+    }
+}
+
+fn parse_unary(tokens: &[Token], pos: &mut usize) -> Option<Expr> {
+    match tokens.get(*pos)? {
+        Token::Not => {
+            *pos += 1;
+            parse_unary(tokens, pos).map(|e| Expr::Not(Box::new(e)))
+        }
+        Token::Open => {
+            *pos += 1;
+            let expr = parse_or(tokens, pos);
+            if tokens.get(*pos) == Some(&Token::Close) {
+                *pos += 1;
+            }
+            expr
+        }
+        Token::Word {
+            field,
+            text,
+            quoted,
+        } => {
+            *pos += 1;
+            let prefix = !quoted && text.ends_with('*');
+            let mut terms = tokenize(text);
+            match terms.len() {
+                0 => None,
+                1 => Some(Expr::Term {
+                    field: *field,
+                    text: terms.pop()?,
+                    prefix,
+                }),
+                _ => Some(Expr::Phrase {
+                    field: *field,
+                    terms,
+                }),
+            }
+        }
+        Token::And | Token::Or | Token::Close => {
+            *pos += 1;
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vuln_db::VulnerabilityDatabase;
+
+    const PACK: &str = r#"
+schema_version: 1
+name: search-test
+version: "1"
+vulnerabilities:
+  - id: AP-1
+    name: ArduPilot parameter buffer overflow
+    description: A stack buffer overflow in the ArduPilot parameter handler.
+    severity: Critical
+    affected_systems: [ArduPilot Copter]
+    exploit_available: true
+    tags: [mavlink]
+  - id: AP-2
+    name: ArduPilot telemetry lacks signing
+    description: Unsigned MAVLink commands are accepted.
+    severity: Low
+    affected_systems: [ArduPilot Plane]
+    tags: [mavlink, authentication]
+  - id: PX4-1
+    name: PX4 mission upload overflow
+    description: An out-of-bounds write when uploading a mission.
+    severity: High
+    affected_systems: [PX4 Autopilot]
+    tags: [mavlink, mission]
+  - id: DJI-1
+    name: DJI firmware downgrade
+    description: Signed firmware can be downgraded to a vulnerable release.
+    severity: Medium
+    affected_systems: [DJI Mavic]
+    exploit_available: true
+    tags: [firmware]
+"#;
+
+    fn database() -> VulnerabilityDatabase {
+        let mut db = VulnerabilityDatabase::empty();
+        db.add_pack(PACK).unwrap();
+        db
+    }
+
+    fn ids(db: &VulnerabilityDatabase, query: &str) -> Vec<String> {
+        let mut ids: Vec<String> = db.search(query).iter().map(|v| v.id.clone()).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn boolean_operators() {
+        let db = database();
+        assert_eq!(ids(&db, "ardupilot AND overflow"), ["AP-1"]);
+        assert_eq!(ids(&db, "ardupilot overflow"), ["AP-1"]);
+        assert_eq!(ids(&db, "ardupilot OR dji"), ["AP-1", "AP-2", "DJI-1"]);
+        assert_eq!(ids(&db, "ardupilot NOT overflow"), ["AP-2"]);
+        assert_eq!(ids(&db, "ardupilot -overflow"), ["AP-2"]);
+        assert_eq!(ids(&db, "NOT mavlink"), ["DJI-1"]);
+        assert_eq!(
+            ids(&db, "ardupilot AND (overflow OR signing)"),
+            ["AP-1", "AP-2"]
+        );
+        assert_eq!(ids(&db, "overflow AND ) OR"), ["AP-1", "PX4-1"]);
+        assert_eq!(ids(&db, "").len(), 4);
+    }
+
+    #[test]
+    fn phrases_prefixes_and_fields() {
+        let db = database();
+        assert_eq!(ids(&db, r#""buffer overflow""#), ["AP-1"]);
+        assert!(ids(&db, r#""overflow buffer""#).is_empty());
+        assert_eq!(ids(&db, r#"name:"upload overflow""#), ["PX4-1"]);
+        assert_eq!(ids(&db, "down*"), ["DJI-1"]);
+        assert!(ids(&db, "down").is_empty());
+        assert_eq!(ids(&db, "affected:px4"), ["PX4-1"]);
+        assert_eq!(ids(&db, "tag:mission"), ["PX4-1"]);
+        assert!(ids(&db, "name:mavlink").is_empty());
+        assert_eq!(ids(&db, "id:ap*"), ["AP-1", "AP-2"]);
+    }
+
+    #[test]
+    fn matches_in_more_fields_rank_higher() {
+        let db = database();
+        let hits = db.search("overflow");
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].id, "AP-1");
+    }
+
+    #[test]
+    fn facets_count_matches_before_filters() {
+        let db = database();
+        let results =
+            db.search_with(&SearchQuery::new("mavlink").with_severity(VulnSeverity::High));
+        assert_eq!(results.total, 1);
+        assert_eq!(results.hits[0].vulnerability.id, "PX4-1");
+        assert_eq!(results.facets.severity.values().sum::<usize>(), 3);
+        assert_eq!(results.facets.severity[&VulnSeverity::Critical], 1);
+        assert_eq!(results.facets.exploit_available, 1);
+        assert_eq!(results.facets.no_exploit, 2);
+        assert_eq!(
+            results.facets.vendors,
+            [("ardupilot".to_string(), 2), ("px4".to_string(), 1)]
+        );
+
+        let exploitable = db.search_with(&SearchQuery::new("").with_exploit_available(true));
+        let mut found: Vec<_> = exploitable
+            .hits
+            .iter()
+            .map(|h| h.vulnerability.id.as_str())
+            .collect();
+        found.sort();
+        assert_eq!(found, ["AP-1", "DJI-1"]);
+
+        let vendor = db.search_with(&SearchQuery::new("mavlink").with_vendor("ArduPilot"));
+        assert_eq!(vendor.total, 2);
+        let limited = db.search_with(&SearchQuery::new("mavlink").with_limit(1));
+        assert_eq!((limited.hits.len(), limited.total), (1, 3));
+    }
+
+    #[test]
+    fn index_is_rebuilt_after_changes() {
+        let mut db = database();
+        assert!(ids(&db, "skydio").is_empty());
+
+        let mut entry = db.get_vulnerability("DJI-1").unwrap().clone();
+        entry.id = "SKY-1".to_string();
+        entry.affected_systems = vec!["Skydio 2".to_string()];
+        db.add_vulnerability(entry);
+        assert_eq!(ids(&db, "skydio"), ["SKY-1"]);
+
+        db.add_pack(
+            r#"
+schema_version: 1
+name: extra
+version: "1"
+vulnerabilities:
+  - id: AUT-1
+    name: Autel remote ID spoofing
+    description: Broadcast remote ID can be spoofed.
+    severity: Medium
+    affected_systems: [Autel EVO]
+"#,
+        )
+        .unwrap();
+        assert_eq!(ids(&db, "autel OR skydio"), ["AUT-1", "SKY-1"]);
+        assert_eq!(db.search_with(&SearchQuery::new("")).total, 6);
+    }
+
+    #[test]
+    fn tokenize_splits_identifiers() {
+        assert_eq!(tokenize("CVE-2014-0160"), ["cve", "2014", "0160"]);
+        assert_eq!(
+            tokenize("px4_drone_autopilot"),
+            ["px4", "drone", "autopilot"]
+        );
+    }
+}
@@ -1,5 +1,22 @@
+use crate::nvd::{self, Cpe, CpeMatch, CvssMetric, NvdCve};
+use crate::search::{SearchHit, SearchIndex, SearchQuery, SearchResults};
+use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+const BUILTIN_PACK: &str = include_str!("../packs/uav_builtin.yaml");
+/// Origin recorded for CVEs imported from NVD feeds. They rank below every
+/// pack, so a pack entry with the same CVE id overrides NVD's.
+const NVD_ORIGIN: &str = "nvd";
+const NVD_PRIORITY: i32 = i32::MIN;
+/// Longest generated name for an NVD entry, which has no title of its own.
+const NVD_NAME_CHARS: usize = 100;
+
+/// Pack format this build understands. See `packs/uav_builtin.yaml`.
+pub const PACK_SCHEMA_VERSION: u32 = 1;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Vulnerability {
@@ -8,8 +25,21 @@ pub struct Vulnerability {
     pub description: String,
     pub severity: VulnSeverity,
     pub cve: Option<String>,
+    #[serde(default)]
+    pub cwe: Option<String>,
     pub affected_systems: Vec<String>,
     pub exploit_available: bool,
+    #[serde(default)]
+    pub remediation: Option<String>,
+    #[serde(default)]
+    pub references: Vec<String>,
+    /// CVSS v3.1 and v4.0 base metrics, for entries imported from NVD.
+    #[serde(default)]
+    pub cvss: Vec<CvssMetric>,
+    /// Free-form keywords such as `mavlink` or `gnss`, searchable with
+    /// `tag:`.
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
@@ -20,70 +50,537 @@ pub enum VulnSeverity {
     Critical,
 }
 
+/// Mirrors the `[database]` section of `config.toml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DatabaseConfig {
+    pub auto_update: bool,
+    pub update_interval_hours: u64,
+    /// Vulnerability packs loaded on top of the built-in one, in order.
+    /// A directory loads every `.yaml`, `.yml` and `.json` file in it by
+    /// file name.
+    pub vuln_packs: Vec<PathBuf>,
+    /// Downloaded NVD 2.0 CVE feeds (`nvdcve-2.0-*.json`, optionally
+    /// `.gz`). A directory imports every feed in it by file name.
+    pub nvd_feeds: Vec<PathBuf>,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            auto_update: true,
+            update_interval_hours: 24,
+            vuln_packs: Vec::new(),
+            nvd_feeds: Vec::new(),
+        }
+    }
+}
+
+/// A pack entry as written on disk. Unknown keys are rejected so a typo
+/// such as `severty` is reported instead of silently dropped.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EntryDef {
+    id: String,
+    name: String,
+    description: String,
+    severity: VulnSeverity,
+    cve: Option<String>,
+    cwe: Option<String>,
+    #[serde(default)]
+    affected_systems: Vec<String>,
+    #[serde(default)]
+    exploit_available: bool,
+    remediation: Option<String>,
+    #[serde(default)]
+    references: Vec<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PackFile {
+    schema_version: u32,
+    name: String,
+    version: String,
+    #[serde(default)]
+    priority: i32,
+    #[serde(default)]
+    vulnerabilities: Vec<EntryDef>,
+}
+
+/// A pack that has been merged into the database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackInfo {
+    pub name: String,
+    pub version: String,
+    pub priority: i32,
+    pub source: Option<PathBuf>,
+    pub entries: usize,
+    /// Entries that replaced one from an earlier pack.
+    pub overrides: usize,
+    /// Entries ignored because a higher-priority pack already defines them.
+    pub shadowed: usize,
+}
+
+/// One NVD feed merged into the database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NvdImport {
+    pub source: Option<PathBuf>,
+    pub added: usize,
+    pub updated: usize,
+    /// CVEs the feed marks `Rejected`, dropped from the database.
+    pub removed: usize,
+    /// CVEs a pack already defines. Their CPE ranges are still indexed.
+    pub shadowed: usize,
+}
+
 pub struct VulnerabilityDatabase {
     vulnerabilities: HashMap<String, Vulnerability>,
+    /// Pack name and priority each entry came from.
+    origins: HashMap<String, (String, i32)>,
+    packs: Vec<PackInfo>,
+    /// Vulnerable CPE ranges by CVE id.
+    cpe_matches: HashMap<String, Vec<CpeMatch>>,
+    /// CVE ids by lowercase CPE product name.
+    products: HashMap<String, HashSet<String>>,
+    /// Modification time of each feed when it was last imported.
+    feed_times: HashMap<PathBuf, SystemTime>,
+    /// Built on the first search after a change, so a bulk import pays for
+    /// one rebuild rather than one per entry.
+    index: OnceLock<SearchIndex>,
 }
 
 impl VulnerabilityDatabase {
     pub fn new() -> Self {
-        let mut db = Self {
+        let mut db = Self::empty();
+        db.add_pack(BUILTIN_PACK)
+            .expect("built-in vulnerability pack must be valid");
+        db
+    }
+
+    pub fn empty() -> Self {
+        Self {
             vulnerabilities: HashMap::new(),
+            origins: HashMap::new(),
+            packs: Vec::new(),
+            cpe_matches: HashMap::new(),
+            products: HashMap::new(),
+            feed_times: HashMap::new(),
+            index: OnceLock::new(),
+        }
+    }
+
+    /// The built-in pack, the NVD feeds in `config.nvd_feeds` and every
+    /// pack in `config.vuln_packs`.
+    pub fn from_config(config: &DatabaseConfig) -> Result<Self> {
+        let mut db = Self::new();
+        db.update_from_feeds(config)?;
+        for path in &config.vuln_packs {
+            if path.is_dir() {
+                db.load_pack_dir(path)?;
+            } else {
+                db.load_pack(path)?;
+            }
+        }
+        Ok(db)
+    }
+
+    /// Loads a pack; `.json` files are parsed as JSON, anything else as
+    /// YAML.
+    pub fn load_pack(&mut self, path: &Path) -> Result<&PackInfo> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read vulnerability pack {}", path.display()))?;
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let file = if is_json {
+            serde_json::from_str(&content).map_err(anyhow::Error::from)
+        } else {
+            serde_yaml::from_str(&content).map_err(anyhow::Error::from)
         };
-        db.load_default_vulnerabilities();
-        db
+        let file =
+            file.with_context(|| format!("invalid vulnerability pack {}", path.display()))?;
+        self.add_pack_file(file, Some(path))
+            .with_context(|| format!("invalid vulnerability pack {}", path.display()))
     }
 
-    fn load_default_vulnerabilities(&mut self) {
-        // MAVLink vulnerabilities
-        self.add_vulnerability(Vulnerability {
-            id: "UAV-001".to_string(),
-            name: "MAVLink Unauthenticated Command Injection".to_string(),
-            description: "MAVLink protocol lacks authentication, allowing arbitrary command injection".to_string(),
-            severity: VulnSeverity::Critical,
-            cve: Some("CVE-2023-XXXXX".to_string()),
-            affected_systems: vec!["ArduPilot".to_string(), "PX4".to_string()],
-            exploit_available: true,
-        });
+    /// Loads every `.yaml`, `.yml` and `.json` file in `dir`, ordered by
+    /// file name. Returns the number of packs loaded.
+    pub fn load_pack_dir(&mut self, dir: &Path) -> Result<usize> {
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+            .with_context(|| format!("failed to read pack directory {}", dir.display()))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.is_file()
+                    && path.extension().is_some_and(|ext| {
+                        ["yaml", "yml", "json"]
+                            .iter()
+                            .any(|known| ext.eq_ignore_ascii_case(known))
+                    })
+            })
+            .collect();
+        paths.sort();
+        for path in &paths {
+            self.load_pack(path)?;
+        }
+        Ok(paths.len())
+    }
 
-        self.add_vulnerability(Vulnerability {
-            id: "UAV-002".to_string(),
-            name: "DJI WiFi Default Credentials".to_string(),
-            description: "DJI drones often ship with default WiFi credentials".to_string(),
-            severity: VulnSeverity::High,
-            cve: None,
-            affected_systems: vec!["DJI Phantom".to_string(), "DJI Mavic".to_string()],
-            exploit_available: true,
-        });
+    pub fn add_pack(&mut self, content: &str) -> Result<&PackInfo> {
+        self.add_pack_file(serde_yaml::from_str(content)?, None)
+    }
 
-        self.add_vulnerability(Vulnerability {
-            id: "UAV-003".to_string(),
-            name: "GPS Spoofing Vulnerability".to_string(),
-            description: "UAVs vulnerable to GPS spoofing attacks due to lack of GPS authentication".to_string(),
-            severity: VulnSeverity::High,
-            cve: None,
-            affected_systems: vec!["Most consumer drones".to_string()],
-            exploit_available: true,
-        });
+    pub fn add_pack_json(&mut self, content: &str) -> Result<&PackInfo> {
+        self.add_pack_file(serde_json::from_str(content)?, None)
+    }
+
+    /// Validates the whole pack before merging anything, so a bad pack
+    /// leaves the database as it was.
+    fn add_pack_file(&mut self, file: PackFile, source: Option<&Path>) -> Result<&PackInfo> {
+        if file.schema_version != PACK_SCHEMA_VERSION {
+            bail!(
+                "pack {} has schema_version {}, this build reads version {}",
+                file.name,
+                file.schema_version,
+                PACK_SCHEMA_VERSION
+            );
+        }
+        if file.name.trim().is_empty() {
+            bail!("pack name is empty");
+        }
+
+        let mut problems = Vec::new();
+        let mut seen = HashSet::new();
+        for (index, entry) in file.vulnerabilities.iter().enumerate() {
+            let label = if entry.id.trim().is_empty() {
+                format!("entry {}", index + 1)
+            } else {
+                format!("entry {} ({})", index + 1, entry.id)
+            };
+            for problem in validate(entry) {
+                problems.push(format!("{}: {}", label, problem));
+            }
+            if !entry.id.is_empty() && !seen.insert(entry.id.as_str()) {
+                problems.push(format!("{}: duplicate id", label));
+            }
+        }
+        if !problems.is_empty() {
+            bail!(
+                "pack {} has {} problem{}:\n  {}",
+                file.name,
+                problems.len(),
+                if problems.len() == 1 { "" } else { "s" },
+                problems.join("\n  ")
+            );
+        }
+
+        let mut info = PackInfo {
+            name: file.name,
+            version: file.version,
+            priority: file.priority,
+            source: source.map(Path::to_path_buf),
+            entries: file.vulnerabilities.len(),
+            overrides: 0,
+            shadowed: 0,
+        };
+        for entry in file.vulnerabilities {
+            match self.origins.get(&entry.id) {
+                Some((_, priority)) if *priority > info.priority => {
+                    info.shadowed += 1;
+                    continue;
+                }
+                Some(_) => info.overrides += 1,
+                None => {}
+            }
+            self.origins
+                .insert(entry.id.clone(), (info.name.clone(), info.priority));
+            self.vulnerabilities.insert(
+                entry.id.clone(),
+                Vulnerability {
+                    id: entry.id,
+                    name: entry.name,
+                    description: entry.description,
+                    severity: entry.severity,
+                    cve: entry.cve,
+                    cwe: entry.cwe,
+                    affected_systems: entry.affected_systems,
+                    exploit_available: entry.exploit_available,
+                    remediation: entry.remediation,
+                    references: entry.references,
+                    cvss: Vec::new(),
+                    tags: entry.tags,
+                },
+            );
+        }
+        self.index = OnceLock::new();
+        self.packs.push(info);
+        Ok(self.packs.last().expect("pack was just added"))
+    }
+
+    /// Imports one NVD 2.0 feed file. CVEs already present are replaced, so
+    /// importing the `modified` feed after the yearly ones brings them up
+    /// to date.
+    pub fn import_nvd(&mut self, path: &Path) -> Result<NvdImport> {
+        let cves = nvd::read_feed(path)?;
+        let mut import = self.import_nvd_cves(cves);
+        import.source = Some(path.to_path_buf());
+        if let Ok(modified) = std::fs::metadata(path).and_then(|m| m.modified()) {
+            self.feed_times.insert(path.to_path_buf(), modified);
+        }
+        Ok(import)
+    }
+
+    pub fn import_nvd_cves(&mut self, cves: Vec<NvdCve>) -> NvdImport {
+        let mut import = NvdImport {
+            source: None,
+            added: 0,
+            updated: 0,
+            removed: 0,
+            shadowed: 0,
+        };
+        for cve in cves {
+            self.unindex(&cve.id);
+            let from_pack = self
+                .origins
+                .get(&cve.id)
+                .is_some_and(|(_, priority)| *priority > NVD_PRIORITY);
+            if cve.rejected {
+                if !from_pack && self.vulnerabilities.remove(&cve.id).is_some() {
+                    self.origins.remove(&cve.id);
+                    import.removed += 1;
+                }
+                continue;
+            }
+            for m in &cve.matches {
+                self.products
+                    .entry(m.criteria.product.clone())
+                    .or_default()
+                    .insert(cve.id.clone());
+            }
+            self.cpe_matches.insert(cve.id.clone(), cve.matches.clone());
+            if from_pack {
+                import.shadowed += 1;
+                continue;
+            }
+            if self.vulnerabilities.contains_key(&cve.id) {
+                import.updated += 1;
+            } else {
+                import.added += 1;
+            }
+            self.origins
+                .insert(cve.id.clone(), (NVD_ORIGIN.to_string(), NVD_PRIORITY));
+            self.vulnerabilities
+                .insert(cve.id.clone(), nvd_vulnerability(cve));
+        }
+        self.index = OnceLock::new();
+        import
+    }
+
+    /// Imports the feeds in `config.nvd_feeds` that are new or have changed
+    /// on disk since they were last imported. With `auto_update` set, the
+    /// application calls this every `update_interval_hours` so replacing a
+    /// feed file in an airgapped lab is enough to update the database.
+    pub fn update_from_feeds(&mut self, config: &DatabaseConfig) -> Result<Vec<NvdImport>> {
+        let mut feeds = Vec::new();
+        for path in &config.nvd_feeds {
+            if path.is_dir() {
+                let mut found: Vec<PathBuf> = std::fs::read_dir(path)
+                    .with_context(|| format!("failed to read feed directory {}", path.display()))?
+                    .filter_map(|entry| entry.ok().map(|e| e.path()))
+                    .filter(|p| {
+                        let name = p
+                            .file_name()
+                            .map(|n| n.to_string_lossy().to_lowercase())
+                            .unwrap_or_default();
+                        p.is_file() && (name.ends_with(".json") || name.ends_with(".json.gz"))
+                    })
+                    .collect();
+                found.sort();
+                feeds.extend(found);
+            } else {
+                feeds.push(path.clone());
+            }
+        }
+
+        let mut imports = Vec::new();
+        for feed in feeds {
+            let modified = std::fs::metadata(&feed)
+                .and_then(|m| m.modified())
+                .with_context(|| format!("failed to read NVD feed {}", feed.display()))?;
+            if self.feed_times.get(&feed) == Some(&modified) {
+                continue;
+            }
+            imports.push(self.import_nvd(&feed)?);
+        }
+        Ok(imports)
+    }
+
+    /// Entries whose CPE ranges cover `version` of `product`, from any
+    /// vendor. Product names are CPE names such as `busybox` or `openssl`.
+    pub fn lookup(&self, product: &str, version: &str) -> Vec<&Vulnerability> {
+        self.lookup_matches(None, product, version)
+    }
+
+    /// Entries matching a CPE 2.3 name, e.g. the `cpe` of an SBOM
+    /// component. An ANY version returns every entry for the product.
+    pub fn lookup_cpe(&self, cpe: &str) -> Vec<&Vulnerability> {
+        let Some(cpe) = Cpe::parse(cpe) else {
+            return Vec::new();
+        };
+        let vendor = Some(cpe.vendor.as_str()).filter(|v| *v != "*");
+        if cpe.version == "*" {
+            let mut found: Vec<&Vulnerability> = self
+                .products
+                .get(&cpe.product)
+                .into_iter()
+                .flatten()
+                .filter(|id| {
+                    self.cpe_matches(id)
+                        .iter()
+                        .any(|m| vendor.is_none_or(|v| m.criteria.vendor == v))
+                })
+                .filter_map(|id| self.vulnerabilities.get(id))
+                .collect();
+            found.sort_by(|a, b| a.id.cmp(&b.id));
+            return found;
+        }
+        let version = match cpe.update.as_str() {
+            "*" | "-" => cpe.version.clone(),
+            update => format!("{}{}", cpe.version, update),
+        };
+        self.lookup_matches(vendor, &cpe.product, &version)
+    }
+
+    fn lookup_matches(
+        &self,
+        vendor: Option<&str>,
+        product: &str,
+        version: &str,
+    ) -> Vec<&Vulnerability> {
+        let product = product.to_lowercase();
+        let vendor = vendor.map(str::to_lowercase);
+        let mut found: Vec<&Vulnerability> = self
+            .products
+            .get(&product)
+            .into_iter()
+            .flatten()
+            .filter(|id| {
+                self.cpe_matches(id).iter().any(|m| {
+                    m.criteria.product == product
+                        && vendor.as_deref().is_none_or(|v| m.criteria.vendor == v)
+                        && m.contains(version)
+                })
+            })
+            .filter_map(|id| self.vulnerabilities.get(id))
+            .collect();
+        found.sort_by(|a, b| a.id.cmp(&b.id));
+        found
+    }
+
+    /// Vulnerable CPE ranges recorded for a CVE.
+    pub fn cpe_matches(&self, cve: &str) -> &[CpeMatch] {
+        self.cpe_matches.get(cve).map_or(&[], Vec::as_slice)
+    }
+
+    fn unindex(&mut self, cve: &str) {
+        for m in self.cpe_matches.remove(cve).unwrap_or_default() {
+            if let Some(ids) = self.products.get_mut(&m.criteria.product) {
+                ids.remove(cve);
+                if ids.is_empty() {
+                    self.products.remove(&m.criteria.product);
+                }
+            }
+        }
+    }
+
+    /// Packs merged so far, in load order.
+    pub fn packs(&self) -> &[PackInfo] {
+        &self.packs
+    }
+
+    /// Name of the pack `id` was taken from; `None` for entries added with
+    /// `add_vulnerability`.
+    pub fn source(&self, id: &str) -> Option<&str> {
+        self.origins.get(id).map(|(pack, _)| pack.as_str())
     }
 
     pub fn add_vulnerability(&mut self, vuln: Vulnerability) {
+        self.origins.remove(&vuln.id);
         self.vulnerabilities.insert(vuln.id.clone(), vuln);
+        self.index = OnceLock::new();
     }
 
     pub fn get_vulnerability(&self, id: &str) -> Option<&Vulnerability> {
         self.vulnerabilities.get(id)
     }
 
+    pub fn vulnerabilities(&self) -> impl Iterator<Item = &Vulnerability> {
+        self.vulnerabilities.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.vulnerabilities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vulnerabilities.is_empty()
+    }
+
+    /// Entries matching `query`, best match first. See `SearchQuery::text`
+    /// for the syntax, e.g. `ardupilot AND (overflow OR "out-of-bounds")`.
     pub fn search(&self, query: &str) -> Vec<&Vulnerability> {
-        self.vulnerabilities
-            .values()
-            .filter(|v| {
-                v.name.to_lowercase().contains(&query.to_lowercase())
-                    || v.description.to_lowercase().contains(&query.to_lowercase())
-            })
+        self.search_with(&SearchQuery::new(query))
+            .hits
+            .into_iter()
+            .map(|hit| hit.vulnerability)
             .collect()
     }
 
+    /// Ranked, filtered search with facet counts.
+    pub fn search_with(&self, query: &SearchQuery) -> SearchResults<'_> {
+        let index = self.index.get_or_init(|| {
+            SearchIndex::build(self.vulnerabilities.values().map(|v| (v, self.vendors(v))))
+        });
+        let (hits, total, facets) = index.search(query);
+        SearchResults {
+            hits: hits
+                .into_iter()
+                .filter_map(|(id, score)| {
+                    self.vulnerabilities.get(id).map(|vulnerability| SearchHit {
+                        vulnerability,
+                        score,
+                    })
+                })
+                .collect(),
+            total,
+            facets,
+        }
+    }
+
+    /// Lowercase vendors an entry affects: the CPE vendors for NVD CVEs,
+    /// otherwise the first word of each affected system (`DJI Mavic` is
+    /// `dji`).
+    pub fn vendors(&self, vuln: &Vulnerability) -> Vec<String> {
+        let mut vendors: Vec<String> = self
+            .cpe_matches(&vuln.id)
+            .iter()
+            .map(|m| m.criteria.vendor.clone())
+            .collect();
+        if vendors.is_empty() {
+            vendors = vuln
+                .affected_systems
+                .iter()
+                .filter_map(|s| s.split_whitespace().next())
+                .map(str::to_lowercase)
+                .collect();
+        }
+        vendors.sort();
+        vendors.dedup();
+        vendors
+    }
+
     pub fn get_by_severity(&self, severity: VulnSeverity) -> Vec<&Vulnerability> {
         self.vulnerabilities
             .values()
@@ -97,3 +594,255 @@ impl Default for VulnerabilityDatabase {
         Self::new()
     }
 }
+
+fn nvd_vulnerability(cve: NvdCve) -> Vulnerability {
+    let metric = cve.preferred_metric();
+    let severity = match metric.and_then(|m| m.base_severity.as_deref()) {
+        Some("CRITICAL") => VulnSeverity::Critical,
+        Some("HIGH") => VulnSeverity::High,
+        Some("LOW") | Some("NONE") => VulnSeverity::Low,
+        Some("MEDIUM") => VulnSeverity::Medium,
+        _ => match metric.map(|m| m.base_score) {
+            Some(score) if score >= 9.0 => VulnSeverity::Critical,
+            Some(score) if score >= 7.0 => VulnSeverity::High,
+            Some(score) if score < 4.0 => VulnSeverity::Low,
+            // Not yet analysed by NVD.
+            _ => VulnSeverity::Medium,
+        },
+    };
+    // NVD has no titles; the first sentence of the description stands in.
+    let sentence = cve
+        .description
+        .split_once(". ")
+        .map_or(cve.description.as_str(), |(first, _)| first)
+        .trim_end_matches('.');
+    let mut name: String = sentence.chars().take(NVD_NAME_CHARS).collect();
+    if name.len() < sentence.len() {
+        name.push('…');
+    }
+    Vulnerability {
+        name: if name.is_empty() {
+            cve.id.clone()
+        } else {
+            name
+        },
+        description: cve.description.clone(),
+        severity,
+        cve: Some(cve.id.clone()),
+        cwe: cve.cwes.first().cloned(),
+        affected_systems: cve.affected_products(),
+        exploit_available: cve.exploit_available,
+        remediation: None,
+        references: cve.references,
+        cvss: cve.cvss,
+        tags: Vec::new(),
+        id: cve.id,
+    }
+}
+
+/// Problems with one pack entry, worded for the person editing the pack.
+fn validate(entry: &EntryDef) -> Vec<String> {
+    let mut problems = Vec::new();
+    if entry.id.trim().is_empty() {
+        problems.push("id is empty".to_string());
+    } else if entry.id.chars().any(char::is_whitespace) {
+        problems.push(format!("id {:?} contains whitespace", entry.id));
+    }
+    if entry.name.trim().is_empty() {
+        problems.push("name is empty".to_string());
+    }
+    if entry.description.trim().is_empty() {
+        problems.push("description is empty".to_string());
+    }
+    if let Some(cve) = &entry.cve {
+        if !is_identifier(cve, "CVE-", true) {
+            problems.push(format!("cve {:?} is not of the form CVE-2024-12345", cve));
+        }
+    }
+    if let Some(cwe) = &entry.cwe {
+        if !is_identifier(cwe, "CWE-", false) {
+            problems.push(format!("cwe {:?} is not of the form CWE-306", cwe));
+        }
+    }
+    for reference in &entry.references {
+        if !(reference.starts_with("https://") || reference.starts_with("http://")) {
+            problems.push(format!("reference {:?} is not an http(s) URL", reference));
+        }
+    }
+    problems
+}
+
+/// `CVE-YYYY-NNNN` (four or more digits) when `with_year`, otherwise
+/// `CWE-N`.
+fn is_identifier(value: &str, prefix: &str, with_year: bool) -> bool {
+    let Some(rest) = value.strip_prefix(prefix) else {
+        return false;
+    };
+    let digits = |s: &str, min: usize| s.len() >= min && s.bytes().all(|b| b.is_ascii_digit());
+    if with_year {
+        rest.split_once('-')
+            .is_some_and(|(year, number)| year.len() == 4 && digits(year, 4) && digits(number, 4))
+    } else {
+        digits(rest, 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pack(name: &str, priority: i32, entry_name: &str) -> String {
+        format!(
+            r#"
+schema_version: 1
+name: {name}
+version: "1"
+priority: {priority}
+vulnerabilities:
+  - id: UAV-001
+    name: {entry_name}
+    description: Overridden entry.
+    severity: Low
+"#
+        )
+    }
+
+    fn error(result: Result<&PackInfo>) -> String {
+        format!("{:#}", result.expect_err("pack should be rejected"))
+    }
+
+    #[test]
+    fn builtin_pack_loads() {
+        let db = VulnerabilityDatabase::new();
+        let info = &db.packs()[0];
+        assert_eq!(info.name, "uavred-builtin");
+        assert_eq!(info.priority, -100);
+        assert_eq!(info.entries, db.len());
+        assert_eq!(db.source("UAV-001"), Some("uavred-builtin"));
+        let entry = db.get_vulnerability("UAV-001").unwrap();
+        assert_eq!(entry.severity, VulnSeverity::Critical);
+        assert_eq!(entry.cwe.as_deref(), Some("CWE-306"));
+
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("packs/uav_builtin.yaml");
+        let mut from_file = VulnerabilityDatabase::empty();
+        let info = from_file.load_pack(&path).unwrap();
+        assert_eq!(info.source.as_deref(), Some(path.as_path()));
+        assert_eq!(from_file.len(), db.len());
+    }
+
+    #[test]
+    fn other_schema_versions_are_rejected() {
+        let mut db = VulnerabilityDatabase::empty();
+        let message = error(
+            db.add_pack(&pack("future", 0, "x").replace("schema_version: 1", "schema_version: 2")),
+        );
+        assert!(message.contains("schema_version 2"), "{message}");
+        assert!(db.is_empty());
+        assert!(db.packs().is_empty());
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let mut db = VulnerabilityDatabase::empty();
+        let message = error(db.add_pack(&pack("typo", 0, "x").replace("severity:", "severty:")));
+        assert!(message.contains("severty"), "{message}");
+
+        let message = error(db.add_pack_json(
+            r#"{"schema_version": 1, "name": "extra", "version": "1", "owner": "me"}"#,
+        ));
+        assert!(message.contains("owner"), "{message}");
+        assert!(db.is_empty());
+    }
+
+    #[test]
+    fn invalid_entries_reject_the_whole_pack() {
+        let mut db = VulnerabilityDatabase::empty();
+        let content = r#"
+schema_version: 1
+name: broken
+version: "1"
+vulnerabilities:
+  - id: OK-1
+    name: Fine
+    description: A valid entry.
+    severity: Low
+  - id: BAD-1
+    name: Bad identifiers
+    description: Malformed CVE and CWE.
+    severity: High
+    cve: CVE-24-1
+    cwe: "306"
+    references: [ftp://example.com]
+  - id: OK-1
+    name: Again
+    description: Duplicate id.
+    severity: Low
+"#;
+        let message = error(db.add_pack(content));
+        assert!(message.contains("4 problems"), "{message}");
+        assert!(message.contains("entry 2 (BAD-1): cve"), "{message}");
+        assert!(
+            message.contains("entry 3 (OK-1): duplicate id"),
+            "{message}"
+        );
+        assert!(db.is_empty());
+    }
+
+    #[test]
+    fn higher_priority_and_later_packs_win() {
+        let mut db = VulnerabilityDatabase::new();
+
+        let info = db.add_pack(&pack("site", 0, "Site wording")).unwrap();
+        assert_eq!((info.overrides, info.shadowed), (1, 0));
+        assert_eq!(db.source("UAV-001"), Some("site"));
+
+        let info = db.add_pack(&pack("team", 0, "Team wording")).unwrap();
+        assert_eq!(info.overrides, 1);
+        assert_eq!(
+            db.get_vulnerability("UAV-001").unwrap().name,
+            "Team wording"
+        );
+
+        let info = db.add_pack(&pack("vendor", -10, "Vendor wording")).unwrap();
+        assert_eq!((info.overrides, info.shadowed), (0, 1));
+        assert_eq!(db.source("UAV-001"), Some("team"));
+
+        db.add_pack(&pack("urgent", 10, "Urgent wording")).unwrap();
+        assert_eq!(db.source("UAV-001"), Some("urgent"));
+        assert_eq!(db.packs().len(), 5);
+
+        let mut entry = db.get_vulnerability("UAV-001").unwrap().clone();
+        entry.name = "Local wording".to_string();
+        db.add_vulnerability(entry);
+        assert_eq!(db.source("UAV-001"), None);
+        let info = db.add_pack(&pack("late", -10, "Late wording")).unwrap();
+        assert_eq!(info.overrides, 0);
+        assert_eq!(
+            db.get_vulnerability("UAV-001").unwrap().name,
+            "Late wording"
+        );
+    }
+
+    #[test]
+    fn pack_directories_load_in_file_name_order() {
+        let dir = std::env::temp_dir().join(format!("uavred-packs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("b.yaml"), pack("second", 0, "From YAML")).unwrap();
+        std::fs::write(
+            dir.join("a.json"),
+            r#"{"schema_version": 1, "name": "first", "version": "1", "vulnerabilities": [
+                {"id": "UAV-001", "name": "From JSON", "description": "d", "severity": "High"}
+            ]}"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a pack").unwrap();
+
+        let mut db = VulnerabilityDatabase::empty();
+        let loaded = db.load_pack_dir(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.unwrap(), 2);
+        let names: Vec<_> = db.packs().iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["first", "second"]);
+        assert_eq!(db.get_vulnerability("UAV-001").unwrap().name, "From YAML");
+    }
+}
@@ -0,0 +1,127 @@
+// 数据模型与 core 漏洞库模型之间的转换
+
+use crate::models::{CvssScore, DetectionSource, Severity, VulnData, VulnSeverity};
+use anyhow::bail;
+use core::vuln_db::{VulnSeverity as DbSeverity, Vulnerability};
+
+impl From<Severity> for VulnSeverity {
+    fn from(severity: Severity) -> Self {
+        match severity {
+            Severity::Critical => VulnSeverity::Critical,
+            Severity::High => VulnSeverity::High,
+            Severity::Medium => VulnSeverity::Medium,
+            Severity::Low => VulnSeverity::Low,
+            Severity::Info => VulnSeverity::Info,
+        }
+    }
+}
+
+impl From<VulnSeverity> for Severity {
+    fn from(severity: VulnSeverity) -> Self {
+        match severity {
+            VulnSeverity::Critical => Severity::Critical,
+            VulnSeverity::High => Severity::High,
+            VulnSeverity::Medium => Severity::Medium,
+            VulnSeverity::Low => Severity::Low,
+            VulnSeverity::Info => Severity::Info,
+        }
+    }
+}
+
+impl From<DbSeverity> for VulnSeverity {
+    fn from(severity: DbSeverity) -> Self {
+        match severity {
+            DbSeverity::Critical => VulnSeverity::Critical,
+            DbSeverity::High => VulnSeverity::High,
+            DbSeverity::Medium => VulnSeverity::Medium,
+            DbSeverity::Low => VulnSeverity::Low,
+        }
+    }
+}
+
+/// 漏洞库没有 Info 级别，转换失败而不是悄悄提升为 Low
+impl TryFrom<VulnSeverity> for DbSeverity {
+    type Error = anyhow::Error;
+
+    fn try_from(severity: VulnSeverity) -> anyhow::Result<Self> {
+        match severity {
+            VulnSeverity::Critical => Ok(DbSeverity::Critical),
+            VulnSeverity::High => Ok(DbSeverity::High),
+            VulnSeverity::Medium => Ok(DbSeverity::Medium),
+            VulnSeverity::Low => Ok(DbSeverity::Low),
+            VulnSeverity::Info => bail!("the vulnerability database has no Info severity"),
+        }
+    }
+}
+
+/// 漏洞库条目转为界面使用的漏洞数据，CVSS 取 NVD 自己的最新版本评分
+impl From<&Vulnerability> for VulnData {
+    fn from(entry: &Vulnerability) -> Self {
+        let mut vuln = VulnData::new(
+            entry.id.clone(),
+            entry.name.clone(),
+            entry.description.clone(),
+            entry.severity.clone().into(),
+        );
+        vuln.cve = entry.cve.clone();
+        vuln.cwe = entry.cwe.clone();
+        vuln.cvss = core::nvd::preferred_metric(&entry.cvss).map(|metric| {
+            CvssScore::from_vector(&metric.vector)
+                .unwrap_or_else(|_| CvssScore::new(metric.base_score, metric.vector.clone()))
+        });
+        vuln.affected = entry.affected_systems.join(", ");
+        vuln.affected_systems = entry.affected_systems.clone();
+        vuln.exploit_available = entry.exploit_available;
+        vuln.references = entry.references.clone();
+        vuln.tags = entry.tags.clone();
+        vuln.remediation = entry.remediation.clone();
+        vuln.detection_location.source = DetectionSource::ThreatIntelligence;
+        vuln
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEVERITIES: [VulnSeverity; 5] = [
+        VulnSeverity::Critical,
+        VulnSeverity::High,
+        VulnSeverity::Medium,
+        VulnSeverity::Low,
+        VulnSeverity::Info,
+    ];
+
+    #[test]
+    fn severity_round_trips_through_risk_severity() {
+        for severity in SEVERITIES {
+            let risk = Severity::from(severity.clone());
+            assert_eq!(VulnSeverity::from(risk), severity);
+        }
+    }
+
+    #[test]
+    fn severity_round_trips_through_database_severity() {
+        for severity in SEVERITIES {
+            match DbSeverity::try_from(severity.clone()) {
+                Ok(db) => assert_eq!(VulnSeverity::from(db), severity),
+                Err(_) => assert_eq!(severity, VulnSeverity::Info),
+            }
+        }
+    }
+
+    #[test]
+    fn database_entry_keeps_its_fields() {
+        let db = core::vuln_db::VulnerabilityDatabase::new();
+        let entry = db.get_vulnerability("UAV-001").expect("built-in entry");
+        let vuln = VulnData::from(entry);
+
+        assert_eq!(vuln.id, entry.id);
+        assert_eq!(vuln.title, entry.name);
+        assert_eq!(vuln.cwe, entry.cwe);
+        assert_eq!(vuln.affected_systems, entry.affected_systems);
+        assert_eq!(vuln.tags, entry.tags);
+        assert_eq!(vuln.remediation, entry.remediation);
+        assert_eq!(DbSeverity::try_from(vuln.severity).unwrap(), entry.severity);
+    }
+}
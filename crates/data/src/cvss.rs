@@ -0,0 +1,845 @@
+// CVSS v3.0 / v3.1 / v4.0 向量解析与评分
+
+use anyhow::{Result, anyhow, bail};
+use std::fmt;
+
+/// CVSS 版本
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CvssVersion {
+    V3_0,
+    V3_1,
+    V4_0,
+}
+
+impl CvssVersion {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CvssVersion::V3_0 => "3.0",
+            CvssVersion::V3_1 => "3.1",
+            CvssVersion::V4_0 => "4.0",
+        }
+    }
+
+    fn metrics(&self) -> &'static [Metric] {
+        match self {
+            CvssVersion::V3_0 | CvssVersion::V3_1 => V3_METRICS,
+            CvssVersion::V4_0 => V4_METRICS,
+        }
+    }
+}
+
+/// 指标定义：缩写、允许的取值、是否为必填的基础指标
+struct Metric {
+    key: &'static str,
+    values: &'static [&'static str],
+    required: bool,
+}
+
+const fn base(key: &'static str, values: &'static [&'static str]) -> Metric {
+    Metric {
+        key,
+        values,
+        required: true,
+    }
+}
+
+/// 可选指标，`X`（未定义）总是允许的
+const fn optional(key: &'static str, values: &'static [&'static str]) -> Metric {
+    Metric {
+        key,
+        values,
+        required: false,
+    }
+}
+
+const V3_METRICS: &[Metric] = &[
+    base("AV", &["N", "A", "L", "P"]),
+    base("AC", &["L", "H"]),
+    base("PR", &["N", "L", "H"]),
+    base("UI", &["N", "R"]),
+    base("S", &["U", "C"]),
+    base("C", &["H", "L", "N"]),
+    base("I", &["H", "L", "N"]),
+    base("A", &["H", "L", "N"]),
+    optional("E", &["X", "H", "F", "P", "U"]),
+    optional("RL", &["X", "U", "W", "T", "O"]),
+    optional("RC", &["X", "C", "R", "U"]),
+    optional("CR", &["X", "H", "M", "L"]),
+    optional("IR", &["X", "H", "M", "L"]),
+    optional("AR", &["X", "H", "M", "L"]),
+    optional("MAV", &["X", "N", "A", "L", "P"]),
+    optional("MAC", &["X", "L", "H"]),
+    optional("MPR", &["X", "N", "L", "H"]),
+    optional("MUI", &["X", "N", "R"]),
+    optional("MS", &["X", "U", "C"]),
+    optional("MC", &["X", "H", "L", "N"]),
+    optional("MI", &["X", "H", "L", "N"]),
+    optional("MA", &["X", "H", "L", "N"]),
+];
+
+const V3_TEMPORAL: &[&str] = &["E", "RL", "RC"];
+const V3_ENVIRONMENTAL: &[&str] = &[
+    "CR", "IR", "AR", "MAV", "MAC", "MPR", "MUI", "MS", "MC", "MI", "MA",
+];
+
+const V4_METRICS: &[Metric] = &[
+    base("AV", &["N", "A", "L", "P"]),
+    base("AC", &["L", "H"]),
+    base("AT", &["N", "P"]),
+    base("PR", &["N", "L", "H"]),
+    base("UI", &["N", "P", "A"]),
+    base("VC", &["H", "L", "N"]),
+    base("VI", &["H", "L", "N"]),
+    base("VA", &["H", "L", "N"]),
+    base("SC", &["H", "L", "N"]),
+    base("SI", &["H", "L", "N"]),
+    base("SA", &["H", "L", "N"]),
+    optional("E", &["X", "A", "P", "U"]),
+    optional("CR", &["X", "H", "M", "L"]),
+    optional("IR", &["X", "H", "M", "L"]),
+    optional("AR", &["X", "H", "M", "L"]),
+    optional("MAV", &["X", "N", "A", "L", "P"]),
+    optional("MAC", &["X", "L", "H"]),
+    optional("MAT", &["X", "N", "P"]),
+    optional("MPR", &["X", "N", "L", "H"]),
+    optional("MUI", &["X", "N", "P", "A"]),
+    optional("MVC", &["X", "H", "L", "N"]),
+    optional("MVI", &["X", "H", "L", "N"]),
+    optional("MVA", &["X", "H", "L", "N"]),
+    optional("MSC", &["X", "H", "L", "N"]),
+    optional("MSI", &["X", "S", "H", "L", "N"]),
+    optional("MSA", &["X", "S", "H", "L", "N"]),
+    // 补充指标，不参与评分
+    optional("S", &["X", "N", "P"]),
+    optional("AU", &["X", "N", "Y"]),
+    optional("R", &["X", "A", "U", "I"]),
+    optional("V", &["X", "D", "C"]),
+    optional("RE", &["X", "L", "M", "H"]),
+    optional("U", &["X", "Clear", "Green", "Amber", "Red"]),
+];
+
+const V4_THREAT: &[&str] = &["E"];
+const V4_ENVIRONMENTAL: &[&str] = &[
+    "CR", "IR", "AR", "MAV", "MAC", "MAT", "MPR", "MUI", "MVC", "MVI", "MVA", "MSC", "MSI", "MSA",
+];
+
+/// 已解析并校验的 CVSS 向量
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CvssVector {
+    version: CvssVersion,
+    /// 与 `version.metrics()` 一一对应，`None` 表示未定义（X）
+    values: Vec<Option<&'static str>>,
+}
+
+/// 向量计算出的各项分数
+#[derive(Debug, Clone, PartialEq)]
+pub struct CvssScores {
+    pub base: f64,
+    /// v3 的时间分数，v4 的 CVSS-BT；向量不含这些指标时为 None
+    pub temporal: Option<f64>,
+    /// v3 的环境分数，v4 的 CVSS-BE / CVSS-BTE
+    pub environmental: Option<f64>,
+    /// v3 的可利用性与影响子分数，v4 没有子分数
+    pub exploitability: Option<f64>,
+    pub impact: Option<f64>,
+}
+
+impl CvssVector {
+    /// 解析 `CVSS:3.1/AV:N/...` 或 `CVSS:4.0/AV:N/...`，
+    /// 拒绝未知或重复的指标、非法取值和缺失的基础指标
+    pub fn parse(vector: &str) -> Result<Self> {
+        let vector = vector.trim();
+        let mut parts = vector.split('/');
+        let version = match parts.next() {
+            Some("CVSS:3.0") => CvssVersion::V3_0,
+            Some("CVSS:3.1") => CvssVersion::V3_1,
+            Some("CVSS:4.0") => CvssVersion::V4_0,
+            Some(prefix) if prefix.starts_with("CVSS:") => {
+                bail!("unsupported CVSS version in {:?}", vector)
+            }
+            _ => bail!("{:?} is not a CVSS v3 or v4 vector", vector),
+        };
+        let mut parsed = Self {
+            version,
+            values: vec![None; version.metrics().len()],
+        };
+        let mut seen = vec![false; version.metrics().len()];
+        for part in parts {
+            let (key, value) = part
+                .split_once(':')
+                .ok_or_else(|| anyhow!("malformed metric {:?} in {:?}", part, vector))?;
+            let index = parsed.index(key)?;
+            if std::mem::replace(&mut seen[index], true) {
+                bail!("metric {} appears twice in {:?}", key, vector);
+            }
+            parsed.set(key, value)?;
+        }
+        let missing: Vec<&str> = version
+            .metrics()
+            .iter()
+            .zip(&parsed.values)
+            .filter(|(metric, value)| metric.required && value.is_none())
+            .map(|(metric, _)| metric.key)
+            .collect();
+        if !missing.is_empty() {
+            bail!("{:?} is missing {}", vector, missing.join(", "));
+        }
+        Ok(parsed)
+    }
+
+    pub fn version(&self) -> CvssVersion {
+        self.version
+    }
+
+    /// 指标取值，未定义的可选指标为 `X`
+    pub fn get(&self, key: &str) -> &'static str {
+        self.version
+            .metrics()
+            .iter()
+            .position(|m| m.key == key)
+            .and_then(|i| self.values[i])
+            .unwrap_or("X")
+    }
+
+    /// 设置一个指标；`X` 清除可选指标
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let index = self.index(key)?;
+        let metric = &self.version.metrics()[index];
+        let value = metric.values.iter().find(|v| **v == value).ok_or_else(|| {
+            anyhow!(
+                "{} is not a valid value for CVSS {} metric {} (expected one of {})",
+                value,
+                self.version.as_str(),
+                key,
+                metric.values.join(", ")
+            )
+        })?;
+        if *value == "X" {
+            self.values[index] = None;
+        } else {
+            self.values[index] = Some(value);
+        }
+        Ok(())
+    }
+
+    /// 以 `MAV:L/CR:H` 的形式批量覆盖指标，例如按测试环境调整环境指标
+    pub fn apply(&mut self, metrics: &str) -> Result<()> {
+        let mut updated = self.clone();
+        for part in metrics.split('/').filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once(':')
+                .ok_or_else(|| anyhow!("malformed metric {:?}", part))?;
+            updated.set(key, value)?;
+        }
+        *self = updated;
+        Ok(())
+    }
+
+    fn index(&self, key: &str) -> Result<usize> {
+        self.version
+            .metrics()
+            .iter()
+            .position(|m| m.key == key)
+            .ok_or_else(|| anyhow!("unknown CVSS {} metric {}", self.version.as_str(), key))
+    }
+
+    fn defines(&self, keys: impl IntoIterator<Item = &'static str>) -> bool {
+        keys.into_iter().any(|key| self.get(key) != "X")
+    }
+
+    pub fn scores(&self) -> CvssScores {
+        match self.version {
+            CvssVersion::V3_0 | CvssVersion::V3_1 => self.v3_scores(),
+            CvssVersion::V4_0 => self.v4_scores(),
+        }
+    }
+
+    fn v3_scores(&self) -> CvssScores {
+        let base = V3Subscores::new(self, false);
+        let base_score = base.score(self.version);
+        let temporal_factor = match self.get("E") {
+            "F" => 0.97,
+            "P" => 0.94,
+            "U" => 0.91,
+            _ => 1.0,
+        } * match self.get("RL") {
+            "W" => 0.97,
+            "T" => 0.96,
+            "O" => 0.95,
+            _ => 1.0,
+        } * match self.get("RC") {
+            "R" => 0.96,
+            "U" => 0.92,
+            _ => 1.0,
+        };
+        let temporal = self
+            .defines(V3_TEMPORAL.iter().copied())
+            .then(|| roundup(self.version, base_score * temporal_factor));
+        let environmental = self.defines(V3_ENVIRONMENTAL.iter().copied()).then(|| {
+            let modified = V3Subscores::new(self, true).score(self.version);
+            roundup(self.version, modified * temporal_factor)
+        });
+        CvssScores {
+            base: base_score,
+            temporal,
+            environmental,
+            exploitability: Some(round1(base.exploitability)),
+            impact: Some(round1(base.impact.max(0.0))),
+        }
+    }
+
+    fn v4_scores(&self) -> CvssScores {
+        let threat = self.defines(V4_THREAT.iter().copied());
+        let environmental = self.defines(V4_ENVIRONMENTAL.iter().copied());
+        CvssScores {
+            base: self.v4_score(false, false),
+            temporal: threat.then(|| self.v4_score(true, false)),
+            environmental: environmental.then(|| self.v4_score(threat, true)),
+            exploitability: None,
+            impact: None,
+        }
+    }
+
+    /// CVSS v4.0 评分：先按 EQ1–EQ6 归入 MacroVector 查表，
+    /// 再按与该 MacroVector 最高严重度向量的距离插值
+    fn v4_score(&self, threat: bool, environmental: bool) -> f64 {
+        let m = |key: &str| -> &'static str {
+            match key {
+                "E" => match self.get("E") {
+                    value if threat && value != "X" => value,
+                    _ => "A",
+                },
+                "CR" | "IR" | "AR" => match self.get(key) {
+                    value if environmental && value != "X" => value,
+                    _ => "H",
+                },
+                _ => {
+                    let modified = self.get(&format!("M{}", key));
+                    if environmental && modified != "X" {
+                        modified
+                    } else {
+                        self.get(key)
+                    }
+                }
+            }
+        };
+
+        if ["VC", "VI", "VA", "SC", "SI", "SA"]
+            .iter()
+            .all(|k| m(k) == "N")
+        {
+            return 0.0;
+        }
+
+        let (av, pr, ui) = (m("AV"), m("PR"), m("UI"));
+        let eq1 = if av == "N" && pr == "N" && ui == "N" {
+            0
+        } else if (av == "N" || pr == "N" || ui == "N") && av != "P" {
+            1
+        } else {
+            2
+        };
+        let eq2 = if m("AC") == "L" && m("AT") == "N" {
+            0
+        } else {
+            1
+        };
+        let (vc, vi, va) = (m("VC"), m("VI"), m("VA"));
+        let eq3 = if vc == "H" && vi == "H" {
+            0
+        } else if vc == "H" || vi == "H" || va == "H" {
+            1
+        } else {
+            2
+        };
+        let eq4 = if m("SI") == "S" || m("SA") == "S" {
+            0
+        } else if m("SC") == "H" || m("SI") == "H" || m("SA") == "H" {
+            1
+        } else {
+            2
+        };
+        let eq5 = match m("E") {
+            "A" => 0,
+            "P" => 1,
+            _ => 2,
+        };
+        let eq6 = if (m("CR") == "H" && vc == "H")
+            || (m("IR") == "H" && vi == "H")
+            || (m("AR") == "H" && va == "H")
+        {
+            0
+        } else {
+            1
+        };
+
+        let macro_vector = [eq1, eq2, eq3, eq4, eq5, eq6];
+        let Some(value) = v4_lookup(macro_vector) else {
+            return 0.0;
+        };
+        let lower = |eq: usize| {
+            let mut next = macro_vector;
+            next[eq] += 1;
+            v4_lookup(next)
+        };
+        let lower_eq3_eq6 = match (eq3, eq6) {
+            (0, 0) => {
+                let left = v4_lookup([eq1, eq2, eq3, eq4, eq5, eq6 + 1]);
+                let right = v4_lookup([eq1, eq2, eq3 + 1, eq4, eq5, eq6]);
+                match (left, right) {
+                    (Some(l), Some(r)) => Some(l.max(r)),
+                    (l, r) => l.or(r),
+                }
+            }
+            (1, 0) => v4_lookup([eq1, eq2, eq3, eq4, eq5, eq6 + 1]),
+            (0, 1) | (1, 1) => v4_lookup([eq1, eq2, eq3 + 1, eq4, eq5, eq6]),
+            _ => None,
+        };
+
+        // 同一 MacroVector 内最高严重度的向量中，第一个各指标都不低于当前向量的
+        let mut max_vectors = Vec::new();
+        for a in max_composed(1, eq1, eq6) {
+            for b in max_composed(2, eq2, eq6) {
+                for c in max_composed(3, eq3, eq6) {
+                    for d in max_composed(4, eq4, eq6) {
+                        for e in max_composed(5, eq5, eq6) {
+                            max_vectors.push(format!("{}{}{}{}{}", a, b, c, d, e));
+                        }
+                    }
+                }
+            }
+        }
+        const DISTANCE_METRICS: [&str; 14] = [
+            "AV", "PR", "UI", "AC", "AT", "VC", "VI", "VA", "SC", "SI", "SA", "CR", "IR", "AR",
+        ];
+        let distances = max_vectors
+            .iter()
+            .map(|max| {
+                DISTANCE_METRICS.map(|key| {
+                    let max_value = max
+                        .split('/')
+                        .find_map(|p| p.strip_prefix(key)?.strip_prefix(':'))
+                        .unwrap_or("X");
+                    v4_level(key, m(key)) - v4_level(key, max_value)
+                })
+            })
+            .find(|d| d.iter().all(|x| *x >= 0.0))
+            .unwrap_or([0.0; 14]);
+        let sum = |range: std::ops::Range<usize>| distances[range].iter().sum::<f64>();
+        let current = [sum(0..3), sum(3..5), sum(5..8) + sum(11..14), sum(8..11)];
+
+        const STEP: f64 = 0.1;
+        let max_severity = [
+            [1.0, 4.0, 5.0][eq1] * STEP,
+            [1.0, 2.0][eq2] * STEP,
+            match (eq3, eq6) {
+                (0, 0) => 7.0,
+                (0, _) => 6.0,
+                (1, _) => 8.0,
+                _ => 10.0,
+            } * STEP,
+            [6.0, 5.0, 4.0][eq4] * STEP,
+        ];
+        let lowers = [lower(0), lower(1), lower_eq3_eq6, lower(3)];
+        let mut existing = 0;
+        let mut total = 0.0;
+        for ((lower, current), max_severity) in lowers.iter().zip(current).zip(max_severity) {
+            if let Some(lower) = lower {
+                existing += 1;
+                total += (value - lower) * current / max_severity;
+            }
+        }
+        // EQ5 的距离恒为 0，但仍计入平均
+        if lower(4).is_some() {
+            existing += 1;
+        }
+        let mean = if existing == 0 {
+            0.0
+        } else {
+            total / existing as f64
+        };
+        round1((value - mean).clamp(0.0, 10.0))
+    }
+}
+
+impl fmt::Display for CvssVector {
+    /// 规范形式：按规范顺序列出，省略未定义的指标
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CVSS:{}", self.version.as_str())?;
+        for (metric, value) in self.version.metrics().iter().zip(&self.values) {
+            if let Some(value) = value {
+                write!(f, "/{}:{}", metric.key, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// v3 的影响与可利用性子分数；`modified` 时使用环境指标
+struct V3Subscores {
+    impact: f64,
+    exploitability: f64,
+    scope_changed: bool,
+}
+
+impl V3Subscores {
+    fn new(vector: &CvssVector, modified: bool) -> Self {
+        let m = |key: &str| -> &'static str {
+            let value = vector.get(&format!("M{}", key));
+            if modified && value != "X" {
+                value
+            } else {
+                vector.get(key)
+            }
+        };
+        let scope_changed = m("S") == "C";
+        let av = match m("AV") {
+            "N" => 0.85,
+            "A" => 0.62,
+            "L" => 0.55,
+            _ => 0.2,
+        };
+        let ac = if m("AC") == "L" { 0.77 } else { 0.44 };
+        let pr = match (m("PR"), scope_changed) {
+            ("N", _) => 0.85,
+            ("L", false) => 0.62,
+            ("L", true) => 0.68,
+            (_, false) => 0.27,
+            (_, true) => 0.5,
+        };
+        let ui = if m("UI") == "N" { 0.85 } else { 0.62 };
+        let cia = |key: &str| match m(key) {
+            "H" => 0.56,
+            "L" => 0.22,
+            _ => 0.0,
+        };
+        let requirement = |key: &str| match vector.get(key) {
+            "H" if modified => 1.5,
+            "L" if modified => 0.5,
+            _ => 1.0,
+        };
+        let mut iss: f64 = 1.0
+            - (1.0 - requirement("CR") * cia("C"))
+                * (1.0 - requirement("IR") * cia("I"))
+                * (1.0 - requirement("AR") * cia("A"));
+        if modified {
+            iss = iss.min(0.915);
+        }
+        let impact = if !scope_changed {
+            6.42 * iss
+        } else if modified && vector.version == CvssVersion::V3_1 {
+            7.52 * (iss - 0.029) - 3.25 * (iss * 0.9731 - 0.02).powi(13)
+        } else {
+            7.52 * (iss - 0.029) - 3.25 * (iss - 0.02).powi(15)
+        };
+        Self {
+            impact,
+            exploitability: 8.22 * av * ac * pr * ui,
+            scope_changed,
+        }
+    }
+
+    fn score(&self, version: CvssVersion) -> f64 {
+        if self.impact <= 0.0 {
+            return 0.0;
+        }
+        let factor = if self.scope_changed { 1.08 } else { 1.0 };
+        roundup(
+            version,
+            (factor * (self.impact + self.exploitability)).min(10.0),
+        )
+    }
+}
+
+/// 向上取一位小数；v3.1 先取整到 5 位小数以避免浮点误差
+fn roundup(version: CvssVersion, value: f64) -> f64 {
+    if version == CvssVersion::V3_0 {
+        return (value * 10.0).ceil() / 10.0;
+    }
+    let scaled = (value * 100_000.0).round() as i64;
+    if scaled % 10_000 == 0 {
+        scaled as f64 / 100_000.0
+    } else {
+        (scaled / 10_000 + 1) as f64 / 10.0
+    }
+}
+
+fn round1(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+/// 各 EQ 在某一等级下的最高严重度向量片段，EQ3 还取决于 EQ6
+fn max_composed(eq: usize, level: usize, eq6: usize) -> &'static [&'static str] {
+    match (eq, level) {
+        (1, 0) => &["AV:N/PR:N/UI:N/"],
+        (1, 1) => &["AV:A/PR:N/UI:N/", "AV:N/PR:L/UI:N/", "AV:N/PR:N/UI:P/"],
+        (1, _) => &["AV:P/PR:N/UI:N/", "AV:A/PR:L/UI:P/"],
+        (2, 0) => &["AC:L/AT:N/"],
+        (2, _) => &["AC:H/AT:N/", "AC:L/AT:P/"],
+        (3, 0) if eq6 == 0 => &["VC:H/VI:H/VA:H/CR:H/IR:H/AR:H/"],
+        (3, 0) => &[
+            "VC:H/VI:H/VA:L/CR:M/IR:M/AR:H/",
+            "VC:H/VI:H/VA:H/CR:M/IR:M/AR:M/",
+        ],
+        (3, 1) if eq6 == 0 => &[
+            "VC:L/VI:H/VA:H/CR:H/IR:H/AR:H/",
+            "VC:H/VI:L/VA:H/CR:H/IR:H/AR:H/",
+        ],
+        (3, 1) => &[
+            "VC:L/VI:H/VA:L/CR:H/IR:M/AR:H/",
+            "VC:L/VI:H/VA:H/CR:H/IR:M/AR:M/",
+            "VC:H/VI:L/VA:H/CR:M/IR:H/AR:M/",
+            "VC:H/VI:L/VA:L/CR:M/IR:H/AR:H/",
+            "VC:L/VI:L/VA:H/CR:H/IR:H/AR:M/",
+        ],
+        (3, _) => &["VC:L/VI:L/VA:L/CR:H/IR:H/AR:H/"],
+        (4, 0) => &["SC:H/SI:S/SA:S/"],
+        (4, 1) => &["SC:H/SI:H/SA:H/"],
+        (4, _) => &["SC:L/SI:L/SA:L/"],
+        (_, 0) => &["E:A/"],
+        (_, 1) => &["E:P/"],
+        _ => &["E:U/"],
+    }
+}
+
+/// 指标取值的严重度等级，数值越小越严重
+fn v4_level(key: &str, value: &str) -> f64 {
+    match (key, value) {
+        ("AV", "N") | ("PR", "N") | ("UI", "N") | ("AC", "L") | ("AT", "N") => 0.0,
+        ("AV", "A") | ("PR", "L") | ("UI", "P") | ("AC", "H") | ("AT", "P") => 0.1,
+        ("AV", "L") | ("PR", "H") | ("UI", "A") => 0.2,
+        ("AV", "P") => 0.3,
+        ("VC" | "VI" | "VA", "H") => 0.0,
+        ("VC" | "VI" | "VA", "L") => 0.1,
+        ("VC" | "VI" | "VA", "N") => 0.2,
+        ("SI" | "SA", "S") => 0.0,
+        ("SC" | "SI" | "SA", "H") => 0.1,
+        ("SC" | "SI" | "SA", "L") => 0.2,
+        ("SC" | "SI" | "SA", "N") => 0.3,
+        ("CR" | "IR" | "AR", "H") => 0.0,
+        ("CR" | "IR" | "AR", "M") => 0.1,
+        ("CR" | "IR" | "AR", "L") => 0.2,
+        _ => 0.0,
+    }
+}
+
+fn v4_lookup(macro_vector: [usize; 6]) -> Option<f64> {
+    let key: String = macro_vector.iter().map(|eq| eq.to_string()).collect();
+    V4_LOOKUP
+        .binary_search_by(|(k, _)| (*k).cmp(key.as_str()))
+        .ok()
+        .map(|i| V4_LOOKUP[i].1)
+}
+
+/// CVSS v4.0 规范中各 MacroVector（EQ1..EQ6）的分数
+#[rustfmt::skip]
+const V4_LOOKUP: &[(&str, f64)] = &[
+    ("000000", 10.0), ("000001", 9.9), ("000010", 9.8), ("000011", 9.5), ("000020", 9.5), ("000021", 9.2),
+    ("000100", 10.0), ("000101", 9.6), ("000110", 9.3), ("000111", 8.7), ("000120", 9.1), ("000121", 8.1),
+    ("000200", 9.3), ("000201", 9.0), ("000210", 8.9), ("000211", 8.0), ("000220", 8.1), ("000221", 6.8),
+    ("001000", 9.8), ("001001", 9.5), ("001010", 9.5), ("001011", 9.2), ("001020", 9.0), ("001021", 8.4),
+    ("001100", 9.3), ("001101", 9.2), ("001110", 8.9), ("001111", 8.1), ("001120", 8.1), ("001121", 6.5),
+    ("001200", 8.8), ("001201", 8.0), ("001210", 7.8), ("001211", 7.0), ("001220", 6.9), ("001221", 4.8),
+    ("002001", 9.2), ("002011", 8.2), ("002021", 7.2), ("002101", 7.9), ("002111", 6.9), ("002121", 5.0),
+    ("002201", 6.9), ("002211", 5.5), ("002221", 2.7),
+    ("010000", 9.9), ("010001", 9.7), ("010010", 9.5), ("010011", 9.2), ("010020", 9.2), ("010021", 8.5),
+    ("010100", 9.5), ("010101", 9.1), ("010110", 9.0), ("010111", 8.3), ("010120", 8.4), ("010121", 7.1),
+    ("010200", 9.2), ("010201", 8.1), ("010210", 8.2), ("010211", 7.1), ("010220", 7.2), ("010221", 5.3),
+    ("011000", 9.5), ("011001", 9.3), ("011010", 9.2), ("011011", 8.5), ("011020", 8.5), ("011021", 7.3),
+    ("011100", 9.2), ("011101", 8.2), ("011110", 8.0), ("011111", 7.2), ("011120", 7.0), ("011121", 5.9),
+    ("011200", 8.4), ("011201", 7.0), ("011210", 7.1), ("011211", 5.2), ("011220", 5.0), ("011221", 3.0),
+    ("012001", 8.6), ("012011", 7.5), ("012021", 5.2), ("012101", 7.1), ("012111", 5.2), ("012121", 2.9),
+    ("012201", 6.3), ("012211", 2.9), ("012221", 1.7),
+    ("100000", 9.8), ("100001", 9.5), ("100010", 9.4), ("100011", 8.7), ("100020", 9.1), ("100021", 8.1),
+    ("100100", 9.4), ("100101", 8.9), ("100110", 8.6), ("100111", 7.4), ("100120", 7.7), ("100121", 6.4),
+    ("100200", 8.7), ("100201", 7.5), ("100210", 7.4), ("100211", 6.3), ("100220", 6.3), ("100221", 4.9),
+    ("101000", 9.4), ("101001", 8.9), ("101010", 8.8), ("101011", 7.7), ("101020", 7.6), ("101021", 6.7),
+    ("101100", 8.6), ("101101", 7.6), ("101110", 7.4), ("101111", 5.8), ("101120", 5.9), ("101121", 5.0),
+    ("101200", 7.2), ("101201", 5.7), ("101210", 5.7), ("101211", 5.2), ("101220", 5.2), ("101221", 2.5),
+    ("102001", 8.3), ("102011", 7.0), ("102021", 5.4), ("102101", 6.5), ("102111", 5.8), ("102121", 2.6),
+    ("102201", 5.3), ("102211", 2.1), ("102221", 1.3),
+    ("110000", 9.5), ("110001", 9.0), ("110010", 8.8), ("110011", 7.6), ("110020", 7.6), ("110021", 7.0),
+    ("110100", 9.0), ("110101", 7.7), ("110110", 7.5), ("110111", 6.2), ("110120", 6.1), ("110121", 5.3),
+    ("110200", 7.7), ("110201", 6.6), ("110210", 6.8), ("110211", 5.9), ("110220", 5.2), ("110221", 3.0),
+    ("111000", 8.9), ("111001", 7.8), ("111010", 7.6), ("111011", 6.7), ("111020", 6.2), ("111021", 5.8),
+    ("111100", 7.4), ("111101", 5.9), ("111110", 5.7), ("111111", 5.7), ("111120", 4.7), ("111121", 2.3),
+    ("111200", 6.1), ("111201", 5.2), ("111210", 5.7), ("111211", 2.9), ("111220", 2.4), ("111221", 1.6),
+    ("112001", 7.1), ("112011", 5.9), ("112021", 3.0), ("112101", 5.8), ("112111", 2.6), ("112121", 1.5),
+    ("112201", 2.3), ("112211", 1.3), ("112221", 0.6),
+    ("200000", 9.3), ("200001", 8.7), ("200010", 8.6), ("200011", 7.2), ("200020", 7.5), ("200021", 5.8),
+    ("200100", 8.6), ("200101", 7.4), ("200110", 7.4), ("200111", 6.1), ("200120", 5.6), ("200121", 3.4),
+    ("200200", 7.0), ("200201", 5.4), ("200210", 5.2), ("200211", 4.0), ("200220", 4.0), ("200221", 2.2),
+    ("201000", 8.5), ("201001", 7.5), ("201010", 7.4), ("201011", 5.5), ("201020", 6.2), ("201021", 5.1),
+    ("201100", 7.2), ("201101", 5.7), ("201110", 5.5), ("201111", 4.1), ("201120", 4.6), ("201121", 1.9),
+    ("201200", 5.3), ("201201", 3.6), ("201210", 3.4), ("201211", 1.9), ("201220", 1.9), ("201221", 0.8),
+    ("202001", 6.4), ("202011", 5.1), ("202021", 2.0), ("202101", 4.7), ("202111", 2.1), ("202121", 1.1),
+    ("202201", 2.4), ("202211", 0.9), ("202221", 0.4),
+    ("210000", 8.8), ("210001", 7.5), ("210010", 7.3), ("210011", 5.3), ("210020", 6.0), ("210021", 5.0),
+    ("210100", 7.3), ("210101", 5.5), ("210110", 5.9), ("210111", 4.0), ("210120", 4.1), ("210121", 2.0),
+    ("210200", 5.4), ("210201", 4.3), ("210210", 4.5), ("210211", 2.2), ("210220", 2.0), ("210221", 1.1),
+    ("211000", 7.5), ("211001", 5.5), ("211010", 5.8), ("211011", 4.5), ("211020", 4.0), ("211021", 2.1),
+    ("211100", 6.1), ("211101", 5.1), ("211110", 4.8), ("211111", 1.8), ("211120", 2.0), ("211121", 0.9),
+    ("211200", 4.6), ("211201", 1.8), ("211210", 1.7), ("211211", 0.7), ("211220", 0.8), ("211221", 0.2),
+    ("212001", 5.3), ("212011", 2.4), ("212021", 1.4), ("212101", 2.4), ("212111", 1.2), ("212121", 0.5),
+    ("212201", 1.0), ("212211", 0.3), ("212221", 0.1),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CvssScore, VulnData, VulnSeverity};
+
+    fn scores(vector: &str) -> CvssScores {
+        CvssVector::parse(vector)
+            .unwrap_or_else(|e| panic!("{vector}: {e}"))
+            .scores()
+    }
+
+    #[test]
+    fn v3_base_scores() {
+        let critical = scores("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H");
+        assert_eq!(critical.base, 9.8);
+        assert_eq!(critical.exploitability, Some(3.9));
+        assert_eq!(critical.impact, Some(5.9));
+        assert_eq!(critical.temporal, None);
+        assert_eq!(critical.environmental, None);
+
+        assert_eq!(
+            scores("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:C/C:H/I:H/A:H").base,
+            10.0
+        );
+        assert_eq!(
+            scores("CVSS:3.1/AV:N/AC:L/PR:L/UI:N/S:C/C:L/I:L/A:N").base,
+            6.4
+        );
+        assert_eq!(
+            scores("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:N/I:N/A:N").base,
+            0.0
+        );
+    }
+
+    #[test]
+    fn v3_temporal_and_environmental_scores() {
+        let temporal = scores("CVSS:3.0/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H/E:P/RL:O/RC:C");
+        assert_eq!(temporal.base, 9.8);
+        assert_eq!(temporal.temporal, Some(8.8));
+        assert_eq!(temporal.environmental, None);
+
+        // 作用域改变后与 S:C 的基础分数一致
+        let environmental = scores("CVSS:3.1/AV:N/AC:L/PR:L/UI:N/S:U/C:L/I:L/A:N/MS:C");
+        assert_eq!(environmental.base, 5.4);
+        assert_eq!(environmental.environmental, Some(6.4));
+    }
+
+    #[test]
+    fn v4_scores() {
+        for (vector, expected) in [
+            (
+                "CVSS:4.0/AV:N/AC:L/AT:N/PR:N/UI:N/VC:H/VI:H/VA:H/SC:N/SI:N/SA:N",
+                9.3,
+            ),
+            (
+                "CVSS:4.0/AV:L/AC:L/AT:N/PR:L/UI:N/VC:H/VI:H/VA:H/SC:N/SI:N/SA:N",
+                8.5,
+            ),
+            (
+                "CVSS:4.0/AV:N/AC:L/AT:N/PR:L/UI:N/VC:H/VI:H/VA:H/SC:N/SI:N/SA:N",
+                8.7,
+            ),
+            (
+                "CVSS:4.0/AV:N/AC:L/AT:N/PR:N/UI:N/VC:H/VI:H/VA:H/SC:H/SI:H/SA:H",
+                10.0,
+            ),
+            (
+                "CVSS:4.0/AV:N/AC:L/AT:N/PR:N/UI:N/VC:L/VI:N/VA:N/SC:N/SI:N/SA:N",
+                6.9,
+            ),
+            (
+                "CVSS:4.0/AV:N/AC:L/AT:N/PR:N/UI:N/VC:N/VI:N/VA:N/SC:N/SI:N/SA:N",
+                0.0,
+            ),
+        ] {
+            let scores = scores(vector);
+            assert_eq!(scores.base, expected, "{vector}");
+            assert_eq!(scores.exploitability, None);
+        }
+
+        // 未定义的 E 按 Attacked 计算，显式的 E:U 降低 CVSS-BT
+        let threat = scores("CVSS:4.0/AV:N/AC:L/AT:N/PR:N/UI:N/VC:H/VI:H/VA:H/SC:N/SI:N/SA:N/E:U");
+        assert_eq!(threat.base, 9.3);
+        assert!(threat.temporal.unwrap() < 9.3);
+        let environmental =
+            scores("CVSS:4.0/AV:N/AC:L/AT:N/PR:N/UI:N/VC:H/VI:H/VA:H/SC:N/SI:N/SA:N/MSI:S");
+        assert_eq!(environmental.environmental, Some(10.0));
+    }
+
+    #[test]
+    fn invalid_vectors_are_rejected() {
+        for (vector, message) in [
+            (
+                "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H/AV:L",
+                "appears twice",
+            ),
+            ("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H", "missing A"),
+            (
+                "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H/XX:Y",
+                "unknown CVSS 3.1 metric XX",
+            ),
+            (
+                "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H/AT:N",
+                "unknown CVSS 3.1 metric AT",
+            ),
+            (
+                "CVSS:3.1/AV:Q/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H",
+                "not a valid value",
+            ),
+            (
+                "CVSS:4.0/AV:N/AC:L/AT:N/PR:N/UI:N/VC:H/VI:H/VA:H/SC:N/SI:N",
+                "missing SA",
+            ),
+            ("CVSS:2.0/AV:N/AC:L/Au:N/C:P/I:P/A:P", "unsupported"),
+            ("AV:N/AC:L/Au:N/C:P/I:P/A:P", "not a CVSS v3 or v4 vector"),
+        ] {
+            let error = CvssVector::parse(vector).expect_err(vector).to_string();
+            assert!(error.contains(message), "{vector}: {error}");
+        }
+    }
+
+    #[test]
+    fn vectors_print_in_canonical_order() {
+        let vector =
+            CvssVector::parse("CVSS:3.1/S:U/AV:N/AC:L/PR:N/UI:N/C:H/I:H/A:H/MAV:X").unwrap();
+        assert_eq!(
+            vector.to_string(),
+            "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H"
+        );
+    }
+
+    #[test]
+    fn adjusting_metrics_recomputes_severity() {
+        let mut vuln = VulnData::new(
+            "V-1".to_string(),
+            "Unauthenticated MAVLink".to_string(),
+            String::new(),
+            VulnSeverity::Info,
+        );
+        assert!(vuln.adjust_cvss("MAV:L").is_err());
+
+        vuln.set_cvss(
+            CvssScore::from_vector("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H").unwrap(),
+        );
+        assert_eq!(vuln.severity, VulnSeverity::Critical);
+
+        // 实验室环境：只能物理接触，且不影响可用性
+        vuln.adjust_cvss("MAV:P/MA:N").unwrap();
+        let cvss = vuln.cvss.clone().unwrap();
+        assert_eq!(cvss.base_score, 9.8);
+        assert!(cvss.environmental_score.unwrap() < 7.0);
+        assert_eq!(vuln.severity, cvss.severity());
+        assert_ne!(vuln.severity, VulnSeverity::Critical);
+
+        // 非法指标不改变已有评分
+        assert!(vuln.adjust_cvss("MAV:Q").is_err());
+        assert_eq!(vuln.cvss.unwrap().vector_string, cvss.vector_string);
+    }
+}
@@ -1,10 +1,13 @@
 pub mod models;
+pub mod cvss;
+mod convert;
 pub mod repository;
 pub mod memory;
 pub mod database;
 pub mod task_store;
 
 pub use models::*;
+pub use cvss::*;
 pub use repository::*;
 pub use memory::*;
 pub use database::*;
@@ -98,6 +98,12 @@ pub struct VulnData {
     pub ai_analysis: Option<AiSecurityAnalysis>,
     pub references: Vec<String>,
     pub tags: Vec<String>,
+    /// 修复建议
+    #[serde(default)]
+    pub remediation: Option<String>,
+    /// 支撑该漏洞的原始观测，如报文十六进制转储
+    #[serde(default)]
+    pub evidence: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
@@ -130,7 +136,7 @@ pub struct CvssScore {
     pub impact: Option<f64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DetectionLocation {
     pub component: String,
     pub file_path: Option<String>,
@@ -139,7 +145,7 @@ pub struct DetectionLocation {
     pub source: DetectionSource,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DetectionSource {
     StaticAnalysis,
     DynamicAnalysis,
@@ -211,9 +217,21 @@ impl VulnData {
             ai_analysis: None,
             references: Vec::new(),
             tags: Vec::new(),
+            remediation: None,
+            evidence: Vec::new(),
         }
     }
 
+    /// 去重键：同一问题（有 CVE 按 CVE，否则按标题）出现在同一位置即视为同一漏洞
+    pub fn dedup_key(&self) -> String {
+        let issue = self.cve.as_deref().unwrap_or(&self.title);
+        format!(
+            "{}@{}",
+            issue.trim().to_lowercase(),
+            self.affected.trim().to_lowercase()
+        )
+    }
+
     pub fn is_high_severity(&self) -> bool {
         matches!(self.severity, VulnSeverity::Critical | VulnSeverity::High)
     }
@@ -221,21 +239,43 @@ impl VulnData {
     pub fn has_exploit(&self) -> bool {
         self.exploit_available && self.exploit_maturity.is_some()
     }
+
+    /// 设置 CVSS 评分，并以其最具体的分数更新严重度
+    pub fn set_cvss(&mut self, cvss: CvssScore) {
+        self.severity = cvss.severity();
+        self.cvss = Some(cvss);
+    }
+
+    /// 按本次测试环境调整 CVSS 指标（如 `MAV:L/MSI:S`）并重新计算严重度
+    pub fn adjust_cvss(&mut self, metrics: &str) -> anyhow::Result<()> {
+        let Some(cvss) = &self.cvss else {
+            anyhow::bail!("{} has no CVSS vector to adjust", self.id);
+        };
+        let adjusted = cvss.with_metrics(metrics)?;
+        self.set_cvss(adjusted);
+        Ok(())
+    }
 }
 
-impl CvssScore {
-    pub fn new(base_score: f64, vector_string: String) -> Self {
-        let base_severity = match base_score {
+impl VulnSeverity {
+    /// CVSS 定性评级（v3 与 v4 相同），0.0 为 Info
+    pub fn from_cvss(score: f64) -> Self {
+        match score {
             s if s >= 9.0 => VulnSeverity::Critical,
             s if s >= 7.0 => VulnSeverity::High,
             s if s >= 4.0 => VulnSeverity::Medium,
             s if s > 0.0 => VulnSeverity::Low,
             _ => VulnSeverity::Info,
-        };
+        }
+    }
+}
 
+impl CvssScore {
+    /// 使用外部给出的基础分数，不校验向量
+    pub fn new(base_score: f64, vector_string: String) -> Self {
         Self {
             base_score,
-            base_severity,
+            base_severity: VulnSeverity::from_cvss(base_score),
             temporal_score: None,
             environmental_score: None,
             vector_string,
@@ -243,6 +283,44 @@ impl CvssScore {
             impact: None,
         }
     }
+
+    /// 解析 CVSS v3.0/v3.1/v4.0 向量并计算全部分数
+    pub fn from_vector(vector: &str) -> anyhow::Result<Self> {
+        Ok(Self::from_parsed(&crate::cvss::CvssVector::parse(vector)?))
+    }
+
+    fn from_parsed(vector: &crate::cvss::CvssVector) -> Self {
+        let scores = vector.scores();
+        Self {
+            base_score: scores.base,
+            base_severity: VulnSeverity::from_cvss(scores.base),
+            temporal_score: scores.temporal,
+            environmental_score: scores.environmental,
+            vector_string: vector.to_string(),
+            exploitability: scores.exploitability,
+            impact: scores.impact,
+        }
+    }
+
+    /// 覆盖部分指标后重新计算，例如实验室环境 `MAV:L/MSI:N/MSA:N`，
+    /// 飞行中 `MSI:S/MSA:S/AR:H`
+    pub fn with_metrics(&self, metrics: &str) -> anyhow::Result<Self> {
+        let mut vector = crate::cvss::CvssVector::parse(&self.vector_string)?;
+        vector.apply(metrics)?;
+        Ok(Self::from_parsed(&vector))
+    }
+
+    /// 最具体的分数：环境分数，其次时间分数，最后基础分数
+    pub fn score(&self) -> f64 {
+        self.environmental_score
+            .or(self.temporal_score)
+            .unwrap_or(self.base_score)
+    }
+
+    /// 与 `score` 对应的严重度
+    pub fn severity(&self) -> VulnSeverity {
+        VulnSeverity::from_cvss(self.score())
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -928,6 +1006,12 @@ pub struct AssetNode {
     pub firmware_version: Option<String>,
     pub manufacturer: Option<String>,
     pub location: Option<String>,
+    /// 从飞控导出的参数、任务和版本信息
+    #[serde(default)]
+    pub vehicle_artifacts: Vec<VehicleArtifacts>,
+    /// 从 .px4/.apj/Intel HEX 固件文件识别出的飞控固件
+    #[serde(default)]
+    pub flight_firmware: Vec<FlightFirmwareInfo>,
 }
 
 impl AssetNode {
@@ -962,6 +1046,8 @@ impl AssetNode {
             firmware_version: None,
             manufacturer: None,
             location: None,
+            vehicle_artifacts: Vec::new(),
+            flight_firmware: Vec::new(),
         }
     }
 
@@ -985,3 +1071,215 @@ impl AssetNode {
         self.severity.color_hex()
     }
 }
+
+/// 飞控参数（PARAM_VALUE）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VehicleParameter {
+    pub name: String,
+    /// 整型参数已按飞控的编码方式还原
+    pub value: f64,
+    /// MAV_PARAM_TYPE
+    pub param_type: u8,
+    pub index: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MissionType {
+    Mission,
+    Fence,
+    Rally,
+}
+
+impl MissionType {
+    pub const ALL: [MissionType; 3] = [MissionType::Mission, MissionType::Fence, MissionType::Rally];
+
+    /// MAV_MISSION_TYPE
+    pub fn mav_value(&self) -> u8 {
+        match self {
+            MissionType::Mission => 0,
+            MissionType::Fence => 1,
+            MissionType::Rally => 2,
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            MissionType::Mission => "航线",
+            MissionType::Fence => "地理围栏",
+            MissionType::Rally => "集结点",
+        }
+    }
+}
+
+/// 任务条目（MISSION_ITEM_INT）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MissionItem {
+    pub seq: u16,
+    /// MAV_FRAME
+    pub frame: u8,
+    /// MAV_CMD
+    pub command: u16,
+    pub current: bool,
+    pub autocontinue: bool,
+    pub params: [f32; 4],
+    /// 纬度 * 1e7 或本地坐标 * 1e4
+    pub x: i32,
+    /// 经度 * 1e7 或本地坐标 * 1e4
+    pub y: i32,
+    pub z: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MissionPlan {
+    pub mission_type: MissionType,
+    /// 飞控在 MISSION_COUNT 中声明的条目数
+    pub expected_count: u16,
+    pub items: Vec<MissionItem>,
+}
+
+impl MissionPlan {
+    pub fn is_complete(&self) -> bool {
+        self.items.len() == usize::from(self.expected_count)
+    }
+}
+
+/// 飞控版本信息（AUTOPILOT_VERSION）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutopilotVersionInfo {
+    /// MAV_PROTOCOL_CAPABILITY 位图
+    pub capabilities: u64,
+    pub flight_sw_version: u32,
+    pub middleware_sw_version: u32,
+    pub os_sw_version: u32,
+    pub board_version: u32,
+    /// 通常为 git hash 前 8 字节的十六进制
+    pub flight_custom_version: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub uid: u64,
+}
+
+impl AutopilotVersionInfo {
+    /// flight_sw_version 按 major.minor.patch-type 解码
+    pub fn flight_version_string(&self) -> String {
+        let v = self.flight_sw_version;
+        let release = match v & 0xff {
+            0 => "dev",
+            64 => "alpha",
+            128 => "beta",
+            192 => "rc",
+            255 => "official",
+            _ => "unknown",
+        };
+        format!("{}.{}.{}-{}", v >> 24, (v >> 16) & 0xff, (v >> 8) & 0xff, release)
+    }
+}
+
+/// 一次从飞控导出的数据，关联到 AssetNode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VehicleArtifacts {
+    pub asset_id: String,
+    /// 连接地址，例如 udp:10.0.0.5:14550
+    pub target: String,
+    pub system_id: u8,
+    pub component_id: u8,
+    pub collected_at: String,
+    pub autopilot_version: Option<AutopilotVersionInfo>,
+    /// 飞控在 PARAM_VALUE 中声明的参数总数
+    pub parameter_count: u16,
+    pub parameters: Vec<VehicleParameter>,
+    pub plans: Vec<MissionPlan>,
+}
+
+impl VehicleArtifacts {
+    pub fn new(asset_id: String, target: String, collected_at: String) -> Self {
+        Self {
+            asset_id,
+            target,
+            system_id: 0,
+            component_id: 0,
+            collected_at,
+            autopilot_version: None,
+            parameter_count: 0,
+            parameters: Vec::new(),
+            plans: Vec::new(),
+        }
+    }
+
+    pub fn parameter(&self, name: &str) -> Option<&VehicleParameter> {
+        self.parameters.iter().find(|p| p.name == name)
+    }
+
+    pub fn plan(&self, mission_type: MissionType) -> Option<&MissionPlan> {
+        self.plans.iter().find(|p| p.mission_type == mission_type)
+    }
+
+    /// 参数表是否完整下载
+    pub fn parameters_complete(&self) -> bool {
+        self.parameters.len() == usize::from(self.parameter_count)
+    }
+
+    /// 与之前一次导出的参数对比，按参数名排序
+    pub fn diff_parameters(&self, previous: &VehicleArtifacts) -> Vec<ParameterChange> {
+        let before: HashMap<&str, f64> = previous
+            .parameters
+            .iter()
+            .map(|p| (p.name.as_str(), p.value))
+            .collect();
+        let after: HashMap<&str, f64> = self
+            .parameters
+            .iter()
+            .map(|p| (p.name.as_str(), p.value))
+            .collect();
+
+        let mut names: Vec<&str> = before.keys().chain(after.keys()).copied().collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+            .into_iter()
+            .filter_map(|name| {
+                let (old, new) = (before.get(name).copied(), after.get(name).copied());
+                (old != new).then(|| ParameterChange {
+                    name: name.to_string(),
+                    before: old,
+                    after: new,
+                })
+            })
+            .collect()
+    }
+}
+
+/// 参数变化；before/after 为 None 表示该参数新增或被移除
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParameterChange {
+    pub name: String,
+    pub before: Option<f64>,
+    pub after: Option<f64>,
+}
+
+/// 飞控固件文件（.px4/.apj/Intel HEX/bin）的识别结果，关联到 AssetNode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlightFirmwareInfo {
+    /// 固件文件路径
+    pub file: String,
+    /// px4、apj、ihex 或 bin
+    pub format: String,
+    /// ArduPilot 或 PX4
+    pub autopilot: Option<String>,
+    /// 机型固件，例如 ArduCopter
+    pub vehicle: Option<String>,
+    pub board_id: Option<u32>,
+    pub board_revision: Option<u32>,
+    /// 板卡名称，例如 CubeOrange、PX4_FMU_V5
+    pub board: Option<String>,
+    pub version: Option<String>,
+    pub git_hash: Option<String>,
+    /// RFC 3339
+    pub build_time: Option<String>,
+    /// 解码后的镜像大小
+    pub image_size: u64,
+    pub image_sha256: String,
+    /// 固件内嵌的参数默认值
+    #[serde(default)]
+    pub parameter_defaults: Vec<VehicleParameter>,
+}
@@ -0,0 +1,124 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const BUILTIN_CREDENTIALS: &str = include_str!("../signatures/credentials.toml");
+
+#[derive(Debug, Clone, Deserialize)]
+struct CredentialFile {
+    #[serde(default)]
+    credentials: Vec<DefaultCredential>,
+}
+
+/// A username/password pair shipped as a factory default somewhere.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DefaultCredential {
+    pub username: String,
+    pub password: String,
+    /// Product or firmware the pair is known from.
+    pub vendor: Option<String>,
+    /// Protocols the pair is tried against, e.g. `ftp`, `telnet`, `rtsp`.
+    #[serde(default)]
+    pub services: Vec<String>,
+}
+
+impl DefaultCredential {
+    /// `user:password`, with `<empty>` for a blank password.
+    pub fn display(&self) -> String {
+        let password = if self.password.is_empty() {
+            "<empty>"
+        } else {
+            &self.password
+        };
+        format!("{}:{}", self.username, password)
+    }
+
+    /// Converts a pair that worked into the form stored on an asset.
+    pub fn to_credential(&self, auth_type: &str) -> data::Credential {
+        data::Credential {
+            username: self.username.clone(),
+            password: self.password.clone(),
+            auth_type: auth_type.to_string(),
+            last_used: Some(chrono::Utc::now().to_rfc3339()),
+        }
+    }
+}
+
+/// Default credentials from `signatures/credentials.toml` plus any
+/// user-supplied lists, shared by the service checkers.
+#[derive(Debug, Clone)]
+pub struct CredentialList {
+    credentials: Vec<DefaultCredential>,
+}
+
+impl CredentialList {
+    pub fn new() -> Self {
+        let mut list = Self::empty();
+        list.add(BUILTIN_CREDENTIALS)
+            .expect("built-in credential list must be valid");
+        list
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            credentials: Vec::new(),
+        }
+    }
+
+    pub fn load(&mut self, path: &Path) -> Result<usize> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read credential file {}", path.display()))?;
+        self.add(&content)
+            .with_context(|| format!("invalid credential file {}", path.display()))
+    }
+
+    /// Adds pairs from a TOML list. They are tried before the ones already
+    /// loaded; a pair that is already present is moved to the front.
+    pub fn add(&mut self, content: &str) -> Result<usize> {
+        let mut file: CredentialFile = toml::from_str(content)?;
+        let count = file.credentials.len();
+        self.credentials.retain(|existing| {
+            !file
+                .credentials
+                .iter()
+                .any(|c| c.username == existing.username && c.password == existing.password)
+        });
+        file.credentials.append(&mut self.credentials);
+        self.credentials = file.credentials;
+        Ok(count)
+    }
+
+    /// Adds a single pair for `services` in front of the list.
+    pub fn push_front(&mut self, username: &str, password: &str, services: &[&str]) {
+        self.credentials
+            .retain(|c| c.username != username || c.password != password);
+        self.credentials.insert(
+            0,
+            DefaultCredential {
+                username: username.to_string(),
+                password: password.to_string(),
+                vendor: None,
+                services: services.iter().map(|s| s.to_string()).collect(),
+            },
+        );
+    }
+
+    pub fn all(&self) -> &[DefaultCredential] {
+        &self.credentials
+    }
+
+    /// Pairs tagged for `service`, in the order they should be tried.
+    /// Untagged pairs apply to every service.
+    pub fn for_service(&self, service: &str) -> Vec<&DefaultCredential> {
+        self.credentials
+            .iter()
+            .filter(|c| c.services.is_empty() || c.services.iter().any(|s| s == service))
+            .collect()
+    }
+}
+
+impl Default for CredentialList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,39 +1,517 @@
-use crate::{ScanResult, ScanType, Finding};
-use anyhow::Result;
+pub mod container;
+pub mod cve;
+pub mod diff;
+#[cfg(test)]
+pub(crate) mod fixtures;
+pub mod flight;
+pub mod hardening;
+pub mod sbom;
+pub mod secrets;
+pub mod signature;
+pub mod squashfs;
+pub mod strings;
+pub mod unpack;
+
+pub use container::{ContainerKind, EntryType};
+pub use cve::{CveDataset, CveRecord, VersionRange};
+pub use diff::{ChangeKind, FileChange, FirmwareDiff, SecretDiff, SymbolDiff};
+pub use flight::{FlightFirmware, FlightImageFormat};
+pub use hardening::{BinaryAudit, BinaryKind, DangerousFunctions, Mitigations, Relro};
+pub use sbom::{Component, ComponentRules, ComponentSource, Sbom};
+pub use secrets::{SecretKind, SecretMatch, SecretRules};
+pub use signature::{EntropyClass, EntropyRegion, SignatureCategory, SignatureHit, SignatureMap};
+pub use strings::{ExtractedString, StringEncoding};
+pub use unpack::{Manifest, ManifestEntry, UnpackLimits, Unpacker};
+
+use crate::{Finding, ScanResult, ScanType};
+use anyhow::{anyhow, bail, Context, Result};
+use core::vuln_db::VulnerabilityDatabase;
+use data::VulnData;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Mirrors the firmware settings of the `[scanner]` section of `config.toml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FirmwareConfig {
+    /// Largest image accepted, and largest single file after decompression.
+    pub firmware_max_size_mb: u64,
+    pub string_min_length: usize,
+    pub extract_timeout_seconds: u64,
+    /// Containers nested deeper than this are not opened.
+    pub extract_max_depth: usize,
+    pub extract_max_files: usize,
+    /// Locally imported CVE files, in the format of
+    /// `signatures/firmware_cves.json`, matched on top of the built-in set.
+    pub cve_datasets: Vec<PathBuf>,
+}
+
+impl Default for FirmwareConfig {
+    fn default() -> Self {
+        Self {
+            firmware_max_size_mb: 512,
+            string_min_length: 4,
+            extract_timeout_seconds: 300,
+            extract_max_depth: 8,
+            extract_max_files: 100_000,
+            cve_datasets: Vec::new(),
+        }
+    }
+}
+
+impl FirmwareConfig {
+    pub fn unpack_limits(&self) -> UnpackLimits {
+        let max_file_bytes = self.firmware_max_size_mb * 1024 * 1024;
+        UnpackLimits {
+            max_file_bytes,
+            // Room for a compressed image to expand into its filesystem.
+            max_total_bytes: max_file_bytes * 4,
+            max_depth: self.extract_max_depth,
+            max_files: self.extract_max_files,
+            timeout: Duration::from_secs(self.extract_timeout_seconds),
+        }
+    }
+}
 
 pub struct FirmwareAnalyzer {
     firmware_path: PathBuf,
+    config: FirmwareConfig,
+    work_dir: Option<PathBuf>,
+    secret_rules: SecretRules,
+    component_rules: ComponentRules,
+    cve_dataset: Option<CveDataset>,
+    vuln_db: Option<Arc<VulnerabilityDatabase>>,
+    dangerous_functions: DangerousFunctions,
 }
 
 impl FirmwareAnalyzer {
     pub fn new(firmware_path: PathBuf) -> Self {
-        Self { firmware_path }
+        Self {
+            firmware_path,
+            config: FirmwareConfig::default(),
+            work_dir: None,
+            secret_rules: SecretRules::new(),
+            component_rules: ComponentRules::new(),
+            cve_dataset: None,
+            vuln_db: None,
+            dangerous_functions: DangerousFunctions::new(),
+        }
+    }
+
+    pub fn with_config(mut self, config: FirmwareConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn with_secret_rules(mut self, secret_rules: SecretRules) -> Self {
+        self.secret_rules = secret_rules;
+        self
+    }
+
+    pub fn with_component_rules(mut self, component_rules: ComponentRules) -> Self {
+        self.component_rules = component_rules;
+        self
+    }
+
+    /// Matches components against `cve_dataset` instead of the built-in
+    /// set plus `cve_datasets` from the config.
+    pub fn with_cve_dataset(mut self, cve_dataset: CveDataset) -> Self {
+        self.cve_dataset = Some(cve_dataset);
+        self
+    }
+
+    /// Also reports the entries of `vuln_db` (e.g. imported NVD feeds)
+    /// matching the CPE of each SBOM component.
+    pub fn with_vulnerability_database(mut self, vuln_db: Arc<VulnerabilityDatabase>) -> Self {
+        self.vuln_db = Some(vuln_db);
+        self
+    }
+
+    pub fn with_dangerous_functions(mut self, dangerous_functions: DangerousFunctions) -> Self {
+        self.dangerous_functions = dangerous_functions;
+        self
+    }
+
+    /// Extracts into `work_dir` instead of a fresh directory under the
+    /// system temp directory.
+    pub fn with_work_dir(mut self, work_dir: PathBuf) -> Self {
+        self.work_dir = Some(work_dir);
+        self
+    }
+
+    /// Where `unpack` extracts to.
+    pub fn work_dir(&self) -> PathBuf {
+        self.work_dir.clone().unwrap_or_else(|| {
+            let name = self
+                .firmware_path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "firmware".to_string());
+            std::env::temp_dir().join(format!(
+                "uavred-{}-{}",
+                name,
+                chrono::Utc::now().format("%Y%m%d%H%M%S%3f")
+            ))
+        })
+    }
+
+    /// Maps signatures and entropy across the raw image without extracting
+    /// anything.
+    pub async fn scan_signatures(&self) -> Result<SignatureMap> {
+        let path = self.firmware_path.clone();
+        let max = self.config.unpack_limits().max_file_bytes;
+        tokio::task::spawn_blocking(move || {
+            let size = std::fs::metadata(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?
+                .len();
+            if size > max {
+                bail!(
+                    "{} is {} bytes, over the {} byte limit",
+                    path.display(),
+                    size,
+                    max
+                );
+            }
+            let data = std::fs::read(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            Ok(SignatureMap::scan(&data))
+        })
+        .await
+        .map_err(|e| anyhow!("signature scan failed: {}", e))?
+    }
+
+    /// Decodes the image if it is a `.px4`, `.apj`, Intel HEX or ArduPilot
+    /// binary build and reads the board, version, git hash and parameter
+    /// defaults from it. `FlightFirmware::apply_to` records them on an asset.
+    pub async fn identify_flight_firmware(&self) -> Result<Option<FlightFirmware>> {
+        let path = self.firmware_path.clone();
+        let max = self.config.unpack_limits().max_file_bytes;
+        tokio::task::spawn_blocking(move || {
+            let size = std::fs::metadata(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?
+                .len();
+            if size > max {
+                bail!(
+                    "{} is {} bytes, over the {} byte limit",
+                    path.display(),
+                    size,
+                    max
+                );
+            }
+            let data = std::fs::read(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            FlightFirmware::parse(&path.to_string_lossy(), &data, max as usize)
+        })
+        .await
+        .map_err(|e| anyhow!("flight firmware identification failed: {}", e))?
+    }
+
+    /// Recursively extracts the image and returns the manifest of
+    /// everything found, also saved as `manifest.json` in the work
+    /// directory.
+    pub async fn unpack(&self) -> Result<Manifest> {
+        let source = self.firmware_path.clone();
+        let work_dir = self.work_dir();
+        let limits = self.config.unpack_limits();
+        // The unpacker checks its deadline between members; this only
+        // catches a single member that takes too long to decompress.
+        let backstop = limits.timeout + Duration::from_secs(30);
+        tracing::info!("Unpacking {:?} into {:?}", source, work_dir);
+        let task = tokio::task::spawn_blocking(move || Unpacker::run(&source, &work_dir, limits));
+        tokio::time::timeout(backstop, task)
+            .await
+            .map_err(|_| anyhow!("firmware extraction timed out"))?
+            .map_err(|e| anyhow!("firmware extraction failed: {}", e))?
     }
 
     pub async fn analyze(&self) -> Result<ScanResult> {
         tracing::info!("Analyzing firmware: {:?}", self.firmware_path);
-        
-        // TODO: Implement firmware analysis
-        // - Extract filesystem
-        // - Search for hardcoded credentials
-        // - Identify vulnerable libraries
-        // - Check for backdoors
-        
+
+        let map = self.scan_signatures().await?;
+        tracing::info!(
+            "Found {} signatures in {:?}, {:.0}% of the content high-entropy and unexplained",
+            map.hits.len(),
+            self.firmware_path,
+            map.encrypted_ratio() * 100.0
+        );
+        if map.is_likely_encrypted() {
+            tracing::warn!(
+                "{:?} looks encrypted; extraction will find little until it is decrypted",
+                self.firmware_path
+            );
+        }
+
+        let manifest = self.unpack().await?;
+        map.save(&manifest.work_dir.join(signature::SIGNATURES_FILE))?;
+        let flight = self.identify_flight_firmware().await.unwrap_or_else(|e| {
+            tracing::warn!(
+                "Could not decode {:?} as flight controller firmware: {}",
+                self.firmware_path,
+                e
+            );
+            None
+        });
+        if let Some(flight) = flight {
+            tracing::info!(
+                "{:?} is {} firmware {} for board {:?} ({}), {} parameter defaults",
+                self.firmware_path,
+                flight.vehicle.as_deref().unwrap_or("flight controller"),
+                flight.version.as_deref().unwrap_or("of unknown version"),
+                flight.board_id,
+                flight.board.as_deref().unwrap_or("unknown board"),
+                flight.parameter_defaults.len()
+            );
+            std::fs::write(
+                manifest.work_dir.join(flight::FLIGHT_FIRMWARE_FILE),
+                serde_json::to_string_pretty(&flight)?,
+            )?;
+        }
+        tracing::info!(
+            "Extracted {} files from {:?}",
+            manifest.files().count().saturating_sub(1),
+            self.firmware_path
+        );
+
+        let secrets = self.find_secrets(&manifest).await?;
+        tracing::info!(
+            "Found {} secrets in {:?}",
+            secrets.len(),
+            self.firmware_path
+        );
+        let mut findings = secrets::findings(&secrets);
+
+        let sbom = self.build_sbom(&manifest).await?;
+        sbom.save(&manifest.work_dir.join(sbom::SBOM_FILE))?;
+        let vulns = self.find_vulnerabilities(&manifest, &sbom)?;
+        tracing::info!(
+            "Identified {} components and {} known vulnerabilities in {:?}",
+            sbom.components.len(),
+            vulns.len(),
+            self.firmware_path
+        );
+        findings.extend(reportable(&vulns));
+
+        let audits = self.audit_binaries(&manifest).await?;
+        let weaknesses = hardening::vulnerabilities(&audits, &self.dangerous_functions, &manifest);
+        tracing::info!(
+            "Audited {} ELF binaries in {:?}, {} hardening findings",
+            audits.len(),
+            self.firmware_path,
+            weaknesses.len()
+        );
+        findings.extend(reportable(&weaknesses));
+
         Ok(ScanResult {
             scan_type: ScanType::Firmware,
             target: self.firmware_path.to_string_lossy().to_string(),
-            findings: vec![],
+            findings,
+            incomplete: false,
+        })
+    }
+
+    /// Printable strings of at least `string_min_length` characters from
+    /// every file in `manifest` worth reading, see `string_sources`.
+    pub async fn extract_strings(&self, manifest: &Manifest) -> Result<Vec<ExtractedString>> {
+        let sources = string_sources(manifest);
+        let min_length = self.config.string_min_length;
+        tokio::task::spawn_blocking(move || {
+            let mut all = Vec::new();
+            for (path, disk_path) in sources {
+                match std::fs::read(&disk_path) {
+                    Ok(data) => all.extend(strings::extract(&path, &data, min_length)),
+                    Err(e) => tracing::warn!("Skipping {}: {}", disk_path.display(), e),
+                }
+            }
+            all
+        })
+        .await
+        .map_err(|e| anyhow!("string extraction failed: {}", e))
+    }
+
+    /// Hardcoded credentials, keys, tokens, endpoints, WiFi passphrases and
+    /// debug backdoors in the strings of the unpacked image.
+    pub async fn find_secrets(&self, manifest: &Manifest) -> Result<Vec<SecretMatch>> {
+        let sources = string_sources(manifest);
+        let min_length = self.config.string_min_length;
+        let rules = self.secret_rules.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut matches = Vec::new();
+            for (path, disk_path) in sources {
+                match std::fs::read(&disk_path) {
+                    Ok(data) => {
+                        matches.extend(rules.classify(&strings::extract(&path, &data, min_length)))
+                    }
+                    Err(e) => tracing::warn!("Skipping {}: {}", disk_path.display(), e),
+                }
+            }
+            matches
+        })
+        .await
+        .map_err(|e| anyhow!("secret scan failed: {}", e))
+    }
+
+    /// Software bill of materials from version strings, file names,
+    /// opkg/dpkg databases and `os-release` in the unpacked image.
+    pub async fn build_sbom(&self, manifest: &Manifest) -> Result<Sbom> {
+        let sources = string_sources(manifest);
+        let paths: Vec<String> = manifest.entries.iter().map(|e| e.path.clone()).collect();
+        let min_length = self.config.string_min_length;
+        let rules = self.component_rules.clone();
+        let source = manifest.source.clone();
+        let sha256 = manifest.root().and_then(|r| r.sha256.clone());
+        tokio::task::spawn_blocking(move || {
+            let mut found: Vec<Component> =
+                paths.iter().filter_map(|p| rules.match_path(p)).collect();
+            for (path, disk_path) in sources {
+                let data = match std::fs::read(&disk_path) {
+                    Ok(data) => data,
+                    Err(e) => {
+                        tracing::warn!("Skipping {}: {}", disk_path.display(), e);
+                        continue;
+                    }
+                };
+                if sbom::is_package_database(&path) {
+                    let content = String::from_utf8_lossy(&data);
+                    for (package, version) in sbom::parse_packages(&content) {
+                        found.push(rules.match_package(&package, &version, &path));
+                    }
+                } else if sbom::is_os_release(&path) {
+                    found.extend(sbom::parse_os_release(
+                        &String::from_utf8_lossy(&data),
+                        &path,
+                    ));
+                } else {
+                    found.extend(rules.match_strings(&strings::extract(&path, &data, min_length)));
+                }
+            }
+            Sbom::new(source, sha256, found)
         })
+        .await
+        .map_err(|e| anyhow!("SBOM generation failed: {}", e))
     }
 
-    pub async fn extract_strings(&self) -> Result<Vec<String>> {
-        // TODO: Extract interesting strings from firmware
-        Ok(vec![])
+    /// Known CVEs affecting the components in `sbom`, located in the
+    /// extracted tree of `manifest`. Database matches for a CVE the dataset
+    /// already reported for the same component are left out.
+    pub fn find_vulnerabilities(&self, manifest: &Manifest, sbom: &Sbom) -> Result<Vec<VulnData>> {
+        let loaded;
+        let dataset = match &self.cve_dataset {
+            Some(dataset) => dataset,
+            None => {
+                let mut dataset = CveDataset::new();
+                for path in &self.config.cve_datasets {
+                    dataset.load(path)?;
+                }
+                loaded = dataset;
+                &loaded
+            }
+        };
+        let mut vulns = dataset.vulnerabilities(sbom, manifest);
+        if let Some(db) = &self.vuln_db {
+            let known: HashSet<String> = vulns.iter().map(|v| v.id.clone()).collect();
+            vulns.extend(
+                cve::database_vulnerabilities(db, sbom, manifest)
+                    .into_iter()
+                    .filter(|v| !known.contains(&v.id)),
+            );
+        }
+        Ok(vulns)
     }
 
-    pub async fn find_vulnerabilities(&self) -> Result<Vec<Finding>> {
-        // TODO: Search for known vulnerabilities
-        Ok(vec![])
+    /// Mitigations, dangerous imports with their callers, and setuid bits
+    /// of every ELF executable and shared object in the unpacked image.
+    pub async fn audit_binaries(&self, manifest: &Manifest) -> Result<Vec<BinaryAudit>> {
+        let files: Vec<(String, PathBuf, Option<u32>)> = manifest
+            .files()
+            .map(|e| (e.path.clone(), manifest.disk_path(e), e.mode))
+            .collect();
+        let functions = self.dangerous_functions.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut audits = Vec::new();
+            for (path, disk_path, mode) in files {
+                let mut magic = [0u8; 4];
+                let is_elf = std::fs::File::open(&disk_path)
+                    .and_then(|mut f| std::io::Read::read_exact(&mut f, &mut magic))
+                    .is_ok()
+                    && hardening::is_elf(&magic);
+                if !is_elf {
+                    continue;
+                }
+                match std::fs::read(&disk_path) {
+                    Ok(data) => audits.extend(hardening::audit(&path, &data, mode, &functions)),
+                    Err(e) => tracing::warn!("Skipping {}: {}", disk_path.display(), e),
+                }
+            }
+            audits
+        })
+        .await
+        .map_err(|e| anyhow!("binary audit failed: {}", e))
     }
+
+    /// Unpacks this image and `newer` and reports the files added, removed
+    /// and changed between them, with symbol differences for binaries,
+    /// unified diffs for text files, and secrets introduced or dropped. The
+    /// result is also saved as `diff.json` in `newer`'s work directory.
+    pub async fn diff(&self, newer: &FirmwareAnalyzer) -> Result<FirmwareDiff> {
+        let old = self.unpack().await?;
+        let new = newer.unpack().await?;
+        let old_secrets = self.find_secrets(&old).await?;
+        let new_secrets = newer.find_secrets(&new).await?;
+        let work_dir = new.work_dir.clone();
+
+        let diff = tokio::task::spawn_blocking(move || {
+            let mut diff = FirmwareDiff::compare(&old, &new);
+            diff.secrets = SecretDiff::new(&old_secrets, &new_secrets);
+            diff
+        })
+        .await
+        .map_err(|e| anyhow!("firmware diff failed: {}", e))?;
+        diff.save(&work_dir.join(diff::DIFF_FILE))?;
+        tracing::info!(
+            "{:?} -> {:?}: {} added, {} removed, {} changed files, {} new and {} removed secrets",
+            self.firmware_path,
+            newer.firmware_path,
+            diff.added().count(),
+            diff.removed().count(),
+            diff.changed().count(),
+            diff.secrets.added.len(),
+            diff.secrets.removed.len()
+        );
+        Ok(diff)
+    }
+}
+
+/// Files whose strings are read: every regular file except containers the
+/// unpacker opened, whose contents are read through their members instead.
+/// Raw images that only had pieces carved out are still read whole.
+fn string_sources(manifest: &Manifest) -> Vec<(String, PathBuf)> {
+    let opened: HashSet<&str> = manifest
+        .entries
+        .iter()
+        .filter(|e| e.extracted_from.is_some())
+        .filter_map(|e| e.parent.as_deref())
+        .collect();
+    manifest
+        .files()
+        .filter(|e| !opened.contains(e.path.as_str()))
+        .map(|e| (e.path.clone(), manifest.disk_path(e)))
+        .collect()
+}
+
+/// `vulns` as findings. Info-level entries have no finding severity and are
+/// only logged.
+fn reportable(vulns: &[VulnData]) -> Vec<Finding> {
+    vulns
+        .iter()
+        .filter_map(|vuln| match Finding::try_from(vuln) {
+            Ok(finding) => Some(finding),
+            Err(e) => {
+                tracing::info!("{:#}", e);
+                None
+            }
+        })
+        .collect()
 }
@@ -0,0 +1,696 @@
+use super::squashfs;
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{Cursor, Read, Write};
+
+const UIMAGE_MAGIC: u32 = 0x2705_1956;
+const UIMAGE_HEADER_LEN: usize = 64;
+const UIMAGE_TYPE_MULTI: u8 = 4;
+
+/// Container and compression formats the unpacker can open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerKind {
+    Gzip,
+    Xz,
+    Lzma,
+    Tar,
+    Cpio,
+    SquashFs,
+    UImage,
+    Zip,
+}
+
+impl ContainerKind {
+    /// Identifies a container that starts at the first byte of `data`.
+    pub fn identify(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&[0x1f, 0x8b, 0x08]) {
+            Some(Self::Gzip)
+        } else if data.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Self::Xz)
+        } else if data.starts_with(b"hsqs") {
+            Some(Self::SquashFs)
+        } else if data.starts_with(&UIMAGE_MAGIC.to_be_bytes()) && data.len() >= UIMAGE_HEADER_LEN {
+            Some(Self::UImage)
+        } else if data.starts_with(b"PK\x03\x04") {
+            Some(Self::Zip)
+        } else if data.starts_with(b"070701")
+            || data.starts_with(b"070702")
+            || data.starts_with(b"070707")
+        {
+            Some(Self::Cpio)
+        } else if data.get(257..262) == Some(b"ustar") {
+            Some(Self::Tar)
+        } else if is_lzma_alone(data) {
+            Some(Self::Lzma)
+        } else {
+            None
+        }
+    }
+
+    /// File extension for a container carved out of a raw image.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gz",
+            Self::Xz => "xz",
+            Self::Lzma => "lzma",
+            Self::Tar => "tar",
+            Self::Cpio => "cpio",
+            Self::SquashFs => "squashfs",
+            Self::UImage => "uimage",
+            Self::Zip => "zip",
+        }
+    }
+}
+
+impl fmt::Display for ContainerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Gzip => "gzip",
+            Self::Xz => "xz",
+            Self::Lzma => "lzma",
+            Self::Tar => "tar",
+            Self::Cpio => "cpio",
+            Self::SquashFs => "squashfs",
+            Self::UImage => "uImage",
+            Self::Zip => "zip",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// `.lzma` streams have no magic; accept the usual properties byte with a
+/// power-of-two dictionary and a plausible (or unknown) size.
+pub(crate) fn is_lzma_alone(data: &[u8]) -> bool {
+    // The range coder always emits a zero first byte.
+    if data.len() < 14 || data[0] != 0x5d || data[13] != 0 {
+        return false;
+    }
+    let dict = u32::from_le_bytes([data[1], data[2], data[3], data[4]]);
+    let size = u64::from_le_bytes(data[5..13].try_into().unwrap_or_default());
+    dict.is_power_of_two() && dict >= 1 << 12 && (size == u64::MAX || size < 1 << 40)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EntryType {
+    File,
+    Directory,
+    /// Symbolic or hard link. Links are recorded, never created on disk.
+    Symlink {
+        target: String,
+    },
+    Device,
+    Other,
+}
+
+/// One member of a container as produced by an extractor.
+#[derive(Debug, Clone)]
+pub struct Member {
+    /// Path inside the container, as stored; sanitised by the unpacker.
+    pub name: String,
+    pub entry_type: EntryType,
+    /// Contents of a regular file, empty otherwise.
+    pub data: Vec<u8>,
+    /// Offset in the container where the member's data, or the compressed
+    /// stream it was decoded from, starts.
+    pub offset: Option<u64>,
+    pub mode: Option<u32>,
+}
+
+impl Member {
+    fn file(name: impl Into<String>, data: Vec<u8>, offset: Option<u64>) -> Self {
+        Self {
+            name: name.into(),
+            entry_type: EntryType::File,
+            data,
+            offset,
+            mode: None,
+        }
+    }
+}
+
+/// The unpacker's overall size, file-count or time budget is used up. The
+/// unpacker's sink raises it; extractors pass it through rather than
+/// skipping the member, so the whole run stops.
+#[derive(Debug)]
+pub struct LimitExceeded(pub String);
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+pub(crate) type Sink<'a> = dyn FnMut(Member) -> Result<()> + 'a;
+
+/// Extracts the members of `data`, a `kind` container named `name`, into
+/// `sink`. No single member may decode to more than `limit` bytes. Members
+/// that cannot be read are reported in the returned warnings; a container
+/// that cannot be opened at all is an error.
+pub(crate) fn extract(
+    kind: ContainerKind,
+    data: &[u8],
+    name: &str,
+    limit: usize,
+    sink: &mut Sink,
+) -> Result<Vec<String>> {
+    let mut warnings = Vec::new();
+    match kind {
+        ContainerKind::Gzip => {
+            let (out, inner) = gunzip(data, limit)?;
+            let inner = inner.unwrap_or_else(|| stream_name(name, &[".gz", ".tgz", ".gzip"]));
+            sink(Member::file(inner, out, Some(0)))?;
+        }
+        ContainerKind::Xz => {
+            let data = &data[..xz_stream_end(data).unwrap_or(data.len())];
+            let out = lzma_limited(data, limit, |input, output| {
+                lzma_rs::xz_decompress(input, output)
+            })?;
+            sink(Member::file(
+                stream_name(name, &[".xz", ".txz"]),
+                out,
+                Some(0),
+            ))?;
+        }
+        ContainerKind::Lzma => {
+            let out = lzma_limited(data, limit, |input, output| {
+                lzma_rs::lzma_decompress(input, output)
+            })?;
+            sink(Member::file(stream_name(name, &[".lzma"]), out, Some(0)))?;
+        }
+        ContainerKind::Tar => extract_tar(data, limit, sink, &mut warnings)?,
+        ContainerKind::Cpio => extract_cpio(data, limit, sink, &mut warnings)?,
+        ContainerKind::SquashFs => squashfs::extract(data, limit, sink, &mut warnings)?,
+        ContainerKind::UImage => extract_uimage(data, limit, sink, &mut warnings)?,
+        ContainerKind::Zip => extract_zip(data, limit, sink, &mut warnings)?,
+    }
+    Ok(warnings)
+}
+
+/// Name for the single member of a compressed stream: the container name
+/// without its extension, `.tgz` becoming `.tar`.
+fn stream_name(name: &str, extensions: &[&str]) -> String {
+    let lower = name.to_ascii_lowercase();
+    for ext in extensions {
+        if lower.ends_with(ext) && lower.len() > ext.len() {
+            let stem = &name[..name.len() - ext.len()];
+            return if ext.starts_with(".t") && *ext != ".tar" {
+                format!("{}.tar", stem)
+            } else {
+                stem.to_string()
+            };
+        }
+    }
+    format!("{}.decompressed", name)
+}
+
+/// Decodes concatenated gzip members and the name stored in the first,
+/// stopping at anything else so that carved streams may carry padding.
+fn gunzip(data: &[u8], limit: usize) -> Result<(Vec<u8>, Option<String>)> {
+    let mut input = data;
+    let mut out = Vec::new();
+    let mut name = None;
+    while input.starts_with(&[0x1f, 0x8b]) {
+        let mut decoder = flate2::bufread::GzDecoder::new(input);
+        let part = read_limited(&mut decoder, limit - out.len())?;
+        if out.is_empty() {
+            name = decoder
+                .header()
+                .and_then(|h| h.filename())
+                .map(|f| String::from_utf8_lossy(f).into_owned());
+        }
+        out.extend_from_slice(&part);
+        input = decoder.into_inner();
+    }
+    Ok((out, name))
+}
+
+/// End of the first xz stream, found by its footer: CRC32 of the backward
+/// size and flags, the flags repeated from the header, then `YZ`. The
+/// decoder rejects anything after it, such as the padding of a carved
+/// stream.
+fn xz_stream_end(data: &[u8]) -> Option<usize> {
+    let flags = data.get(6..8)?;
+    let mut from = 12;
+    while let Some(found) = data.get(from..)?.windows(2).position(|w| w == b"YZ") {
+        let end = from + found + 2;
+        let footer = &data[end - 12..end];
+        let mut crc = flate2::Crc::new();
+        crc.update(&footer[4..10]);
+        if &footer[8..10] == flags && crc.sum().to_le_bytes() == footer[..4] {
+            return Some(end);
+        }
+        from += found + 1;
+    }
+    None
+}
+
+pub(crate) fn read_limited(reader: &mut dyn Read, limit: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    reader.take(limit as u64 + 1).read_to_end(&mut out)?;
+    if out.len() > limit {
+        bail!("member decodes to more than {} bytes", limit);
+    }
+    Ok(out)
+}
+
+/// Runs an lzma-rs decoder into a buffer that refuses to grow past `limit`.
+pub(crate) fn lzma_limited<F>(data: &[u8], limit: usize, decode: F) -> Result<Vec<u8>>
+where
+    F: FnOnce(&mut std::io::BufReader<&[u8]>, &mut LimitedWriter) -> lzma_rs::error::Result<()>,
+{
+    let mut output = LimitedWriter {
+        buf: Vec::new(),
+        limit,
+        exceeded: false,
+    };
+    let result = decode(&mut std::io::BufReader::new(data), &mut output);
+    if output.exceeded {
+        bail!("member decodes to more than {} bytes", limit);
+    }
+    result.map_err(|e| anyhow!("{:?}", e))?;
+    Ok(output.buf)
+}
+
+pub(crate) struct LimitedWriter {
+    buf: Vec<u8>,
+    limit: usize,
+    exceeded: bool,
+}
+
+impl Write for LimitedWriter {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        if self.buf.len() + data.len() > self.limit {
+            self.exceeded = true;
+            return Err(std::io::Error::other("output limit reached"));
+        }
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Separates per-member failures, which become warnings, from limit
+/// violations, which must stop the unpacker.
+fn member_error(error: anyhow::Error, name: &str, warnings: &mut Vec<String>) -> Result<()> {
+    if error.is::<LimitExceeded>() {
+        return Err(error);
+    }
+    warnings.push(format!("{}: {:#}", name, error));
+    Ok(())
+}
+
+fn extract_tar(
+    data: &[u8],
+    limit: usize,
+    sink: &mut Sink,
+    warnings: &mut Vec<String>,
+) -> Result<()> {
+    let mut archive = tar::Archive::new(Cursor::new(data));
+    for entry in archive.entries()? {
+        let mut entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                warnings.push(format!("tar: {}", e));
+                break;
+            }
+        };
+        let name = String::from_utf8_lossy(&entry.path_bytes()).into_owned();
+        let header = entry.header();
+        let mode = header.mode().ok();
+        let link = || {
+            entry
+                .link_name_bytes()
+                .map(|t| String::from_utf8_lossy(&t).into_owned())
+                .unwrap_or_default()
+        };
+        let entry_type = match header.entry_type() {
+            tar::EntryType::Regular | tar::EntryType::Continuous => EntryType::File,
+            tar::EntryType::Directory => EntryType::Directory,
+            tar::EntryType::Symlink | tar::EntryType::Link => EntryType::Symlink { target: link() },
+            tar::EntryType::Char | tar::EntryType::Block | tar::EntryType::Fifo => {
+                EntryType::Device
+            }
+            // Global pax headers and other metadata records.
+            _ => continue,
+        };
+        let offset = Some(entry.raw_file_position());
+        let data = if entry_type == EntryType::File {
+            match read_limited(&mut entry, limit) {
+                Ok(data) => data,
+                Err(e) => {
+                    member_error(e, &name, warnings)?;
+                    continue;
+                }
+            }
+        } else {
+            Vec::new()
+        };
+        sink(Member {
+            name,
+            entry_type,
+            data,
+            offset,
+            mode,
+        })?;
+    }
+    Ok(())
+}
+
+/// `newc`/`crc` (hex) and `odc` (octal) cpio archives, as used for Linux
+/// initramfs images.
+fn extract_cpio(
+    data: &[u8],
+    limit: usize,
+    sink: &mut Sink,
+    warnings: &mut Vec<String>,
+) -> Result<()> {
+    let field = |pos: usize, len: usize, radix: u32| -> Result<usize> {
+        let raw = data
+            .get(pos..pos + len)
+            .ok_or_else(|| anyhow!("truncated cpio header at {}", pos))?;
+        let text = std::str::from_utf8(raw).map_err(|_| anyhow!("bad cpio header at {}", pos))?;
+        usize::from_str_radix(text, radix).map_err(|_| anyhow!("bad cpio header at {}", pos))
+    };
+    let align4 = |n: usize| (n + 3) & !3;
+
+    let mut pos = 0;
+    while pos + 6 <= data.len() {
+        let magic = &data[pos..pos + 6];
+        let (mode, size, name_size, name_start, newc) = match magic {
+            b"070701" | b"070702" => (
+                field(pos + 14, 8, 16)?,
+                field(pos + 54, 8, 16)?,
+                field(pos + 94, 8, 16)?,
+                pos + 110,
+                true,
+            ),
+            b"070707" => (
+                field(pos + 18, 6, 8)?,
+                field(pos + 65, 11, 8)?,
+                field(pos + 59, 6, 8)?,
+                pos + 76,
+                false,
+            ),
+            _ => {
+                warnings.push(format!("cpio: no header at offset {}, stopping", pos));
+                break;
+            }
+        };
+        let name_end = name_start + name_size;
+        let name = data
+            .get(name_start..name_end)
+            .map(|n| {
+                String::from_utf8_lossy(n)
+                    .trim_end_matches('\0')
+                    .to_string()
+            })
+            .ok_or_else(|| anyhow!("truncated cpio name at {}", name_start))?;
+        let data_start = if newc { align4(name_end) } else { name_end };
+        let data_end = data_start + size;
+        let Some(contents) = data.get(data_start..data_end) else {
+            warnings.push(format!("cpio: {} is truncated", name));
+            break;
+        };
+        pos = if newc { align4(data_end) } else { data_end };
+        if name == "TRAILER!!!" {
+            break;
+        }
+
+        let entry_type = match mode & 0o170000 {
+            0o100000 => EntryType::File,
+            0o040000 => EntryType::Directory,
+            0o120000 => EntryType::Symlink {
+                target: String::from_utf8_lossy(contents).into_owned(),
+            },
+            0o020000 | 0o060000 | 0o010000 => EntryType::Device,
+            _ => EntryType::Other,
+        };
+        if entry_type == EntryType::File && size > limit {
+            member_error(anyhow!("larger than {} bytes", limit), &name, warnings)?;
+            continue;
+        }
+        sink(Member {
+            data: if entry_type == EntryType::File {
+                contents.to_vec()
+            } else {
+                Vec::new()
+            },
+            name,
+            entry_type,
+            offset: Some(data_start as u64),
+            mode: Some(mode as u32 & 0o7777),
+        })?;
+    }
+    Ok(())
+}
+
+/// U-Boot legacy images: a 64-byte header followed by the payload, or by a
+/// size table and several payloads for multi-file images.
+fn extract_uimage(
+    data: &[u8],
+    limit: usize,
+    sink: &mut Sink,
+    warnings: &mut Vec<String>,
+) -> Result<()> {
+    let be32 = |pos: usize| {
+        data.get(pos..pos + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    };
+    let size = be32(12).ok_or_else(|| anyhow!("truncated uImage header"))?;
+    let image_type = data[30];
+    let compression = data[31];
+    let raw_name = &data[32..UIMAGE_HEADER_LEN];
+    let name: String = String::from_utf8_lossy(raw_name)
+        .trim_end_matches('\0')
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "-_.".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let name = if name.is_empty() {
+        "image".to_string()
+    } else {
+        name
+    };
+    if UIMAGE_HEADER_LEN + size > data.len() {
+        warnings.push(format!(
+            "uImage: header declares {} bytes, file has {}",
+            size,
+            data.len() - UIMAGE_HEADER_LEN
+        ));
+    }
+
+    let mut parts = Vec::new();
+    if image_type == UIMAGE_TYPE_MULTI {
+        let mut sizes = Vec::new();
+        let mut pos = UIMAGE_HEADER_LEN;
+        while let Some(part) = be32(pos) {
+            pos += 4;
+            if part == 0 {
+                break;
+            }
+            sizes.push(part);
+        }
+        for (i, part) in sizes.into_iter().enumerate() {
+            parts.push((format!("{}-{}", name, i), pos, part));
+            pos += (part + 3) & !3;
+        }
+    } else {
+        parts.push((name, UIMAGE_HEADER_LEN, size));
+    }
+
+    for (part_name, start, len) in parts {
+        let end = (start + len).min(data.len());
+        let Some(payload) = data.get(start..end) else {
+            warnings.push(format!("uImage: {} lies outside the file", part_name));
+            continue;
+        };
+        let decoded = match compression {
+            0 => Ok((part_name.clone(), payload.to_vec())),
+            1 => read_limited(&mut flate2::read::MultiGzDecoder::new(payload), limit)
+                .map(|d| (part_name.clone(), d)),
+            3 => lzma_limited(payload, limit, |input, output| {
+                lzma_rs::lzma_decompress(input, output)
+            })
+            .map(|d| (part_name.clone(), d)),
+            // bzip2, LZO, LZ4 and zstd payloads are kept as they are.
+            other => {
+                let ext = match other {
+                    2 => "bz2",
+                    4 => "lzo",
+                    5 => "lz4",
+                    6 => "zst",
+                    _ => "bin",
+                };
+                Ok((format!("{}.{}", part_name, ext), payload.to_vec()))
+            }
+        };
+        match decoded {
+            Ok((member_name, contents)) if contents.len() <= limit => {
+                sink(Member::file(member_name, contents, Some(start as u64)))?
+            }
+            Ok((member_name, _)) => member_error(
+                anyhow!("larger than {} bytes", limit),
+                &member_name,
+                warnings,
+            )?,
+            Err(e) => member_error(e, &part_name, warnings)?,
+        }
+    }
+    Ok(())
+}
+
+/// ZIP-packed update bundles. Encrypted members and unsupported compression
+/// methods are reported and skipped.
+fn extract_zip(
+    data: &[u8],
+    limit: usize,
+    sink: &mut Sink,
+    warnings: &mut Vec<String>,
+) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data))?;
+    for i in 0..archive.len() {
+        let index_name = archive.name_for_index(i).unwrap_or("<unnamed>").to_string();
+        let mut file = match archive.by_index(i) {
+            Ok(file) => file,
+            Err(e) => {
+                warnings.push(format!("{}: {}", index_name, e));
+                continue;
+            }
+        };
+        let name = file.name().to_string();
+        let offset = Some(file.data_start());
+        let mode = file.unix_mode().map(|m| m & 0o7777);
+        if file.is_dir() {
+            sink(Member {
+                name,
+                entry_type: EntryType::Directory,
+                data: Vec::new(),
+                offset,
+                mode,
+            })?;
+            continue;
+        }
+        if file.size() > limit as u64 {
+            member_error(anyhow!("larger than {} bytes", limit), &name, warnings)?;
+            continue;
+        }
+        let contents = match read_limited(&mut file, limit) {
+            Ok(contents) => contents,
+            Err(e) => {
+                member_error(e, &name, warnings)?;
+                continue;
+            }
+        };
+        let entry_type = if file.is_symlink() {
+            EntryType::Symlink {
+                target: String::from_utf8_lossy(&contents).into_owned(),
+            }
+        } else {
+            EntryType::File
+        };
+        let data = if entry_type == EntryType::File {
+            contents
+        } else {
+            Vec::new()
+        };
+        sink(Member {
+            name,
+            entry_type,
+            data,
+            offset,
+            mode,
+        })?;
+    }
+    if archive.is_empty() {
+        bail!("ZIP archive has no entries");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fixtures;
+    use super::*;
+
+    fn members(kind: ContainerKind, data: &[u8]) -> (Vec<Member>, Vec<String>) {
+        let mut members = Vec::new();
+        let warnings = extract(kind, data, "image", 1024, &mut |member| {
+            members.push(member);
+            Ok(())
+        })
+        .unwrap();
+        (members, warnings)
+    }
+
+    #[test]
+    fn identifies_containers_by_magic() {
+        let tar = fixtures::tar(&[("a", b"1")]);
+        assert_eq!(ContainerKind::identify(&tar), Some(ContainerKind::Tar));
+        assert_eq!(
+            ContainerKind::identify(&fixtures::gzip("a", b"1")),
+            Some(ContainerKind::Gzip)
+        );
+        assert_eq!(
+            ContainerKind::identify(&fixtures::cpio(&[])),
+            Some(ContainerKind::Cpio)
+        );
+        assert_eq!(
+            ContainerKind::identify(b"hsqs\0\0\0\0"),
+            Some(ContainerKind::SquashFs)
+        );
+        assert_eq!(ContainerKind::identify(b"\x7fELF\x02\x01\x01"), None);
+        assert_eq!(ContainerKind::identify(&tar[..200]), None);
+    }
+
+    #[test]
+    fn stream_members_are_named_after_the_container() {
+        assert_eq!(stream_name("rootfs.tgz", &[".gz", ".tgz"]), "rootfs.tar");
+        assert_eq!(stream_name("kernel.XZ", &[".xz"]), "kernel");
+        assert_eq!(stream_name("blob", &[".gz"]), "blob.decompressed");
+
+        let (members, _) = members(ContainerKind::Gzip, &fixtures::gzip("vmlinux", b"kernel"));
+        assert_eq!(members[0].name, "vmlinux");
+        assert_eq!(members[0].data, b"kernel");
+    }
+
+    #[test]
+    fn cpio_members_and_truncation() {
+        let archive = fixtures::cpio(&[
+            ("etc", 0o040755, b""),
+            ("etc/shadow", 0o100600, b"root::0:0:99999:7:::\n"),
+            ("big", 0o100644, &[0; 2048]),
+            ("dev/console", 0o020600, b""),
+        ]);
+        let (found, warnings) = members(ContainerKind::Cpio, &archive);
+        let names: Vec<_> = found.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["etc", "etc/shadow", "dev/console"]);
+        assert_eq!(found[1].mode, Some(0o600));
+        assert_eq!(found[2].entry_type, EntryType::Device);
+        assert!(warnings[0].starts_with("big: larger than 1024 bytes"));
+
+        let (found, warnings) = members(ContainerKind::Cpio, &archive[..250]);
+        assert_eq!(found.len(), 1);
+        assert_eq!(warnings, ["cpio: etc/shadow is truncated"]);
+        let header = extract(
+            ContainerKind::Cpio,
+            &archive[..200],
+            "image",
+            1024,
+            &mut |_| Ok(()),
+        );
+        assert!(header.is_err());
+    }
+}
@@ -0,0 +1,403 @@
+use super::sbom::{Component, Sbom};
+use super::unpack::Manifest;
+use anyhow::{Context, Result};
+use core::nvd::compare_versions;
+use core::vuln_db::VulnerabilityDatabase;
+use data::{CvssScore, DetectionSource, ScanType, VulnData, VulnSeverity};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::path::Path;
+
+const BUILTIN_CVES: &str = include_str!("../../signatures/firmware_cves.json");
+
+/// Versions of a product one CVE applies to. A bound that is left out is
+/// open; a range with no bounds covers every version.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VersionRange {
+    /// First affected version, inclusive.
+    pub introduced: Option<String>,
+    /// First fixed version, exclusive.
+    pub fixed: Option<String>,
+    /// Last affected version, inclusive, when no fix is known.
+    pub last_affected: Option<String>,
+}
+
+impl VersionRange {
+    pub fn contains(&self, version: &str) -> bool {
+        let within = |bound: &Option<String>, ok: fn(Ordering) -> bool| {
+            bound
+                .as_deref()
+                .is_none_or(|b| ok(compare_versions(version, b)))
+        };
+        within(&self.introduced, |o| o != Ordering::Less)
+            && within(&self.fixed, |o| o == Ordering::Less)
+            && within(&self.last_affected, |o| o != Ordering::Greater)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CveRecord {
+    pub id: String,
+    /// Component name from `components.toml` this record applies to.
+    pub product: String,
+    pub summary: String,
+    pub cvss_score: Option<f64>,
+    pub cvss_vector: Option<String>,
+    pub cwe: Option<String>,
+    #[serde(default)]
+    pub exploit_available: bool,
+    #[serde(default)]
+    pub references: Vec<String>,
+    /// The record applies when the version is in any of these.
+    pub affected: Vec<VersionRange>,
+}
+
+impl CveRecord {
+    pub fn affects(&self, component: &Component) -> bool {
+        self.product.eq_ignore_ascii_case(&component.name)
+            && self.affected.iter().any(|r| r.contains(&component.version))
+    }
+
+    /// The lowest fixed version above `version`, if any range has one.
+    pub fn fixed_after(&self, version: &str) -> Option<&str> {
+        self.affected
+            .iter()
+            .filter_map(|r| r.fixed.as_deref())
+            .filter(|f| compare_versions(version, f) == Ordering::Less)
+            .min_by(|a, b| compare_versions(a, b))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct CveFile {
+    #[serde(default)]
+    cves: Vec<CveRecord>,
+}
+
+/// CVE records for firmware components: the seed set in
+/// `signatures/firmware_cves.json` plus locally imported files in the same
+/// format.
+#[derive(Debug, Clone, Default)]
+pub struct CveDataset {
+    records: Vec<CveRecord>,
+}
+
+impl CveDataset {
+    pub fn new() -> Self {
+        let mut dataset = Self::empty();
+        dataset
+            .add(BUILTIN_CVES)
+            .expect("built-in CVE dataset must be valid");
+        dataset
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            records: Vec::new(),
+        }
+    }
+
+    pub fn load(&mut self, path: &Path) -> Result<usize> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read CVE dataset {}", path.display()))?;
+        self.add(&content)
+            .with_context(|| format!("invalid CVE dataset {}", path.display()))
+    }
+
+    /// Adds records, replacing any already loaded with the same id and
+    /// product so a newer import overrides the seed data.
+    pub fn add(&mut self, content: &str) -> Result<usize> {
+        let file: CveFile = serde_json::from_str(content)?;
+        let count = file.cves.len();
+        for record in file.cves {
+            self.records
+                .retain(|r| !(r.id == record.id && r.product == record.product));
+            self.records.push(record);
+        }
+        Ok(count)
+    }
+
+    pub fn records(&self) -> &[CveRecord] {
+        &self.records
+    }
+
+    pub fn matches<'a>(&'a self, component: &'a Component) -> impl Iterator<Item = &'a CveRecord> {
+        self.records.iter().filter(move |r| r.affects(component))
+    }
+
+    /// One `VulnData` per CVE and affected component in `sbom`, located at
+    /// the component's first file in the extracted tree.
+    pub fn vulnerabilities(&self, sbom: &Sbom, manifest: &Manifest) -> Vec<VulnData> {
+        let detection_time = chrono::Utc::now().to_rfc3339();
+        let target = sbom.source.to_string_lossy().into_owned();
+        let mut vulns = Vec::new();
+
+        for component in &sbom.components {
+            let file_path = component_file(component, manifest);
+            for record in self.matches(component) {
+                // A v3/v4 vector is scored locally so environmental
+                // adjustments later recompute from the same numbers; older
+                // vectors keep the score the feed supplied.
+                let cvss = record
+                    .cvss_vector
+                    .as_deref()
+                    .and_then(|vector| CvssScore::from_vector(vector).ok())
+                    .or_else(|| {
+                        record.cvss_score.map(|score| {
+                            CvssScore::new(score, record.cvss_vector.clone().unwrap_or_default())
+                        })
+                    });
+                let severity = cvss
+                    .as_ref()
+                    .map(CvssScore::severity)
+                    .unwrap_or(VulnSeverity::Medium);
+                let mut vuln = VulnData::new(
+                    format!(
+                        "FW-CVE:{}:{}:{}",
+                        record.id, component.name, component.version
+                    ),
+                    format!("{} in {} {}", record.id, component.name, component.version),
+                    format!(
+                        "{} {} found in {} is affected by {}: {}",
+                        component.name,
+                        component.version,
+                        component.files.join(", "),
+                        record.id,
+                        record.summary
+                    ),
+                    severity,
+                );
+                vuln.cve = Some(record.id.clone());
+                vuln.cwe = record.cwe.clone();
+                vuln.cvss = cvss;
+                vuln.affected = format!("{} {}", component.name, component.version);
+                vuln.affected_systems = vec![target.clone()];
+                vuln.detection_time = detection_time.clone();
+                vuln.detection_location.component = component.name.clone();
+                vuln.detection_location.file_path = file_path.clone();
+                vuln.detection_location.source = DetectionSource::StaticAnalysis;
+                vuln.scan_type = ScanType::Firmware;
+                vuln.exploit_available = record.exploit_available;
+                vuln.references = record.references.clone();
+                vuln.tags = vec![
+                    "firmware".to_string(),
+                    "sbom".to_string(),
+                    component.name.clone(),
+                ];
+                vuln.remediation = Some(match record.fixed_after(&component.version) {
+                    Some(fixed) => format!("Upgrade {} to {} or later.", component.name, fixed),
+                    None => format!(
+                        "No fixed {} release is recorded; replace the component \
+                         or apply the vendor's patch.",
+                        component.name
+                    ),
+                });
+                vulns.push(vuln);
+            }
+        }
+        vulns
+    }
+}
+
+/// Entries of the vulnerability database matching the CPE of a component in
+/// `sbom`, reported like `CveDataset::vulnerabilities`. Components without
+/// a CPE are not looked up.
+pub fn database_vulnerabilities(
+    db: &VulnerabilityDatabase,
+    sbom: &Sbom,
+    manifest: &Manifest,
+) -> Vec<VulnData> {
+    let detection_time = chrono::Utc::now().to_rfc3339();
+    let target = sbom.source.to_string_lossy().into_owned();
+    let mut vulns = Vec::new();
+
+    for component in &sbom.components {
+        let Some(cpe) = &component.cpe else {
+            continue;
+        };
+        let file_path = component_file(component, manifest);
+        for entry in db.lookup_cpe(cpe) {
+            let id = entry.cve.as_deref().unwrap_or(&entry.id);
+            let mut vuln = VulnData::from(entry);
+            vuln.id = format!("FW-CVE:{}:{}:{}", id, component.name, component.version);
+            vuln.title = format!("{} in {} {}", id, component.name, component.version);
+            vuln.description = format!(
+                "{} {} found in {} is affected by {}: {}",
+                component.name,
+                component.version,
+                component.files.join(", "),
+                id,
+                entry.description
+            );
+            vuln.cve = Some(id.to_string());
+            vuln.affected = format!("{} {}", component.name, component.version);
+            vuln.affected_systems = vec![target.clone()];
+            vuln.detection_time = detection_time.clone();
+            vuln.detection_location.component = component.name.clone();
+            vuln.detection_location.file_path = file_path.clone();
+            vuln.detection_location.source = DetectionSource::StaticAnalysis;
+            vuln.scan_type = ScanType::Firmware;
+            vuln.tags = vec![
+                "firmware".to_string(),
+                "sbom".to_string(),
+                "nvd".to_string(),
+                component.name.clone(),
+            ];
+            if vuln.remediation.is_none() {
+                vuln.remediation = Some(format!(
+                    "Upgrade {} to a release that fixes {}, or apply the vendor's patch.",
+                    component.name, id
+                ));
+            }
+            vulns.push(vuln);
+        }
+    }
+    vulns
+}
+
+/// The component's first file in the extracted tree.
+fn component_file(component: &Component, manifest: &Manifest) -> Option<String> {
+    component.files.first().map(|f| {
+        manifest
+            .get(f)
+            .map(|e| manifest.disk_path(e))
+            .unwrap_or_else(|| manifest.work_dir.join(f))
+            .to_string_lossy()
+            .into_owned()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::firmware::ComponentSource;
+    use std::path::PathBuf;
+
+    fn range(introduced: Option<&str>, fixed: Option<&str>, last: Option<&str>) -> VersionRange {
+        VersionRange {
+            introduced: introduced.map(str::to_string),
+            fixed: fixed.map(str::to_string),
+            last_affected: last.map(str::to_string),
+        }
+    }
+
+    fn component(name: &str, version: &str) -> Component {
+        Component {
+            name: name.to_string(),
+            version: version.to_string(),
+            kind: "application".to_string(),
+            cpe: None,
+            files: vec![format!("bin/{}", name)],
+            evidence: Vec::new(),
+            source: ComponentSource::VersionString,
+        }
+    }
+
+    fn ids(dataset: &CveDataset, name: &str, version: &str) -> Vec<String> {
+        let component = component(name, version);
+        let mut ids: Vec<String> = dataset.matches(&component).map(|r| r.id.clone()).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn version_ranges() {
+        let heartbleed = range(Some("1.0.1"), Some("1.0.1g"), None);
+        assert!(!heartbleed.contains("1.0.0"));
+        assert!(heartbleed.contains("1.0.1"));
+        assert!(heartbleed.contains("1.0.1f"));
+        assert!(!heartbleed.contains("1.0.1g"));
+        assert!(!heartbleed.contains("1.0.2"));
+
+        let until = range(None, None, Some("1.27.2"));
+        assert!(until.contains("1.27.2"));
+        assert!(!until.contains("1.28.0"));
+        assert!(range(None, None, None).contains("anything"));
+        assert!(range(None, Some("4.5.0"), None).contains("4.5.0-rc1"));
+    }
+
+    #[test]
+    fn builtin_records_match_affected_versions() {
+        let dataset = CveDataset::new();
+        let busybox = ids(&dataset, "busybox", "1.27.2");
+        assert!(busybox.contains(&"CVE-2017-16544".to_string()));
+        assert!(busybox.contains(&"CVE-2018-1000517".to_string()));
+        assert!(!busybox.contains(&"CVE-2016-2148".to_string()));
+        assert!(!ids(&dataset, "busybox", "1.36.1").contains(&"CVE-2018-1000517".to_string()));
+        assert!(ids(&dataset, "openssl", "1.0.1f").contains(&"CVE-2014-0160".to_string()));
+        assert!(!ids(&dataset, "OpenSSL", "1.0.1g").contains(&"CVE-2014-0160".to_string()));
+
+        let record = dataset
+            .records()
+            .iter()
+            .find(|r| r.id == "CVE-2016-0800")
+            .unwrap();
+        assert_eq!(record.fixed_after("1.0.1f"), Some("1.0.1s"));
+    }
+
+    #[test]
+    fn imported_records_replace_builtin_ones() {
+        let mut dataset = CveDataset::new();
+        let before = dataset.records().len();
+        let added = dataset
+            .add(
+                r#"{"cves": [{
+                    "id": "CVE-2018-1000517",
+                    "product": "busybox",
+                    "summary": "wget overflow, revised range.",
+                    "cvss_score": null,
+                    "cvss_vector": null,
+                    "cwe": null,
+                    "affected": [{"introduced": "1.20.0", "fixed": "1.28.0"}]
+                }]}"#,
+            )
+            .unwrap();
+        assert_eq!(added, 1);
+        assert_eq!(dataset.records().len(), before);
+        assert!(!ids(&dataset, "busybox", "1.28.1").contains(&"CVE-2018-1000517".to_string()));
+    }
+
+    #[test]
+    fn vulnerabilities_carry_score_and_fix() {
+        let sbom = Sbom::new(
+            PathBuf::from("fw.bin"),
+            None,
+            vec![component("busybox", "1.27.2")],
+        );
+        let manifest = Manifest {
+            source: PathBuf::from("fw.bin"),
+            work_dir: PathBuf::from("/work"),
+            entries: Vec::new(),
+            warnings: Vec::new(),
+            truncated: false,
+        };
+        let vulns = CveDataset::new().vulnerabilities(&sbom, &manifest);
+        let wget = vulns
+            .iter()
+            .find(|v| v.cve.as_deref() == Some("CVE-2018-1000517"))
+            .unwrap();
+        assert_eq!(wget.id, "FW-CVE:CVE-2018-1000517:busybox:1.27.2");
+        assert_eq!(wget.severity, VulnSeverity::Critical);
+        assert_eq!(wget.cvss.as_ref().unwrap().base_score, 9.8);
+        assert_eq!(wget.cwe.as_deref(), Some("CWE-119"));
+        assert_eq!(wget.affected, "busybox 1.27.2");
+        assert_eq!(
+            wget.detection_location.file_path.as_deref(),
+            Some("/work/bin/busybox")
+        );
+        assert_eq!(
+            wget.remediation.as_deref(),
+            Some("Upgrade busybox to 1.29.0 or later.")
+        );
+
+        let tab = vulns
+            .iter()
+            .find(|v| v.cve.as_deref() == Some("CVE-2017-16544"))
+            .unwrap();
+        assert!(tab
+            .remediation
+            .as_deref()
+            .unwrap()
+            .starts_with("No fixed busybox release"));
+    }
+}
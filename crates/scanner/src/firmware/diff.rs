@@ -0,0 +1,575 @@
+use super::container::ContainerKind;
+use super::hardening::is_elf;
+use super::secrets::{self, SecretMatch};
+use super::unpack::{Manifest, ManifestEntry};
+use crate::{Finding, Severity};
+use anyhow::{Context, Result};
+use goblin::elf::Elf;
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+pub const DIFF_FILE: &str = "diff.json";
+/// Text files larger than this are compared by hash only.
+const MAX_TEXT_DIFF_BYTES: u64 = 1024 * 1024;
+const DIFF_CONTEXT_LINES: usize = 3;
+/// Changed functions named in a finding's description.
+const DESCRIBED_FUNCTIONS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// Function and import differences between two builds of one ELF.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SymbolDiff {
+    pub functions_added: Vec<String>,
+    pub functions_removed: Vec<String>,
+    /// Functions present in both whose size changed.
+    pub functions_changed: Vec<String>,
+    pub imports_added: Vec<String>,
+    pub imports_removed: Vec<String>,
+}
+
+impl SymbolDiff {
+    pub fn is_empty(&self) -> bool {
+        self.functions_added.is_empty()
+            && self.functions_removed.is_empty()
+            && self.functions_changed.is_empty()
+            && self.imports_added.is_empty()
+            && self.imports_removed.is_empty()
+    }
+}
+
+/// One file that differs between the two images.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileChange {
+    /// Path inside the unpacked tree with the per-image parts (the image
+    /// name, carving offsets) normalised away, so both sides share it.
+    pub key: String,
+    pub kind: ChangeKind,
+    /// Manifest path in the older image.
+    pub old_path: Option<String>,
+    /// Manifest path in the newer image.
+    pub new_path: Option<String>,
+    pub old_size: Option<u64>,
+    pub new_size: Option<u64>,
+    pub old_mode: Option<u32>,
+    pub new_mode: Option<u32>,
+    /// Set for changed ELF binaries.
+    pub symbols: Option<SymbolDiff>,
+    /// Set for changed text files, such as configs and scripts.
+    pub unified_diff: Option<String>,
+}
+
+/// Secrets introduced or dropped by the newer image, merged on rule, value
+/// and SSID regardless of where they are.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SecretDiff {
+    pub added: Vec<SecretMatch>,
+    pub removed: Vec<SecretMatch>,
+}
+
+impl SecretDiff {
+    pub fn new(old: &[SecretMatch], new: &[SecretMatch]) -> Self {
+        let key = |m: &SecretMatch| (m.rule.clone(), m.value.clone(), m.ssid.clone());
+        let old_keys: HashSet<_> = old.iter().map(key).collect();
+        let new_keys: HashSet<_> = new.iter().map(key).collect();
+        Self {
+            added: new
+                .iter()
+                .filter(|m| !old_keys.contains(&key(m)))
+                .cloned()
+                .collect(),
+            removed: old
+                .iter()
+                .filter(|m| !new_keys.contains(&key(m)))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// Everything that differs between two firmware images.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FirmwareDiff {
+    pub old_source: PathBuf,
+    pub new_source: PathBuf,
+    pub changes: Vec<FileChange>,
+    pub secrets: SecretDiff,
+}
+
+impl FirmwareDiff {
+    /// Compares the file trees of two unpacked images. Secrets are left
+    /// empty; the analyzer fills them in.
+    pub fn compare(old: &Manifest, new: &Manifest) -> Self {
+        let old_entries = keyed(old);
+        let new_entries = keyed(new);
+        let keys: BTreeSet<&String> = old_entries.keys().chain(new_entries.keys()).collect();
+
+        let mut changes = Vec::new();
+        for key in keys {
+            let change = match (old_entries.get(key), new_entries.get(key)) {
+                (Some(o), Some(n)) => compare_entries(key, old, o, new, n),
+                (Some(o), None) => Some(FileChange::one_sided(key, ChangeKind::Removed, o)),
+                (None, Some(n)) => Some(FileChange::one_sided(key, ChangeKind::Added, n)),
+                (None, None) => None,
+            };
+            changes.extend(change);
+        }
+        Self {
+            old_source: old.source.clone(),
+            new_source: new.source.clone(),
+            changes,
+            secrets: SecretDiff::default(),
+        }
+    }
+
+    pub fn added(&self) -> impl Iterator<Item = &FileChange> {
+        self.changes.iter().filter(|c| c.kind == ChangeKind::Added)
+    }
+
+    pub fn removed(&self) -> impl Iterator<Item = &FileChange> {
+        self.changes
+            .iter()
+            .filter(|c| c.kind == ChangeKind::Removed)
+    }
+
+    pub fn changed(&self) -> impl Iterator<Item = &FileChange> {
+        self.changes
+            .iter()
+            .filter(|c| c.kind == ChangeKind::Changed)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read firmware diff {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("invalid firmware diff {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("failed to write firmware diff {}", path.display()))
+    }
+
+    /// Secrets the newer image introduced, secrets it dropped, binaries
+    /// whose functions changed size (candidate silent fixes) and files that
+    /// gained a setuid or setgid bit.
+    pub fn findings(&self) -> Vec<Finding> {
+        let new_name = file_name(&self.new_source);
+        let old_name = file_name(&self.old_source);
+        let mut findings = Vec::new();
+
+        for mut finding in secrets::findings(&self.secrets.added) {
+            finding.title = format!("{} (new in {})", finding.title, new_name);
+            findings.push(finding);
+        }
+        for mut finding in secrets::findings(&self.secrets.removed) {
+            finding.severity = Severity::Low;
+            finding.title = format!("{} (removed in {})", finding.title, new_name);
+            finding.description = format!(
+                "{} Present in {} but not in {}; if it was a credential or key, \
+                 devices still running {} are exposed and it may still be \
+                 accepted server-side.",
+                finding.description, old_name, new_name, old_name
+            );
+            findings.push(finding);
+        }
+
+        for change in self.changed() {
+            let Some(symbols) = &change.symbols else {
+                continue;
+            };
+            if symbols.functions_changed.is_empty() && symbols.imports_removed.is_empty() {
+                continue;
+            }
+            let shown: Vec<&str> = symbols
+                .functions_changed
+                .iter()
+                .take(DESCRIBED_FUNCTIONS)
+                .map(|s| s.as_str())
+                .collect();
+            let more = symbols.functions_changed.len().saturating_sub(shown.len());
+            let mut description = format!(
+                "{} changed between {} and {}.",
+                change.key, old_name, new_name
+            );
+            if !shown.is_empty() {
+                description.push_str(&format!(
+                    " Functions that changed size: {}{}.",
+                    shown.join(", "),
+                    if more > 0 {
+                        format!(" and {} more", more)
+                    } else {
+                        String::new()
+                    }
+                ));
+            }
+            if !symbols.imports_removed.is_empty() {
+                description.push_str(&format!(
+                    " Imports dropped: {}.",
+                    symbols.imports_removed.join(", ")
+                ));
+            }
+            description.push_str(
+                " Patched functions that the release notes do not mention point at \
+                 silently fixed vulnerabilities, which the older image still has.",
+            );
+            findings.push(
+                Finding::new(
+                    Severity::Low,
+                    format!("Binary changed: {}", change.key),
+                    description,
+                )
+                .with_affected(change.new_path.clone().unwrap_or_default()),
+            );
+        }
+
+        for change in &self.changes {
+            let privileged = |mode: Option<u32>| mode.is_some_and(|m| m & 0o6000 != 0);
+            if privileged(change.new_mode) && !privileged(change.old_mode) {
+                findings.push(
+                    Finding::new(
+                        Severity::Medium,
+                        format!("New setuid/setgid file: {}", change.key),
+                        format!(
+                            "{} has mode {:04o} in {} and was {} in {}. (CWE-250)",
+                            change.key,
+                            change.new_mode.unwrap_or(0),
+                            new_name,
+                            match change.old_mode {
+                                Some(mode) => format!("{:04o}", mode),
+                                None => "absent".to_string(),
+                            },
+                            old_name
+                        ),
+                    )
+                    .with_affected(change.new_path.clone().unwrap_or_default()),
+                );
+            }
+        }
+        findings
+    }
+}
+
+impl FileChange {
+    fn one_sided(key: &str, kind: ChangeKind, entry: &ManifestEntry) -> Self {
+        let (old, new) = match kind {
+            ChangeKind::Removed => (Some(entry), None),
+            _ => (None, Some(entry)),
+        };
+        Self {
+            key: key.to_string(),
+            kind,
+            old_path: old.map(|e| e.path.clone()),
+            new_path: new.map(|e| e.path.clone()),
+            old_size: old.map(|e| e.size),
+            new_size: new.map(|e| e.size),
+            old_mode: old.and_then(|e| e.mode),
+            new_mode: new.and_then(|e| e.mode),
+            symbols: None,
+            unified_diff: None,
+        }
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.to_string_lossy().into_owned())
+}
+
+/// Entries by a path both images agree on: members of a container are
+/// keyed below the container's key instead of below the image's file
+/// name, the payload of a single-stream container (gzip, xz, lzma, uImage)
+/// takes the container's key since it is named after it, and carved
+/// members are numbered per type instead of named by offset. Containers
+/// the unpacker opened are left out; their members are compared instead.
+fn keyed(manifest: &Manifest) -> BTreeMap<String, &ManifestEntry> {
+    let mut keys: HashMap<&str, String> = HashMap::new();
+    let mut carved: HashMap<(String, String), usize> = HashMap::new();
+    let mut entries = BTreeMap::new();
+    for entry in &manifest.entries {
+        let key = match entry.parent.as_deref() {
+            None => String::new(),
+            Some(parent) => {
+                let parent_key = keys.get(parent).cloned().unwrap_or_default();
+                let prefix = format!("{}.extracted/", parent);
+                let member = entry.path.strip_prefix(&prefix).unwrap_or(&entry.path);
+                let member = match entry.extracted_from {
+                    None => {
+                        let ext = member.rsplit_once('.').map_or("bin", |(_, e)| e);
+                        let n = carved
+                            .entry((parent_key.clone(), ext.to_string()))
+                            .or_default();
+                        *n += 1;
+                        format!("@{}{}", ext, n)
+                    }
+                    Some(
+                        ContainerKind::Gzip
+                        | ContainerKind::Xz
+                        | ContainerKind::Lzma
+                        | ContainerKind::UImage,
+                    ) => {
+                        keys.insert(&entry.path, parent_key.clone());
+                        entries.insert(parent_key, entry);
+                        continue;
+                    }
+                    Some(_) => member.to_string(),
+                };
+                if parent_key.is_empty() {
+                    member
+                } else {
+                    format!("{}/{}", parent_key, member)
+                }
+            }
+        };
+        keys.insert(&entry.path, key.clone());
+        entries.insert(key, entry);
+    }
+    let opened: HashSet<&str> = manifest
+        .entries
+        .iter()
+        .filter(|e| e.extracted_from.is_some())
+        .filter_map(|e| e.parent.as_deref())
+        .collect();
+    entries.retain(|_, e| !opened.contains(e.path.as_str()));
+    // The images themselves always differ.
+    entries.remove("");
+    entries
+}
+
+fn compare_entries(
+    key: &str,
+    old_manifest: &Manifest,
+    old: &ManifestEntry,
+    new_manifest: &Manifest,
+    new: &ManifestEntry,
+) -> Option<FileChange> {
+    if old.entry_type == new.entry_type && old.sha256 == new.sha256 && old.mode == new.mode {
+        return None;
+    }
+    let mut change = FileChange {
+        key: key.to_string(),
+        kind: ChangeKind::Changed,
+        old_path: Some(old.path.clone()),
+        new_path: Some(new.path.clone()),
+        old_size: Some(old.size),
+        new_size: Some(new.size),
+        old_mode: old.mode,
+        new_mode: new.mode,
+        symbols: None,
+        unified_diff: None,
+    };
+    if !(old.is_file() && new.is_file()) || old.sha256 == new.sha256 {
+        return Some(change);
+    }
+
+    let read = |manifest: &Manifest, entry: &ManifestEntry| {
+        let path = manifest.disk_path(entry);
+        std::fs::read(&path)
+            .map_err(|e| tracing::warn!("Skipping {}: {}", path.display(), e))
+            .ok()
+    };
+    let (Some(old_data), Some(new_data)) = (read(old_manifest, old), read(new_manifest, new))
+    else {
+        return Some(change);
+    };
+    if is_elf(&old_data) && is_elf(&new_data) {
+        change.symbols = symbol_diff(&old_data, &new_data);
+    } else if old.size.max(new.size) <= MAX_TEXT_DIFF_BYTES {
+        if let (Some(old_text), Some(new_text)) = (as_text(&old_data), as_text(&new_data)) {
+            change.unified_diff = Some(
+                TextDiff::from_lines(old_text, new_text)
+                    .unified_diff()
+                    .context_radius(DIFF_CONTEXT_LINES)
+                    .header(&old.path, &new.path)
+                    .to_string(),
+            );
+        }
+    }
+    Some(change)
+}
+
+fn as_text(data: &[u8]) -> Option<&str> {
+    if data.contains(&0) {
+        return None;
+    }
+    std::str::from_utf8(data).ok()
+}
+
+/// Defined functions with their sizes, and imported symbols.
+fn symbols(data: &[u8]) -> Option<(BTreeMap<String, u64>, BTreeSet<String>)> {
+    let elf = Elf::parse(data).ok()?;
+    let mut functions = BTreeMap::new();
+    for (sym, name) in elf
+        .syms
+        .iter()
+        .map(|s| (s, elf.strtab.get_at(s.st_name)))
+        .chain(
+            elf.dynsyms
+                .iter()
+                .map(|s| (s, elf.dynstrtab.get_at(s.st_name))),
+        )
+    {
+        let Some(name) = name.filter(|n| !n.is_empty()) else {
+            continue;
+        };
+        if sym.is_function() && sym.st_shndx != 0 {
+            functions.insert(name.to_string(), sym.st_size);
+        }
+    }
+    let imports = elf
+        .dynsyms
+        .iter()
+        .filter(|s| s.st_shndx == 0)
+        .filter_map(|s| elf.dynstrtab.get_at(s.st_name))
+        .filter(|n| !n.is_empty())
+        .map(str::to_string)
+        .collect();
+    Some((functions, imports))
+}
+
+fn symbol_diff(old: &[u8], new: &[u8]) -> Option<SymbolDiff> {
+    let (old_functions, old_imports) = symbols(old)?;
+    let (new_functions, new_imports) = symbols(new)?;
+    Some(SymbolDiff {
+        functions_added: new_functions
+            .keys()
+            .filter(|f| !old_functions.contains_key(*f))
+            .cloned()
+            .collect(),
+        functions_removed: old_functions
+            .keys()
+            .filter(|f| !new_functions.contains_key(*f))
+            .cloned()
+            .collect(),
+        functions_changed: old_functions
+            .iter()
+            .filter(|(f, size)| new_functions.get(*f).is_some_and(|s| s != *size))
+            .map(|(f, _)| f.clone())
+            .collect(),
+        imports_added: new_imports.difference(&old_imports).cloned().collect(),
+        imports_removed: old_imports.difference(&new_imports).cloned().collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fixtures::{self, ElfOptions, Scratch};
+    use super::super::FirmwareAnalyzer;
+    use super::*;
+
+    /// A gzip-compressed initramfs; the two releases differ only in the
+    /// files passed in.
+    fn image(files: &[(&str, u32, &[u8])]) -> Vec<u8> {
+        fixtures::gzip("rootfs.cpio", &fixtures::cpio(files))
+    }
+
+    #[test]
+    fn compares_two_releases() {
+        let scratch = Scratch::new("diff");
+        let old_app = fixtures::elf(&ElfOptions {
+            imports: &["strcpy", "system"],
+            ..ElfOptions::default()
+        });
+        let new_app = fixtures::elf(&ElfOptions {
+            imports: &["system"],
+            ..ElfOptions::default()
+        });
+        let helper = b"#!/bin/sh\nexec /usr/bin/app \"$@\"\n";
+        let old = scratch.write(
+            "fw-1.0.cpio.gz",
+            &image(&[
+                ("etc/version", 0o100644, b"1.0\n"),
+                (
+                    "etc/wifi.conf",
+                    0o100600,
+                    b"ssid=\"SkyLink-5G\"\nwpa_passphrase=hunter2drone\n",
+                ),
+                ("usr/bin/app", 0o100755, &old_app),
+                ("usr/sbin/helper", 0o100755, helper),
+                ("bin/legacy", 0o100755, b"#!/bin/sh\necho legacy\n"),
+            ]),
+        );
+        let new = scratch.write(
+            "fw-1.1.cpio.gz",
+            &image(&[
+                ("etc/version", 0o100644, b"1.1\n"),
+                (
+                    "etc/wifi.conf",
+                    0o100600,
+                    b"ssid=\"SkyLink-5G\"\nwpa_passphrase=skylink-fleet-2024\n",
+                ),
+                ("etc/banner", 0o100644, b"SkyLink\n"),
+                ("usr/bin/app", 0o100755, &new_app),
+                ("usr/sbin/helper", 0o104755, helper),
+            ]),
+        );
+
+        let diff = crate::test_runtime()
+            .block_on(async {
+                FirmwareAnalyzer::new(old)
+                    .with_work_dir(scratch.path().join("old"))
+                    .diff(&FirmwareAnalyzer::new(new).with_work_dir(scratch.path().join("new")))
+                    .await
+            })
+            .unwrap();
+
+        let keys = |changes: Vec<&FileChange>| -> Vec<String> {
+            changes.into_iter().map(|c| c.key.clone()).collect()
+        };
+        assert_eq!(keys(diff.added().collect()), ["etc/banner"]);
+        assert_eq!(keys(diff.removed().collect()), ["bin/legacy"]);
+        assert_eq!(
+            keys(diff.changed().collect()),
+            [
+                "etc/version",
+                "etc/wifi.conf",
+                "usr/bin/app",
+                "usr/sbin/helper"
+            ]
+        );
+
+        let change = |key: &str| diff.changes.iter().find(|c| c.key == key).unwrap();
+        let version = change("etc/version").unified_diff.as_deref().unwrap();
+        assert!(version.contains("-1.0\n+1.1\n"), "{}", version);
+        let symbols = change("usr/bin/app").symbols.as_ref().unwrap();
+        assert_eq!(symbols.imports_removed, ["strcpy"]);
+        assert!(symbols.imports_added.is_empty());
+        let helper = change("usr/sbin/helper");
+        assert_eq!(
+            (helper.old_mode, helper.new_mode),
+            (Some(0o755), Some(0o4755))
+        );
+        assert!(helper.unified_diff.is_none());
+
+        let value =
+            |m: &Vec<SecretMatch>| -> Vec<String> { m.iter().map(|m| m.value.clone()).collect() };
+        assert_eq!(value(&diff.secrets.added), ["skylink-fleet-2024"]);
+        assert_eq!(value(&diff.secrets.removed), ["hunter2drone"]);
+
+        let findings = diff.findings();
+        let titles: Vec<&str> = findings.iter().map(|f| f.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            [
+                "Hardcoded WiFi credentials (new in fw-1.1.cpio.gz)",
+                "Hardcoded WiFi credentials (removed in fw-1.1.cpio.gz)",
+                "Binary changed: usr/bin/app",
+                "New setuid/setgid file: usr/sbin/helper",
+            ]
+        );
+        assert_eq!(findings[1].severity, Severity::Low);
+        assert!(findings[2].description.contains("Imports dropped: strcpy."));
+
+        let saved = FirmwareDiff::load(&scratch.path().join("new").join(DIFF_FILE)).unwrap();
+        assert_eq!(saved.changes.len(), diff.changes.len());
+    }
+}
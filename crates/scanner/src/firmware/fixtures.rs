@@ -0,0 +1,251 @@
+//! Small firmware images built in memory for the extraction and analysis
+//! tests.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// A directory under the system temp directory, removed on drop.
+pub struct Scratch(PathBuf);
+
+impl Scratch {
+    pub fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("uavred-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).expect("failed to create scratch directory");
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Writes `data` to `name` inside the directory.
+    pub fn write(&self, name: &str, data: &[u8]) -> PathBuf {
+        let path = self.0.join(name);
+        std::fs::write(&path, data).expect("failed to write fixture");
+        path
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A tar archive of regular files. Names go into the header as given, so
+/// archives with `..` paths can be built.
+pub fn tar(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut builder = tar::Builder::new(Vec::new());
+    for (name, data) in files {
+        let mut header = tar::Header::new_gnu();
+        header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_cksum();
+        builder.append(&header, *data).expect("failed to build tar");
+    }
+    builder.into_inner().expect("failed to build tar")
+}
+
+/// A gzip stream that records `name` as the original file name.
+pub fn gzip(name: &str, data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::GzBuilder::new()
+        .filename(name)
+        .write(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data).expect("failed to build gzip");
+    encoder.finish().expect("failed to build gzip")
+}
+
+/// A `newc` cpio archive of `(name, mode, contents)` entries; a symlink's
+/// contents are its target.
+pub fn cpio(entries: &[(&str, u32, &[u8])]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let trailer = ("TRAILER!!!", 0, &[][..]);
+    for (name, mode, data) in entries.iter().copied().chain([trailer]) {
+        let fields = [
+            1,
+            mode,
+            0,
+            0,
+            1,
+            0,
+            data.len() as u32,
+            0,
+            0,
+            0,
+            0,
+            name.len() as u32 + 1,
+            0,
+        ];
+        out.extend_from_slice(b"070701");
+        for field in fields {
+            out.extend_from_slice(format!("{:08x}", field).as_bytes());
+        }
+        out.extend_from_slice(name.as_bytes());
+        out.push(0);
+        out.resize(out.len().next_multiple_of(4), 0);
+        out.extend_from_slice(data);
+        out.resize(out.len().next_multiple_of(4), 0);
+    }
+    out
+}
+
+/// Deterministic bytes that look like ciphertext.
+pub fn noise(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed | 1;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 24) as u8
+        })
+        .collect()
+}
+
+/// How `elf` links its binary. The default is a position-dependent
+/// executable with an executable stack and no RELRO.
+#[derive(Default)]
+pub struct ElfOptions<'a> {
+    pub pie: bool,
+    /// A shared object: position-independent, without an interpreter.
+    pub shared: bool,
+    pub nx: bool,
+    pub relro: bool,
+    pub bind_now: bool,
+    pub interpreter: Option<&'a str>,
+    pub imports: &'a [&'a str],
+}
+
+/// A minimal x86-64 ELF with program headers, a dynamic section and
+/// undefined symbols for `imports`, but no code and no section headers.
+pub fn elf(options: &ElfOptions) -> Vec<u8> {
+    const PT_LOAD: u32 = 1;
+    const PT_DYNAMIC: u32 = 2;
+    const PT_INTERP: u32 = 3;
+    const PT_GNU_STACK: u32 = 0x6474_e551;
+    const PT_GNU_RELRO: u32 = 0x6474_e552;
+
+    let dynamic_pie = options.pie || options.shared;
+    let base: u64 = if dynamic_pie { 0 } else { 0x40_0000 };
+    let interpreter = match options.interpreter {
+        _ if options.shared => None,
+        Some(interpreter) => Some(interpreter),
+        None => Some("/lib64/ld-linux-x86-64.so.2"),
+    };
+    let phnum = 3 + interpreter.is_some() as usize + options.relro as usize;
+
+    let mut body = Vec::new();
+    let interp_offset = body.len();
+    if let Some(interpreter) = interpreter {
+        body.extend_from_slice(interpreter.as_bytes());
+        body.push(0);
+    }
+    let interp_size = body.len() - interp_offset;
+
+    let strtab_offset = body.len();
+    let mut strtab = b"\0libc.so.6\0".to_vec();
+    let mut names = Vec::new();
+    for import in options.imports {
+        names.push(strtab.len() as u32);
+        strtab.extend_from_slice(import.as_bytes());
+        strtab.push(0);
+    }
+    body.extend_from_slice(&strtab);
+    body.resize(body.len().next_multiple_of(8), 0);
+
+    let symtab_offset = body.len();
+    body.extend_from_slice(&[0; 24]);
+    for name in &names {
+        body.extend_from_slice(&name.to_le_bytes());
+        // STB_GLOBAL, STT_FUNC; undefined, so section index and value are 0.
+        body.extend_from_slice(&[0x12, 0, 0, 0]);
+        body.extend_from_slice(&[0; 16]);
+    }
+
+    // A SysV hash table with one bucket; the loader only needs nchain here.
+    let hash_offset = body.len();
+    let nsyms = names.len() as u32 + 1;
+    for word in [1, nsyms, 0].into_iter().chain((0..nsyms).map(|_| 0)) {
+        body.extend_from_slice(&word.to_le_bytes());
+    }
+    body.resize(body.len().next_multiple_of(8), 0);
+
+    let start = 64 + 56 * phnum as u64;
+    let address = |offset: usize| base + start + offset as u64;
+    let dynamic_offset = body.len();
+    let mut dynamic = vec![
+        (1, 1),
+        (4, address(hash_offset)),
+        (5, address(strtab_offset)),
+        (6, address(symtab_offset)),
+        (10, strtab.len() as u64),
+        (11, 24),
+    ];
+    if options.bind_now {
+        dynamic.push((30, 0x8));
+    }
+    if options.pie && !options.shared {
+        dynamic.push((0x6fff_fffb, 0x0800_0000));
+    }
+    dynamic.push((0, 0));
+    for (tag, value) in &dynamic {
+        body.extend_from_slice(&u64::to_le_bytes(*tag));
+        body.extend_from_slice(&value.to_le_bytes());
+    }
+    let dynamic_size = body.len() - dynamic_offset;
+    let size = start + body.len() as u64;
+
+    let mut out = Vec::new();
+    out.extend_from_slice(b"\x7fELF\x02\x01\x01");
+    out.resize(16, 0);
+    let e_type: u16 = if dynamic_pie { 3 } else { 2 };
+    out.extend_from_slice(&e_type.to_le_bytes());
+    out.extend_from_slice(&62u16.to_le_bytes());
+    out.extend_from_slice(&1u32.to_le_bytes());
+    out.extend_from_slice(&base.to_le_bytes());
+    out.extend_from_slice(&64u64.to_le_bytes());
+    out.extend_from_slice(&0u64.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    for half in [64u16, 56, phnum as u16, 64, 0, 0] {
+        out.extend_from_slice(&half.to_le_bytes());
+    }
+
+    let mut segment = |p_type: u32, flags: u32, offset: u64, length: u64| {
+        out.extend_from_slice(&p_type.to_le_bytes());
+        out.extend_from_slice(&flags.to_le_bytes());
+        out.extend_from_slice(&offset.to_le_bytes());
+        for value in [base + offset, base + offset, length, length, 8] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    };
+    if interpreter.is_some() {
+        segment(
+            PT_INTERP,
+            4,
+            start + interp_offset as u64,
+            interp_size as u64,
+        );
+    }
+    segment(PT_LOAD, 5, 0, size);
+    segment(
+        PT_DYNAMIC,
+        6,
+        start + dynamic_offset as u64,
+        dynamic_size as u64,
+    );
+    segment(PT_GNU_STACK, if options.nx { 6 } else { 7 }, 0, 0);
+    if options.relro {
+        segment(
+            PT_GNU_RELRO,
+            4,
+            start + dynamic_offset as u64,
+            dynamic_size as u64,
+        );
+    }
+    out.extend_from_slice(&body);
+    out
+}
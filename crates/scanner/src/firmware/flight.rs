@@ -0,0 +1,453 @@
+use super::container::read_limited;
+use super::unpack::sha256_hex;
+use crate::network::uav_detect::AutopilotFamily;
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use data::{AssetNode, FlightFirmwareInfo, VehicleParameter};
+use regex::bytes::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::OnceLock;
+
+pub const FLIGHT_FIRMWARE_FILE: &str = "flight_firmware.json";
+
+const PX4_MAGIC: &str = "PX4FWv1";
+const APJ_MAGIC: &str = "APJFWv1";
+/// `MAV_AUTOPILOT_ARDUPILOTMEGA` and `MAV_AUTOPILOT_PX4`.
+const MAV_AUTOPILOT_ARDUPILOT: u64 = 3;
+const MAV_AUTOPILOT_PX4: u64 = 12;
+/// Header of ArduPilot's embedded parameter defaults (`AP_Param.cpp`):
+/// `PARMDEF\0`, eight magic bytes, then the maximum and used length of the
+/// text that follows as little-endian u16s.
+const PARAM_DEFAULTS_MAGIC: &[u8] = b"PARMDEF\0\x55\x37\xf4\xa0\x38\x5d\x48\x5b";
+/// MAV_PARAM_TYPE_INT32 and MAV_PARAM_TYPE_REAL32.
+const PARAM_TYPE_INT32: u8 = 6;
+const PARAM_TYPE_REAL32: u8 = 9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlightImageFormat {
+    /// PX4 `.px4`: JSON with a zlib-compressed, base64-encoded image.
+    Px4,
+    /// ArduPilot `.apj`: the same container with its own magic.
+    Apj,
+    /// Intel HEX, as produced for bootloaders and DFU flashing.
+    #[serde(rename = "ihex")]
+    IntelHex,
+    /// A raw flash image recognised by the ArduPilot version banner.
+    Bin,
+}
+
+impl FlightImageFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Px4 => "px4",
+            Self::Apj => "apj",
+            Self::IntelHex => "ihex",
+            Self::Bin => "bin",
+        }
+    }
+}
+
+/// What a flight-controller firmware file says about the build inside it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlightFirmware {
+    pub file: String,
+    pub format: FlightImageFormat,
+    pub autopilot: Option<AutopilotFamily>,
+    /// Vehicle firmware from the version banner, e.g. `ArduCopter`.
+    pub vehicle: Option<String>,
+    pub board_id: Option<u32>,
+    pub board_revision: Option<u32>,
+    /// Board name, e.g. `CubeOrange` or `PX4_FMU_V5`.
+    pub board: Option<String>,
+    pub version: Option<String>,
+    pub git_hash: Option<String>,
+    /// RFC 3339.
+    pub build_time: Option<String>,
+    /// Flash address of the first byte, for Intel HEX.
+    pub load_address: Option<u32>,
+    pub image_size: u64,
+    pub image_sha256: String,
+    /// Defaults compiled into the image: `parameters.xml` for PX4, the
+    /// `PARMDEF` block for ArduPilot.
+    pub parameter_defaults: Vec<VehicleParameter>,
+    /// The decoded flash image.
+    #[serde(skip)]
+    pub image: Vec<u8>,
+}
+
+impl FlightFirmware {
+    /// Decodes `data` if it is a `.px4`, `.apj`, Intel HEX or ArduPilot
+    /// binary image; `None` if it is none of these. Images are capped at
+    /// `limit` bytes after decompression.
+    pub fn parse(file: &str, data: &[u8], limit: usize) -> Result<Option<Self>> {
+        let start = data.iter().position(|b| !b.is_ascii_whitespace());
+        match start.map(|i| data[i]) {
+            Some(b'{') => Self::parse_json(file, data, limit),
+            Some(b':') if looks_like_intel_hex(data) => {
+                let text = std::str::from_utf8(data).context("Intel HEX is not ASCII")?;
+                let (address, image) = parse_intel_hex(text, limit)?;
+                let mut firmware = Self::from_image(file, FlightImageFormat::IntelHex, image);
+                firmware.load_address = Some(address);
+                firmware.fingerprint();
+                Ok(Some(firmware))
+            }
+            _ if banner().is_match(data) => {
+                let mut firmware = Self::from_image(file, FlightImageFormat::Bin, data.to_vec());
+                firmware.fingerprint();
+                Ok(Some(firmware))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn from_image(file: &str, format: FlightImageFormat, image: Vec<u8>) -> Self {
+        Self {
+            file: file.to_string(),
+            format,
+            autopilot: None,
+            vehicle: None,
+            board_id: None,
+            board_revision: None,
+            board: None,
+            version: None,
+            git_hash: None,
+            build_time: None,
+            load_address: None,
+            image_size: image.len() as u64,
+            image_sha256: sha256_hex(&image),
+            parameter_defaults: Vec::new(),
+            image,
+        }
+    }
+
+    fn parse_json(file: &str, data: &[u8], limit: usize) -> Result<Option<Self>> {
+        let Ok(header) = serde_json::from_slice::<Value>(data) else {
+            return Ok(None);
+        };
+        let format = match header.get("magic").and_then(Value::as_str) {
+            Some(PX4_MAGIC) => FlightImageFormat::Px4,
+            Some(APJ_MAGIC) => FlightImageFormat::Apj,
+            _ => return Ok(None),
+        };
+        let text = |key: &str| {
+            header
+                .get(key)
+                .and_then(Value::as_str)
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+        };
+        let number = |key: &str| header.get(key).and_then(Value::as_u64);
+
+        let image = text("image").ok_or_else(|| anyhow!("{} has no image", file))?;
+        let image = inflate(&image, limit).with_context(|| format!("invalid image in {}", file))?;
+        let mut firmware = Self::from_image(file, format, image);
+        if let Some(size) = number("image_size") {
+            if size != firmware.image_size {
+                tracing::warn!(
+                    "{} declares a {} byte image but decodes to {} bytes",
+                    file,
+                    size,
+                    firmware.image_size
+                );
+            }
+        }
+        firmware.board_id = number("board_id").and_then(|n| u32::try_from(n).ok());
+        firmware.board_revision = number("board_revision").and_then(|n| u32::try_from(n).ok());
+        firmware.board = text("summary");
+        firmware.build_time = number("build_time")
+            .and_then(|t| chrono::DateTime::from_timestamp(i64::try_from(t).ok()?, 0))
+            .map(|t| t.to_rfc3339());
+        firmware.autopilot = Some(match number("mav_autopilot") {
+            Some(MAV_AUTOPILOT_PX4) => AutopilotFamily::Px4,
+            Some(MAV_AUTOPILOT_ARDUPILOT) => AutopilotFamily::ArduPilot,
+            Some(other) => AutopilotFamily::Other(other as u8),
+            None if format == FlightImageFormat::Px4 => AutopilotFamily::Px4,
+            None => AutopilotFamily::ArduPilot,
+        });
+
+        // `git_identity` is `git describe` output for PX4 and the short
+        // commit hash for ArduPilot.
+        let identity = text("git_identity");
+        firmware.git_hash = text("git_hash").or_else(|| {
+            identity
+                .clone()
+                .filter(|i| i.chars().all(|c| c.is_ascii_hexdigit()))
+        });
+        if format == FlightImageFormat::Px4 {
+            firmware.version = identity.filter(|i| Some(i) != firmware.git_hash.as_ref());
+        }
+
+        if let Some(xml) = text("parameter_xml") {
+            match inflate(&xml, limit).and_then(|xml| parse_parameter_xml(&xml)) {
+                Ok(defaults) => firmware.parameter_defaults = defaults,
+                Err(e) => tracing::warn!("Ignoring parameter_xml in {}: {}", file, e),
+            }
+        }
+        firmware.fingerprint();
+        Ok(Some(firmware))
+    }
+
+    /// Fills in what the image itself reveals: ArduPilot's version banner,
+    /// e.g. `ArduCopter V4.5.1 (9ab3fd07)`, and embedded parameter defaults.
+    fn fingerprint(&mut self) {
+        if let Some(caps) = banner().captures(&self.image) {
+            let text = |i: usize| {
+                caps.get(i)
+                    .map(|m| String::from_utf8_lossy(m.as_bytes()).into_owned())
+            };
+            self.autopilot.get_or_insert(AutopilotFamily::ArduPilot);
+            self.vehicle = text(1);
+            self.version = self.version.take().or_else(|| text(2));
+            self.git_hash = self.git_hash.take().or_else(|| text(3));
+        }
+        if self.parameter_defaults.is_empty() {
+            self.parameter_defaults = embedded_defaults(&self.image);
+        }
+    }
+
+    /// Version as the asset should show it, e.g. `ArduCopter 4.5.1`.
+    pub fn display_version(&self) -> Option<String> {
+        let version = self.version.as_deref()?;
+        Some(match &self.vehicle {
+            Some(vehicle) => format!("{} {}", vehicle, version),
+            None => version.to_string(),
+        })
+    }
+
+    pub fn info(&self) -> FlightFirmwareInfo {
+        FlightFirmwareInfo {
+            file: self.file.clone(),
+            format: self.format.as_str().to_string(),
+            autopilot: self.autopilot.as_ref().map(|a| match a {
+                AutopilotFamily::ArduPilot => "ArduPilot".to_string(),
+                AutopilotFamily::Px4 => "PX4".to_string(),
+                other => format!("{:?}", other),
+            }),
+            vehicle: self.vehicle.clone(),
+            board_id: self.board_id,
+            board_revision: self.board_revision,
+            board: self.board.clone(),
+            version: self.version.clone(),
+            git_hash: self.git_hash.clone(),
+            build_time: self.build_time.clone(),
+            image_size: self.image_size,
+            image_sha256: self.image_sha256.clone(),
+            parameter_defaults: self.parameter_defaults.clone(),
+        }
+    }
+
+    /// Records the build on `asset`, replacing an earlier record of the same
+    /// image, and sets its firmware version if none is known yet.
+    pub fn apply_to(&self, asset: &mut AssetNode) {
+        asset
+            .flight_firmware
+            .retain(|f| f.image_sha256 != self.image_sha256);
+        asset.flight_firmware.push(self.info());
+        if asset.firmware_version.is_none() {
+            asset.firmware_version = self.display_version();
+        }
+    }
+}
+
+/// ArduPilot's firmware string, e.g. `ArduCopter V4.5.1 (9ab3fd07)`.
+fn banner() -> &'static Regex {
+    static BANNER: OnceLock<Regex> = OnceLock::new();
+    BANNER.get_or_init(|| {
+        Regex::new(
+            r"(Ardu(?:Copter|Plane|Rover|Sub|Heli)|AntennaTracker|Blimp|AP_Periph) V(\d+\.\d+\.\d+(?:-[A-Za-z0-9]+)?)(?: \(([0-9a-f]{8})\))?",
+        )
+        .unwrap()
+    })
+}
+
+fn inflate(encoded: &str, limit: usize) -> Result<Vec<u8>> {
+    let compressed = decode_base64(encoded)?;
+    read_limited(
+        &mut flate2::read::ZlibDecoder::new(compressed.as_slice()),
+        limit,
+    )
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>> {
+    let compact: String = text.split_ascii_whitespace().collect();
+    STANDARD.decode(compact).context("invalid base64 image")
+}
+
+fn looks_like_intel_hex(data: &[u8]) -> bool {
+    data.split(|&b| b == b'\n')
+        .map(|line| line.trim_ascii())
+        .find(|line| !line.is_empty())
+        .is_some_and(|line| {
+            line.len() >= 11 && line[0] == b':' && line[1..].iter().all(u8::is_ascii_hexdigit)
+        })
+}
+
+/// Decodes Intel HEX into the flash address of its first byte and a
+/// contiguous image, with gaps between records filled with `0xff` as in
+/// erased flash.
+pub fn parse_intel_hex(text: &str, limit: usize) -> Result<(u32, Vec<u8>)> {
+    let mut records: Vec<(u32, Vec<u8>)> = Vec::new();
+    let mut base = 0u32;
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let bytes = line
+            .as_bytes()
+            .strip_prefix(b":")
+            .filter(|hex| hex.len() % 2 == 0 && hex.iter().all(u8::is_ascii_hexdigit))
+            .map(|hex| {
+                hex.chunks(2)
+                    .map(|pair| (hex_value(pair[0]) << 4) | hex_value(pair[1]))
+                    .collect::<Vec<u8>>()
+            })
+            .filter(|b| b.len() >= 5 && b.len() == usize::from(b[0]) + 5)
+            .ok_or_else(|| anyhow!("malformed Intel HEX record on line {}", number + 1))?;
+        if bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0 {
+            bail!("Intel HEX checksum mismatch on line {}", number + 1);
+        }
+        let offset = u32::from(u16::from_be_bytes([bytes[1], bytes[2]]));
+        let payload = &bytes[4..bytes.len() - 1];
+        match bytes[3] {
+            0x00 => records.push((base.wrapping_add(offset), payload.to_vec())),
+            0x01 => break,
+            0x02 if payload.len() == 2 => {
+                base = u32::from(u16::from_be_bytes([payload[0], payload[1]])) << 4
+            }
+            0x04 if payload.len() == 2 => {
+                base = u32::from(u16::from_be_bytes([payload[0], payload[1]])) << 16
+            }
+            // Start addresses say nothing about the image contents.
+            0x03 | 0x05 => {}
+            kind => bail!(
+                "unsupported Intel HEX record type {:02x} on line {}",
+                kind,
+                number + 1
+            ),
+        }
+    }
+
+    let start = records
+        .iter()
+        .map(|(address, _)| *address)
+        .min()
+        .ok_or_else(|| anyhow!("Intel HEX contains no data"))?;
+    let end = records
+        .iter()
+        .map(|(address, data)| u64::from(*address) + data.len() as u64)
+        .max()
+        .unwrap_or(u64::from(start));
+    let size = (end - u64::from(start)) as usize;
+    if size > limit {
+        bail!(
+            "Intel HEX spans {} bytes, over the {} byte limit",
+            size,
+            limit
+        );
+    }
+    let mut image = vec![0xff; size];
+    for (address, data) in records {
+        let at = (address - start) as usize;
+        image[at..at + data.len()].copy_from_slice(&data);
+    }
+    Ok((start, image))
+}
+
+/// Value of an ASCII hex digit the caller has already validated.
+fn hex_value(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        _ => digit - b'A' + 10,
+    }
+}
+
+/// PX4's `parameters.xml`: `<parameter name=".." type="INT32|FLOAT"
+/// default="..">` in groups.
+fn parse_parameter_xml(xml: &[u8]) -> Result<Vec<VehicleParameter>> {
+    let text = std::str::from_utf8(xml).context("parameters.xml is not UTF-8")?;
+    let doc = roxmltree::Document::parse(text).context("invalid parameters.xml")?;
+    Ok(doc
+        .descendants()
+        .filter(|n| n.has_tag_name("parameter"))
+        .filter_map(|n| {
+            let name = n.attribute("name")?;
+            let value = n.attribute("default")?.trim().parse::<f64>().ok()?;
+            let param_type = match n.attribute("type") {
+                Some("INT32") => PARAM_TYPE_INT32,
+                _ => PARAM_TYPE_REAL32,
+            };
+            Some((name.to_string(), value, param_type))
+        })
+        .enumerate()
+        .map(|(index, (name, value, param_type))| VehicleParameter {
+            name,
+            value,
+            param_type,
+            index: index as u16,
+        })
+        .collect())
+}
+
+/// ArduPilot's embedded defaults: `NAME VALUE` lines, separated by spaces,
+/// commas or `=` as in a defaults file.
+fn embedded_defaults(image: &[u8]) -> Vec<VehicleParameter> {
+    let Some(at) = image
+        .windows(PARAM_DEFAULTS_MAGIC.len())
+        .position(|w| w == PARAM_DEFAULTS_MAGIC)
+    else {
+        return Vec::new();
+    };
+    let header = at + PARAM_DEFAULTS_MAGIC.len();
+    let Some(lengths) = image.get(header..header + 4) else {
+        return Vec::new();
+    };
+    let length = usize::from(u16::from_le_bytes([lengths[2], lengths[3]]));
+    let Some(text) = image.get(header + 4..header + 4 + length) else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(text)
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .filter_map(|line| {
+            let mut parts = line
+                .split(|c: char| c.is_whitespace() || c == ',' || c == '=')
+                .filter(|p| !p.is_empty());
+            let name = parts.next()?;
+            let value = parts.next()?.parse::<f64>().ok()?;
+            Some((name.to_string(), value))
+        })
+        .enumerate()
+        .map(|(index, (name, value))| VehicleParameter {
+            name,
+            value,
+            param_type: PARAM_TYPE_REAL32,
+            index: index as u16,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intel_hex_rejects_non_ascii_records() {
+        let err = parse_intel_hex(":020000040800F2\n:aé0\n", 1 << 20).unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{err}");
+        assert!(parse_intel_hex(":0g0000040800F2\n", 1 << 20).is_err());
+    }
+
+    #[test]
+    fn intel_hex_places_data_at_its_extended_address() {
+        let text = ":020000040800F2\n:0400000001020304F2\n:00000001FF\n";
+        let (address, image) = parse_intel_hex(text, 1 << 20).unwrap();
+        assert_eq!(address, 0x0800_0000);
+        assert_eq!(image, [1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,1137 @@
+use super::unpack::Manifest;
+use crate::Severity;
+use anyhow::{Context, Result};
+use data::{DetectionSource, ScanType, VulnData, VulnSeverity};
+use goblin::elf::dynamic::{DF_1_NOW, DF_1_PIE, DF_BIND_NOW, DT_BIND_NOW, DT_PLTGOT};
+use goblin::elf::header::{EM_386, EM_AARCH64, EM_ARM, EM_MIPS, EM_X86_64, ET_DYN, ET_EXEC};
+use goblin::elf::program_header::{PF_X, PT_GNU_RELRO, PT_GNU_STACK};
+use goblin::elf::section_header::{SHF_EXECINSTR, SHT_NOBITS};
+use goblin::elf::Elf;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+const BUILTIN_FUNCTIONS: &str = include_str!("../../signatures/dangerous_functions.toml");
+
+/// Call sites kept per imported function.
+const MAX_CALL_SITES: usize = 64;
+/// Calling functions named in a finding's description.
+const DESCRIBED_CALLERS: usize = 10;
+
+const DT_MIPS_LOCAL_GOTNO: u64 = 0x7000_000a;
+const DT_MIPS_GOTSYM: u64 = 0x7000_0013;
+/// `_gp` points this far into the MIPS GOT so 16-bit offsets reach all of it.
+const MIPS_GP_BIAS: u64 = 0x7ff0;
+
+/// Imports that `_FORTIFY_SOURCE` replaces with a `__*_chk` variant.
+const FORTIFIABLE: &[&str] = &[
+    "memcpy",
+    "memmove",
+    "memset",
+    "strcpy",
+    "strncpy",
+    "strcat",
+    "strncat",
+    "stpcpy",
+    "sprintf",
+    "snprintf",
+    "vsprintf",
+    "vsnprintf",
+    "printf",
+    "fprintf",
+    "gets",
+    "fgets",
+    "read",
+    "realpath",
+];
+
+#[derive(Debug, Clone, Deserialize)]
+struct FunctionFile {
+    #[serde(default)]
+    functions: Vec<DangerousFunction>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DangerousFunction {
+    pub name: String,
+    pub severity: Severity,
+    pub cwe: Option<String>,
+    pub description: String,
+    pub remediation: Option<String>,
+}
+
+impl DangerousFunction {
+    fn executes_commands(&self) -> bool {
+        self.cwe.as_deref() == Some("CWE-78")
+    }
+}
+
+/// Imports flagged by the audit, from `signatures/dangerous_functions.toml`
+/// plus any user-supplied files.
+#[derive(Debug, Clone)]
+pub struct DangerousFunctions {
+    functions: Vec<DangerousFunction>,
+}
+
+impl DangerousFunctions {
+    pub fn new() -> Self {
+        let mut functions = Self::empty();
+        functions
+            .add(BUILTIN_FUNCTIONS)
+            .expect("built-in dangerous function list must be valid");
+        functions
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            functions: Vec::new(),
+        }
+    }
+
+    pub fn load(&mut self, path: &Path) -> Result<usize> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read dangerous functions {}", path.display()))?;
+        self.add(&content)
+            .with_context(|| format!("invalid dangerous functions {}", path.display()))
+    }
+
+    /// Adds entries, replacing any already loaded under the same name.
+    pub fn add(&mut self, content: &str) -> Result<usize> {
+        let file: FunctionFile = toml::from_str(content)?;
+        let count = file.functions.len();
+        for function in file.functions {
+            self.functions.retain(|f| f.name != function.name);
+            self.functions.push(function);
+        }
+        Ok(count)
+    }
+
+    pub fn functions(&self) -> &[DangerousFunction] {
+        &self.functions
+    }
+
+    pub fn get(&self, name: &str) -> Option<&DangerousFunction> {
+        self.functions.iter().find(|f| f.name == name)
+    }
+}
+
+impl Default for DangerousFunctions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BinaryKind {
+    Executable,
+    SharedObject,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Relro {
+    None,
+    Partial,
+    Full,
+}
+
+/// Exploit mitigations compiled into one binary, as checksec reports them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mitigations {
+    /// The stack is not executable.
+    pub nx: bool,
+    /// `None` for shared objects, which are always position-independent.
+    pub pie: Option<bool>,
+    pub relro: Relro,
+    pub canary: bool,
+    /// `None` when nothing fortifiable is imported, or the C library
+    /// (uClibc, musl) has no fortified variants.
+    pub fortify: Option<bool>,
+}
+
+impl Mitigations {
+    /// Names of the mitigations that are missing.
+    pub fn missing(&self) -> Vec<&'static str> {
+        let mut missing = Vec::new();
+        if !self.nx {
+            missing.push("NX");
+        }
+        if self.pie == Some(false) {
+            missing.push("PIE");
+        }
+        match self.relro {
+            Relro::None => missing.push("RELRO"),
+            Relro::Partial => missing.push("full RELRO"),
+            Relro::Full => {}
+        }
+        if !self.canary {
+            missing.push("stack canaries");
+        }
+        if self.fortify == Some(false) {
+            missing.push("FORTIFY_SOURCE");
+        }
+        missing
+    }
+}
+
+/// Where an imported function is called.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallSite {
+    pub address: u64,
+    /// Symbol of the enclosing function, if the binary still has one.
+    pub function: Option<String>,
+}
+
+impl CallSite {
+    /// The enclosing function's name, or the call's address in a stripped
+    /// binary.
+    pub fn label(&self) -> String {
+        self.function
+            .clone()
+            .unwrap_or_else(|| format!("0x{:x}", self.address))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DangerousImport {
+    pub name: String,
+    pub call_sites: Vec<CallSite>,
+}
+
+/// Hardening audit of one ELF executable or shared object.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BinaryAudit {
+    /// Manifest path.
+    pub file: String,
+    pub kind: BinaryKind,
+    pub machine: String,
+    pub mitigations: Mitigations,
+    pub imports: Vec<DangerousImport>,
+    pub mode: Option<u32>,
+}
+
+impl BinaryAudit {
+    pub fn is_setuid(&self) -> bool {
+        self.mode.is_some_and(|m| m & 0o4000 != 0)
+    }
+
+    pub fn is_setgid(&self) -> bool {
+        self.mode.is_some_and(|m| m & 0o2000 != 0)
+    }
+}
+
+pub fn is_elf(data: &[u8]) -> bool {
+    data.starts_with(b"\x7fELF")
+}
+
+/// Audits `data` if it is an ELF executable or shared object; relocatable
+/// objects such as kernel modules are skipped.
+pub fn audit(
+    file: &str,
+    data: &[u8],
+    mode: Option<u32>,
+    functions: &DangerousFunctions,
+) -> Option<BinaryAudit> {
+    let elf = match Elf::parse(data) {
+        Ok(elf) => elf,
+        Err(e) => {
+            tracing::debug!("Not auditing {}: {}", file, e);
+            return None;
+        }
+    };
+    let e_type = elf.header.e_type;
+    if e_type != ET_EXEC && e_type != ET_DYN {
+        return None;
+    }
+    let flags_1 = elf.dynamic.as_ref().map_or(0, |d| d.info.flags_1);
+    let flags = elf.dynamic.as_ref().map_or(0, |d| d.info.flags);
+    let kind = if e_type == ET_DYN && elf.interpreter.is_none() && flags_1 & DF_1_PIE == 0 {
+        BinaryKind::SharedObject
+    } else {
+        BinaryKind::Executable
+    };
+
+    let imported: Vec<&str> = elf
+        .dynsyms
+        .iter()
+        .filter(|s| s.st_shndx == 0)
+        .filter_map(|s| elf.dynstrtab.get_at(s.st_name))
+        .filter(|n| !n.is_empty())
+        .collect();
+    let has_symbol = |name: &str| {
+        imported.contains(&name)
+            || elf
+                .syms
+                .iter()
+                .any(|s| elf.strtab.get_at(s.st_name) == Some(name))
+    };
+
+    let nx = elf
+        .program_headers
+        .iter()
+        .find(|p| p.p_type == PT_GNU_STACK)
+        .is_some_and(|p| p.p_flags & PF_X == 0);
+    let bind_now = flags & DF_BIND_NOW != 0
+        || flags_1 & DF_1_NOW != 0
+        || elf
+            .dynamic
+            .as_ref()
+            .is_some_and(|d| d.dyns.iter().any(|d| d.d_tag == DT_BIND_NOW));
+    let relro = match (
+        elf.program_headers.iter().any(|p| p.p_type == PT_GNU_RELRO),
+        bind_now,
+    ) {
+        (false, _) => Relro::None,
+        (true, false) => Relro::Partial,
+        (true, true) => Relro::Full,
+    };
+    let canary = has_symbol("__stack_chk_fail") || has_symbol("__stack_chk_guard");
+    let fortified = imported
+        .iter()
+        .any(|n| n.starts_with("__") && n.ends_with("_chk") && *n != "__stack_chk_fail");
+    let fortifiable = imported.iter().any(|n| FORTIFIABLE.contains(n));
+    let libc_without_fortify = elf
+        .interpreter
+        .is_some_and(|i| i.contains("uClibc") || i.contains("musl"))
+        || elf
+            .libraries
+            .iter()
+            .any(|l| l.contains("uClibc") || l.starts_with("libc.so.0") || l.contains("musl"));
+    let fortify = if fortified {
+        Some(true)
+    } else if fortifiable && !libc_without_fortify {
+        Some(false)
+    } else {
+        None
+    };
+
+    let dangerous: Vec<&str> = imported
+        .iter()
+        .copied()
+        .filter(|n| functions.get(n).is_some())
+        .collect();
+    let mut sites = if dangerous.is_empty() {
+        HashMap::new()
+    } else {
+        CallResolver::new(&elf, data).call_sites(&dangerous)
+    };
+    let imports = dangerous
+        .iter()
+        .map(|name| DangerousImport {
+            name: name.to_string(),
+            call_sites: sites.remove(*name).unwrap_or_default(),
+        })
+        .collect();
+
+    Some(BinaryAudit {
+        file: file.to_string(),
+        kind,
+        machine: goblin::elf::header::machine_to_str(elf.header.e_machine).to_string(),
+        mitigations: Mitigations {
+            nx,
+            pie: match kind {
+                BinaryKind::SharedObject => None,
+                BinaryKind::Executable => Some(e_type == ET_DYN),
+            },
+            relro,
+            canary,
+            fortify,
+        },
+        imports,
+        mode,
+    })
+}
+
+/// Finds calls to imported functions by resolving PLT stubs and GOT slots
+/// to symbols and scanning executable code for branches to them.
+struct CallResolver<'a> {
+    elf: &'a Elf<'a>,
+    data: &'a [u8],
+    /// GOT slot address to imported symbol.
+    slots: HashMap<u64, String>,
+    /// PLT stub address to imported symbol.
+    stubs: HashMap<u64, String>,
+    /// Function symbols as (start, size, name), sorted by start.
+    functions: Vec<(u64, u64, String)>,
+}
+
+impl<'a> CallResolver<'a> {
+    fn new(elf: &'a Elf<'a>, data: &'a [u8]) -> Self {
+        let mut slots = HashMap::new();
+        for reloc in elf
+            .pltrelocs
+            .iter()
+            .chain(elf.dynrelas.iter())
+            .chain(elf.dynrels.iter())
+        {
+            if reloc.r_sym == 0 {
+                continue;
+            }
+            if let Some(name) = elf
+                .dynsyms
+                .get(reloc.r_sym)
+                .and_then(|s| elf.dynstrtab.get_at(s.st_name))
+            {
+                slots.insert(reloc.r_offset, name.to_string());
+            }
+        }
+
+        let thumb_bit = if elf.header.e_machine == EM_ARM {
+            !1
+        } else {
+            !0
+        };
+        let mut functions: Vec<(u64, u64, String)> = elf
+            .syms
+            .iter()
+            .map(|s| (s, elf.strtab.get_at(s.st_name)))
+            .chain(
+                elf.dynsyms
+                    .iter()
+                    .map(|s| (s, elf.dynstrtab.get_at(s.st_name))),
+            )
+            .filter(|(s, _)| s.is_function() && s.st_value != 0 && s.st_shndx != 0)
+            .filter_map(|(s, name)| {
+                let name = name.filter(|n| !n.is_empty())?;
+                Some((s.st_value & thumb_bit, s.st_size, name.to_string()))
+            })
+            .collect();
+        functions.sort_by_key(|f| f.0);
+        functions.dedup_by_key(|f| f.0);
+
+        let mut resolver = Self {
+            elf,
+            data,
+            slots,
+            stubs: HashMap::new(),
+            functions,
+        };
+        resolver.find_stubs();
+        resolver
+    }
+
+    fn section(&self, name: &str) -> Option<(u64, &'a [u8], u64)> {
+        let data: &'a [u8] = self.data;
+        self.elf.section_headers.iter().find_map(|sh| {
+            if self.elf.shdr_strtab.get_at(sh.sh_name) != Some(name) || sh.sh_type == SHT_NOBITS {
+                return None;
+            }
+            let start = sh.sh_offset as usize;
+            let bytes = data.get(start..start.checked_add(sh.sh_size as usize)?)?;
+            Some((sh.sh_addr, bytes, sh.sh_entsize))
+        })
+    }
+
+    /// Executable sections, or executable segments if the section headers
+    /// were stripped.
+    fn code(&self) -> Vec<(u64, &'a [u8])> {
+        let data: &'a [u8] = self.data;
+        let sections: Vec<(u64, &'a [u8])> = self
+            .elf
+            .section_headers
+            .iter()
+            .filter(|sh| sh.sh_flags & SHF_EXECINSTR as u64 != 0 && sh.sh_type != SHT_NOBITS)
+            .filter_map(|sh| {
+                let start = sh.sh_offset as usize;
+                Some((
+                    sh.sh_addr,
+                    data.get(start..start.checked_add(sh.sh_size as usize)?)?,
+                ))
+            })
+            .collect();
+        if !sections.is_empty() {
+            return sections;
+        }
+        self.elf
+            .program_headers
+            .iter()
+            .filter(|p| p.p_type == goblin::elf::program_header::PT_LOAD && p.p_flags & PF_X != 0)
+            .filter_map(|p| {
+                let start = p.p_offset as usize;
+                Some((
+                    p.p_vaddr,
+                    data.get(start..start.checked_add(p.p_filesz as usize)?)?,
+                ))
+            })
+            .collect()
+    }
+
+    fn u32_at(&self, bytes: &[u8], offset: usize) -> Option<u32> {
+        let word: [u8; 4] = bytes.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.elf.little_endian {
+            u32::from_le_bytes(word)
+        } else {
+            u32::from_be_bytes(word)
+        })
+    }
+
+    fn u16_at(&self, bytes: &[u8], offset: usize) -> Option<u16> {
+        let half: [u8; 2] = bytes.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.elf.little_endian {
+            u16::from_le_bytes(half)
+        } else {
+            u16::from_be_bytes(half)
+        })
+    }
+
+    /// `DT_PLTGOT` as an address; goblin's `DynamicInfo` holds it as a
+    /// file offset.
+    fn pltgot(&self) -> Option<u64> {
+        self.dynamic_value(DT_PLTGOT)
+    }
+
+    fn dynamic_value(&self, tag: u64) -> Option<u64> {
+        self.elf
+            .dynamic
+            .as_ref()?
+            .dyns
+            .iter()
+            .find(|d| d.d_tag == tag)
+            .map(|d| d.d_val)
+    }
+
+    /// Decodes each PLT entry to the GOT slot it jumps through.
+    fn find_stubs(&mut self) {
+        let mut stubs = HashMap::new();
+        for name in [".plt", ".plt.sec", ".plt.got"] {
+            let Some((addr, bytes, entsize)) = self.section(name) else {
+                continue;
+            };
+            match self.elf.header.e_machine {
+                EM_X86_64 | EM_386 => {
+                    let entsize = if entsize == 0 { 16 } else { entsize };
+                    let got_base = self.pltgot().unwrap_or(0);
+                    for i in 0..bytes.len().saturating_sub(5) {
+                        let disp = i32::from_le_bytes([
+                            bytes[i + 2],
+                            bytes[i + 3],
+                            bytes[i + 4],
+                            bytes[i + 5],
+                        ]);
+                        let slot = match (bytes[i], bytes[i + 1], self.elf.is_64) {
+                            // jmp *disp(%rip)
+                            (0xff, 0x25, true) => {
+                                (addr + i as u64 + 6).wrapping_add_signed(disp as i64)
+                            }
+                            // jmp *abs32
+                            (0xff, 0x25, false) => disp as u32 as u64,
+                            // jmp *off(%ebx), PIC i386
+                            (0xff, 0xa3, false) => got_base.wrapping_add_signed(disp as i64),
+                            _ => continue,
+                        };
+                        if let Some(symbol) = self.slots.get(&slot) {
+                            let entry = addr + (i as u64 / entsize) * entsize;
+                            stubs.insert(entry, symbol.clone());
+                        }
+                    }
+                }
+                EM_ARM => {
+                    // add ip, pc, #a; add ip, ip, #b; ...; ldr pc, [ip, #c]!
+                    let rotated = |w: u32| (w & 0xff).rotate_right(((w >> 8) & 0xf) * 2) as u64;
+                    let mut i = 0;
+                    while i + 4 <= bytes.len() {
+                        let Some(first) = self.u32_at(bytes, i) else {
+                            break;
+                        };
+                        if first & 0xffff_f000 != 0xe28f_c000 {
+                            i += 4;
+                            continue;
+                        }
+                        let start = addr + i as u64;
+                        let mut ip = start + 8 + rotated(first);
+                        let mut j = i + 4;
+                        while let Some(w) = self.u32_at(bytes, j) {
+                            if w & 0xffff_f000 == 0xe28c_c000 {
+                                ip += rotated(w);
+                                j += 4;
+                                continue;
+                            }
+                            if w & 0xffff_f000 == 0xe5bc_f000 {
+                                if let Some(symbol) = self.slots.get(&(ip + (w & 0xfff) as u64)) {
+                                    stubs.insert(start, symbol.clone());
+                                    // Thumb callers enter through `bx pc; nop`.
+                                    if i >= 4 && self.u32_at(bytes, i - 4) == Some(0x46c0_4778) {
+                                        stubs.insert(start - 4, symbol.clone());
+                                    }
+                                }
+                            }
+                            break;
+                        }
+                        i = j.max(i + 4);
+                    }
+                }
+                EM_AARCH64 => {
+                    // adrp x16, page; ldr x17, [x16, #off]
+                    for i in (0..bytes.len()).step_by(4) {
+                        let (Some(adrp), Some(ldr)) =
+                            (self.u32_at(bytes, i), self.u32_at(bytes, i + 4))
+                        else {
+                            break;
+                        };
+                        if adrp & 0x9f00_001f != 0x9000_0010 || ldr & 0xffc0_03ff != 0xf940_0211 {
+                            continue;
+                        }
+                        let pc = addr + i as u64;
+                        let imm = (((adrp >> 29) & 0x3) | (((adrp >> 5) & 0x7_ffff) << 2)) as u64;
+                        let page = (pc & !0xfff).wrapping_add_signed(sign_extend(imm << 12, 33));
+                        let slot = page + (((ldr >> 10) & 0xfff) as u64) * 8;
+                        if let Some(symbol) = self.slots.get(&slot) {
+                            stubs.insert(pc, symbol.clone());
+                        }
+                    }
+                }
+                EM_MIPS => {
+                    // lui t7, %hi(slot); lw t9, %lo(slot)(t7)
+                    for i in (0..bytes.len()).step_by(4) {
+                        let (Some(lui), Some(lw)) =
+                            (self.u32_at(bytes, i), self.u32_at(bytes, i + 4))
+                        else {
+                            break;
+                        };
+                        if lui & 0xffff_0000 != 0x3c0f_0000 || lw & 0xffff_0000 != 0x8df9_0000 {
+                            continue;
+                        }
+                        let slot = (((lui & 0xffff) as u64) << 16)
+                            .wrapping_add_signed((lw & 0xffff) as u16 as i16 as i64)
+                            & 0xffff_ffff;
+                        if let Some(symbol) = self.slots.get(&slot) {
+                            stubs.insert(addr + i as u64, symbol.clone());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.stubs = stubs;
+    }
+
+    /// MIPS PIC code loads callee addresses from the GOT, whose global
+    /// part follows the dynamic symbol table from `DT_MIPS_GOTSYM` on.
+    fn mips_got_slots(&self) -> HashMap<u64, String> {
+        let mut slots = HashMap::new();
+        let (Some(pltgot), Some(local), Some(gotsym)) = (
+            self.pltgot(),
+            self.dynamic_value(DT_MIPS_LOCAL_GOTNO),
+            self.dynamic_value(DT_MIPS_GOTSYM),
+        ) else {
+            return slots;
+        };
+        let entry = if self.elf.is_64 { 8 } else { 4 };
+        for (index, sym) in self.elf.dynsyms.iter().enumerate().skip(gotsym as usize) {
+            if let Some(name) = self.elf.dynstrtab.get_at(sym.st_name) {
+                let slot = pltgot + (local + index as u64 - gotsym) * entry;
+                slots.insert(slot, name.to_string());
+            }
+        }
+        slots
+    }
+
+    fn call_sites(&self, wanted: &[&str]) -> HashMap<String, Vec<CallSite>> {
+        let mut sites: HashMap<String, Vec<CallSite>> = HashMap::new();
+        let mut record = |address: u64, symbol: &String| {
+            if !wanted.contains(&symbol.as_str()) {
+                return;
+            }
+            let list = sites.entry(symbol.clone()).or_default();
+            if list.len() < MAX_CALL_SITES {
+                list.push(CallSite {
+                    address,
+                    function: self.enclosing_function(address),
+                });
+            }
+        };
+        let mips_slots = match self.elf.header.e_machine {
+            EM_MIPS => self.mips_got_slots(),
+            _ => HashMap::new(),
+        };
+        let gp = self.pltgot().map(|g| g + MIPS_GP_BIAS);
+
+        for (addr, bytes) in self.code() {
+            match self.elf.header.e_machine {
+                EM_X86_64 | EM_386 => {
+                    for i in 0..bytes.len().saturating_sub(4) {
+                        let rel = |at: usize| {
+                            bytes
+                                .get(at..at + 4)
+                                .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as i64)
+                        };
+                        let pc = addr + i as u64;
+                        if bytes[i] == 0xe8 {
+                            // call rel32
+                            let Some(disp) = rel(i + 1) else { continue };
+                            if let Some(symbol) =
+                                self.stubs.get(&(pc + 5).wrapping_add_signed(disp))
+                            {
+                                record(pc, symbol);
+                            }
+                        } else if bytes[i] == 0xff && bytes[i + 1] == 0x15 && self.elf.is_64 {
+                            // call *disp(%rip), -fno-plt
+                            let Some(disp) = rel(i + 2) else { continue };
+                            if let Some(symbol) =
+                                self.slots.get(&(pc + 6).wrapping_add_signed(disp))
+                            {
+                                record(pc, symbol);
+                            }
+                        }
+                    }
+                }
+                EM_ARM => {
+                    for i in (0..bytes.len()).step_by(4) {
+                        let Some(w) = self.u32_at(bytes, i) else {
+                            break;
+                        };
+                        let pc = addr + i as u64;
+                        // bl; blx immediate always switches to Thumb, so
+                        // never lands on an ARM PLT stub.
+                        if w & 0x0f00_0000 != 0x0b00_0000 || w >> 28 == 0xf {
+                            continue;
+                        }
+                        let target = (pc + 8)
+                            .wrapping_add_signed(sign_extend(((w & 0xff_ffff) << 2) as u64, 26));
+                        if let Some(symbol) = self.stubs.get(&target) {
+                            record(pc, symbol);
+                        }
+                    }
+                    for i in (0..bytes.len()).step_by(2) {
+                        let (Some(hi), Some(lo)) =
+                            (self.u16_at(bytes, i), self.u16_at(bytes, i + 2))
+                        else {
+                            break;
+                        };
+                        if hi & 0xf800 != 0xf000 || lo & 0xc000 != 0xc000 {
+                            continue;
+                        }
+                        let s = ((hi >> 10) & 1) as u64;
+                        let j1 = ((lo >> 13) & 1) as u64;
+                        let j2 = ((lo >> 11) & 1) as u64;
+                        let i1 = 1 ^ (j1 ^ s);
+                        let i2 = 1 ^ (j2 ^ s);
+                        let imm = (s << 24)
+                            | (i1 << 23)
+                            | (i2 << 22)
+                            | (((hi & 0x3ff) as u64) << 12)
+                            | (((lo & 0x7ff) as u64) << 1);
+                        let offset = sign_extend(imm, 25);
+                        let pc = addr + i as u64;
+                        let target = if lo & 0x1000 != 0 {
+                            // bl, Thumb to Thumb
+                            (pc + 4).wrapping_add_signed(offset)
+                        } else {
+                            // blx, Thumb to ARM
+                            ((pc + 4) & !3).wrapping_add_signed(offset)
+                        };
+                        if let Some(symbol) = self.stubs.get(&target) {
+                            record(pc, symbol);
+                        }
+                    }
+                }
+                EM_AARCH64 => {
+                    for i in (0..bytes.len()).step_by(4) {
+                        let Some(w) = self.u32_at(bytes, i) else {
+                            break;
+                        };
+                        if w & 0xfc00_0000 != 0x9400_0000 {
+                            continue;
+                        }
+                        let pc = addr + i as u64;
+                        let target =
+                            pc.wrapping_add_signed(sign_extend(((w & 0x3ff_ffff) << 2) as u64, 28));
+                        if let Some(symbol) = self.stubs.get(&target) {
+                            record(pc, symbol);
+                        }
+                    }
+                }
+                EM_MIPS => {
+                    for i in (0..bytes.len()).step_by(4) {
+                        let Some(w) = self.u32_at(bytes, i) else {
+                            break;
+                        };
+                        let pc = addr + i as u64;
+                        let opcode = w >> 26;
+                        let loads_t9 = matches!(opcode, 0x23 | 0x37) && (w >> 16) & 0x1f == 25;
+                        if let (true, Some(gp)) = (loads_t9, gp) {
+                            // lw/ld t9, off(base) shortly followed by jalr t9
+                            // or jr t9. The base is $gp, or a register
+                            // holding the same value in unoptimised code.
+                            let jumps = (1..=4).any(|k| {
+                                self.u32_at(bytes, i + 4 * k)
+                                    .is_some_and(|n| n == 0x0320_f809 || n == 0x0320_0008)
+                            });
+                            let slot = gp.wrapping_add_signed((w & 0xffff) as u16 as i16 as i64);
+                            if let (true, Some(symbol)) = (jumps, mips_slots.get(&slot)) {
+                                record(pc, symbol);
+                            }
+                        } else if opcode == 3 {
+                            // jal into a PLT stub, non-PIC code
+                            let target =
+                                ((pc + 4) & !0x0fff_ffff) | (((w & 0x3ff_ffff) as u64) << 2);
+                            if let Some(symbol) = self.stubs.get(&target) {
+                                record(pc, symbol);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        sites
+    }
+
+    fn enclosing_function(&self, address: u64) -> Option<String> {
+        let index = self.functions.partition_point(|f| f.0 <= address);
+        let (start, size, name) = self.functions.get(index.checked_sub(1)?)?;
+        (*size == 0 || address < start + size).then(|| name.clone())
+    }
+}
+
+fn sign_extend(value: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((value << shift) as i64) >> shift
+}
+
+fn raise(severity: VulnSeverity) -> VulnSeverity {
+    match severity {
+        VulnSeverity::Info => VulnSeverity::Low,
+        VulnSeverity::Low => VulnSeverity::Medium,
+        VulnSeverity::Medium => VulnSeverity::High,
+        VulnSeverity::High | VulnSeverity::Critical => VulnSeverity::Critical,
+    }
+}
+
+fn cwe_reference(cwe: &str) -> Option<String> {
+    cwe.strip_prefix("CWE-")
+        .map(|id| format!("https://cwe.mitre.org/data/definitions/{}.html", id))
+}
+
+/// Findings as `VulnData`: one per binary with missing mitigations
+/// (CWE-693), one per dangerous import with the calling functions, and one
+/// per setuid or setgid binary (CWE-250).
+pub fn vulnerabilities(
+    audits: &[BinaryAudit],
+    functions: &DangerousFunctions,
+    manifest: &Manifest,
+) -> Vec<VulnData> {
+    let detection_time = chrono::Utc::now().to_rfc3339();
+    let target = manifest.source.to_string_lossy().into_owned();
+    let mut vulns = Vec::new();
+
+    for audit in audits {
+        let name = audit.file.rsplit('/').next().unwrap_or(&audit.file);
+        let disk_path = manifest
+            .get(&audit.file)
+            .map(|e| manifest.disk_path(e))
+            .unwrap_or_else(|| manifest.work_dir.join(&audit.file))
+            .to_string_lossy()
+            .into_owned();
+        let kind = match audit.kind {
+            BinaryKind::Executable => "executable",
+            BinaryKind::SharedObject => "shared object",
+        };
+        let privileged = audit.is_setuid() || audit.is_setgid();
+        let fill = |mut vuln: VulnData, cwe: &str, function: Option<String>| {
+            vuln.cwe = Some(cwe.to_string());
+            vuln.references = cwe_reference(cwe).into_iter().collect();
+            vuln.affected = audit.file.clone();
+            vuln.affected_systems = vec![target.clone()];
+            vuln.detection_time = detection_time.clone();
+            vuln.detection_location.component = name.to_string();
+            vuln.detection_location.file_path = Some(disk_path.clone());
+            vuln.detection_location.function = function;
+            vuln.detection_location.source = DetectionSource::StaticAnalysis;
+            vuln.scan_type = ScanType::Firmware;
+            vuln.tags = vec![
+                "firmware".to_string(),
+                "elf".to_string(),
+                "hardening".to_string(),
+            ];
+            vuln
+        };
+
+        let missing = audit.mitigations.missing();
+        if !missing.is_empty() {
+            let severity = if !audit.mitigations.nx || privileged {
+                VulnSeverity::Medium
+            } else {
+                VulnSeverity::Low
+            };
+            let mut vuln = VulnData::new(
+                format!("FW-HARDENING:{}", audit.file),
+                format!("{} built without {}", name, missing.join(", ")),
+                format!(
+                    "{} ({} {}) lacks {}. Memory corruption bugs in it are \
+                     easier to turn into code execution.",
+                    audit.file,
+                    audit.machine,
+                    kind,
+                    missing.join(", ")
+                ),
+                severity,
+            );
+            vuln.remediation = Some(hardening_flags(&audit.mitigations));
+            vulns.push(fill(vuln, "CWE-693", None));
+        }
+
+        for import in &audit.imports {
+            let Some(function) = functions.get(&import.name) else {
+                continue;
+            };
+            let mut callers: Vec<String> = Vec::new();
+            for site in &import.call_sites {
+                let label = site.label();
+                if !callers.contains(&label) {
+                    callers.push(label);
+                }
+            }
+            let called_from = match callers.len() {
+                0 => "; no call sites could be resolved".to_string(),
+                n if n > DESCRIBED_CALLERS => format!(
+                    ", called from {} and {} more",
+                    callers[..DESCRIBED_CALLERS].join(", "),
+                    n - DESCRIBED_CALLERS
+                ),
+                _ => format!(", called from {}", callers.join(", ")),
+            };
+            let mut severity: VulnSeverity = function.severity.clone().into();
+            if privileged && function.executes_commands() {
+                severity = raise(severity);
+            }
+            let mut vuln = VulnData::new(
+                format!("FW-DANGEROUS-CALL:{}:{}", audit.file, import.name),
+                format!("{} calls {}", name, import.name),
+                format!(
+                    "{} imports {}{}. {}{}",
+                    audit.file,
+                    import.name,
+                    called_from,
+                    function.description,
+                    if privileged {
+                        " The binary runs with raised privileges."
+                    } else {
+                        ""
+                    }
+                ),
+                severity,
+            );
+            vuln.remediation = function.remediation.clone();
+            let cwe = function.cwe.as_deref().unwrap_or("CWE-676");
+            vulns.push(fill(vuln, cwe, callers.into_iter().next()));
+        }
+
+        if privileged {
+            let bit = match (audit.is_setuid(), audit.is_setgid()) {
+                (true, true) => "setuid and setgid",
+                (true, false) => "setuid",
+                _ => "setgid",
+            };
+            let runs_commands = audit.imports.iter().any(|i| {
+                functions
+                    .get(&i.name)
+                    .is_some_and(|f| f.executes_commands())
+            });
+            let mut vuln = VulnData::new(
+                format!("FW-SETUID:{}", audit.file),
+                format!("{} binary {}", capitalize(bit), name),
+                format!(
+                    "{} has mode {:04o} and runs with its owner's privileges \
+                     whoever starts it{}.",
+                    audit.file,
+                    audit.mode.unwrap_or(0),
+                    if runs_commands {
+                        ", and it runs shell commands"
+                    } else {
+                        ""
+                    }
+                ),
+                if runs_commands {
+                    VulnSeverity::High
+                } else {
+                    VulnSeverity::Medium
+                },
+            );
+            vuln.remediation = Some(format!(
+                "Remove the {} bit unless the program needs it, and drop \
+                 privileges as soon as it no longer does.",
+                bit
+            ));
+            vulns.push(fill(vuln, "CWE-250", None));
+        }
+    }
+    vulns
+}
+
+/// Compiler and linker flags that add the missing mitigations.
+fn hardening_flags(mitigations: &Mitigations) -> String {
+    let mut flags = Vec::new();
+    if !mitigations.nx {
+        flags.push("-Wl,-z,noexecstack");
+    }
+    if mitigations.pie == Some(false) {
+        flags.push("-fPIE -pie");
+    }
+    if mitigations.relro != Relro::Full {
+        flags.push("-Wl,-z,relro,-z,now");
+    }
+    if !mitigations.canary {
+        flags.push("-fstack-protector-strong");
+    }
+    if mitigations.fortify == Some(false) {
+        flags.push("-O2 -D_FORTIFY_SOURCE=2");
+    }
+    format!("Rebuild with {}.", flags.join(" "))
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fixtures::{self, ElfOptions};
+    use super::*;
+    use std::path::PathBuf;
+
+    fn mitigations(options: &ElfOptions) -> Mitigations {
+        let data = fixtures::elf(options);
+        audit("usr/bin/test", &data, None, &DangerousFunctions::new())
+            .expect("fixture is not an ELF binary")
+            .mitigations
+    }
+
+    #[test]
+    fn reads_nx_pie_and_relro() {
+        let weak = mitigations(&ElfOptions {
+            imports: &["strcpy"],
+            ..ElfOptions::default()
+        });
+        assert!(!weak.nx);
+        assert_eq!(weak.pie, Some(false));
+        assert_eq!(weak.relro, Relro::None);
+        assert!(!weak.canary);
+        assert_eq!(weak.fortify, Some(false));
+        assert_eq!(
+            weak.missing(),
+            ["NX", "PIE", "RELRO", "stack canaries", "FORTIFY_SOURCE"]
+        );
+        assert_eq!(
+            hardening_flags(&weak),
+            "Rebuild with -Wl,-z,noexecstack -fPIE -pie -Wl,-z,relro,-z,now \
+             -fstack-protector-strong -O2 -D_FORTIFY_SOURCE=2."
+        );
+
+        let partial = mitigations(&ElfOptions {
+            pie: true,
+            nx: true,
+            relro: true,
+            ..ElfOptions::default()
+        });
+        assert_eq!(partial.pie, Some(true));
+        assert_eq!(partial.relro, Relro::Partial);
+        assert_eq!(partial.fortify, None);
+        assert_eq!(partial.missing(), ["full RELRO", "stack canaries"]);
+
+        let hardened = mitigations(&ElfOptions {
+            pie: true,
+            nx: true,
+            relro: true,
+            bind_now: true,
+            imports: &["__stack_chk_fail", "__strcpy_chk"],
+            ..ElfOptions::default()
+        });
+        assert!(hardened.nx);
+        assert_eq!(hardened.relro, Relro::Full);
+        assert!(hardened.canary);
+        assert_eq!(hardened.fortify, Some(true));
+        assert!(hardened.missing().is_empty());
+    }
+
+    #[test]
+    fn classifies_binaries() {
+        let functions = DangerousFunctions::new();
+        let data = fixtures::elf(&ElfOptions {
+            shared: true,
+            nx: true,
+            ..ElfOptions::default()
+        });
+        let library = audit("lib/libfoo.so", &data, None, &functions).unwrap();
+        assert_eq!(library.kind, BinaryKind::SharedObject);
+        assert_eq!(library.machine, "X86_64");
+        assert_eq!(library.mitigations.pie, None);
+
+        // uClibc and musl have no fortified variants to link against.
+        let musl = mitigations(&ElfOptions {
+            interpreter: Some("/lib/ld-musl-armhf.so.1"),
+            imports: &["strcpy"],
+            ..ElfOptions::default()
+        });
+        assert_eq!(musl.fortify, None);
+
+        let mut object = fixtures::elf(&ElfOptions::default());
+        object[16] = 1;
+        assert!(is_elf(&object));
+        assert!(audit("lib/modules/mod.ko", &object, None, &functions).is_none());
+        assert!(!is_elf(b"#!/bin/sh\n"));
+        assert!(audit("etc/rc", b"\x7fELF garbage", None, &functions).is_none());
+    }
+
+    #[test]
+    fn setuid_binaries_raise_severity() {
+        let functions = DangerousFunctions::new();
+        let data = fixtures::elf(&ElfOptions {
+            nx: true,
+            imports: &["system", "__stack_chk_fail"],
+            ..ElfOptions::default()
+        });
+        let audit = audit("usr/sbin/updater", &data, Some(0o104755), &functions).unwrap();
+        assert!(audit.is_setuid());
+        assert!(!audit.is_setgid());
+        assert_eq!(audit.imports.len(), 1);
+        assert!(audit.imports[0].call_sites.is_empty());
+
+        let manifest = Manifest {
+            source: PathBuf::from("fw.bin"),
+            work_dir: PathBuf::from("/work"),
+            entries: Vec::new(),
+            warnings: Vec::new(),
+            truncated: false,
+        };
+        let vulns = vulnerabilities(&[audit], &functions, &manifest);
+        let summary: Vec<_> = vulns
+            .iter()
+            .map(|v| (v.id.as_str(), v.severity.clone(), v.cwe.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (
+                    "FW-HARDENING:usr/sbin/updater",
+                    VulnSeverity::Medium,
+                    Some("CWE-693")
+                ),
+                (
+                    "FW-DANGEROUS-CALL:usr/sbin/updater:system",
+                    VulnSeverity::High,
+                    Some("CWE-78")
+                ),
+                (
+                    "FW-SETUID:usr/sbin/updater",
+                    VulnSeverity::High,
+                    Some("CWE-250")
+                ),
+            ]
+        );
+        assert_eq!(vulns[0].title, "updater built without PIE, RELRO");
+        assert!(vulns[1]
+            .description
+            .contains("no call sites could be resolved"));
+        assert!(vulns[2].description.contains("mode 104755"));
+        assert_eq!(
+            vulns[2].detection_location.file_path.as_deref(),
+            Some("/work/usr/sbin/updater")
+        );
+    }
+}
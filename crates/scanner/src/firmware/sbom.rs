@@ -0,0 +1,586 @@
+use super::strings::ExtractedString;
+use anyhow::{Context, Result};
+use regex::{Regex, RegexSet};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+const BUILTIN_COMPONENTS: &str = include_str!("../../signatures/components.toml");
+
+pub const SBOM_FILE: &str = "sbom.cdx.json";
+/// Distinct strings kept as evidence for one component.
+const MAX_EVIDENCE: usize = 5;
+
+#[derive(Debug, Clone, Deserialize)]
+struct ComponentFile {
+    #[serde(default)]
+    components: Vec<ComponentDef>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ComponentDef {
+    name: String,
+    kind: String,
+    cpe: Option<String>,
+    #[serde(default)]
+    strings: Vec<String>,
+    #[serde(default)]
+    paths: Vec<String>,
+    #[serde(default)]
+    packages: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ComponentRule {
+    pub name: String,
+    pub kind: String,
+    /// CPE 2.3 prefix up to the product.
+    pub cpe: Option<String>,
+    pub strings: Vec<Regex>,
+    pub paths: Vec<Regex>,
+    pub packages: Vec<String>,
+}
+
+/// How a component was recognised.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentSource {
+    VersionString,
+    FilePath,
+    PackageDatabase,
+    OsRelease,
+}
+
+/// One versioned piece of software found in the image.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Component {
+    pub name: String,
+    pub version: String,
+    /// CycloneDX component type.
+    pub kind: String,
+    pub cpe: Option<String>,
+    /// Manifest paths of the files it was found in.
+    pub files: Vec<String>,
+    /// The strings, paths or package entries that gave it away.
+    pub evidence: Vec<String>,
+    pub source: ComponentSource,
+}
+
+impl Component {
+    fn new(
+        name: &str,
+        version: &str,
+        kind: &str,
+        cpe: Option<&str>,
+        source: ComponentSource,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            version: version.to_string(),
+            kind: kind.to_string(),
+            cpe: cpe.map(|prefix| format!("{}:{}:*:*:*:*:*:*:*", prefix, version)),
+            files: Vec::new(),
+            evidence: Vec::new(),
+            source,
+        }
+    }
+
+    pub fn purl(&self) -> String {
+        format!("pkg:generic/{}@{}", self.name, self.version)
+    }
+
+    pub fn bom_ref(&self) -> String {
+        format!("{}@{}", self.name, self.version)
+    }
+}
+
+/// Component recognition rules from `signatures/components.toml` plus any
+/// user-supplied files.
+#[derive(Debug, Clone)]
+pub struct ComponentRules {
+    rules: Vec<ComponentRule>,
+    /// Prefilter over every rule's string patterns; `string_owner[i]` is
+    /// the (rule, pattern) index of pattern `i`.
+    set: RegexSet,
+    string_owner: Vec<(usize, usize)>,
+}
+
+impl ComponentRules {
+    pub fn new() -> Self {
+        let mut rules = Self::empty();
+        rules
+            .add(BUILTIN_COMPONENTS)
+            .expect("built-in component rules must be valid");
+        rules
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            rules: Vec::new(),
+            set: RegexSet::empty(),
+            string_owner: Vec::new(),
+        }
+    }
+
+    pub fn load(&mut self, path: &Path) -> Result<usize> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read component rules {}", path.display()))?;
+        self.add(&content)
+            .with_context(|| format!("invalid component rules {}", path.display()))
+    }
+
+    pub fn add(&mut self, content: &str) -> Result<usize> {
+        let file: ComponentFile = toml::from_str(content)?;
+        let count = file.components.len();
+        for def in file.components {
+            let compile = |patterns: &[String]| -> Result<Vec<Regex>> {
+                patterns
+                    .iter()
+                    .map(|p| {
+                        let re = Regex::new(p)
+                            .with_context(|| format!("invalid pattern for {}: {}", def.name, p))?;
+                        anyhow::ensure!(
+                            re.capture_names().any(|n| n == Some("version")),
+                            "pattern for {} has no `version` group: {}",
+                            def.name,
+                            p
+                        );
+                        Ok(re)
+                    })
+                    .collect()
+            };
+            let strings = compile(&def.strings)?;
+            let paths = compile(&def.paths)?;
+            self.rules.push(ComponentRule {
+                name: def.name,
+                kind: def.kind,
+                cpe: def.cpe,
+                strings,
+                paths,
+                packages: def.packages,
+            });
+        }
+        self.string_owner = self
+            .rules
+            .iter()
+            .enumerate()
+            .flat_map(|(r, rule)| (0..rule.strings.len()).map(move |s| (r, s)))
+            .collect();
+        self.set = RegexSet::new(
+            self.rules
+                .iter()
+                .flat_map(|r| r.strings.iter().map(|s| s.as_str())),
+        )?;
+        Ok(count)
+    }
+
+    pub fn rules(&self) -> &[ComponentRule] {
+        &self.rules
+    }
+
+    /// Components named by version strings in one file's strings.
+    pub fn match_strings(&self, strings: &[ExtractedString]) -> Vec<Component> {
+        let mut found = Vec::new();
+        for string in strings {
+            for index in self.set.matches(&string.value).iter() {
+                let (r, s) = self.string_owner[index];
+                let rule = &self.rules[r];
+                let Some(version) = rule.strings[s]
+                    .captures(&string.value)
+                    .and_then(|c| c.name("version"))
+                else {
+                    continue;
+                };
+                let mut component =
+                    rule.component(version.as_str(), ComponentSource::VersionString);
+                component.files.push(string.file.clone());
+                component.evidence.push(string.value.trim().to_string());
+                found.push(component);
+            }
+        }
+        found
+    }
+
+    /// A component named by a file's path, e.g. `lib/libuClibc-0.9.33.2.so`.
+    pub fn match_path(&self, path: &str) -> Option<Component> {
+        self.rules.iter().find_map(|rule| {
+            let version = rule
+                .paths
+                .iter()
+                .find_map(|p| p.captures(path).and_then(|c| c.name("version")))?;
+            let mut component = rule.component(version.as_str(), ComponentSource::FilePath);
+            component.files.push(path.to_string());
+            component.evidence.push(path.to_string());
+            Some(component)
+        })
+    }
+
+    /// A package database entry, under the rule's name if one lists the
+    /// package and under its own name otherwise.
+    pub fn match_package(&self, package: &str, version: &str, file: &str) -> Component {
+        let version = package_version(version);
+        let mut component = match self
+            .rules
+            .iter()
+            .find(|r| r.packages.iter().any(|p| p == package))
+        {
+            Some(rule) => rule.component(version, ComponentSource::PackageDatabase),
+            None => Component::new(
+                package,
+                version,
+                "application",
+                None,
+                ComponentSource::PackageDatabase,
+            ),
+        };
+        component.files.push(file.to_string());
+        component
+            .evidence
+            .push(format!("Package: {} Version: {}", package, version));
+        component
+    }
+}
+
+impl Default for ComponentRules {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ComponentRule {
+    fn component(&self, version: &str, source: ComponentSource) -> Component {
+        Component::new(&self.name, version, &self.kind, self.cpe.as_deref(), source)
+    }
+}
+
+/// Drops a Debian epoch (`1:`) and package revision (`-3`), leaving the
+/// upstream version CVE data is keyed on.
+fn package_version(version: &str) -> &str {
+    let version = version.split_once(':').map_or(version, |(_, v)| v);
+    match version.rsplit_once('-') {
+        Some((upstream, revision))
+            if !upstream.is_empty()
+                && revision
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '.') =>
+        {
+            upstream
+        }
+        _ => version,
+    }
+}
+
+/// Whether `path` is an opkg or dpkg database: a status file or an opkg
+/// `.control` file.
+pub fn is_package_database(path: &str) -> bool {
+    path.ends_with("/opkg/status")
+        || path.ends_with("/dpkg/status")
+        || (path.contains("/opkg/info/") && path.ends_with(".control"))
+}
+
+/// `(package, version)` pairs from the stanzas of an opkg or dpkg status or
+/// control file.
+pub fn parse_packages(content: &str) -> Vec<(String, String)> {
+    let mut packages = Vec::new();
+    for stanza in content.split("\n\n") {
+        let mut name = None;
+        let mut version = None;
+        let mut installed = true;
+        for line in stanza.lines() {
+            if let Some(value) = line.strip_prefix("Package:") {
+                name = Some(value.trim());
+            } else if let Some(value) = line.strip_prefix("Version:") {
+                version = Some(value.trim());
+            } else if let Some(value) = line.strip_prefix("Status:") {
+                installed = !value.contains("not-installed");
+            }
+        }
+        if let (Some(name), Some(version), true) = (name, version, installed) {
+            packages.push((name.to_string(), version.to_string()));
+        }
+    }
+    packages
+}
+
+pub fn is_os_release(path: &str) -> bool {
+    path.ends_with("etc/os-release") || path.ends_with("usr/lib/os-release")
+}
+
+/// The distribution named by an `os-release` file.
+pub fn parse_os_release(content: &str, file: &str) -> Option<Component> {
+    let fields: HashMap<&str, &str> = content
+        .lines()
+        .filter_map(|l| l.split_once('='))
+        .map(|(k, v)| (k.trim(), v.trim().trim_matches('"').trim_matches('\'')))
+        .collect();
+    let name = fields.get("ID").or_else(|| fields.get("NAME"))?;
+    let version = fields.get("VERSION_ID").or_else(|| fields.get("VERSION"))?;
+    let mut component = Component::new(
+        &name.to_ascii_lowercase(),
+        version,
+        "operating-system",
+        None,
+        ComponentSource::OsRelease,
+    );
+    component.files.push(file.to_string());
+    if let Some(pretty) = fields.get("PRETTY_NAME") {
+        component.evidence.push(pretty.to_string());
+    }
+    Some(component)
+}
+
+/// The software bill of materials of one image.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sbom {
+    pub source: PathBuf,
+    /// SHA-256 of the image.
+    pub sha256: Option<String>,
+    pub components: Vec<Component>,
+}
+
+impl Sbom {
+    /// Merges detections of the same name and version, keeping the first
+    /// one's source and collecting every file and piece of evidence.
+    pub fn new(source: PathBuf, sha256: Option<String>, found: Vec<Component>) -> Self {
+        let mut components: Vec<Component> = Vec::new();
+        let mut index: HashMap<(String, String), usize> = HashMap::new();
+        for component in found {
+            let key = (component.name.clone(), component.version.clone());
+            match index.get(&key) {
+                Some(&i) => {
+                    let existing = &mut components[i];
+                    for file in component.files {
+                        if !existing.files.contains(&file) {
+                            existing.files.push(file);
+                        }
+                    }
+                    for evidence in component.evidence {
+                        if existing.evidence.len() < MAX_EVIDENCE
+                            && !existing.evidence.contains(&evidence)
+                        {
+                            existing.evidence.push(evidence);
+                        }
+                    }
+                }
+                None => {
+                    index.insert(key, components.len());
+                    components.push(component);
+                }
+            }
+        }
+        components.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.version.cmp(&b.version)));
+        Self {
+            source,
+            sha256,
+            components,
+        }
+    }
+
+    /// The SBOM as a CycloneDX 1.5 JSON document.
+    pub fn to_cyclonedx(&self) -> Value {
+        let name = self
+            .source
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "firmware".to_string());
+        let mut firmware = json!({
+            "type": "firmware",
+            "bom-ref": name,
+            "name": name,
+        });
+        if let Some(sha256) = &self.sha256 {
+            firmware["hashes"] = json!([{ "alg": "SHA-256", "content": sha256 }]);
+        }
+
+        let components: Vec<Value> = self
+            .components
+            .iter()
+            .map(|c| {
+                let occurrences: Vec<Value> =
+                    c.files.iter().map(|f| json!({ "location": f })).collect();
+                let mut properties: Vec<Value> = vec![json!({
+                    "name": "uavred:source",
+                    "value": serde_json::to_value(c.source).unwrap_or(Value::Null),
+                })];
+                properties.extend(
+                    c.evidence
+                        .iter()
+                        .map(|e| json!({ "name": "uavred:evidence", "value": e })),
+                );
+                let mut value = json!({
+                    "type": c.kind,
+                    "bom-ref": c.bom_ref(),
+                    "name": c.name,
+                    "version": c.version,
+                    "purl": c.purl(),
+                    "evidence": { "occurrences": occurrences },
+                    "properties": properties,
+                });
+                if let Some(cpe) = &c.cpe {
+                    value["cpe"] = json!(cpe);
+                }
+                value
+            })
+            .collect();
+
+        let mut bom = json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "version": 1,
+            "metadata": {
+                "timestamp": chrono::Utc::now().to_rfc3339(),
+                "tools": {
+                    "components": [{
+                        "type": "application",
+                        "name": "uavred",
+                        "version": env!("CARGO_PKG_VERSION"),
+                    }]
+                },
+                "component": firmware,
+            },
+            "components": components,
+            "dependencies": [{
+                "ref": name,
+                "dependsOn": self.components.iter().map(|c| c.bom_ref()).collect::<Vec<_>>(),
+            }],
+        });
+        // Derived from the image hash so rescanning the same image keeps
+        // the same serial number.
+        if let Some(sha256) = self.sha256.as_deref().filter(|s| s.len() >= 32) {
+            bom["serialNumber"] = json!(format!(
+                "urn:uuid:{}-{}-{}-{}-{}",
+                &sha256[0..8],
+                &sha256[8..12],
+                &sha256[12..16],
+                &sha256[16..20],
+                &sha256[20..32]
+            ));
+        }
+        bom
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(&self.to_cyclonedx())?)
+            .with_context(|| format!("failed to write SBOM {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::strings;
+    use super::*;
+
+    const BUSYBOX: &[u8] =
+        b"\x7fELF\0\0BusyBox v1.27.2 (2018-01-01 00:00:00 UTC) multi-call binary.\0\
+                             OpenSSL 1.0.1f 6 Jan 2014\0SSH-2.0-dropbear_2017.75\0";
+
+    fn found(rules: &ComponentRules, data: &[u8]) -> Vec<(String, String)> {
+        rules
+            .match_strings(&strings::extract("bin/busybox", data, 4))
+            .into_iter()
+            .map(|c| (c.name, c.version))
+            .collect()
+    }
+
+    #[test]
+    fn versions_come_from_strings_and_paths() {
+        let rules = ComponentRules::new();
+        let versions = found(&rules, BUSYBOX);
+        assert_eq!(
+            versions,
+            [
+                ("busybox".to_string(), "1.27.2".to_string()),
+                ("openssl".to_string(), "1.0.1f".to_string()),
+                ("dropbear".to_string(), "2017.75".to_string()),
+            ]
+        );
+
+        let busybox = &rules.match_strings(&strings::extract("bin/busybox", BUSYBOX, 4))[0];
+        assert_eq!(busybox.source, ComponentSource::VersionString);
+        assert_eq!(
+            busybox.cpe.as_deref(),
+            Some("cpe:2.3:a:busybox:busybox:1.27.2:*:*:*:*:*:*:*")
+        );
+        assert_eq!(busybox.files, ["bin/busybox"]);
+
+        let uclibc = rules
+            .match_path("rootfs/lib/libuClibc-0.9.33.2.so")
+            .unwrap();
+        assert_eq!(
+            (uclibc.name.as_str(), uclibc.version.as_str()),
+            ("uclibc", "0.9.33.2")
+        );
+        assert_eq!(uclibc.source, ComponentSource::FilePath);
+        assert!(rules.match_path("rootfs/lib/libuClibc.so").is_none());
+    }
+
+    #[test]
+    fn package_databases_and_os_release() {
+        let status = "Package: busybox\nVersion: 1:1.31.1-r3\nStatus: install ok installed\n\n\
+                      Package: libssl\nVersion: 1.0.2k-1\n\n\
+                      Package: removed\nVersion: 1.0\nStatus: deinstall ok not-installed\n";
+        let packages = parse_packages(status);
+        assert_eq!(packages.len(), 2);
+        assert!(is_package_database("rootfs/usr/lib/opkg/status"));
+        assert!(is_package_database(
+            "rootfs/usr/lib/opkg/info/busybox.control"
+        ));
+        assert!(!is_package_database("rootfs/etc/status"));
+
+        let rules = ComponentRules::new();
+        let (name, version) = &packages[1];
+        let openssl = rules.match_package(name, version, "usr/lib/opkg/status");
+        assert_eq!(
+            (openssl.name.as_str(), openssl.version.as_str()),
+            ("openssl", "1.0.2k")
+        );
+        assert_eq!(openssl.source, ComponentSource::PackageDatabase);
+        let busybox = rules.match_package("busybox", "1:1.31.1-r3", "usr/lib/opkg/status");
+        assert_eq!(busybox.version, "1.31.1");
+        let other = rules.match_package("px4-tools", "2.0", "usr/lib/opkg/status");
+        assert_eq!((other.name.as_str(), other.cpe), ("px4-tools", None));
+
+        let os = parse_os_release(
+            "NAME=\"OpenWrt\"\nID=\"openwrt\"\nVERSION_ID=\"19.07.7\"\nPRETTY_NAME=\"OpenWrt 19.07.7\"\n",
+            "etc/os-release",
+        )
+        .unwrap();
+        assert_eq!(
+            (os.name.as_str(), os.version.as_str()),
+            ("openwrt", "19.07.7")
+        );
+        assert_eq!(os.kind, "operating-system");
+        assert_eq!(os.evidence, ["OpenWrt 19.07.7"]);
+    }
+
+    #[test]
+    fn sbom_merges_detections_and_exports_cyclonedx() {
+        let rules = ComponentRules::new();
+        let mut found = rules.match_strings(&strings::extract("bin/busybox", BUSYBOX, 4));
+        found.extend(rules.match_strings(&strings::extract("sbin/udhcpc", BUSYBOX, 4)));
+        let sha256 = "ab".repeat(32);
+        let sbom = Sbom::new(PathBuf::from("/tmp/fw.bin"), Some(sha256), found);
+
+        let names: Vec<_> = sbom.components.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["busybox", "dropbear", "openssl"]);
+        assert_eq!(sbom.components[0].files, ["bin/busybox", "sbin/udhcpc"]);
+        assert_eq!(sbom.components[0].evidence.len(), 1);
+
+        let bom = sbom.to_cyclonedx();
+        assert_eq!(bom["bomFormat"], "CycloneDX");
+        assert_eq!(bom["metadata"]["component"]["name"], "fw.bin");
+        assert_eq!(
+            bom["serialNumber"],
+            "urn:uuid:abababab-abab-abab-abab-abababababab"
+        );
+        let busybox = &bom["components"][0];
+        assert_eq!(busybox["bom-ref"], "busybox@1.27.2");
+        assert_eq!(busybox["purl"], "pkg:generic/busybox@1.27.2");
+        assert_eq!(
+            busybox["evidence"]["occurrences"][1]["location"],
+            "sbin/udhcpc"
+        );
+        assert_eq!(bom["dependencies"][0]["dependsOn"][2], "openssl@1.0.1f");
+    }
+}
@@ -0,0 +1,507 @@
+use super::container::{lzma_limited, read_limited, EntryType, Member, Sink};
+use anyhow::{anyhow, bail, Result};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+const MAGIC: &[u8; 4] = b"hsqs";
+const SUPERBLOCK_LEN: usize = 96;
+const METADATA_BLOCK_LEN: usize = 8192;
+const NO_FRAGMENT: u32 = 0xffff_ffff;
+const UNCOMPRESSED_DATA: u32 = 1 << 24;
+/// Directory nesting beyond this is treated as corrupt.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, Copy)]
+enum Compressor {
+    Gzip,
+    Lzma,
+    Xz,
+    Unsupported(u16),
+}
+
+#[derive(Debug)]
+struct Superblock {
+    block_size: u32,
+    fragment_count: u32,
+    compressor: Compressor,
+    root_inode: u64,
+    inode_table: u64,
+    directory_table: u64,
+    fragment_table: u64,
+}
+
+impl Superblock {
+    fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < SUPERBLOCK_LEN || &data[..4] != MAGIC {
+            bail!("not a little-endian SquashFS image");
+        }
+        let u16_at = |pos: usize| u16::from_le_bytes([data[pos], data[pos + 1]]);
+        let u32_at = |pos: usize| u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap());
+        let u64_at = |pos: usize| u64::from_le_bytes(data[pos..pos + 8].try_into().unwrap());
+
+        let major = u16_at(28);
+        if major != 4 {
+            bail!("SquashFS {}.{} is not supported", major, u16_at(30));
+        }
+        let block_size = u32_at(12);
+        if !block_size.is_power_of_two() || !(4096..=1 << 20).contains(&block_size) {
+            bail!("invalid SquashFS block size {}", block_size);
+        }
+        Ok(Self {
+            block_size,
+            fragment_count: u32_at(16),
+            compressor: match u16_at(20) {
+                1 => Compressor::Gzip,
+                2 => Compressor::Lzma,
+                4 => Compressor::Xz,
+                other => Compressor::Unsupported(other),
+            },
+            root_inode: u64_at(32),
+            inode_table: u64_at(64),
+            directory_table: u64_at(72),
+            fragment_table: u64_at(80),
+        })
+    }
+}
+
+enum InodeKind {
+    Directory {
+        start_block: u32,
+        offset: u16,
+        size: u32,
+    },
+    File {
+        start_block: u64,
+        size: u64,
+        fragment: u32,
+        fragment_offset: u32,
+        blocks: Vec<u32>,
+    },
+    Symlink(String),
+    Device,
+    Other,
+}
+
+struct Inode {
+    kind: InodeKind,
+    mode: u16,
+}
+
+struct Image<'a> {
+    data: &'a [u8],
+    superblock: Superblock,
+    /// Decoded metadata blocks by absolute position, with the position of
+    /// the block that follows.
+    metadata: RefCell<HashMap<u64, (Vec<u8>, u64)>>,
+    fragments: RefCell<HashMap<u32, Vec<u8>>>,
+    /// Files larger than this are skipped.
+    limit: usize,
+}
+
+/// Reads bytes from a run of metadata blocks starting `offset` bytes into
+/// the block at `position`.
+struct MetadataReader<'i, 'a> {
+    image: &'i Image<'a>,
+    block: Vec<u8>,
+    pos: usize,
+    next: u64,
+}
+
+impl<'i, 'a> MetadataReader<'i, 'a> {
+    fn new(image: &'i Image<'a>, position: u64, offset: usize) -> Result<Self> {
+        let (block, next) = image.metadata_block(position)?;
+        if offset > block.len() {
+            bail!("metadata offset {} outside block at {}", offset, position);
+        }
+        Ok(Self {
+            image,
+            block,
+            pos: offset,
+            next,
+        })
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(len);
+        while out.len() < len {
+            if self.pos == self.block.len() {
+                let (block, next) = self.image.metadata_block(self.next)?;
+                if block.is_empty() {
+                    bail!("empty metadata block at {}", self.next);
+                }
+                self.block = block;
+                self.pos = 0;
+                self.next = next;
+            }
+            let take = (len - out.len()).min(self.block.len() - self.pos);
+            out.extend_from_slice(&self.block[self.pos..self.pos + take]);
+            self.pos += take;
+        }
+        Ok(out)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let b = self.bytes(8)?;
+        Ok(u64::from_le_bytes(b.try_into().unwrap()))
+    }
+}
+
+impl<'a> Image<'a> {
+    fn decompress(&self, raw: &[u8], limit: usize) -> Result<Vec<u8>> {
+        match self.superblock.compressor {
+            Compressor::Gzip => read_limited(&mut flate2::read::ZlibDecoder::new(raw), limit),
+            Compressor::Xz => lzma_limited(raw, limit, |input, output| {
+                lzma_rs::xz_decompress(input, output)
+            }),
+            Compressor::Lzma => lzma_limited(raw, limit, |input, output| {
+                lzma_rs::lzma_decompress(input, output)
+            }),
+            Compressor::Unsupported(id) => {
+                let name = match id {
+                    3 => "LZO",
+                    5 => "LZ4",
+                    6 => "zstd",
+                    _ => "unknown",
+                };
+                bail!("SquashFS compressor {} ({}) is not supported", id, name)
+            }
+        }
+    }
+
+    fn slice(&self, start: u64, len: usize) -> Result<&'a [u8]> {
+        usize::try_from(start)
+            .ok()
+            .and_then(|start| self.data.get(start..start.checked_add(len)?))
+            .ok_or_else(|| anyhow!("{} bytes at {} lie outside the image", len, start))
+    }
+
+    fn metadata_block(&self, position: u64) -> Result<(Vec<u8>, u64)> {
+        if let Some(cached) = self.metadata.borrow().get(&position) {
+            return Ok(cached.clone());
+        }
+        let header = self.slice(position, 2)?;
+        let header = u16::from_le_bytes([header[0], header[1]]);
+        let len = (header & 0x7fff) as usize;
+        let raw = self.slice(position + 2, len)?;
+        let block = if header & 0x8000 != 0 {
+            raw.to_vec()
+        } else {
+            self.decompress(raw, METADATA_BLOCK_LEN)?
+        };
+        let entry = (block, position + 2 + len as u64);
+        self.metadata.borrow_mut().insert(position, entry.clone());
+        Ok(entry)
+    }
+
+    fn inode(&self, reference: u64) -> Result<Inode> {
+        let mut r = MetadataReader::new(
+            self,
+            self.superblock.inode_table + (reference >> 16),
+            (reference & 0xffff) as usize,
+        )?;
+        let inode_type = r.u16()?;
+        let mode = r.u16()?;
+        // uid/gid index, mtime, inode number
+        r.bytes(12)?;
+
+        let kind = match inode_type {
+            1 => {
+                let start_block = r.u32()?;
+                let _nlink = r.u32()?;
+                let size = r.u16()? as u32;
+                let offset = r.u16()?;
+                InodeKind::Directory {
+                    start_block,
+                    offset,
+                    size,
+                }
+            }
+            8 => {
+                let _nlink = r.u32()?;
+                let size = r.u32()?;
+                let start_block = r.u32()?;
+                let _parent = r.u32()?;
+                let _index_count = r.u16()?;
+                let offset = r.u16()?;
+                InodeKind::Directory {
+                    start_block,
+                    offset,
+                    size,
+                }
+            }
+            2 | 9 => {
+                let (start_block, size, fragment, fragment_offset) = if inode_type == 2 {
+                    let start = r.u32()? as u64;
+                    let fragment = r.u32()?;
+                    let fragment_offset = r.u32()?;
+                    (start, r.u32()? as u64, fragment, fragment_offset)
+                } else {
+                    let start = r.u64()?;
+                    let size = r.u64()?;
+                    let _sparse = r.u64()?;
+                    let _nlink = r.u32()?;
+                    let fragment = r.u32()?;
+                    let fragment_offset = r.u32()?;
+                    let _xattr = r.u32()?;
+                    (start, size, fragment, fragment_offset)
+                };
+                let block_size = self.superblock.block_size as u64;
+                let count = if fragment == NO_FRAGMENT {
+                    size.div_ceil(block_size)
+                } else {
+                    size / block_size
+                };
+                if size > self.data.len() as u64 * 1024 {
+                    bail!("implausible file size {}", size);
+                }
+                let blocks = (0..count).map(|_| r.u32()).collect::<Result<Vec<_>>>()?;
+                InodeKind::File {
+                    start_block,
+                    size,
+                    fragment,
+                    fragment_offset,
+                    blocks,
+                }
+            }
+            3 | 10 => {
+                let _nlink = r.u32()?;
+                let len = r.u32()? as usize;
+                if len > 4096 {
+                    bail!("symlink target of {} bytes", len);
+                }
+                InodeKind::Symlink(String::from_utf8_lossy(&r.bytes(len)?).into_owned())
+            }
+            4 | 5 | 6 | 7 | 11 | 12 | 13 | 14 => InodeKind::Device,
+            _ => InodeKind::Other,
+        };
+        Ok(Inode { kind, mode })
+    }
+
+    /// Entries of a directory as (name, inode reference).
+    fn read_dir(&self, start_block: u32, offset: u16, size: u32) -> Result<Vec<(String, u64)>> {
+        // The listing size counts the implicit `.` and `..`.
+        let mut remaining = size.saturating_sub(3) as usize;
+        let mut entries = Vec::new();
+        if remaining == 0 {
+            return Ok(entries);
+        }
+        let mut r = MetadataReader::new(
+            self,
+            self.superblock.directory_table + start_block as u64,
+            offset as usize,
+        )?;
+        while remaining >= 12 {
+            let count = r.u32()? as usize + 1;
+            let inode_block = r.u32()? as u64;
+            let _inode_number = r.u32()?;
+            remaining -= 12;
+            if count > 256 {
+                bail!("directory header with {} entries", count);
+            }
+            for _ in 0..count {
+                let inode_offset = r.u16()? as u64;
+                let _inode_delta = r.u16()?;
+                let _type = r.u16()?;
+                let name_len = r.u16()? as usize + 1;
+                let name = String::from_utf8_lossy(&r.bytes(name_len)?).into_owned();
+                entries.push((name, (inode_block << 16) | inode_offset));
+                remaining = remaining.saturating_sub(8 + name_len);
+            }
+        }
+        Ok(entries)
+    }
+
+    fn fragment(&self, index: u32) -> Result<Vec<u8>> {
+        if let Some(block) = self.fragments.borrow().get(&index) {
+            return Ok(block.clone());
+        }
+        if index >= self.superblock.fragment_count {
+            bail!("fragment {} out of range", index);
+        }
+        // The fragment table is an array of pointers to metadata blocks of
+        // 512 16-byte entries each.
+        let pointer_pos = self.superblock.fragment_table + (index as u64 / 512) * 8;
+        let pointer = u64::from_le_bytes(self.slice(pointer_pos, 8)?.try_into().unwrap());
+        let mut r = MetadataReader::new(self, pointer, (index as usize % 512) * 16)?;
+        let start = r.u64()?;
+        let size = r.u32()?;
+        let raw = self.slice(start, (size & !UNCOMPRESSED_DATA) as usize)?;
+        let block = if size & UNCOMPRESSED_DATA != 0 {
+            raw.to_vec()
+        } else {
+            self.decompress(raw, self.superblock.block_size as usize)?
+        };
+        self.fragments.borrow_mut().insert(index, block.clone());
+        Ok(block)
+    }
+
+    fn read_file(&self, inode: &InodeKind) -> Result<Vec<u8>> {
+        let InodeKind::File {
+            start_block,
+            size,
+            fragment,
+            fragment_offset,
+            blocks,
+        } = inode
+        else {
+            bail!("not a regular file");
+        };
+        if *size > self.limit as u64 {
+            bail!("larger than {} bytes", self.limit);
+        }
+        let size = *size as usize;
+        let block_size = self.superblock.block_size as usize;
+        let mut out = Vec::with_capacity(size);
+        let mut pos = *start_block;
+        for &block in blocks {
+            let len = (block & !UNCOMPRESSED_DATA) as usize;
+            if len == 0 {
+                // Sparse block.
+                out.resize(out.len() + block_size.min(size - out.len()), 0);
+                continue;
+            }
+            let raw = self.slice(pos, len)?;
+            pos += len as u64;
+            if block & UNCOMPRESSED_DATA != 0 {
+                out.extend_from_slice(raw);
+            } else {
+                out.extend(self.decompress(raw, block_size)?);
+            }
+        }
+        if *fragment != NO_FRAGMENT {
+            let tail = size % block_size;
+            let block = self.fragment(*fragment)?;
+            let start = *fragment_offset as usize;
+            let piece = block
+                .get(start..start + tail)
+                .ok_or_else(|| anyhow!("fragment {} is too short", fragment))?;
+            out.extend_from_slice(piece);
+        }
+        out.truncate(size);
+        Ok(out)
+    }
+
+    fn walk(
+        &self,
+        reference: u64,
+        path: &str,
+        depth: usize,
+        visited: &mut HashSet<u64>,
+        sink: &mut Sink,
+        warnings: &mut Vec<String>,
+    ) -> Result<()> {
+        if depth > MAX_DEPTH || !visited.insert(reference) {
+            warnings.push(format!("squashfs: directory loop at {}", path));
+            return Ok(());
+        }
+        let inode = self.inode(reference)?;
+        let InodeKind::Directory {
+            start_block,
+            offset,
+            size,
+        } = inode.kind
+        else {
+            bail!("root inode is not a directory");
+        };
+
+        for (name, child) in self.read_dir(start_block, offset, size)? {
+            let child_path = if path.is_empty() {
+                name
+            } else {
+                format!("{}/{}", path, name)
+            };
+            let inode = match self.inode(child) {
+                Ok(inode) => inode,
+                Err(e) => {
+                    warnings.push(format!("{}: {:#}", child_path, e));
+                    continue;
+                }
+            };
+            let mode = Some(inode.mode as u32 & 0o7777);
+            match &inode.kind {
+                InodeKind::Directory { .. } => {
+                    sink(Member {
+                        name: child_path.clone(),
+                        entry_type: EntryType::Directory,
+                        data: Vec::new(),
+                        offset: None,
+                        mode,
+                    })?;
+                    if let Err(e) =
+                        self.walk(child, &child_path, depth + 1, visited, sink, warnings)
+                    {
+                        if e.is::<super::container::LimitExceeded>() {
+                            return Err(e);
+                        }
+                        warnings.push(format!("{}: {:#}", child_path, e));
+                    }
+                }
+                InodeKind::File {
+                    start_block,
+                    blocks,
+                    ..
+                } => match self.read_file(&inode.kind) {
+                    Ok(data) => sink(Member {
+                        name: child_path,
+                        entry_type: EntryType::File,
+                        data,
+                        offset: (!blocks.is_empty()).then_some(*start_block),
+                        mode,
+                    })?,
+                    Err(e) => warnings.push(format!("{}: {:#}", child_path, e)),
+                },
+                InodeKind::Symlink(target) => sink(Member {
+                    name: child_path,
+                    entry_type: EntryType::Symlink {
+                        target: target.clone(),
+                    },
+                    data: Vec::new(),
+                    offset: None,
+                    mode,
+                })?,
+                InodeKind::Device | InodeKind::Other => sink(Member {
+                    name: child_path,
+                    entry_type: if matches!(inode.kind, InodeKind::Device) {
+                        EntryType::Device
+                    } else {
+                        EntryType::Other
+                    },
+                    data: Vec::new(),
+                    offset: None,
+                    mode,
+                })?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Extracts a SquashFS 4.0 image (gzip, LZMA or XZ compressed). Data block
+/// offsets in the produced members are absolute positions in the image.
+pub(crate) fn extract(
+    data: &[u8],
+    limit: usize,
+    sink: &mut Sink,
+    warnings: &mut Vec<String>,
+) -> Result<()> {
+    let superblock = Superblock::parse(data)?;
+    let root = superblock.root_inode;
+    let image = Image {
+        data,
+        superblock,
+        metadata: RefCell::new(HashMap::new()),
+        fragments: RefCell::new(HashMap::new()),
+        limit,
+    };
+    image.walk(root, "", 0, &mut HashSet::new(), sink, warnings)
+}
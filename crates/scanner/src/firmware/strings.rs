@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StringEncoding {
+    Ascii,
+    Utf16Le,
+}
+
+/// A run of printable characters found in an extracted file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtractedString {
+    /// Manifest path of the file the string was found in.
+    pub file: String,
+    /// Byte offset of the first character in that file.
+    pub offset: u64,
+    pub encoding: StringEncoding,
+    pub value: String,
+}
+
+fn is_printable(b: u8) -> bool {
+    (0x20..0x7f).contains(&b) || b == b'\t'
+}
+
+/// Printable ASCII and UTF-16LE strings of at least `min_length`
+/// characters, ordered by offset. Lines of a text file come out as separate
+/// strings.
+pub fn extract(file: &str, data: &[u8], min_length: usize) -> Vec<ExtractedString> {
+    let min_length = min_length.max(1);
+    let mut strings = Vec::new();
+    let mut push = |offset: usize, encoding, value: String| {
+        strings.push(ExtractedString {
+            file: file.to_string(),
+            offset: offset as u64,
+            encoding,
+            value,
+        });
+    };
+
+    let mut start = 0;
+    for (i, &b) in data.iter().chain(std::iter::once(&0)).enumerate() {
+        if is_printable(b) {
+            continue;
+        }
+        if i - start >= min_length {
+            push(
+                start,
+                StringEncoding::Ascii,
+                String::from_utf8_lossy(&data[start..i]).into_owned(),
+            );
+        }
+        start = i + 1;
+    }
+
+    // UTF-16LE text is ASCII interleaved with zero bytes, at either
+    // alignment.
+    for alignment in 0..2 {
+        let mut run = String::new();
+        let mut run_start = alignment;
+        let mut pos = alignment;
+        loop {
+            let unit = data.get(pos..pos + 2);
+            match unit {
+                Some(&[c, 0]) if is_printable(c) => run.push(c as char),
+                _ => {
+                    // A run that starts right after printable ASCII began
+                    // with the last character of that ASCII string.
+                    if run_start > 0 && is_printable(data[run_start - 1]) && !run.is_empty() {
+                        run.remove(0);
+                        run_start += 2;
+                    }
+                    if run.len() >= min_length {
+                        push(run_start, StringEncoding::Utf16Le, std::mem::take(&mut run));
+                    }
+                    run.clear();
+                    run_start = pos + 2;
+                }
+            }
+            if unit.is_none() {
+                break;
+            }
+            pos += 2;
+        }
+    }
+
+    strings.sort_by_key(|s| s.offset);
+    strings
+}
@@ -0,0 +1,564 @@
+use super::container::{self, ContainerKind, EntryType, LimitExceeded, Member};
+use super::signature;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// File name of the manifest written into the work directory.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Bounds on one unpacking run. Hitting the per-file limit skips that file;
+/// hitting any other limit stops the run with a truncated manifest.
+#[derive(Debug, Clone)]
+pub struct UnpackLimits {
+    /// Largest input image, and largest single file after decompression.
+    pub max_file_bytes: u64,
+    /// Total bytes written to the work directory.
+    pub max_total_bytes: u64,
+    /// Containers nested deeper than this are recorded but not opened.
+    pub max_depth: usize,
+    pub max_files: usize,
+    pub timeout: Duration,
+}
+
+impl Default for UnpackLimits {
+    fn default() -> Self {
+        Self {
+            max_file_bytes: 512 * 1024 * 1024,
+            max_total_bytes: 2 * 1024 * 1024 * 1024,
+            max_depth: 8,
+            max_files: 100_000,
+            timeout: Duration::from_secs(300),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Path relative to the work directory, `/`-separated. The input image
+    /// itself is listed under its file name and is not copied.
+    pub path: String,
+    /// Entry this one was extracted from; `None` for the input image.
+    pub parent: Option<String>,
+    /// Format of the parent it was extracted from; `None` for the input
+    /// image and for containers carved out of a raw parent.
+    pub extracted_from: Option<ContainerKind>,
+    /// Offset in the parent where this entry's data, or the compressed
+    /// stream it was decoded from, starts.
+    pub offset: Option<u64>,
+    pub size: u64,
+    /// Lowercase hex SHA-256 of the contents, for regular files.
+    pub sha256: Option<String>,
+    /// Nesting level; 0 for the input image.
+    pub depth: usize,
+    #[serde(flatten)]
+    pub entry_type: EntryType,
+    pub mode: Option<u32>,
+    /// Container format recognised in this entry's contents.
+    pub format: Option<ContainerKind>,
+}
+
+impl ManifestEntry {
+    pub fn is_file(&self) -> bool {
+        self.entry_type == EntryType::File
+    }
+}
+
+/// Everything one unpacking run produced, in extraction order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub source: PathBuf,
+    pub work_dir: PathBuf,
+    pub entries: Vec<ManifestEntry>,
+    /// Members that could not be extracted and containers that could not be
+    /// opened.
+    pub warnings: Vec<String>,
+    /// A size, file-count, depth or time limit stopped extraction early.
+    pub truncated: bool,
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read manifest {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("invalid manifest {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("failed to write manifest {}", path.display()))
+    }
+
+    /// The input image.
+    pub fn root(&self) -> Option<&ManifestEntry> {
+        self.entries.first()
+    }
+
+    pub fn get(&self, path: &str) -> Option<&ManifestEntry> {
+        self.entries.iter().find(|e| e.path == path)
+    }
+
+    /// Regular files, including the input image.
+    pub fn files(&self) -> impl Iterator<Item = &ManifestEntry> {
+        self.entries.iter().filter(|e| e.is_file())
+    }
+
+    /// Where `entry`'s contents are on disk.
+    pub fn disk_path(&self, entry: &ManifestEntry) -> PathBuf {
+        if entry.parent.is_none() {
+            self.source.clone()
+        } else {
+            self.work_dir.join(&entry.path)
+        }
+    }
+}
+
+/// Recursively extracts nested containers from a firmware image into a
+/// work directory. Member paths are sanitised and links are only recorded,
+/// so nothing is ever written outside the work directory.
+pub struct Unpacker {
+    limits: UnpackLimits,
+    work_dir: PathBuf,
+    deadline: Instant,
+    written: u64,
+    paths: HashSet<String>,
+    manifest: Manifest,
+}
+
+impl Unpacker {
+    /// Unpacks `source` into `work_dir`, which is created if needed and must
+    /// be empty, and writes the manifest next to the extracted files.
+    pub fn run(source: &Path, work_dir: &Path, limits: UnpackLimits) -> Result<Manifest> {
+        let size = std::fs::metadata(source)
+            .with_context(|| format!("failed to read {}", source.display()))?
+            .len();
+        if size > limits.max_file_bytes {
+            bail!(
+                "{} is {} bytes, over the {} byte limit",
+                source.display(),
+                size,
+                limits.max_file_bytes
+            );
+        }
+        std::fs::create_dir_all(work_dir)
+            .with_context(|| format!("failed to create {}", work_dir.display()))?;
+        if std::fs::read_dir(work_dir)?.next().is_some() {
+            bail!("work directory {} is not empty", work_dir.display());
+        }
+        let data = std::fs::read(source)
+            .with_context(|| format!("failed to read {}", source.display()))?;
+        let name = source
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "firmware.bin".to_string());
+
+        let mut unpacker = Self {
+            deadline: Instant::now() + limits.timeout,
+            limits,
+            work_dir: work_dir.to_path_buf(),
+            written: 0,
+            paths: HashSet::from([name.clone(), MANIFEST_FILE.to_string()]),
+            manifest: Manifest {
+                source: source.to_path_buf(),
+                work_dir: work_dir.to_path_buf(),
+                entries: Vec::new(),
+                warnings: Vec::new(),
+                truncated: false,
+            },
+        };
+        unpacker.manifest.entries.push(ManifestEntry {
+            path: name.clone(),
+            parent: None,
+            extracted_from: None,
+            offset: None,
+            size,
+            sha256: Some(sha256_hex(&data)),
+            depth: 0,
+            entry_type: EntryType::File,
+            mode: None,
+            format: None,
+        });
+        if let Err(e) = unpacker.open(0, &data) {
+            unpacker.stop(e)?;
+        }
+
+        let manifest = unpacker.manifest;
+        manifest.save(&work_dir.join(MANIFEST_FILE))?;
+        tracing::info!(
+            "Unpacked {}: {} entries, {} bytes written, {} warnings{}",
+            source.display(),
+            manifest.entries.len(),
+            unpacker.written,
+            manifest.warnings.len(),
+            if manifest.truncated {
+                ", truncated"
+            } else {
+                ""
+            }
+        );
+        Ok(manifest)
+    }
+
+    /// Records a global limit as truncation; anything else is a real error.
+    fn stop(&mut self, error: anyhow::Error) -> Result<()> {
+        match error.downcast::<LimitExceeded>() {
+            Ok(limit) => {
+                self.manifest.truncated = true;
+                self.manifest.warnings.push(format!("stopped: {}", limit));
+                Ok(())
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Identifies the entry at `index` and, if it is a container, extracts
+    /// its members below `<path>.extracted/`.
+    fn open(&mut self, index: usize, data: &[u8]) -> Result<()> {
+        let Some(kind) = ContainerKind::identify(data) else {
+            return self.carve(index, data);
+        };
+        let entry = &mut self.manifest.entries[index];
+        entry.format = Some(kind);
+        let (path, depth) = (entry.path.clone(), entry.depth);
+        if depth >= self.limits.max_depth {
+            self.manifest.truncated = true;
+            self.manifest.warnings.push(format!(
+                "{}: {} container not opened, depth limit {} reached",
+                path, kind, self.limits.max_depth
+            ));
+            return Ok(());
+        }
+
+        let dir = format!("{}.extracted", path);
+        let name = path.rsplit('/').next().unwrap_or(&path).to_string();
+        let limit = self.limits.max_file_bytes.min(usize::MAX as u64) as usize;
+        let result = container::extract(kind, data, &name, limit, &mut |member| {
+            self.add(member, &path, Some(kind), &dir, depth + 1)
+        });
+        match result {
+            Ok(warnings) => self
+                .manifest
+                .warnings
+                .extend(warnings.into_iter().map(|w| format!("{}: {}", path, w))),
+            Err(e) if e.is::<LimitExceeded>() => return Err(e),
+            Err(e) => self
+                .manifest
+                .warnings
+                .push(format!("{}: failed to extract as {}: {:#}", path, kind, e)),
+        }
+        Ok(())
+    }
+
+    /// Raw flash dumps and decompressed kernels hold containers at arbitrary
+    /// offsets. Each one the signature scan finds in the input image, or in
+    /// a decoded stream, is carved out as a member of its own, ending where
+    /// its header says or else at the next signature.
+    fn carve(&mut self, index: usize, data: &[u8]) -> Result<()> {
+        let entry = &self.manifest.entries[index];
+        let raw = entry.depth == 0
+            || matches!(
+                entry.extracted_from,
+                Some(
+                    ContainerKind::Gzip
+                        | ContainerKind::Xz
+                        | ContainerKind::Lzma
+                        | ContainerKind::UImage
+                )
+            );
+        if !raw || entry.depth >= self.limits.max_depth {
+            return Ok(());
+        }
+        let (path, depth) = (entry.path.clone(), entry.depth);
+        let dir = format!("{}.extracted", path);
+
+        let hits = signature::scan_signatures(data);
+        let mut covered = 0;
+        for (i, hit) in hits.iter().enumerate() {
+            let Some(kind) = hit.container else {
+                continue;
+            };
+            // Offset 0 would have been identified; anything inside an
+            // object already carved belongs to that object.
+            if hit.offset == 0 || hit.offset < covered {
+                continue;
+            }
+            let end = hit
+                .end()
+                .or_else(|| hits.get(i + 1).map(|next| next.offset))
+                .unwrap_or(data.len() as u64);
+            covered = hit.end().unwrap_or(covered);
+            let (start, end) = (hit.offset as usize, end as usize);
+            let member = Member {
+                name: format!("{:x}.{}", start, kind.extension()),
+                entry_type: EntryType::File,
+                data: data[start..end].to_vec(),
+                offset: Some(hit.offset),
+                mode: None,
+            };
+            self.add(member, &path, None, &dir, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    fn add(
+        &mut self,
+        member: Member,
+        parent: &str,
+        kind: Option<ContainerKind>,
+        dir: &str,
+        depth: usize,
+    ) -> Result<()> {
+        if Instant::now() > self.deadline {
+            return Err(LimitExceeded(format!(
+                "extraction took longer than {}s",
+                self.limits.timeout.as_secs()
+            ))
+            .into());
+        }
+        if self.manifest.entries.len() >= self.limits.max_files {
+            return Err(LimitExceeded(format!("more than {} files", self.limits.max_files)).into());
+        }
+        let Some(name) = sanitize(&member.name) else {
+            if member.entry_type != EntryType::Directory {
+                self.manifest
+                    .warnings
+                    .push(format!("{}: skipped unsafe path {:?}", parent, member.name));
+            }
+            return Ok(());
+        };
+        let is_file = member.entry_type == EntryType::File;
+        let mut path = format!("{}/{}", dir, name);
+        if self.paths.contains(&path) {
+            if member.entry_type == EntryType::Directory {
+                return Ok(());
+            }
+            path = (1..)
+                .map(|n| format!("{}~{}", path, n))
+                .find(|p| !self.paths.contains(p))
+                .unwrap_or_default();
+        }
+
+        let disk_path = self.work_dir.join(&path);
+        let written = match &member.entry_type {
+            EntryType::Directory => std::fs::create_dir_all(&disk_path),
+            EntryType::File => {
+                let size = member.data.len() as u64;
+                if self.written + size > self.limits.max_total_bytes {
+                    return Err(LimitExceeded(format!(
+                        "more than {} bytes extracted",
+                        self.limits.max_total_bytes
+                    ))
+                    .into());
+                }
+                self.written += size;
+                disk_path
+                    .parent()
+                    .map_or(Ok(()), std::fs::create_dir_all)
+                    .and_then(|_| std::fs::write(&disk_path, &member.data))
+            }
+            _ => Ok(()),
+        };
+        if let Err(e) = written {
+            self.manifest
+                .warnings
+                .push(format!("{}: could not write {}: {}", parent, path, e));
+            return Ok(());
+        }
+
+        self.paths.insert(path.clone());
+        self.manifest.entries.push(ManifestEntry {
+            path,
+            parent: Some(parent.to_string()),
+            extracted_from: kind,
+            offset: member.offset,
+            size: member.data.len() as u64,
+            sha256: is_file.then(|| sha256_hex(&member.data)),
+            depth,
+            entry_type: member.entry_type,
+            mode: member.mode,
+            format: None,
+        });
+        if is_file {
+            let index = self.manifest.entries.len() - 1;
+            self.open(index, &member.data)?;
+        }
+        Ok(())
+    }
+}
+
+/// Relative, `/`-separated form of an archive path, or `None` if it is
+/// empty or tries to climb out with `..`.
+fn sanitize(name: &str) -> Option<String> {
+    let parts: Vec<String> = name
+        .split(['/', '\\'])
+        .filter(|p| !p.is_empty() && *p != ".")
+        .map(|p| p.replace(|c: char| c.is_control() || c == ':', "_"))
+        .collect();
+    if parts.is_empty() || parts.iter().any(|p| p == "..") {
+        return None;
+    }
+    Some(parts.join("/"))
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fixtures::{self, Scratch};
+    use super::*;
+
+    const PASSWD: &[u8] = b"root:x:0:0:root:/root:/bin/sh\n";
+
+    /// `fw.tar.gz`: a gzip-compressed tar holding `/etc/passwd` and an
+    /// initramfs cpio with a directory, a script and a symlink.
+    fn image() -> Vec<u8> {
+        let initramfs = fixtures::cpio(&[
+            ("bin", 0o040755, b""),
+            ("init", 0o100755, b"#!/bin/sh\nexec /bin/sh\n"),
+            ("bin/sh", 0o120777, b"busybox"),
+        ]);
+        let tar = fixtures::tar(&[("etc/passwd", PASSWD), ("boot/initramfs.cpio", &initramfs)]);
+        fixtures::gzip("rootfs.tar", &tar)
+    }
+
+    fn unpack(scratch: &Scratch, data: &[u8], limits: UnpackLimits) -> Result<Manifest> {
+        let source = scratch.write("fw.tar.gz", data);
+        Unpacker::run(&source, &scratch.path().join("out"), limits)
+    }
+
+    #[test]
+    fn unpacks_nested_gzip_tar_and_cpio() {
+        let scratch = Scratch::new("unpack-nested");
+        let manifest = unpack(&scratch, &image(), UnpackLimits::default()).unwrap();
+        assert!(!manifest.truncated);
+        assert!(manifest.warnings.is_empty(), "{:?}", manifest.warnings);
+
+        let tar = manifest.get("fw.tar.gz.extracted/rootfs.tar").unwrap();
+        assert_eq!(manifest.root().unwrap().format, Some(ContainerKind::Gzip));
+        assert_eq!(tar.extracted_from, Some(ContainerKind::Gzip));
+        assert_eq!(tar.format, Some(ContainerKind::Tar));
+
+        let passwd = manifest
+            .get("fw.tar.gz.extracted/rootfs.tar.extracted/etc/passwd")
+            .unwrap();
+        assert_eq!(passwd.depth, 2);
+        assert_eq!(passwd.sha256.as_deref(), Some(sha256_hex(PASSWD).as_str()));
+        assert_eq!(std::fs::read(manifest.disk_path(passwd)).unwrap(), PASSWD);
+
+        let cpio = "fw.tar.gz.extracted/rootfs.tar.extracted/boot/initramfs.cpio.extracted";
+        let init = manifest.get(&format!("{}/init", cpio)).unwrap();
+        assert_eq!(init.extracted_from, Some(ContainerKind::Cpio));
+        assert_eq!(init.mode, Some(0o755));
+        let link = manifest.get(&format!("{}/bin/sh", cpio)).unwrap();
+        assert_eq!(
+            link.entry_type,
+            EntryType::Symlink {
+                target: "busybox".to_string()
+            }
+        );
+        // Links are recorded, never created.
+        assert!(!manifest.disk_path(link).exists());
+
+        let saved = Manifest::load(&manifest.work_dir.join(MANIFEST_FILE)).unwrap();
+        assert_eq!(saved.entries.len(), manifest.entries.len());
+    }
+
+    #[test]
+    fn parent_directory_paths_are_skipped() {
+        let scratch = Scratch::new("unpack-traversal");
+        let tar = fixtures::tar(&[
+            ("../escaped", b"outside"),
+            ("etc/../../escaped-too", b"outside"),
+            ("./etc//hosts", b"127.0.0.1 localhost\n"),
+        ]);
+        let source = scratch.write("fw.tar", &tar);
+        let manifest = Unpacker::run(
+            &source,
+            &scratch.path().join("out"),
+            UnpackLimits::default(),
+        )
+        .unwrap();
+
+        let skipped: Vec<_> = manifest
+            .warnings
+            .iter()
+            .filter(|w| w.contains("skipped unsafe path"))
+            .collect();
+        assert_eq!(skipped.len(), 2, "{:?}", manifest.warnings);
+        assert!(manifest.get("fw.tar.extracted/etc/hosts").is_some());
+        assert!(!scratch.path().join("escaped").exists());
+        assert!(!scratch.path().join("escaped-too").exists());
+
+        assert_eq!(sanitize("a/./b\\c"), Some("a/b/c".to_string()));
+        assert_eq!(sanitize("/etc/passwd"), Some("etc/passwd".to_string()));
+        assert_eq!(sanitize("C:evil"), Some("C_evil".to_string()));
+        assert_eq!(sanitize("a/../../b"), None);
+        assert_eq!(sanitize("./"), None);
+    }
+
+    #[test]
+    fn limits_stop_or_skip_extraction() {
+        let limited = |limits: UnpackLimits| {
+            let scratch = Scratch::new("unpack-limits");
+            unpack(&scratch, &image(), limits).unwrap()
+        };
+
+        let manifest = limited(UnpackLimits {
+            max_files: 3,
+            ..UnpackLimits::default()
+        });
+        assert!(manifest.truncated);
+        assert_eq!(manifest.entries.len(), 3);
+        assert!(manifest.warnings[0].contains("more than 3 files"));
+
+        let manifest = limited(UnpackLimits {
+            max_total_bytes: 3072,
+            ..UnpackLimits::default()
+        });
+        assert!(manifest.truncated);
+        assert!(manifest.warnings[0].contains("more than 3072 bytes extracted"));
+
+        let manifest = limited(UnpackLimits {
+            max_depth: 2,
+            ..UnpackLimits::default()
+        });
+        assert!(manifest.truncated);
+        assert!(manifest.warnings[0].contains("cpio container not opened"));
+        assert!(manifest.entries.iter().all(|e| e.depth <= 2));
+
+        // A member that decodes past the per-file limit is skipped; the
+        // run carries on.
+        let bomb = fixtures::gzip("big.bin", &[0u8; 64 * 1024]);
+        let tar = fixtures::tar(&[("big.bin.gz", &bomb), ("small.txt", b"ok")]);
+        let scratch = Scratch::new("unpack-file-limit");
+        let source = scratch.write("fw.tar", &tar);
+        let limits = UnpackLimits {
+            max_file_bytes: 4096,
+            ..UnpackLimits::default()
+        };
+        let manifest = Unpacker::run(&source, &scratch.path().join("out"), limits).unwrap();
+        assert!(!manifest.truncated);
+        assert!(manifest.get("fw.tar.extracted/small.txt").is_some());
+        assert!(manifest
+            .get("fw.tar.extracted/big.bin.gz.extracted/big.bin")
+            .is_none());
+        assert!(
+            manifest.warnings[0].contains("member decodes to more than 4096 bytes"),
+            "{:?}",
+            manifest.warnings
+        );
+
+        let too_big = UnpackLimits {
+            max_file_bytes: 100,
+            ..UnpackLimits::default()
+        };
+        let error = Unpacker::run(&source, &scratch.path().join("out2"), too_big).unwrap_err();
+        assert!(error.to_string().contains("over the 100 byte limit"));
+    }
+}
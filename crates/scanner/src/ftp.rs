@@ -0,0 +1,924 @@
+use crate::credentials::{CredentialList, DefaultCredential};
+use crate::SecurityConfig;
+use crate::{Finding, ScanResult, ScanType, Severity};
+use anyhow::{anyhow, bail, Context, Result};
+use data::AssetNode;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+/// Listings are cut at this size; camera SD cards can hold thousands of files.
+const MAX_LISTING_BYTES: usize = 256 * 1024;
+
+/// Entries quoted as evidence per directory.
+const EVIDENCE_ENTRIES: usize = 10;
+
+/// Mirrors the `[ftp]` section of `config.toml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FtpConfig {
+    pub connect_timeout_ms: u64,
+    pub read_timeout_ms: u64,
+    pub anonymous_password: String,
+    /// Upload and delete a small file in each listed directory to prove
+    /// write access. Off by default: it modifies the target.
+    pub write_test: bool,
+    /// Directories below the login directory that are listed and write-tested.
+    pub max_directories: usize,
+}
+
+impl Default for FtpConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout_ms: 3000,
+            read_timeout_ms: 5000,
+            anonymous_password: "anonymous@example.com".to_string(),
+            write_test: false,
+            max_directories: 8,
+        }
+    }
+}
+
+/// A control-connection reply; multi-line replies are joined with `\n`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FtpReply {
+    pub code: u16,
+    pub text: String,
+}
+
+impl FtpReply {
+    fn is_positive(&self) -> bool {
+        (100..400).contains(&self.code)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FtpListing {
+    pub path: String,
+    pub entries: Vec<String>,
+}
+
+/// What one working login could see and do.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FtpAccess {
+    pub credential: DefaultCredential,
+    pub listings: Vec<FtpListing>,
+    pub writable: Vec<String>,
+    /// Control-connection transcript of the session.
+    pub transcript: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FtpReport {
+    pub target: String,
+    pub banner: String,
+    /// The server advertises `AUTH TLS` / `AUTH SSL` in FEAT.
+    pub auth_tls: bool,
+    /// The server asked for a password (or let us in) over plaintext.
+    pub plaintext_auth: bool,
+    pub anonymous: Option<FtpAccess>,
+    pub logins: Vec<FtpAccess>,
+    pub attempts: usize,
+    /// The server started refusing connections during credential testing.
+    pub locked_out: bool,
+    /// Credential testing stopped at the rejected-login budget.
+    pub stopped_early: bool,
+}
+
+impl FtpReport {
+    fn affected(&self) -> String {
+        format!("{}/tcp", self.target)
+    }
+
+    pub fn findings(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let affected = self.affected();
+
+        if let Some(access) = &self.anonymous {
+            findings.push(
+                access
+                    .with_transcript(Finding::new(
+                        Severity::High,
+                        "Anonymous FTP login allowed".to_string(),
+                        format!(
+                            "{} accepts anonymous logins, exposing files such as \
+                             flight logs, media and configuration to anyone on the link.",
+                            affected
+                        ),
+                    ))
+                    .with_affected(affected.clone()),
+            );
+        }
+
+        for access in &self.logins {
+            let vendor = access
+                .credential
+                .vendor
+                .as_ref()
+                .map(|v| format!(" ({} default)", v))
+                .unwrap_or_default();
+            findings.push(
+                access
+                    .with_transcript(Finding::new(
+                        Severity::Critical,
+                        "Default FTP credentials accepted".to_string(),
+                        format!(
+                            "{} accepts the default login {}{} (CWE-798).",
+                            affected,
+                            access.credential.display(),
+                            vendor
+                        ),
+                    ))
+                    .with_affected(affected.clone()),
+            );
+        }
+
+        for access in self.anonymous.iter().chain(&self.logins) {
+            let anonymous = access.credential.username == "anonymous";
+            for dir in &access.writable {
+                let (severity, title) = if anonymous {
+                    (Severity::Critical, "Anonymous FTP write access")
+                } else {
+                    (Severity::High, "Writable FTP directory")
+                };
+                findings.push(
+                    Finding::new(
+                        severity,
+                        title.to_string(),
+                        format!(
+                            "{} as {} can upload files to {}. Writable storage on \
+                             the vehicle can be used to plant scripts, missions or \
+                             firmware images.",
+                            affected, access.credential.username, dir
+                        ),
+                    )
+                    .with_affected(affected.clone())
+                    .with_evidence(format!("STOR {} accepted", dir)),
+                );
+            }
+
+            let listed: Vec<&FtpListing> = access
+                .listings
+                .iter()
+                .filter(|l| !l.entries.is_empty())
+                .collect();
+            if !listed.is_empty() {
+                let mut finding = Finding::new(
+                    Severity::Low,
+                    "FTP directory listing available".to_string(),
+                    format!(
+                        "{} as {} lists {} director{} with {} entries.",
+                        affected,
+                        access.credential.username,
+                        listed.len(),
+                        if listed.len() == 1 { "y" } else { "ies" },
+                        listed.iter().map(|l| l.entries.len()).sum::<usize>()
+                    ),
+                )
+                .with_affected(affected.clone());
+                for listing in listed {
+                    for entry in listing.entries.iter().take(EVIDENCE_ENTRIES) {
+                        finding = finding.with_evidence(format!("{}: {}", listing.path, entry));
+                    }
+                }
+                findings.push(finding);
+            }
+        }
+
+        if self.plaintext_auth {
+            let (severity, detail) = if self.auth_tls {
+                (Severity::Low, "supports AUTH TLS but does not require it")
+            } else {
+                (Severity::Medium, "does not offer AUTH TLS")
+            };
+            findings.push(
+                Finding::new(
+                    severity,
+                    "FTP accepts cleartext authentication".to_string(),
+                    format!(
+                        "{} {}, so usernames and passwords cross the link in \
+                         cleartext (CWE-319).",
+                        affected, detail
+                    ),
+                )
+                .with_affected(affected.clone())
+                .with_evidence(format!("Banner: {}", self.banner)),
+            );
+        }
+        findings
+    }
+
+    pub fn scan_result(&self) -> ScanResult {
+        ScanResult {
+            scan_type: ScanType::Network,
+            target: self.affected(),
+            findings: self.findings(),
+            incomplete: false,
+        }
+    }
+
+    /// Working logins as `data::Credential` with `auth_type = "ftp"`.
+    pub fn credentials(&self) -> Vec<data::Credential> {
+        self.anonymous
+            .iter()
+            .chain(&self.logins)
+            .map(|access| access.credential.to_credential("ftp"))
+            .collect()
+    }
+
+    /// Records working logins on `asset`, skipping ones already present.
+    pub fn apply_to(&self, asset: &mut AssetNode) {
+        for credential in self.credentials() {
+            let known = asset.credentials.iter().any(|c| {
+                c.auth_type == credential.auth_type
+                    && c.username == credential.username
+                    && c.password == credential.password
+            });
+            if !known {
+                asset.credentials.push(credential);
+            }
+        }
+    }
+}
+
+impl FtpAccess {
+    fn with_transcript(&self, mut finding: Finding) -> Finding {
+        for line in &self.transcript {
+            finding = finding.with_evidence(line.clone());
+        }
+        finding
+    }
+}
+
+enum LoginOutcome {
+    Success(FtpSession),
+    Rejected,
+    /// 421 or a refused connection: the server is throttling us.
+    Refused,
+}
+
+/// Tests an FTP server for anonymous access, default credentials, listable
+/// and writable directories and cleartext authentication.
+pub struct FtpChecker {
+    config: FtpConfig,
+    credentials: CredentialList,
+    max_failed_attempts: usize,
+}
+
+impl FtpChecker {
+    pub fn new() -> Self {
+        Self::with_config(FtpConfig::default())
+    }
+
+    pub fn with_config(config: FtpConfig) -> Self {
+        Self {
+            config,
+            credentials: CredentialList::new(),
+            max_failed_attempts: SecurityConfig::default().max_failed_attempts,
+        }
+    }
+
+    /// Replaces the default-credential list, e.g. with one that also loaded
+    /// vendor-specific pairs.
+    pub fn with_credentials(mut self, credentials: CredentialList) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    /// Takes the rejected-login budget from `[security]`.
+    pub fn with_security(mut self, security: &SecurityConfig) -> Self {
+        self.max_failed_attempts = security.max_failed_attempts;
+        self
+    }
+
+    pub async fn check(&self, addr: SocketAddr) -> Result<FtpReport> {
+        tracing::info!("Checking FTP service on {}", addr);
+        let (mut session, greeting) = FtpSession::connect(addr, &self.config).await?;
+        if greeting.code != 220 {
+            bail!(
+                "{} is not accepting FTP sessions: {} {}",
+                addr,
+                greeting.code,
+                greeting.text
+            );
+        }
+        let features = session.command("FEAT").await?;
+        let auth_tls = features.code == 211 && {
+            let text = features.text.to_ascii_uppercase();
+            text.contains("AUTH TLS") || text.contains("AUTH SSL")
+        };
+        session.quit().await;
+
+        let mut report = FtpReport {
+            target: addr.to_string(),
+            banner: greeting.text.replace('\n', " "),
+            auth_tls,
+            plaintext_auth: false,
+            anonymous: None,
+            logins: Vec::new(),
+            attempts: 0,
+            locked_out: false,
+            stopped_early: false,
+        };
+
+        let anonymous = DefaultCredential {
+            username: "anonymous".to_string(),
+            password: self.config.anonymous_password.clone(),
+            vendor: None,
+            services: vec!["ftp".to_string()],
+        };
+        let mut failures = 0;
+        let candidates = std::iter::once(&anonymous).chain(
+            self.credentials
+                .for_service("ftp")
+                .into_iter()
+                .filter(|c| c.username != "anonymous"),
+        );
+        for credential in candidates {
+            if self.max_failed_attempts > 0 && failures >= self.max_failed_attempts {
+                tracing::info!(
+                    "Stopping FTP credential tests on {} after {} failures",
+                    addr,
+                    failures
+                );
+                report.stopped_early = true;
+                break;
+            }
+            report.attempts += 1;
+            match self.try_login(addr, credential, &mut report).await {
+                LoginOutcome::Success(session) => {
+                    let access = self.explore(session, credential).await;
+                    if credential.username == "anonymous" {
+                        report.anonymous = Some(access);
+                    } else {
+                        report.logins.push(access);
+                        // One working default is enough to prove the point
+                        // without hammering the device.
+                        break;
+                    }
+                }
+                LoginOutcome::Rejected => failures += 1,
+                LoginOutcome::Refused => {
+                    report.locked_out = true;
+                    break;
+                }
+            }
+        }
+
+        tracing::info!(
+            "FTP check of {}: anonymous={}, {} default logins, {} attempts",
+            addr,
+            report.anonymous.is_some(),
+            report.logins.len(),
+            report.attempts
+        );
+        Ok(report)
+    }
+
+    async fn try_login(
+        &self,
+        addr: SocketAddr,
+        credential: &DefaultCredential,
+        report: &mut FtpReport,
+    ) -> LoginOutcome {
+        let Ok((mut session, greeting)) = FtpSession::connect(addr, &self.config).await else {
+            return LoginOutcome::Refused;
+        };
+        if greeting.code != 220 {
+            return LoginOutcome::Refused;
+        }
+        let user = match session
+            .command(&format!("USER {}", credential.username))
+            .await
+        {
+            Ok(reply) => reply,
+            Err(_) => return LoginOutcome::Refused,
+        };
+        let reply = match user.code {
+            230 => user,
+            331 | 332 => {
+                report.plaintext_auth = true;
+                match session
+                    .command(&format!("PASS {}", credential.password))
+                    .await
+                {
+                    Ok(reply) => reply,
+                    Err(_) => return LoginOutcome::Rejected,
+                }
+            }
+            421 => return LoginOutcome::Refused,
+            _ => return LoginOutcome::Rejected,
+        };
+        match reply.code {
+            230 | 202 => {
+                report.plaintext_auth = true;
+                LoginOutcome::Success(session)
+            }
+            421 => LoginOutcome::Refused,
+            _ => {
+                session.quit().await;
+                LoginOutcome::Rejected
+            }
+        }
+    }
+
+    /// Lists the login directory and its subdirectories and, if enabled,
+    /// proves write access by uploading and deleting a marker file.
+    async fn explore(&self, mut session: FtpSession, credential: &DefaultCredential) -> FtpAccess {
+        let mut access = FtpAccess {
+            credential: credential.clone(),
+            listings: Vec::new(),
+            writable: Vec::new(),
+            transcript: Vec::new(),
+        };
+
+        let root = match session.command("PWD").await {
+            Ok(reply) if reply.code == 257 => {
+                parse_pwd(&reply.text).unwrap_or_else(|| "/".to_string())
+            }
+            _ => "/".to_string(),
+        };
+        let mut dirs = vec![root.clone()];
+        if let Ok(Some(entries)) = session.list(&root).await {
+            dirs.extend(
+                entries
+                    .iter()
+                    .filter_map(|entry| directory_name(entry))
+                    .filter(|name| name != "." && name != "..")
+                    .take(self.config.max_directories)
+                    .map(|name| join_path(&root, &name)),
+            );
+            access.listings.push(FtpListing {
+                path: root.clone(),
+                entries,
+            });
+        }
+        for dir in dirs.iter().skip(1) {
+            if let Ok(Some(entries)) = session.list(dir).await {
+                access.listings.push(FtpListing {
+                    path: dir.clone(),
+                    entries,
+                });
+            }
+        }
+
+        if self.config.write_test {
+            let nonce = chrono::Utc::now().timestamp_millis();
+            for dir in &dirs {
+                let path = join_path(dir, &format!(".uavred-write-test-{}", nonce));
+                if session
+                    .store(&path, b"uavred write test\n")
+                    .await
+                    .unwrap_or(false)
+                {
+                    access.writable.push(dir.clone());
+                    match session.command(&format!("DELE {}", path)).await {
+                        Ok(reply) if reply.is_positive() => {}
+                        _ => tracing::warn!("Could not remove FTP write test file {}", path),
+                    }
+                }
+            }
+        }
+
+        session.quit().await;
+        access.transcript = session.transcript;
+        access
+    }
+}
+
+impl Default for FtpChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One FTP control connection.
+struct FtpSession {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+    peer: SocketAddr,
+    read_timeout: Duration,
+    connect_timeout: Duration,
+    transcript: Vec<String>,
+}
+
+impl FtpSession {
+    async fn connect(addr: SocketAddr, config: &FtpConfig) -> Result<(Self, FtpReply)> {
+        let connect_timeout = Duration::from_millis(config.connect_timeout_ms);
+        let stream = timeout(connect_timeout, TcpStream::connect(addr))
+            .await
+            .map_err(|_| anyhow!("connection to {} timed out", addr))?
+            .with_context(|| format!("failed to connect to {}", addr))?;
+        let (reader, writer) = stream.into_split();
+        let mut session = Self {
+            reader: BufReader::new(reader),
+            writer,
+            peer: addr,
+            read_timeout: Duration::from_millis(config.read_timeout_ms),
+            connect_timeout,
+            transcript: Vec::new(),
+        };
+        let greeting = session.read_reply().await?;
+        Ok((session, greeting))
+    }
+
+    async fn command(&mut self, command: &str) -> Result<FtpReply> {
+        self.transcript.push(format!("> {}", command));
+        timeout(
+            self.read_timeout,
+            self.writer.write_all(format!("{}\r\n", command).as_bytes()),
+        )
+        .await
+        .map_err(|_| anyhow!("timed out sending {}", command))??;
+        self.read_reply().await
+    }
+
+    /// Reads one reply. A multi-line reply starts with `123-` and ends with
+    /// a line starting `123 `; lines in between need not carry the code.
+    async fn read_reply(&mut self) -> Result<FtpReply> {
+        let first = self.read_line().await?;
+        let code = first
+            .get(..3)
+            .and_then(|c| c.parse::<u16>().ok())
+            .ok_or_else(|| anyhow!("malformed FTP reply: {}", first))?;
+        let mut text = vec![first.get(4..).unwrap_or_default().trim().to_string()];
+        if first.as_bytes().get(3) == Some(&b'-') {
+            let end = format!("{} ", code);
+            loop {
+                let line = self.read_line().await?;
+                if line.starts_with(&end) || line == end.trim_end() {
+                    text.push(line.get(4..).unwrap_or_default().trim().to_string());
+                    break;
+                }
+                text.push(line.trim().to_string());
+            }
+        }
+        Ok(FtpReply {
+            code,
+            text: text.join("\n"),
+        })
+    }
+
+    async fn read_line(&mut self) -> Result<String> {
+        let mut line = String::new();
+        let read = timeout(self.read_timeout, self.reader.read_line(&mut line))
+            .await
+            .map_err(|_| anyhow!("timed out waiting for FTP reply"))??;
+        if read == 0 {
+            bail!("FTP server closed the connection");
+        }
+        let line = line.trim_end().to_string();
+        self.transcript.push(format!("< {}", line));
+        Ok(line)
+    }
+
+    /// Opens a passive data connection. The address in the PASV reply is
+    /// ignored in favour of the control peer, which also sidesteps FTP
+    /// bounce targets and NATed servers announcing private addresses.
+    async fn open_data(&mut self) -> Result<TcpStream> {
+        let reply = self.command("EPSV").await?;
+        let port = if reply.code == 229 {
+            parse_epsv(&reply.text)
+        } else {
+            let reply = self.command("PASV").await?;
+            (reply.code == 227)
+                .then(|| parse_pasv(&reply.text))
+                .flatten()
+        }
+        .ok_or_else(|| anyhow!("server refused passive mode"))?;
+
+        let addr = SocketAddr::new(self.peer.ip(), port);
+        timeout(self.connect_timeout, TcpStream::connect(addr))
+            .await
+            .map_err(|_| anyhow!("data connection to {} timed out", addr))?
+            .with_context(|| format!("failed to open data connection to {}", addr))
+    }
+
+    /// `LIST` of `path`, or `None` if the server refused it.
+    async fn list(&mut self, path: &str) -> Result<Option<Vec<String>>> {
+        let mut data = self.open_data().await?;
+        let reply = self.command(&format!("LIST {}", path)).await?;
+        if !matches!(reply.code, 125 | 150) {
+            return Ok(None);
+        }
+
+        let mut listing = Vec::new();
+        let mut buf = [0u8; 8192];
+        while listing.len() < MAX_LISTING_BYTES {
+            match timeout(self.read_timeout, data.read(&mut buf)).await {
+                Ok(Ok(n)) if n > 0 => listing.extend_from_slice(&buf[..n]),
+                _ => break,
+            }
+        }
+        drop(data);
+        let done = self.read_reply().await?;
+        if !done.is_positive() {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            String::from_utf8_lossy(&listing)
+                .lines()
+                .map(|l| l.trim_end().to_string())
+                .filter(|l| !l.is_empty() && !l.starts_with("total "))
+                .collect(),
+        ))
+    }
+
+    /// Uploads `content` to `path`; true if the server confirmed the transfer.
+    async fn store(&mut self, path: &str, content: &[u8]) -> Result<bool> {
+        let mut data = self.open_data().await?;
+        let reply = self.command(&format!("STOR {}", path)).await?;
+        if !matches!(reply.code, 125 | 150) {
+            return Ok(false);
+        }
+        timeout(self.read_timeout, data.write_all(content))
+            .await
+            .map_err(|_| anyhow!("timed out uploading {}", path))??;
+        data.shutdown().await.ok();
+        drop(data);
+        Ok(self.read_reply().await?.code == 226)
+    }
+
+    async fn quit(&mut self) {
+        let _ = self.command("QUIT").await;
+    }
+}
+
+fn parse_pwd(text: &str) -> Option<String> {
+    let start = text.find('"')? + 1;
+    let end = start + text[start..].find('"')?;
+    Some(text[start..end].to_string())
+}
+
+/// `227 Entering Passive Mode (h1,h2,h3,h4,p1,p2)`.
+fn parse_pasv(text: &str) -> Option<u16> {
+    let start = text
+        .find('(')
+        .map_or_else(|| text.find(|c: char| c.is_ascii_digit()), |i| Some(i + 1))?;
+    let numbers: Vec<u16> = text[start..]
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|s| !s.is_empty())
+        .take(6)
+        .filter_map(|s| s.parse().ok())
+        .collect();
+    match numbers.as_slice() {
+        [_, _, _, _, p1, p2] if *p1 < 256 && *p2 < 256 => Some(p1 * 256 + p2),
+        _ => None,
+    }
+}
+
+/// `229 Entering Extended Passive Mode (|||port|)`.
+fn parse_epsv(text: &str) -> Option<u16> {
+    let start = text.find("(|||")? + 4;
+    let end = start + text[start..].find('|')?;
+    text[start..end].parse().ok()
+}
+
+/// Name of a directory entry in a Unix (`drwxr-xr-x ... name`) or DOS
+/// (`01-01-24 12:00PM <DIR> name`) listing line.
+fn directory_name(entry: &str) -> Option<String> {
+    let fields: Vec<&str> = entry.split_whitespace().collect();
+    if entry.starts_with('d') && fields.len() >= 9 {
+        return Some(fields[8..].join(" "));
+    }
+    let dir = fields
+        .iter()
+        .position(|f| f.eq_ignore_ascii_case("<DIR>"))?;
+    (dir + 1 < fields.len()).then(|| fields[dir + 1..].join(" "))
+}
+
+fn join_path(dir: &str, name: &str) -> String {
+    if dir.ends_with('/') {
+        format!("{}{}", dir, name)
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::net::TcpListener;
+
+    /// In-process FTP server with a `/` containing a `DCIM` directory that
+    /// accepts uploads.
+    #[derive(Default)]
+    struct Stub {
+        anonymous: bool,
+        logins: Vec<(&'static str, &'static str)>,
+        commands: Mutex<Vec<String>>,
+        files: Mutex<Vec<String>>,
+    }
+
+    impl Stub {
+        async fn start(self) -> (SocketAddr, Arc<Self>) {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let stub = Arc::new(self);
+            let server = stub.clone();
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(server.clone().serve(stream));
+                }
+            });
+            (addr, stub)
+        }
+
+        async fn serve(self: Arc<Self>, stream: TcpStream) {
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            let mut user = String::new();
+            let mut passive: Option<TcpListener> = None;
+            let _ = writer.write_all(b"220 stub FTP ready\r\n").await;
+            while let Ok(Some(line)) = lines.next_line().await {
+                self.commands.lock().unwrap().push(line.clone());
+                let (verb, arg) = line.split_once(' ').unwrap_or((&line, ""));
+                let reply = match verb {
+                    "FEAT" => "211-Features:\r\n EPSV\r\n211 End".to_string(),
+                    "USER" => {
+                        user = arg.to_string();
+                        "331 Password required".to_string()
+                    }
+                    "PASS" => {
+                        let anonymous = self.anonymous && user == "anonymous";
+                        if anonymous || self.logins.contains(&(user.as_str(), arg)) {
+                            "230 Logged in".to_string()
+                        } else {
+                            "530 Login incorrect".to_string()
+                        }
+                    }
+                    "PWD" => "257 \"/\" is the current directory".to_string(),
+                    "EPSV" => {
+                        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                        let port = listener.local_addr().unwrap().port();
+                        passive = Some(listener);
+                        format!("229 Entering Extended Passive Mode (|||{}|)", port)
+                    }
+                    "LIST" => {
+                        let listing = match arg {
+                            "/" => {
+                                "drwxr-xr-x 2 ftp ftp 4096 Jan 01 00:00 DCIM\r\n\
+                                 -rw-r--r-- 1 ftp ftp 2048 Jan 01 00:00 flight.log\r\n"
+                            }
+                            "/DCIM" => "-rw-r--r-- 1 ftp ftp 9999 Jan 01 00:00 IMG_0001.JPG\r\n",
+                            _ => "",
+                        };
+                        let Some((mut data, _)) = accept(&mut passive).await else {
+                            break;
+                        };
+                        let _ = writer.write_all(b"150 Listing\r\n").await;
+                        let _ = data.write_all(listing.as_bytes()).await;
+                        drop(data);
+                        "226 Transfer complete".to_string()
+                    }
+                    "STOR" if arg.starts_with("/DCIM/") => {
+                        let Some((mut data, _)) = accept(&mut passive).await else {
+                            break;
+                        };
+                        let _ = writer.write_all(b"150 Ready\r\n").await;
+                        let mut content = Vec::new();
+                        let _ = data.read_to_end(&mut content).await;
+                        self.files.lock().unwrap().push(arg.to_string());
+                        "226 Transfer complete".to_string()
+                    }
+                    "STOR" => "553 Permission denied".to_string(),
+                    "DELE" => {
+                        let mut files = self.files.lock().unwrap();
+                        let before = files.len();
+                        files.retain(|f| f != arg);
+                        if files.len() < before {
+                            "250 Deleted".to_string()
+                        } else {
+                            "550 No such file".to_string()
+                        }
+                    }
+                    "QUIT" => {
+                        let _ = writer.write_all(b"221 Bye\r\n").await;
+                        break;
+                    }
+                    _ => "502 Not implemented".to_string(),
+                };
+                if writer
+                    .write_all(format!("{}\r\n", reply).as_bytes())
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        }
+
+        fn stores(&self) -> usize {
+            let commands = self.commands.lock().unwrap();
+            commands.iter().filter(|c| c.starts_with("STOR ")).count()
+        }
+    }
+
+    async fn accept(passive: &mut Option<TcpListener>) -> Option<(TcpStream, SocketAddr)> {
+        passive.take()?.accept().await.ok()
+    }
+
+    fn checker(credentials: &[(&str, &str)], write_test: bool) -> FtpChecker {
+        let mut list = CredentialList::empty();
+        for (username, password) in credentials.iter().rev() {
+            list.push_front(username, password, &["ftp"]);
+        }
+        FtpChecker::with_config(FtpConfig {
+            write_test,
+            ..FtpConfig::default()
+        })
+        .with_credentials(list)
+    }
+
+    #[test]
+    fn finds_anonymous_default_login_listing_and_writable_dir() {
+        crate::test_runtime().block_on(async {
+            let (addr, stub) = Stub {
+                anonymous: true,
+                logins: vec![("admin", "admin")],
+                ..Stub::default()
+            }
+            .start()
+            .await;
+
+            let report = checker(&[("root", "root"), ("admin", "admin")], true)
+                .check(addr)
+                .await
+                .unwrap();
+
+            assert!(report.plaintext_auth);
+            assert!(!report.auth_tls);
+            assert_eq!(report.attempts, 3);
+            let anonymous = report.anonymous.as_ref().expect("anonymous login");
+            assert_eq!(anonymous.writable, ["/DCIM"]);
+            assert_eq!(report.logins.len(), 1);
+            let login = &report.logins[0];
+            assert_eq!(login.credential.display(), "admin:admin");
+            assert_eq!(login.listings.len(), 2);
+            assert_eq!(login.listings[0].path, "/");
+            assert!(login.listings[1].entries[0].ends_with("IMG_0001.JPG"));
+            assert_eq!(login.writable, ["/DCIM"]);
+            // Every marker file was removed again.
+            assert!(stub.files.lock().unwrap().is_empty());
+
+            let titles: Vec<String> = report.findings().into_iter().map(|f| f.title).collect();
+            for title in [
+                "Anonymous FTP login allowed",
+                "Default FTP credentials accepted",
+                "Anonymous FTP write access",
+                "Writable FTP directory",
+                "FTP directory listing available",
+                "FTP accepts cleartext authentication",
+            ] {
+                assert!(titles.iter().any(|t| t == title), "missing {title}");
+            }
+        });
+    }
+
+    #[test]
+    fn write_test_is_opt_in() {
+        crate::test_runtime().block_on(async {
+            let (addr, stub) = Stub {
+                anonymous: true,
+                ..Stub::default()
+            }
+            .start()
+            .await;
+
+            let report = checker(&[], false).check(addr).await.unwrap();
+            assert!(report.anonymous.unwrap().writable.is_empty());
+            assert_eq!(stub.stores(), 0);
+        });
+    }
+
+    #[test]
+    fn stops_after_max_failed_attempts() {
+        crate::test_runtime().block_on(async {
+            let (addr, _stub) = Stub::default().start().await;
+            let credentials = [("a", "1"), ("b", "2"), ("c", "3"), ("d", "4"), ("e", "5")];
+
+            let report = checker(&credentials, false).check(addr).await.unwrap();
+            assert_eq!(report.attempts, 3);
+            assert!(report.stopped_early);
+            assert!(!report.locked_out);
+
+            let unlimited = SecurityConfig {
+                max_failed_attempts: 0,
+                ..SecurityConfig::default()
+            };
+            let report = checker(&credentials, false)
+                .with_security(&unlimited)
+                .check(addr)
+                .await
+                .unwrap();
+            assert_eq!(report.attempts, 6);
+            assert!(!report.stopped_early);
+        });
+    }
+}
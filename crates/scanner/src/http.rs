@@ -0,0 +1,918 @@
+pub mod client;
+
+use crate::{Finding, ScanResult, ScanType, Severity};
+use anyhow::{Context, Result};
+use client::{HttpClient, HttpExchange, Origin};
+use data::{AnomalyType, HttpMethod, Protocol, TrafficEntry, TrafficSource};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+const BUILTIN_SIGNATURES: &str = include_str!("../signatures/http_apps.toml");
+const BUILTIN_WORDLIST: &str = include_str!("../signatures/http_paths.txt");
+
+/// Links to these are recorded by the crawler but never fetched.
+const STATIC_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "bmp", "ico", "svg", "webp", "css", "js", "map", "woff", "woff2",
+    "ttf", "eot", "mp4", "mkv", "avi", "h264", "zip", "tar", "gz", "bin", "apj", "px4", "pdf",
+];
+
+/// Mirrors the `[http]` section of `config.toml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    pub user_agent: String,
+    pub request_timeout_ms: u64,
+    /// Pages fetched by the crawler, not counting wordlist and fingerprint
+    /// requests.
+    pub max_pages: usize,
+    pub max_depth: usize,
+    /// Response bodies are cut at this size; MJPEG endpoints never end.
+    pub max_body_kb: usize,
+    pub content_discovery: bool,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            user_agent: "Mozilla/5.0 (compatible; uavred)".to_string(),
+            request_timeout_ms: 5000,
+            max_pages: 100,
+            max_depth: 3,
+            max_body_kb: 512,
+            content_discovery: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SignatureFile {
+    #[serde(default)]
+    apps: Vec<AppSignatureDef>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct AppSignatureDef {
+    name: String,
+    category: String,
+    #[serde(default)]
+    paths: Vec<String>,
+    status: Option<u16>,
+    header: Option<String>,
+    body: Option<String>,
+    title: Option<String>,
+    version: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WebAppSignature {
+    pub name: String,
+    /// autopilot, companion, gcs, camera or fleet.
+    pub category: String,
+    pub paths: Vec<String>,
+    pub status: Option<u16>,
+    pub header: Option<Regex>,
+    pub body: Option<Regex>,
+    pub title: Option<Regex>,
+    pub version: Option<String>,
+}
+
+/// A UAV or GCS web application recognised in a response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebAppMatch {
+    pub name: String,
+    pub category: String,
+    pub version: Option<String>,
+    /// Path of the response that matched.
+    pub path: String,
+    pub status: u16,
+}
+
+/// How a page came to be requested.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PageSource {
+    Crawl,
+    Robots,
+    Wordlist,
+    Fingerprint,
+    /// Request for a path that cannot exist, to learn the server's 404.
+    Baseline,
+}
+
+impl std::fmt::Display for PageSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PageSource::Crawl => write!(f, "crawl"),
+            PageSource::Robots => write!(f, "robots.txt"),
+            PageSource::Wordlist => write!(f, "wordlist"),
+            PageSource::Fingerprint => write!(f, "fingerprint"),
+            PageSource::Baseline => write!(f, "soft-404 baseline"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpPage {
+    pub path: String,
+    pub status: u16,
+    pub title: Option<String>,
+    pub content_type: Option<String>,
+    pub length: usize,
+    pub source: PageSource,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpReconReport {
+    /// `http(s)://host:port` the recon ran against.
+    pub origin: String,
+    /// Every path that answered with something other than a (soft) 404.
+    pub pages: Vec<HttpPage>,
+    pub apps: Vec<WebAppMatch>,
+    /// Every request made, including misses, numbered from 1 in order.
+    pub traffic: Vec<TrafficEntry>,
+}
+
+impl HttpReconReport {
+    pub fn findings(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for app in &self.apps {
+            let url = format!("{}{}", self.origin, app.path);
+            let (severity, access) = if (200..300).contains(&app.status) {
+                (Severity::Medium, "is reachable without authentication")
+            } else {
+                (Severity::Low, "is exposed behind a login")
+            };
+            let name = match &app.version {
+                Some(version) => format!("{} {}", app.name, version),
+                None => app.name.clone(),
+            };
+            findings.push(
+                Finding::new(
+                    severity,
+                    format!("UAV web interface exposed: {}", app.name),
+                    format!(
+                        "{} ({}) at {} {}. Web interfaces on the vehicle or ground \
+                         segment often allow configuration, parameter or firmware \
+                         changes and should not be reachable from untrusted networks.",
+                        name, app.category, url, access
+                    ),
+                )
+                .with_affected(url)
+                .with_evidence(format!("GET {} -> {}", app.path, app.status)),
+            );
+        }
+
+        for page in &self.pages {
+            if page.status == 200
+                && page
+                    .title
+                    .as_deref()
+                    .is_some_and(|t| t.starts_with("Index of"))
+            {
+                let url = format!("{}{}", self.origin, page.path);
+                findings.push(
+                    Finding::new(
+                        Severity::Low,
+                        "Directory listing enabled".to_string(),
+                        format!("{} returns a directory index.", url),
+                    )
+                    .with_affected(url),
+                );
+            }
+        }
+        findings
+    }
+
+    pub fn scan_result(&self) -> ScanResult {
+        ScanResult {
+            scan_type: ScanType::Network,
+            target: self.origin.clone(),
+            findings: self.findings(),
+            incomplete: false,
+        }
+    }
+}
+
+/// Crawls a web interface, runs wordlist content discovery and fingerprints
+/// UAV/GCS web applications. Every request is kept as a `TrafficEntry`.
+pub struct HttpRecon {
+    config: HttpConfig,
+    signatures: Vec<WebAppSignature>,
+    wordlist: Vec<String>,
+    asset: Option<(String, String)>,
+}
+
+impl HttpRecon {
+    pub fn new() -> Self {
+        Self::with_config(HttpConfig::default())
+    }
+
+    pub fn with_config(config: HttpConfig) -> Self {
+        let mut recon = Self {
+            config,
+            signatures: Vec::new(),
+            wordlist: Vec::new(),
+            asset: None,
+        };
+        recon
+            .add_signatures(BUILTIN_SIGNATURES)
+            .expect("built-in HTTP signatures must be valid");
+        recon.add_wordlist(BUILTIN_WORDLIST);
+        recon
+    }
+
+    /// Tags recorded traffic with the asset it belongs to.
+    pub fn with_asset(
+        mut self,
+        asset_id: impl Into<String>,
+        asset_name: impl Into<String>,
+    ) -> Self {
+        self.asset = Some((asset_id.into(), asset_name.into()));
+        self
+    }
+
+    /// Loads extra application fingerprints from a TOML file. User
+    /// signatures take precedence over the built-in ones.
+    pub fn load_signatures(&mut self, path: &Path) -> Result<usize> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read signature file {}", path.display()))?;
+        self.add_signatures(&content)
+            .with_context(|| format!("invalid signature file {}", path.display()))
+    }
+
+    pub fn add_signatures(&mut self, content: &str) -> Result<usize> {
+        let file: SignatureFile = toml::from_str(content)?;
+        let compile = |name: &str, pattern: Option<String>| {
+            pattern
+                .map(|p| {
+                    Regex::new(&p).with_context(|| format!("invalid pattern for {}: {}", name, p))
+                })
+                .transpose()
+        };
+
+        let mut compiled = Vec::with_capacity(file.apps.len());
+        for def in file.apps {
+            compiled.push(WebAppSignature {
+                header: compile(&def.name, def.header)?,
+                body: compile(&def.name, def.body)?,
+                title: compile(&def.name, def.title)?,
+                name: def.name,
+                category: def.category,
+                paths: def.paths,
+                status: def.status,
+                version: def.version,
+            });
+        }
+
+        let count = compiled.len();
+        // Loaded later means more specific, so it is matched first.
+        compiled.append(&mut self.signatures);
+        self.signatures = compiled;
+        Ok(count)
+    }
+
+    pub fn signatures(&self) -> &[WebAppSignature] {
+        &self.signatures
+    }
+
+    pub fn load_wordlist(&mut self, path: &Path) -> Result<usize> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read wordlist {}", path.display()))?;
+        Ok(self.add_wordlist(&content))
+    }
+
+    /// Appends paths (one per line, `#` comments) to the discovery wordlist.
+    pub fn add_wordlist(&mut self, content: &str) -> usize {
+        let before = self.wordlist.len();
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let path = if line.starts_with('/') {
+                line.to_string()
+            } else {
+                format!("/{}", line)
+            };
+            if !self.wordlist.contains(&path) {
+                self.wordlist.push(path);
+            }
+        }
+        self.wordlist.len() - before
+    }
+
+    /// Runs the recon against `url` (`http://host:port/start`, or a bare
+    /// `host[:port]`).
+    pub async fn run(&self, url: &str) -> Result<HttpReconReport> {
+        let (origin, start) = Origin::parse(url)?;
+        tracing::info!("Starting HTTP recon on {}", origin);
+        let client = HttpClient::new(
+            self.config.user_agent.clone(),
+            Duration::from_millis(self.config.request_timeout_ms),
+            self.config.max_body_kb * 1024,
+        );
+        let mut run = ReconRun {
+            recon: self,
+            client,
+            origin,
+            visited: HashSet::new(),
+            soft_404: None,
+            report: HttpReconReport {
+                origin: String::new(),
+                pages: Vec::new(),
+                apps: Vec::new(),
+                traffic: Vec::new(),
+            },
+        };
+        run.report.origin = run.origin.to_string();
+
+        // The first request doubles as a liveness check: if it fails there
+        // is no point walking the wordlist.
+        let first = run.fetch(&start, PageSource::Crawl).await?;
+        run.calibrate_soft_404().await;
+        run.crawl(first).await;
+        if self.config.content_discovery {
+            run.discover(&self.wordlist, PageSource::Wordlist).await;
+        }
+        let fingerprint_paths: Vec<String> = self
+            .signatures
+            .iter()
+            .flat_map(|s| s.paths.iter().cloned())
+            .collect();
+        run.discover(&fingerprint_paths, PageSource::Fingerprint)
+            .await;
+
+        tracing::info!(
+            "HTTP recon of {}: {} requests, {} pages, {} applications",
+            run.report.origin,
+            run.report.traffic.len(),
+            run.report.pages.len(),
+            run.report.apps.len()
+        );
+        Ok(run.report)
+    }
+
+    fn match_apps(&self, exchange: &HttpExchange, title: Option<&str>) -> Vec<WebAppMatch> {
+        let response = &exchange.response;
+        let headers: String = response
+            .headers
+            .iter()
+            .map(|(n, v)| format!("{}: {}\n", n, v))
+            .collect();
+        let body = response.body_text();
+
+        self.signatures
+            .iter()
+            .filter_map(|sig| {
+                if sig.status.is_some_and(|s| s != response.status) {
+                    return None;
+                }
+                if sig.header.is_none() && sig.body.is_none() && sig.title.is_none() {
+                    return None;
+                }
+                let mut version = None;
+                for (pattern, text) in [
+                    (&sig.header, Some(headers.as_str())),
+                    (&sig.body, Some(body.as_str())),
+                    (&sig.title, title),
+                ] {
+                    let Some(pattern) = pattern else {
+                        continue;
+                    };
+                    let caps = pattern.captures(text?)?;
+                    if let Some(template) = &sig.version {
+                        let mut out = String::new();
+                        caps.expand(template, &mut out);
+                        let out = out.trim().to_string();
+                        if !out.is_empty() {
+                            version = Some(out);
+                        }
+                    }
+                }
+                Some(WebAppMatch {
+                    name: sig.name.clone(),
+                    category: sig.category.clone(),
+                    version,
+                    path: exchange.path.clone(),
+                    status: response.status,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Default for HttpRecon {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// State of one `HttpRecon::run`.
+struct ReconRun<'a> {
+    recon: &'a HttpRecon,
+    client: HttpClient,
+    origin: Origin,
+    visited: HashSet<String>,
+    /// Status and body length the server returns for a path that cannot
+    /// exist, for servers that answer 200 to everything.
+    soft_404: Option<(u16, usize)>,
+    report: HttpReconReport,
+}
+
+impl ReconRun<'_> {
+    /// Requests `path`, records the traffic and returns the exchange.
+    async fn fetch(&mut self, path: &str, source: PageSource) -> Result<HttpExchange> {
+        self.visited.insert(path.to_string());
+        let id = self.report.traffic.len() as i64 + 1;
+        let started = Instant::now();
+        let result = self.client.get(&self.origin, path).await;
+        let exchange = match result {
+            Ok(exchange) => exchange,
+            Err(e) => {
+                tracing::debug!("GET {}{} failed: {}", self.origin, path, e);
+                let mut entry = failed_traffic_entry(id, &self.origin, path, source, &e);
+                entry.duration_ms = started.elapsed().as_millis() as u64;
+                self.record(entry);
+                return Err(e);
+            }
+        };
+        self.record(traffic_entry(id, &exchange, source));
+
+        if source == PageSource::Baseline {
+            return Ok(exchange);
+        }
+
+        let title = exchange
+            .response
+            .is_html()
+            .then(|| html_title(&exchange.response.body_text()))
+            .flatten();
+        for app in self.recon.match_apps(&exchange, title.as_deref()) {
+            if !self.report.apps.iter().any(|a| a.name == app.name) {
+                tracing::info!("{} identified at {}{}", app.name, self.origin, app.path);
+                self.report.apps.push(app);
+            }
+        }
+        if self.is_hit(&exchange) && !self.is_duplicate(path, &exchange) {
+            self.report.pages.push(HttpPage {
+                path: path.to_string(),
+                status: exchange.response.status,
+                title,
+                content_type: exchange.response.header("content-type").map(str::to_string),
+                length: exchange.response.body.len(),
+                source,
+            });
+        }
+        Ok(exchange)
+    }
+
+    fn record(&mut self, mut entry: TrafficEntry) {
+        if let Some((asset_id, asset_name)) = &self.recon.asset {
+            entry.asset_id = asset_id.clone();
+            entry.asset_name = asset_name.clone();
+        }
+        self.report.traffic.push(entry);
+    }
+
+    async fn calibrate_soft_404(&mut self) {
+        let nonce = chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        let probe = format!("/uavred-{:x}-404", nonce);
+        if let Ok(exchange) = self.fetch(&probe, PageSource::Baseline).await {
+            let response = &exchange.response;
+            if response.status != 404 {
+                self.soft_404 = Some((response.status, response.body.len()));
+            }
+        }
+    }
+
+    fn is_hit(&self, exchange: &HttpExchange) -> bool {
+        let response = &exchange.response;
+        if matches!(response.status, 404 | 410) || response.status >= 500 {
+            return false;
+        }
+        match self.soft_404 {
+            // Pages echoing the path differ by a few bytes from the template.
+            Some((status, length)) => {
+                status != response.status || response.body.len().abs_diff(length) > 32
+            }
+            None => true,
+        }
+    }
+
+    /// Query-string variants (`/?action=stream`) that return the same page
+    /// as the bare path are not worth listing again.
+    fn is_duplicate(&self, path: &str, exchange: &HttpExchange) -> bool {
+        let base = path.split('?').next().unwrap_or(path);
+        self.report.pages.iter().any(|page| {
+            page.path.split('?').next() == Some(base)
+                && page.status == exchange.response.status
+                && page.length == exchange.response.body.len()
+        })
+    }
+
+    async fn crawl(&mut self, first: HttpExchange) {
+        let mut queue = VecDeque::new();
+        if !self.visited.contains("/robots.txt") {
+            queue.push_back(("/robots.txt".to_string(), 1, PageSource::Robots));
+        }
+        self.enqueue_links(&first, 1, &mut queue);
+
+        let mut pages = 1;
+        while let Some((path, depth, source)) = queue.pop_front() {
+            if pages >= self.recon.config.max_pages {
+                break;
+            }
+            if self.visited.contains(&path) {
+                continue;
+            }
+            let Ok(exchange) = self.fetch(&path, source).await else {
+                continue;
+            };
+            pages += 1;
+
+            if path == "/robots.txt" {
+                if exchange.response.status == 200 {
+                    for path in robots_paths(&exchange.response.body_text()) {
+                        queue.push_back((path, 1, PageSource::Robots));
+                    }
+                }
+            } else if depth < self.recon.config.max_depth {
+                self.enqueue_links(&exchange, depth + 1, &mut queue);
+            }
+        }
+    }
+
+    fn enqueue_links(
+        &self,
+        exchange: &HttpExchange,
+        depth: usize,
+        queue: &mut VecDeque<(String, usize, PageSource)>,
+    ) {
+        let response = &exchange.response;
+        let mut links = Vec::new();
+        if let Some(location) = response.header("location") {
+            links.push(location.to_string());
+        }
+        if response.is_html() {
+            links.extend(extract_links(&response.body_text()));
+        }
+        for link in links {
+            let Some(path) = resolve_link(&self.origin, &exchange.path, &link) else {
+                continue;
+            };
+            if self.visited.contains(&path)
+                || is_static(&path)
+                || queue.iter().any(|(p, _, _)| *p == path)
+            {
+                continue;
+            }
+            queue.push_back((path, depth, PageSource::Crawl));
+        }
+    }
+
+    async fn discover(&mut self, paths: &[String], source: PageSource) {
+        for path in paths {
+            if self.visited.contains(path) {
+                continue;
+            }
+            let _ = self.fetch(path, source).await;
+        }
+    }
+}
+
+/// Converts an exchange into a traffic log entry. `id` is local to the recon
+/// run; storage assigns the final one.
+pub fn traffic_entry(id: i64, exchange: &HttpExchange, source: PageSource) -> TrafficEntry {
+    let response = &exchange.response;
+    let mut entry = request_entry(
+        id,
+        &exchange.origin,
+        &exchange.method,
+        &exchange.path,
+        source,
+    );
+    entry.status = response.status;
+    entry.status_text = (!response.reason.is_empty()).then(|| response.reason.clone());
+    entry.duration_ms = exchange.duration.as_millis() as u64;
+    entry.request_size = exchange.request_raw.len() as u64;
+    entry.response_size = response.raw.len() as u64;
+    entry.target_ip = exchange.peer.clone();
+    entry.request_raw = Some(String::from_utf8_lossy(&exchange.request_raw).into_owned());
+    entry.request_headers = exchange.request_headers.clone();
+    entry.response_raw = Some(String::from_utf8_lossy(&response.raw).into_owned());
+    entry.response_headers = response.headers.clone();
+    entry.response_body = (!response.body.is_empty()).then(|| response.body_text());
+    if response.status >= 500 {
+        entry.anomalies.push(AnomalyType::StatusAnomaly);
+    }
+    if response.truncated {
+        entry.anomalies.push(AnomalyType::SizeAnomaly);
+        entry.anomaly_description = Some("response body truncated".to_string());
+    }
+    entry
+}
+
+/// A GET that got no response (connection refused or reset, timeout, TLS
+/// failure). Status 0 and the error as the anomaly description.
+pub fn failed_traffic_entry(
+    id: i64,
+    origin: &Origin,
+    path: &str,
+    source: PageSource,
+    error: &anyhow::Error,
+) -> TrafficEntry {
+    let mut entry = request_entry(id, origin, "GET", path, source);
+    entry.anomalies.push(AnomalyType::ResponseAnomaly);
+    entry.anomaly_description = Some(format!("no response: {:#}", error));
+    entry
+}
+
+/// The request side of an entry, shared by answered and failed requests.
+fn request_entry(
+    id: i64,
+    origin: &Origin,
+    method: &str,
+    target: &str,
+    source: PageSource,
+) -> TrafficEntry {
+    let mut entry = TrafficEntry::new(id, chrono::Utc::now().to_rfc3339());
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), Some(query.to_string())),
+        None => (target.to_string(), None),
+    };
+    entry.extension = path
+        .rsplit('/')
+        .next()
+        .and_then(|segment| segment.rsplit_once('.'))
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .filter(|ext| !ext.is_empty());
+    entry.method = Some(http_method(method));
+    entry.path = path;
+    entry.query = query;
+    entry.host = origin.host.clone();
+    entry.port = origin.port;
+    entry.tls = origin.tls;
+    entry.protocol = if origin.tls {
+        Protocol::HTTPS
+    } else {
+        Protocol::HTTP
+    };
+    entry.source = TrafficSource::Workflow;
+    entry.notes = Some(format!("http recon: {}", source));
+    entry
+}
+
+fn http_method(method: &str) -> HttpMethod {
+    match method.to_ascii_uppercase().as_str() {
+        "GET" => HttpMethod::GET,
+        "POST" => HttpMethod::POST,
+        "PUT" => HttpMethod::PUT,
+        "DELETE" => HttpMethod::DELETE,
+        "PATCH" => HttpMethod::PATCH,
+        "HEAD" => HttpMethod::HEAD,
+        "OPTIONS" => HttpMethod::OPTIONS,
+        "CONNECT" => HttpMethod::CONNECT,
+        "TRACE" => HttpMethod::TRACE,
+        _ => HttpMethod::Unknown,
+    }
+}
+
+fn html_title(body: &str) -> Option<String> {
+    static TITLE: OnceLock<Regex> = OnceLock::new();
+    let re = TITLE.get_or_init(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap());
+    let title = re.captures(body)?[1]
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!title.is_empty()).then_some(title)
+}
+
+fn extract_links(body: &str) -> Vec<String> {
+    static LINK: OnceLock<Regex> = OnceLock::new();
+    let re = LINK.get_or_init(|| {
+        Regex::new(r#"(?i)\b(?:href|src|action)\s*=\s*["']([^"'<>\s]+)["']"#).unwrap()
+    });
+    re.captures_iter(body)
+        .map(|caps| caps[1].replace("&amp;", "&"))
+        .collect()
+}
+
+/// Paths named in `Disallow`, `Allow` and `Sitemap` lines; wildcards are
+/// cut off.
+fn robots_paths(body: &str) -> Vec<String> {
+    body.lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            let key = key.trim().to_ascii_lowercase();
+            if !matches!(key.as_str(), "disallow" | "allow" | "sitemap") {
+                return None;
+            }
+            let value = value.split('#').next()?.trim();
+            let value = match value.find(['*', '$']) {
+                Some(i) => &value[..i],
+                None => value,
+            };
+            value.starts_with('/').then(|| value.to_string())
+        })
+        .filter(|path| path != "/")
+        .collect()
+}
+
+/// Resolves `link` found on `base` to a same-origin path, or `None` for
+/// links to other hosts and non-HTTP schemes.
+fn resolve_link(origin: &Origin, base: &str, link: &str) -> Option<String> {
+    let link = link.split('#').next()?.trim();
+    if link.is_empty() {
+        return None;
+    }
+
+    let target = if let Some(rest) = link.strip_prefix("//") {
+        let scheme = if origin.tls { "https" } else { "http" };
+        let (other, path) = Origin::parse(&format!("{}://{}", scheme, rest)).ok()?;
+        (other == *origin).then_some(path)?
+    } else if link.contains("://") {
+        let (other, path) = Origin::parse(link).ok()?;
+        (other == *origin).then_some(path)?
+    } else if link.starts_with('/') {
+        link.to_string()
+    } else if link.split_once(':').is_some_and(|(scheme, _)| {
+        scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '+')
+    }) {
+        // mailto:, javascript:, data:, tel:, ...
+        return None;
+    } else if link.starts_with('?') {
+        format!("{}{}", base.split('?').next().unwrap_or("/"), link)
+    } else {
+        let base_path = base.split('?').next().unwrap_or("/");
+        let dir = &base_path[..base_path.rfind('/').map_or(0, |i| i + 1)];
+        format!("{}{}", if dir.is_empty() { "/" } else { dir }, link)
+    };
+    Some(normalize_path(&target))
+}
+
+/// Removes `.` and `..` segments, keeping the query and a trailing slash.
+fn normalize_path(path: &str) -> String {
+    let (path, query) = match path.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (path, None),
+    };
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    let mut normalized = format!("/{}", segments.join("/"));
+    if path.ends_with('/') && !segments.is_empty() {
+        normalized.push('/');
+    }
+    if let Some(query) = query {
+        normalized.push('?');
+        normalized.push_str(query);
+    }
+    normalized
+}
+
+fn is_static(path: &str) -> bool {
+    let path = path.split('?').next().unwrap_or_default();
+    path.rsplit('/')
+        .next()
+        .and_then(|segment| segment.rsplit_once('.'))
+        .is_some_and(|(_, ext)| STATIC_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// Serves a small autopilot web UI on 127.0.0.1 and returns its origin.
+    async fn stub_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream));
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    async fn serve(mut stream: TcpStream) {
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            match stream.read(&mut buf).await {
+                Ok(n) if n > 0 => request.extend_from_slice(&buf[..n]),
+                _ => return,
+            }
+        }
+        let request = String::from_utf8_lossy(&request);
+        let path = request.split_whitespace().nth(1).unwrap_or("/");
+        let (status, content_type, body) = match path {
+            "/" => (
+                "200 OK",
+                "text/html",
+                "<html><head><title>Vehicle</title></head><body>\
+                 <a href=\"/status.html\">Status</a></body></html>",
+            ),
+            "/status.html" => (
+                "200 OK",
+                "text/html",
+                "<html><head><title>BlueOS</title></head><body>\
+                 <a href=\"/reset\">Reset</a></body></html>",
+            ),
+            "/config.json" => ("200 OK", "application/json", "{\"wifi_password\": \"x\"}"),
+            "/mavlink/vehicles" => ("200 OK", "application/json", "[1]"),
+            // Drops the connection without answering.
+            "/reset" => return,
+            _ => ("404 Not Found", "text/plain", "not found"),
+        };
+        let response = format!(
+            "HTTP/1.1 {}\r\nServer: ArduPilot\r\nContent-Type: {}\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            content_type,
+            body.len(),
+            body
+        );
+        let _ = stream.write_all(response.as_bytes()).await;
+    }
+
+    #[test]
+    fn crawls_discovers_and_identifies_a_local_server() {
+        crate::test_runtime().block_on(async {
+            let origin = stub_server().await;
+            let recon = HttpRecon::new().with_asset("asset-1", "companion");
+            let report = recon.run(&origin).await.unwrap();
+
+            let page = |path: &str| report.pages.iter().find(|p| p.path == path);
+            assert_eq!(page("/").unwrap().source, PageSource::Crawl);
+            let status = page("/status.html").expect("crawled page");
+            assert_eq!(status.source, PageSource::Crawl);
+            assert_eq!(status.title.as_deref(), Some("BlueOS"));
+            assert_eq!(page("/config.json").unwrap().source, PageSource::Wordlist);
+            assert!(page("/admin").is_none(), "404s are not pages");
+
+            let apps: Vec<&str> = report.apps.iter().map(|a| a.name.as_str()).collect();
+            for app in ["ArduPilot web server", "BlueOS", "mavlink2rest"] {
+                assert!(apps.contains(&app), "{app} not identified in {apps:?}");
+            }
+
+            // Every request is recorded, the failed one without a response.
+            for (i, entry) in report.traffic.iter().enumerate() {
+                assert_eq!(entry.id, i as i64 + 1);
+                assert_eq!(entry.asset_id, "asset-1");
+            }
+            let reset = report
+                .traffic
+                .iter()
+                .find(|e| e.path == "/reset")
+                .expect("failed request recorded");
+            assert_eq!(reset.status, 0);
+            assert!(reset.response_raw.is_none());
+            assert!(reset.anomaly_description.is_some());
+            assert!(report
+                .traffic
+                .iter()
+                .any(|e| e.path == "/admin" && e.status == 404));
+        });
+    }
+
+    #[test]
+    fn failed_first_request_is_recorded() {
+        crate::test_runtime().block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let origin = format!("http://{}", listener.local_addr().unwrap());
+            drop(listener);
+
+            let (origin, _) = Origin::parse(&origin).unwrap();
+            let recon = HttpRecon::new();
+            let mut run = ReconRun {
+                recon: &recon,
+                client: HttpClient::new(String::new(), Duration::from_secs(2), 1024),
+                origin,
+                visited: HashSet::new(),
+                soft_404: None,
+                report: HttpReconReport {
+                    origin: String::new(),
+                    pages: Vec::new(),
+                    apps: Vec::new(),
+                    traffic: Vec::new(),
+                },
+            };
+            assert!(run.fetch("/", PageSource::Crawl).await.is_err());
+            assert_eq!(run.report.traffic.len(), 1);
+            assert_eq!(run.report.traffic[0].status, 0);
+        });
+    }
+}
@@ -0,0 +1,359 @@
+use crate::tls;
+use anyhow::{anyhow, bail, Context, Result};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Instant};
+
+/// Scheme, host and port of a web application, e.g. `https://10.0.0.5:8443`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Origin {
+    pub tls: bool,
+    pub host: String,
+    pub port: u16,
+}
+
+impl Origin {
+    /// Parses `http://host[:port][/path]`, `https://...` or a bare
+    /// `host[:port]` (plain HTTP, TLS if the port is 443 or 8443). Returns the
+    /// origin and the path, `/` if none was given.
+    pub fn parse(url: &str) -> Result<(Self, String)> {
+        let (scheme, rest) = match url.split_once("://") {
+            Some((scheme, rest)) => (Some(scheme.to_ascii_lowercase()), rest),
+            None => (None, url),
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], rest[i..].to_string()),
+            None => (rest, "/".to_string()),
+        };
+        if authority.is_empty() {
+            bail!("missing host in URL: {}", url);
+        }
+
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && !port.contains(']') => {
+                let port = port
+                    .parse::<u16>()
+                    .map_err(|_| anyhow!("invalid port in URL: {}", url))?;
+                (host, Some(port))
+            }
+            _ => (authority, None),
+        };
+        let tls = match scheme.as_deref() {
+            Some("https") => true,
+            Some("http") => false,
+            Some(other) => bail!("unsupported scheme {}", other),
+            None => matches!(port, Some(443 | 8443)),
+        };
+        let port = port.unwrap_or(if tls { 443 } else { 80 });
+
+        Ok((
+            Self {
+                tls,
+                host: host.trim_matches(|c| c == '[' || c == ']').to_string(),
+                port,
+            },
+            path,
+        ))
+    }
+
+    /// `Host` header value; the port is omitted when it is the default.
+    pub fn host_header(&self) -> String {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+        if self.port == if self.tls { 443 } else { 80 } {
+            host
+        } else {
+            format!("{}:{}", host, self.port)
+        }
+    }
+
+    fn connect_address(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+impl std::fmt::Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let scheme = if self.tls { "https" } else { "http" };
+        write!(f, "{}://{}", scheme, self.host_header())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub reason: String,
+    pub headers: Vec<(String, String)>,
+    /// Body after de-chunking, cut at the client's body limit.
+    pub body: Vec<u8>,
+    /// The response exactly as received, up to the body limit.
+    pub raw: Vec<u8>,
+    pub truncated: bool,
+}
+
+impl HttpResponse {
+    /// First header named `name`, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn body_text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    pub fn is_html(&self) -> bool {
+        self.header("content-type")
+            .is_some_and(|ct| ct.to_ascii_lowercase().contains("html"))
+    }
+}
+
+/// One request and its response, with the bytes actually sent.
+#[derive(Debug, Clone)]
+pub struct HttpExchange {
+    pub origin: Origin,
+    pub method: String,
+    pub path: String,
+    pub request_headers: Vec<(String, String)>,
+    pub request_raw: Vec<u8>,
+    pub response: HttpResponse,
+    pub duration: Duration,
+    /// Address of the peer we connected to.
+    pub peer: Option<String>,
+}
+
+/// Minimal HTTP/1.1 client: one connection per request with
+/// `Connection: close`, no redirects, no compression. Certificates are not
+/// validated, and the raw bytes on the wire are kept for the traffic log.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    user_agent: String,
+    timeout: Duration,
+    max_body_bytes: usize,
+}
+
+impl HttpClient {
+    pub fn new(user_agent: impl Into<String>, timeout: Duration, max_body_bytes: usize) -> Self {
+        Self {
+            user_agent: user_agent.into(),
+            timeout,
+            max_body_bytes,
+        }
+    }
+
+    pub async fn get(&self, origin: &Origin, path: &str) -> Result<HttpExchange> {
+        self.send(origin, "GET", path, &[], None).await
+    }
+
+    pub async fn send(
+        &self,
+        origin: &Origin,
+        method: &str,
+        path: &str,
+        extra_headers: &[(String, String)],
+        body: Option<&[u8]>,
+    ) -> Result<HttpExchange> {
+        let mut headers = vec![
+            ("Host".to_string(), origin.host_header()),
+            ("User-Agent".to_string(), self.user_agent.clone()),
+            ("Accept".to_string(), "*/*".to_string()),
+            ("Accept-Encoding".to_string(), "identity".to_string()),
+            ("Connection".to_string(), "close".to_string()),
+        ];
+        for (name, value) in extra_headers {
+            headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+            headers.push((name.clone(), value.clone()));
+        }
+        if let Some(body) = body {
+            headers.push(("Content-Length".to_string(), body.len().to_string()));
+        }
+
+        let mut request_raw = format!("{} {} HTTP/1.1\r\n", method, path).into_bytes();
+        for (name, value) in &headers {
+            request_raw.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+        }
+        request_raw.extend_from_slice(b"\r\n");
+        if let Some(body) = body {
+            request_raw.extend_from_slice(body);
+        }
+
+        let started = Instant::now();
+        let deadline = started + self.timeout;
+        let address = origin.connect_address();
+        let stream = timeout(self.timeout, TcpStream::connect(&address))
+            .await
+            .map_err(|_| anyhow!("connection to {} timed out", address))?
+            .with_context(|| format!("failed to connect to {}", address))?;
+        let peer = stream.peer_addr().ok().map(|a| a.ip().to_string());
+
+        let raw = if origin.tls {
+            let mut stream = tls::connect(stream, &origin.host).await?;
+            self.exchange(&mut stream, &request_raw, deadline).await?
+        } else {
+            let mut stream = stream;
+            self.exchange(&mut stream, &request_raw, deadline).await?
+        };
+        let duration = started.elapsed();
+
+        let head_only = method.eq_ignore_ascii_case("HEAD");
+        let response = parse_response(raw, head_only, self.max_body_bytes)
+            .with_context(|| format!("invalid HTTP response from {}", origin))?;
+
+        Ok(HttpExchange {
+            origin: origin.clone(),
+            method: method.to_string(),
+            path: path.to_string(),
+            request_headers: headers,
+            request_raw,
+            response,
+            duration,
+            peer,
+        })
+    }
+
+    /// Writes the request and reads until the server closes, the deadline
+    /// passes or the body limit is reached.
+    async fn exchange<S>(
+        &self,
+        stream: &mut S,
+        request: &[u8],
+        deadline: Instant,
+    ) -> Result<Vec<u8>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        tokio::time::timeout_at(deadline, stream.write_all(request))
+            .await
+            .map_err(|_| anyhow!("timed out sending request"))??;
+
+        // Headers are allowed on top of the body limit.
+        let limit = self.max_body_bytes + 64 * 1024;
+        let mut raw = Vec::new();
+        let mut buf = [0u8; 8192];
+        while raw.len() < limit {
+            match tokio::time::timeout_at(deadline, stream.read(&mut buf)).await {
+                Ok(Ok(0)) => break,
+                Ok(Ok(n)) => raw.extend_from_slice(&buf[..n]),
+                // TLS peers that skip close_notify end up here.
+                Ok(Err(_)) if !raw.is_empty() => break,
+                Ok(Err(e)) => return Err(e.into()),
+                Err(_) if raw.is_empty() => bail!("timed out waiting for a response"),
+                Err(_) => break,
+            }
+            if response_complete(&raw) {
+                break;
+            }
+        }
+        Ok(raw)
+    }
+}
+
+/// True once a `Content-Length` body has fully arrived, so servers that
+/// ignore `Connection: close` do not hold us until the deadline.
+fn response_complete(raw: &[u8]) -> bool {
+    let Some(head_end) = find(raw, b"\r\n\r\n") else {
+        return false;
+    };
+    let head = String::from_utf8_lossy(&raw[..head_end]);
+    let content_length = head.lines().skip(1).find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("content-length")
+            .then(|| value.trim().parse::<usize>().ok())
+            .flatten()
+    });
+    content_length.is_some_and(|len| raw.len() >= head_end + 4 + len)
+}
+
+fn parse_response(raw: Vec<u8>, head_only: bool, max_body: usize) -> Result<HttpResponse> {
+    let head_end =
+        find(&raw, b"\r\n\r\n").ok_or_else(|| anyhow!("no end of headers in response"))?;
+    let head = String::from_utf8_lossy(&raw[..head_end]).into_owned();
+    let mut lines = head.split("\r\n");
+
+    let status_line = lines.next().unwrap_or_default();
+    let mut parts = status_line.splitn(3, ' ');
+    let version = parts.next().unwrap_or_default();
+    if !version.starts_with("HTTP/") {
+        bail!("not an HTTP status line: {}", status_line);
+    }
+    let status = parts
+        .next()
+        .and_then(|s| s.parse::<u16>().ok())
+        .ok_or_else(|| anyhow!("invalid status line: {}", status_line))?;
+    let reason = parts.next().unwrap_or_default().to_string();
+
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            Some((name.trim().to_string(), value.trim().to_string()))
+        })
+        .collect();
+
+    let mut body = if head_only || status == 204 || status == 304 || status / 100 == 1 {
+        Vec::new()
+    } else {
+        raw[head_end + 4..].to_vec()
+    };
+    let chunked = headers.iter().any(|(n, v)| {
+        n.eq_ignore_ascii_case("transfer-encoding") && v.to_ascii_lowercase().contains("chunked")
+    });
+    if chunked {
+        body = dechunk(&body);
+    } else if let Some(len) = headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.parse::<usize>().ok())
+    {
+        body.truncate(len);
+    }
+    let truncated = body.len() > max_body;
+    body.truncate(max_body);
+
+    Ok(HttpResponse {
+        status,
+        reason,
+        headers,
+        body,
+        raw,
+        truncated,
+    })
+}
+
+/// Decodes a chunked body, keeping whatever arrived before a malformed or
+/// missing chunk.
+fn dechunk(mut data: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    while let Some(line_end) = find(data, b"\r\n") {
+        let size_field = String::from_utf8_lossy(&data[..line_end]);
+        let size_hex = size_field.split(';').next().unwrap_or_default().trim();
+        let Ok(size) = usize::from_str_radix(size_hex, 16) else {
+            break;
+        };
+        if size == 0 {
+            break;
+        }
+        let start = line_end + 2;
+        let end = (start + size).min(data.len());
+        body.extend_from_slice(&data[start..end]);
+        if end + 2 > data.len() {
+            break;
+        }
+        data = &data[end + 2..];
+    }
+    body
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
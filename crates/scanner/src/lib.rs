@@ -1,7 +1,18 @@
 pub mod network;
+pub mod param_audit;
+pub mod pipeline;
 pub mod protocol;
+pub mod credentials;
 pub mod firmware;
+pub mod ftp;
+pub mod http;
+pub mod rtsp;
+pub mod service;
+pub mod telnet;
+pub mod tls;
 
+use anyhow::{bail, Context};
+use data::{DetectionLocation, VulnData};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,6 +20,44 @@ pub struct ScanResult {
     pub scan_type: ScanType,
     pub target: String,
     pub findings: Vec<Finding>,
+    /// Set when probes were abandoned at their timeout, so open ports or
+    /// services may be missing from `findings`.
+    #[serde(default)]
+    pub incomplete: bool,
+}
+
+impl ScanResult {
+    /// The findings as `VulnData` for the Vulns view, with repeated
+    /// findings merged. Use a `pipeline::VulnPipeline` directly to merge
+    /// several results or update vulnerabilities already recorded.
+    pub fn vulnerabilities(&self) -> Vec<VulnData> {
+        let mut pipeline = pipeline::VulnPipeline::new();
+        pipeline.add_result(self);
+        pipeline.into_vulns()
+    }
+}
+
+/// Mirrors the `[security]` section of `config.toml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SecurityConfig {
+    pub require_authorization: bool,
+    pub log_all_actions: bool,
+    pub encrypt_sensitive_data: bool,
+    /// Rejected logins a credential checker accepts per service before it
+    /// stops, so lockout policies are not tripped; 0 tries the whole list.
+    pub max_failed_attempts: usize,
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        Self {
+            require_authorization: true,
+            log_all_actions: true,
+            encrypt_sensitive_data: true,
+            max_failed_attempts: 3,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,18 +67,124 @@ pub enum ScanType {
     Firmware,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Finding {
     pub severity: Severity,
     pub title: String,
     pub description: String,
     pub cve: Option<String>,
+    /// Where the finding was observed, e.g. `10.0.0.5:22/tcp`.
+    pub affected: Option<String>,
+    /// Raw observations backing the finding, e.g. hex dumps of frames.
+    #[serde(default)]
+    pub evidence: Vec<String>,
+    #[serde(default)]
+    pub cwe: Option<String>,
+    #[serde(default)]
+    pub remediation: Option<String>,
+    /// Component, file and function the finding was detected in, for
+    /// findings taken from analysers that record them.
+    #[serde(default)]
+    pub location: Option<DetectionLocation>,
+}
+
+/// Findings use the vulnerability database's scale, which has no Info
+/// level.
+pub use core::vuln_db::VulnSeverity as Severity;
+
+impl Finding {
+    pub fn new(severity: Severity, title: String, description: String) -> Self {
+        Self {
+            severity,
+            title,
+            description,
+            cve: None,
+            affected: None,
+            evidence: Vec::new(),
+            cwe: None,
+            remediation: None,
+            location: None,
+        }
+    }
+
+    pub fn with_cve(mut self, cve: impl Into<String>) -> Self {
+        self.cve = Some(cve.into());
+        self
+    }
+
+    pub fn with_affected(mut self, affected: impl Into<String>) -> Self {
+        self.affected = Some(affected.into());
+        self
+    }
+
+    pub fn with_evidence(mut self, evidence: impl Into<String>) -> Self {
+        self.evidence.push(evidence.into());
+        self
+    }
+
+    pub fn with_cwe(mut self, cwe: impl Into<String>) -> Self {
+        self.cwe = Some(cwe.into());
+        self
+    }
+
+    pub fn with_remediation(mut self, remediation: impl Into<String>) -> Self {
+        self.remediation = Some(remediation.into());
+        self
+    }
+}
+
+impl From<ScanType> for data::ScanType {
+    fn from(scan_type: ScanType) -> Self {
+        match scan_type {
+            ScanType::Network => data::ScanType::Network,
+            ScanType::Protocol => data::ScanType::Protocol,
+            ScanType::Firmware => data::ScanType::Firmware,
+        }
+    }
+}
+
+impl TryFrom<data::ScanType> for ScanType {
+    type Error = anyhow::Error;
+
+    fn try_from(scan_type: data::ScanType) -> anyhow::Result<Self> {
+        match scan_type {
+            data::ScanType::Network => Ok(ScanType::Network),
+            data::ScanType::Protocol => Ok(ScanType::Protocol),
+            data::ScanType::Firmware => Ok(ScanType::Firmware),
+            other => bail!("scanners do not produce {:?} results", other),
+        }
+    }
+}
+
+/// Reports a `VulnData` alongside other scan output, keeping its CWE,
+/// remediation and detection location so `pipeline::promote` gives them
+/// back. Info has no finding severity, so such entries are rejected.
+impl TryFrom<&VulnData> for Finding {
+    type Error = anyhow::Error;
+
+    fn try_from(vuln: &VulnData) -> anyhow::Result<Self> {
+        let severity = Severity::try_from(vuln.severity.clone())
+            .with_context(|| format!("cannot report {} as a finding", vuln.id))?;
+        Ok(Self {
+            severity,
+            title: vuln.title.clone(),
+            description: vuln.description.clone(),
+            cve: vuln.cve.clone(),
+            affected: Some(vuln.affected.clone()).filter(|a| !a.is_empty()),
+            evidence: vuln.evidence.clone(),
+            cwe: vuln.cwe.clone(),
+            remediation: vuln.remediation.clone(),
+            location: Some(vuln.detection_location.clone()),
+        })
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
-pub enum Severity {
-    Low,
-    Medium,
-    High,
-    Critical,
+/// Runtime for async tests. `#[tokio::test]` expands to `::core` paths,
+/// which this crate's `core` dependency shadows.
+#[cfg(test)]
+pub(crate) fn test_runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("failed to build test runtime")
 }
@@ -1,34 +1,396 @@
-use crate::{ScanResult, ScanType};
+pub mod port_scan;
+pub mod targets;
+pub mod uav_detect;
+
+use crate::service::ServiceIdentifier;
+use crate::{Finding, ScanResult, ScanType, Severity};
 use anyhow::Result;
+use data::Service;
+use port_scan::{OpenPort, Transport};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use uav_detect::UavDevice;
+
+/// Mirrors the `[network]` section of `config.toml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// Upper bound for a single probe, including banner grabbing. Probes
+    /// still running by then are abandoned and the result marked incomplete.
+    pub timeout_seconds: u64,
+    pub max_concurrent_scans: usize,
+    pub common_uav_ports: Vec<u16>,
+    pub connect_timeout_ms: u64,
+    pub tcp_ports: Vec<u16>,
+    pub udp_ports: Vec<u16>,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            timeout_seconds: 30,
+            max_concurrent_scans: 10,
+            common_uav_ports: vec![14550, 14551, 5760, 5761, 8554, 8080],
+            connect_timeout_ms: 1000,
+            tcp_ports: port_scan::DEFAULT_TCP_PORTS.to_vec(),
+            udp_ports: port_scan::DEFAULT_UDP_PORTS.to_vec(),
+        }
+    }
+}
 
 pub struct NetworkScanner {
     target_range: String,
+    config: NetworkConfig,
+    services: Arc<ServiceIdentifier>,
 }
 
 impl NetworkScanner {
     pub fn new(target_range: String) -> Self {
-        Self { target_range }
+        Self::with_config(target_range, NetworkConfig::default())
+    }
+
+    pub fn with_config(target_range: String, config: NetworkConfig) -> Self {
+        Self {
+            target_range,
+            config,
+            services: Arc::new(ServiceIdentifier::new()),
+        }
+    }
+
+    /// Replaces the built-in service signatures, e.g. with one that also
+    /// loaded vendor-specific GCS signatures.
+    pub fn with_service_identifier(mut self, services: ServiceIdentifier) -> Self {
+        self.services = Arc::new(services);
+        self
     }
 
     pub async fn scan(&self) -> Result<ScanResult> {
         tracing::info!("Starting network scan on: {}", self.target_range);
-        
-        // TODO: Implement actual network scanning
-        // - Port scanning
-        // - Service detection
-        // - UAV protocol detection (MAVLink, DJI, etc.)
-        
+
+        let (open_ports, ports_timed_out) = self.probe_ports().await?;
+        let (services, services_timed_out) = self.grab_services(&open_ports).await;
+        let findings = open_ports
+            .iter()
+            .map(|port| {
+                let service = services
+                    .iter()
+                    .find(|(p, _)| p == port)
+                    .map(|(_, service)| service);
+                open_port_finding(port, service)
+            })
+            .collect();
+
         Ok(ScanResult {
             scan_type: ScanType::Network,
             target: self.target_range.clone(),
-            findings: vec![],
+            findings,
+            incomplete: ports_timed_out + services_timed_out > 0,
         })
     }
 
-    pub async fn detect_uav_devices(&self) -> Result<Vec<String>> {
-        // TODO: Implement UAV device detection
-        // - Detect common UAV ports (14550 for MAVLink, etc.)
-        // - Identify wireless protocols (WiFi, radio frequencies)
-        Ok(vec![])
+    /// Runs TCP connect and UDP probes against every host in the target range.
+    pub async fn scan_ports(&self) -> Result<Vec<OpenPort>> {
+        Ok(self.probe_ports().await?.0)
+    }
+
+    /// `scan_ports`, also returning how many probes timed out.
+    async fn probe_ports(&self) -> Result<(Vec<OpenPort>, usize)> {
+        let specs = targets::parse_target_range(&self.target_range)?;
+        let hosts = targets::resolve_targets(&specs).await?;
+
+        let mut probes = Vec::new();
+        for ip in &hosts {
+            for port in merge_ports(&self.config.tcp_ports, &self.config.common_uav_ports) {
+                probes.push((*ip, port, Transport::Tcp));
+            }
+            for port in merge_ports(&self.config.udp_ports, &self.config.common_uav_ports) {
+                probes.push((*ip, port, Transport::Udp));
+            }
+        }
+        tracing::debug!(
+            "Probing {} ports across {} hosts",
+            probes.len(),
+            hosts.len()
+        );
+
+        let probe_timeout = Duration::from_millis(self.config.connect_timeout_ms);
+        let jobs = probes.into_iter().map(|(ip, port, transport)| async move {
+            match transport {
+                Transport::Tcp => port_scan::probe_tcp(ip, port, probe_timeout).await,
+                Transport::Udp => port_scan::probe_udp(ip, port, probe_timeout).await,
+            }
+        });
+
+        let (mut open, timed_out) = self.run_bounded(jobs).await;
+        open.sort_by_key(|p| (p.ip, p.transport == Transport::Udp, p.port));
+        Ok((open, timed_out))
+    }
+
+    /// Grabs banners from `ports` and matches them against the service
+    /// signatures. Ports that never answered are left out.
+    pub async fn identify_services(&self, ports: &[OpenPort]) -> Vec<(OpenPort, Service)> {
+        self.grab_services(ports).await.0
+    }
+
+    /// `identify_services`, also returning how many probes timed out.
+    async fn grab_services(&self, ports: &[OpenPort]) -> (Vec<(OpenPort, Service)>, usize) {
+        let jobs = ports.iter().cloned().map(|port| {
+            let services = self.services.clone();
+            async move {
+                let service = services.identify(&port).await?;
+                Some((port, service))
+            }
+        });
+
+        let (mut identified, timed_out) = self.run_bounded(jobs).await;
+        identified.sort_by_key(|(port, _)| (port.ip, port.transport == Transport::Udp, port.port));
+        (identified, timed_out)
+    }
+
+    /// Fingerprints MAVLink, RTSP and web endpoints on `common_uav_ports` to
+    /// tell vehicles, ground stations, companion computers and payloads apart.
+    pub async fn detect_uav_devices(&self) -> Result<Vec<UavDevice>> {
+        tracing::info!("Detecting UAV devices on: {}", self.target_range);
+
+        let specs = targets::parse_target_range(&self.target_range)?;
+        let hosts = targets::resolve_targets(&specs).await?;
+        let probe_timeout = Duration::from_millis(self.config.connect_timeout_ms);
+
+        let mut jobs = Vec::new();
+        for ip in hosts {
+            for port in merge_ports(&self.config.common_uav_ports, &[]) {
+                jobs.push(async move {
+                    let devices = uav_detect::fingerprint(ip, port, probe_timeout).await;
+                    (!devices.is_empty()).then_some(devices)
+                });
+            }
+        }
+
+        let mut devices: Vec<UavDevice> = self
+            .run_bounded(jobs)
+            .await
+            .0
+            .into_iter()
+            .flatten()
+            .collect();
+        devices.sort_by_key(|d| (d.ip, d.port));
+        Ok(devices)
+    }
+
+    /// Runs `jobs` with at most `max_concurrent_scans` in flight, giving
+    /// each `timeout_seconds`. Jobs are only started as earlier ones finish,
+    /// so large ranges never queue more than the window. Returns the results
+    /// and how many jobs did not finish, either abandoned at their timeout or
+    /// failed (a probe task that panicked).
+    async fn run_bounded<T, F>(&self, jobs: impl IntoIterator<Item = F>) -> (Vec<T>, usize)
+    where
+        T: Send + 'static,
+        F: Future<Output = Option<T>> + Send + 'static,
+    {
+        let window = self.config.max_concurrent_scans.max(1);
+        let probe_timeout = Duration::from_secs(self.config.timeout_seconds);
+
+        let mut jobs = jobs.into_iter();
+        let mut tasks = JoinSet::new();
+        let mut results = Vec::new();
+        let mut timed_out = 0;
+        let mut failed = 0;
+        loop {
+            while tasks.len() < window {
+                let Some(job) = jobs.next() else { break };
+                tasks.spawn(tokio::time::timeout(probe_timeout, job));
+            }
+            match tasks.join_next().await {
+                Some(Ok(Ok(Some(result)))) => results.push(result),
+                Some(Ok(Err(_))) => timed_out += 1,
+                Some(Ok(Ok(None))) => {}
+                Some(Err(e)) => {
+                    tracing::error!("Scan of {}: probe task failed: {}", self.target_range, e);
+                    failed += 1;
+                }
+                None => break,
+            }
+        }
+
+        if timed_out > 0 {
+            tracing::warn!(
+                "Scan of {}: {} probes hit the {}s timeout, results are incomplete",
+                self.target_range,
+                timed_out,
+                self.config.timeout_seconds
+            );
+        }
+        (results, timed_out + failed)
+    }
+}
+
+fn merge_ports(ports: &[u16], extra: &[u16]) -> Vec<u16> {
+    let mut merged: Vec<u16> = ports.iter().chain(extra).copied().collect();
+    merged.sort_unstable();
+    merged.dedup();
+    merged
+}
+
+fn open_port_finding(port: &OpenPort, service: Option<&Service>) -> Finding {
+    let name = service
+        .map(|s| s.service_name.as_str())
+        .or(port.service_name())
+        .unwrap_or("unknown");
+    let how = match port.transport {
+        Transport::Tcp => "accepted a TCP connection",
+        Transport::Udp => "replied to a UDP probe",
+    };
+
+    let mut description = format!("{}:{} {}", port.ip, port.port, how);
+    if let Some(version) = service.and_then(|s| s.version.as_ref()) {
+        description.push_str(&format!("\nVersion: {}", version));
+    }
+    if let Some(banner) = service.and_then(|s| s.banner.as_ref()) {
+        description.push_str(&format!("\nBanner: {}", banner));
+    }
+
+    Finding::new(
+        Severity::Low,
+        format!("Open port {}/{} ({})", port.port, port.transport, name),
+        description,
+    )
+    .with_affected(port.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::{TcpListener, UdpSocket};
+
+    fn scanner(tcp_ports: Vec<u16>, udp_ports: Vec<u16>) -> NetworkScanner {
+        NetworkScanner::with_config(
+            "127.0.0.1".to_string(),
+            NetworkConfig {
+                timeout_seconds: 5,
+                max_concurrent_scans: 4,
+                common_uav_ports: vec![],
+                connect_timeout_ms: 500,
+                tcp_ports,
+                udp_ports,
+            },
+        )
+    }
+
+    /// A port nothing listens on: bound once, then released.
+    async fn closed_tcp_port() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().port()
+    }
+
+    #[test]
+    fn finds_local_listeners() {
+        crate::test_runtime().block_on(async {
+            let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let tcp_port = tcp.local_addr().unwrap().port();
+            tokio::spawn(async move {
+                while let Ok((mut stream, _)) = tcp.accept().await {
+                    let _ = stream.write_all(b"SSH-2.0-OpenSSH_8.9\r\n").await;
+                }
+            });
+
+            let udp = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let udp_port = udp.local_addr().unwrap().port();
+            tokio::spawn(async move {
+                let mut buf = [0u8; 2048];
+                while let Ok((len, peer)) = udp.recv_from(&mut buf).await {
+                    let _ = udp.send_to(&buf[..len], peer).await;
+                }
+            });
+
+            let closed = closed_tcp_port().await;
+            let result = scanner(vec![tcp_port, closed], vec![udp_port])
+                .scan()
+                .await
+                .unwrap();
+
+            assert!(!result.incomplete);
+            let affected: Vec<_> = result
+                .findings
+                .iter()
+                .filter_map(|f| f.affected.clone())
+                .collect();
+            assert_eq!(
+                affected,
+                [
+                    format!("127.0.0.1:{}/tcp", tcp_port),
+                    format!("127.0.0.1:{}/udp", udp_port)
+                ]
+            );
+            assert!(result.findings[0]
+                .description
+                .contains("SSH-2.0-OpenSSH_8.9"));
+        });
+    }
+
+    /// Counts a job as running until it finishes or is dropped at its
+    /// timeout.
+    struct Running(Arc<AtomicUsize>);
+
+    impl Running {
+        fn start(running: &Arc<AtomicUsize>, peak: &AtomicUsize) -> Self {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            Self(running.clone())
+        }
+    }
+
+    impl Drop for Running {
+        fn drop(&mut self) {
+            self.0.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn bounds_concurrency_and_reports_timeouts() {
+        crate::test_runtime().block_on(async {
+            let mut scanner = scanner(vec![], vec![]);
+            scanner.config.timeout_seconds = 1;
+            scanner.config.max_concurrent_scans = 3;
+
+            let running = Arc::new(AtomicUsize::new(0));
+            let peak = Arc::new(AtomicUsize::new(0));
+            let jobs = (0..20u64).map(|i| {
+                let running = running.clone();
+                let peak = peak.clone();
+                async move {
+                    let _running = Running::start(&running, &peak);
+                    let delay = if i == 7 { 60_000 } else { 10 };
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                    Some(i)
+                }
+            });
+
+            let (results, timed_out) = scanner.run_bounded(jobs).await;
+            assert_eq!(results.len(), 19);
+            assert_eq!(timed_out, 1);
+            assert!(peak.load(Ordering::SeqCst) <= 3);
+        });
+    }
+
+    #[test]
+    fn panicked_probe_counts_as_unfinished() {
+        crate::test_runtime().block_on(async {
+            let scanner = scanner(vec![], vec![]);
+            let jobs = (0..5u64).map(|i| async move {
+                if i == 2 {
+                    panic!("probe {} failed", i);
+                }
+                Some(i)
+            });
+
+            let (results, unfinished) = scanner.run_bounded(jobs).await;
+            assert_eq!(results.len(), 4);
+            assert_eq!(unfinished, 1);
+        });
     }
 }
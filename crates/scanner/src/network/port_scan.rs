@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time::timeout;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Transport {
+    Tcp,
+    Udp,
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Transport::Tcp => write!(f, "tcp"),
+            Transport::Udp => write!(f, "udp"),
+        }
+    }
+}
+
+/// A port that answered a TCP connect or UDP probe.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpenPort {
+    pub ip: IpAddr,
+    pub port: u16,
+    pub transport: Transport,
+    /// First bytes returned by a UDP probe, if any.
+    pub response: Option<Vec<u8>>,
+}
+
+impl OpenPort {
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.ip, self.port)
+    }
+
+    pub fn service_name(&self) -> Option<&'static str> {
+        well_known_service(self.port, self.transport)
+    }
+}
+
+impl fmt::Display for OpenPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.socket_addr(), self.transport)
+    }
+}
+
+/// Ports probed by default, on top of the configured UAV ports.
+pub const DEFAULT_TCP_PORTS: &[u16] = &[
+    21, 22, 23, 25, 53, 80, 110, 139, 143, 443, 445, 554, 1883, 1935, 2000, 3000, 3389, 5000, 5555,
+    5600, 5900, 8000, 8081, 8443, 8888, 9000, 9090,
+];
+
+pub const DEFAULT_UDP_PORTS: &[u16] = &[53, 67, 69, 123, 161, 1900, 5353, 5600, 14555];
+
+/// A connect that completes within `connect_timeout` means the port is open.
+pub async fn probe_tcp(ip: IpAddr, port: u16, connect_timeout: Duration) -> Option<OpenPort> {
+    match timeout(connect_timeout, TcpStream::connect((ip, port))).await {
+        Ok(Ok(_stream)) => Some(OpenPort {
+            ip,
+            port,
+            transport: Transport::Tcp,
+            response: None,
+        }),
+        _ => None,
+    }
+}
+
+/// UDP ports are only reported open when they reply to the probe payload;
+/// silent ports are indistinguishable from filtered ones.
+pub async fn probe_udp(ip: IpAddr, port: u16, reply_timeout: Duration) -> Option<OpenPort> {
+    let bind_addr: SocketAddr = match ip {
+        IpAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+        IpAddr::V6(_) => (std::net::Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(bind_addr).await.ok()?;
+    socket.connect((ip, port)).await.ok()?;
+    socket.send(udp_payload(port)).await.ok()?;
+
+    let mut buf = vec![0u8; 2048];
+    match timeout(reply_timeout, socket.recv(&mut buf)).await {
+        Ok(Ok(len)) => {
+            buf.truncate(len);
+            Some(OpenPort {
+                ip,
+                port,
+                transport: Transport::Udp,
+                response: Some(buf),
+            })
+        }
+        // ICMP port unreachable surfaces as ConnectionRefused: the port is closed.
+        _ => None,
+    }
+}
+
+/// MAVLink v1 HEARTBEAT from system 255 / component 0 (MAV_TYPE_GCS).
+pub const MAVLINK_GCS_HEARTBEAT: &[u8] = &[
+    0xfe, 0x09, 0x00, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x06, 0x08, 0x00, 0x00, 0x03, 0xa1,
+    0xdf,
+];
+
+const DNS_VERSION_QUERY: &[u8] = &[
+    0x00, 0x01, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, b'v', b'e', b'r',
+    b's', b'i', b'o', b'n', 0x04, b'b', b'i', b'n', b'd', 0x00, 0x00, 0x10, 0x00, 0x03,
+];
+
+const NTP_CLIENT_REQUEST: &[u8] = &[
+    0xe3, 0x00, 0x04, 0xfa, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// SNMPv1 GetRequest for sysDescr.0 with community `public`.
+const SNMP_SYSDESCR_PUBLIC: &[u8] = &[
+    0x30, 0x26, 0x02, 0x01, 0x00, 0x04, 0x06, b'p', b'u', b'b', b'l', b'i', b'c', 0xa0, 0x19, 0x02,
+    0x01, 0x01, 0x02, 0x01, 0x00, 0x02, 0x01, 0x00, 0x30, 0x0e, 0x30, 0x0c, 0x06, 0x08, 0x2b, 0x06,
+    0x01, 0x02, 0x01, 0x01, 0x01, 0x00, 0x05, 0x00,
+];
+
+const SSDP_DISCOVER: &[u8] = b"M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 1\r\nST: ssdp:all\r\n\r\n";
+
+fn udp_payload(port: u16) -> &'static [u8] {
+    match port {
+        53 | 5353 => DNS_VERSION_QUERY,
+        123 => NTP_CLIENT_REQUEST,
+        161 => SNMP_SYSDESCR_PUBLIC,
+        1900 => SSDP_DISCOVER,
+        14540..=14560 => MAVLINK_GCS_HEARTBEAT,
+        _ => b"\r\n",
+    }
+}
+
+pub fn well_known_service(port: u16, transport: Transport) -> Option<&'static str> {
+    let name = match (transport, port) {
+        (Transport::Tcp, 21) => "ftp",
+        (Transport::Tcp, 22) => "ssh",
+        (Transport::Tcp, 23) => "telnet",
+        (Transport::Tcp, 25) => "smtp",
+        (_, 53) => "domain",
+        (Transport::Udp, 67) => "dhcp",
+        (Transport::Udp, 69) => "tftp",
+        (Transport::Tcp, 80)
+        | (Transport::Tcp, 8000)
+        | (Transport::Tcp, 8080)
+        | (Transport::Tcp, 8081) => "http",
+        (Transport::Tcp, 110) => "pop3",
+        (Transport::Udp, 123) => "ntp",
+        (Transport::Tcp, 139) => "netbios-ssn",
+        (Transport::Tcp, 143) => "imap",
+        (Transport::Udp, 161) => "snmp",
+        (Transport::Tcp, 443) | (Transport::Tcp, 8443) => "https",
+        (Transport::Tcp, 445) => "microsoft-ds",
+        (Transport::Tcp, 554) | (Transport::Tcp, 8554) => "rtsp",
+        (Transport::Tcp, 1883) => "mqtt",
+        (Transport::Udp, 1900) => "ssdp",
+        (Transport::Tcp, 1935) => "rtmp",
+        (Transport::Tcp, 3389) => "rdp",
+        (Transport::Udp, 5353) => "mdns",
+        (Transport::Udp, 5600) => "gstreamer-video",
+        (Transport::Tcp, 5760) | (Transport::Tcp, 5761) => "mavlink-tcp",
+        (Transport::Tcp, 5900) => "vnc",
+        (Transport::Udp, 14540..=14560) => "mavlink",
+        _ => return None,
+    };
+    Some(name)
+}
@@ -0,0 +1,175 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Upper bound on the number of hosts a single target range may expand to.
+pub const MAX_TARGET_HOSTS: usize = 65_536;
+
+/// One entry of a target range, before hostname resolution.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TargetSpec {
+    Address(IpAddr),
+    Hostname(String),
+}
+
+/// Parses a target range such as `10.0.0.1`, `10.0.0.0/24`, `10.0.0.1-20`,
+/// `10.0.0.1-10.0.0.20`, `gcs.lab.local` or a comma/whitespace separated list
+/// of any of those.
+pub fn parse_target_range(range: &str) -> Result<Vec<TargetSpec>> {
+    let mut specs = Vec::new();
+    let mut seen = HashSet::new();
+
+    for token in range
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|t| !t.is_empty())
+    {
+        for spec in parse_token(token)? {
+            if seen.insert(spec.clone()) {
+                specs.push(spec);
+            }
+            if specs.len() > MAX_TARGET_HOSTS {
+                bail!(
+                    "target range expands to more than {} hosts",
+                    MAX_TARGET_HOSTS
+                );
+            }
+        }
+    }
+
+    if specs.is_empty() {
+        bail!("empty target range");
+    }
+    Ok(specs)
+}
+
+/// Resolves hostnames and returns the de-duplicated list of addresses to scan.
+pub async fn resolve_targets(specs: &[TargetSpec]) -> Result<Vec<IpAddr>> {
+    let mut addrs = Vec::new();
+    let mut seen = HashSet::new();
+
+    for spec in specs {
+        match spec {
+            TargetSpec::Address(ip) => {
+                if seen.insert(*ip) {
+                    addrs.push(*ip);
+                }
+            }
+            TargetSpec::Hostname(host) => {
+                let resolved = tokio::net::lookup_host((host.as_str(), 0))
+                    .await
+                    .with_context(|| format!("failed to resolve {}", host))?;
+                for addr in resolved {
+                    if seen.insert(addr.ip()) {
+                        addrs.push(addr.ip());
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(addrs)
+}
+
+fn parse_token(token: &str) -> Result<Vec<TargetSpec>> {
+    if let Ok(ip) = token.parse::<IpAddr>() {
+        return Ok(vec![TargetSpec::Address(ip)]);
+    }
+
+    if let Some((base, prefix)) = token.split_once('/') {
+        return parse_cidr(base, prefix);
+    }
+
+    if let Some((start, end)) = token.split_once('-') {
+        if let Ok(start) = start.parse::<Ipv4Addr>() {
+            return parse_dash_range(start, end);
+        }
+    }
+
+    if is_hostname(token) {
+        return Ok(vec![TargetSpec::Hostname(token.to_string())]);
+    }
+
+    Err(anyhow!("invalid target: {}", token))
+}
+
+fn parse_cidr(base: &str, prefix: &str) -> Result<Vec<TargetSpec>> {
+    let prefix: u32 = prefix
+        .parse()
+        .with_context(|| format!("invalid prefix length in {}/{}", base, prefix))?;
+
+    match base.parse::<IpAddr>()? {
+        IpAddr::V4(ip) => {
+            if prefix > 32 {
+                bail!("invalid IPv4 prefix length: /{}", prefix);
+            }
+            let host_bits = 32 - prefix;
+            if host_bits > MAX_TARGET_HOSTS.trailing_zeros() {
+                bail!("{}/{} is too large to scan", ip, prefix);
+            }
+            let mask = u32::MAX.checked_shl(host_bits).unwrap_or(0);
+            let network = u32::from(ip) & mask;
+            Ok((0..1u64 << host_bits)
+                .map(|offset| TargetSpec::Address(Ipv4Addr::from(network + offset as u32).into()))
+                .collect())
+        }
+        IpAddr::V6(ip) => {
+            if prefix > 128 {
+                bail!("invalid IPv6 prefix length: /{}", prefix);
+            }
+            let host_bits = 128 - prefix;
+            if host_bits > MAX_TARGET_HOSTS.trailing_zeros() {
+                bail!("{}/{} is too large to scan", ip, prefix);
+            }
+            let mask = u128::MAX.checked_shl(host_bits).unwrap_or(0);
+            let network = u128::from(ip) & mask;
+            Ok((0..1u128 << host_bits)
+                .map(|offset| TargetSpec::Address(Ipv6Addr::from(network + offset).into()))
+                .collect())
+        }
+    }
+}
+
+fn parse_dash_range(start: Ipv4Addr, end: &str) -> Result<Vec<TargetSpec>> {
+    let end = match end.parse::<Ipv4Addr>() {
+        Ok(end) => end,
+        Err(_) => {
+            // Short form: 10.0.0.1-20 only replaces the last octet.
+            let last: u8 = end
+                .parse()
+                .with_context(|| format!("invalid range end: {}", end))?;
+            let [a, b, c, _] = start.octets();
+            Ipv4Addr::new(a, b, c, last)
+        }
+    };
+
+    let (start, end) = (u32::from(start), u32::from(end));
+    if end < start {
+        bail!(
+            "range end {} is before start {}",
+            Ipv4Addr::from(end),
+            Ipv4Addr::from(start)
+        );
+    }
+    if (end - start) as usize >= MAX_TARGET_HOSTS {
+        bail!(
+            "{}-{} is too large to scan",
+            Ipv4Addr::from(start),
+            Ipv4Addr::from(end)
+        );
+    }
+
+    Ok((start..=end)
+        .map(|ip| TargetSpec::Address(Ipv4Addr::from(ip).into()))
+        .collect())
+}
+
+fn is_hostname(token: &str) -> bool {
+    token.len() <= 253
+        && token.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
@@ -0,0 +1,433 @@
+use super::port_scan::{Transport, MAVLINK_GCS_HEARTBEAT};
+use crate::protocol::mavlink::{self, CrcCheck, Dialect, MavlinkVersion};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time::{timeout, Instant};
+
+/// Evidence is truncated to this many bytes per device.
+const MAX_EVIDENCE_BYTES: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeviceRole {
+    Vehicle,
+    GroundStation,
+    CompanionComputer,
+    Payload,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AutopilotFamily {
+    ArduPilot,
+    Px4,
+    Generic,
+    Other(u8),
+}
+
+/// A host/port that looks like part of a UAV system.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UavDevice {
+    pub ip: IpAddr,
+    pub port: u16,
+    pub transport: Transport,
+    pub role: DeviceRole,
+    pub vendor: Option<String>,
+    pub autopilot: Option<AutopilotFamily>,
+    /// 0-100, how sure the fingerprint is.
+    pub confidence: u8,
+    pub details: String,
+    /// Raw bytes the fingerprint was derived from.
+    pub evidence: Vec<u8>,
+}
+
+impl UavDevice {
+    fn new(addr: SocketAddr, transport: Transport, evidence: &[u8]) -> Self {
+        Self {
+            ip: addr.ip(),
+            port: addr.port(),
+            transport,
+            role: DeviceRole::Unknown,
+            vendor: None,
+            autopilot: None,
+            confidence: 0,
+            details: String::new(),
+            evidence: evidence[..evidence.len().min(MAX_EVIDENCE_BYTES)].to_vec(),
+        }
+    }
+
+    /// `udp:ip:port` or `tcp:ip:port` when the device answered with a MAVLink
+    /// HEARTBEAT, for handing to `ProtocolAnalyzer`.
+    pub fn mavlink_target(&self) -> Option<String> {
+        find_heartbeat(&self.evidence)?;
+        Some(format!(
+            "{}:{}",
+            self.transport,
+            SocketAddr::new(self.ip, self.port)
+        ))
+    }
+}
+
+/// Picks the fingerprinting probe that fits `port` and runs it.
+pub async fn fingerprint(ip: IpAddr, port: u16, probe_timeout: Duration) -> Vec<UavDevice> {
+    let addr = SocketAddr::new(ip, port);
+    match port {
+        8554 | 554 => probe_rtsp(addr, probe_timeout).await.into_iter().collect(),
+        80 | 8000 | 8080 | 8081 => probe_http(addr, probe_timeout).await.into_iter().collect(),
+        5760..=5763 => probe_mavlink_tcp(addr, probe_timeout)
+            .await
+            .into_iter()
+            .collect(),
+        _ => {
+            let mut devices: Vec<_> = probe_mavlink_udp(addr, probe_timeout)
+                .await
+                .into_iter()
+                .collect();
+            if devices.is_empty() {
+                devices.extend(probe_mavlink_tcp(addr, probe_timeout).await);
+            }
+            devices
+        }
+    }
+}
+
+async fn probe_mavlink_udp(addr: SocketAddr, probe_timeout: Duration) -> Option<UavDevice> {
+    let bind_addr: SocketAddr = match addr {
+        SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+        SocketAddr::V6(_) => (std::net::Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(bind_addr).await.ok()?;
+    socket.connect(addr).await.ok()?;
+    socket.send(MAVLINK_GCS_HEARTBEAT).await.ok()?;
+
+    let deadline = Instant::now() + probe_timeout;
+    let mut received = Vec::new();
+    let mut buf = vec![0u8; 2048];
+    while let Ok(Ok(len)) = tokio::time::timeout_at(deadline, socket.recv(&mut buf)).await {
+        received.extend_from_slice(&buf[..len]);
+        if let Some(device) = classify_mavlink(addr, Transport::Udp, &received) {
+            return Some(device);
+        }
+    }
+    None
+}
+
+async fn probe_mavlink_tcp(addr: SocketAddr, probe_timeout: Duration) -> Option<UavDevice> {
+    let mut stream = timeout(probe_timeout, TcpStream::connect(addr))
+        .await
+        .ok()?
+        .ok()?;
+    stream.write_all(MAVLINK_GCS_HEARTBEAT).await.ok()?;
+
+    let received = read_until(&mut stream, probe_timeout, |data| {
+        find_heartbeat(data).is_some()
+    })
+    .await;
+    if received.is_empty() {
+        return None;
+    }
+    classify_mavlink(addr, Transport::Tcp, &received).or_else(|| {
+        let mut device = UavDevice::new(addr, Transport::Tcp, &received);
+        device.confidence = 20;
+        device.details = "TCP service on a MAVLink port did not answer with MAVLink".to_string();
+        Some(device)
+    })
+}
+
+async fn probe_rtsp(addr: SocketAddr, probe_timeout: Duration) -> Option<UavDevice> {
+    let mut stream = timeout(probe_timeout, TcpStream::connect(addr))
+        .await
+        .ok()?
+        .ok()?;
+    let request = format!(
+        "OPTIONS rtsp://{}/ RTSP/1.0\r\nCSeq: 1\r\nUser-Agent: uavred\r\n\r\n",
+        addr
+    );
+    stream.write_all(request.as_bytes()).await.ok()?;
+
+    let received = read_until(&mut stream, probe_timeout, |data| {
+        data.windows(4).any(|w| w == b"\r\n\r\n")
+    })
+    .await;
+    let text = String::from_utf8_lossy(&received);
+    if !text.starts_with("RTSP/1.") {
+        return None;
+    }
+
+    let server = header_value(&text, "server");
+    let mut device = UavDevice::new(addr, Transport::Tcp, &received);
+    device.role = DeviceRole::Payload;
+    device.confidence = 50;
+    device.details = match &server {
+        Some(server) => format!("RTSP video server ({})", server),
+        None => "RTSP video server".to_string(),
+    };
+    if let Some(hint) = server.as_deref().and_then(match_keywords) {
+        apply_hint(&mut device, hint);
+    }
+    Some(device)
+}
+
+async fn probe_http(addr: SocketAddr, probe_timeout: Duration) -> Option<UavDevice> {
+    let mut stream = timeout(probe_timeout, TcpStream::connect(addr))
+        .await
+        .ok()?
+        .ok()?;
+    let request = format!(
+        "GET / HTTP/1.1\r\nHost: {}\r\nUser-Agent: uavred\r\nConnection: close\r\n\r\n",
+        addr
+    );
+    stream.write_all(request.as_bytes()).await.ok()?;
+
+    let received = read_until(&mut stream, probe_timeout, |data| data.len() >= 16 * 1024).await;
+    let text = String::from_utf8_lossy(&received);
+    if !text.starts_with("HTTP/") {
+        return None;
+    }
+
+    let hint = match_keywords(&text)?;
+    let mut device = UavDevice::new(addr, Transport::Tcp, &received);
+    device.confidence = 70;
+    device.details = format!("HTTP interface matches {}", hint.label);
+    apply_hint(&mut device, hint);
+    Some(device)
+}
+
+async fn read_until(
+    stream: &mut TcpStream,
+    probe_timeout: Duration,
+    done: impl Fn(&[u8]) -> bool,
+) -> Vec<u8> {
+    let deadline = Instant::now() + probe_timeout;
+    let mut received = Vec::new();
+    let mut buf = vec![0u8; 4096];
+    while let Ok(Ok(len)) = tokio::time::timeout_at(deadline, stream.read(&mut buf)).await {
+        if len == 0 {
+            break;
+        }
+        received.extend_from_slice(&buf[..len]);
+        if done(&received) {
+            break;
+        }
+    }
+    received
+}
+
+fn header_value(response: &str, name: &str) -> Option<String> {
+    response.lines().skip(1).find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| value.trim().to_string())
+    })
+}
+
+struct KeywordHint {
+    keyword: &'static str,
+    label: &'static str,
+    vendor: &'static str,
+    role: DeviceRole,
+    autopilot: Option<AutopilotFamily>,
+}
+
+const KEYWORD_HINTS: &[KeywordHint] = &[
+    KeywordHint {
+        keyword: "ardupilot",
+        label: "ArduPilot web UI",
+        vendor: "ArduPilot",
+        role: DeviceRole::Vehicle,
+        autopilot: Some(AutopilotFamily::ArduPilot),
+    },
+    KeywordHint {
+        keyword: "px4",
+        label: "PX4 web UI",
+        vendor: "PX4",
+        role: DeviceRole::Vehicle,
+        autopilot: Some(AutopilotFamily::Px4),
+    },
+    KeywordHint {
+        keyword: "blueos",
+        label: "BlueOS companion",
+        vendor: "Blue Robotics",
+        role: DeviceRole::CompanionComputer,
+        autopilot: None,
+    },
+    KeywordHint {
+        keyword: "rpanion",
+        label: "Rpanion-server companion",
+        vendor: "Rpanion",
+        role: DeviceRole::CompanionComputer,
+        autopilot: None,
+    },
+    KeywordHint {
+        keyword: "apsync",
+        label: "APSync companion",
+        vendor: "ArduPilot",
+        role: DeviceRole::CompanionComputer,
+        autopilot: None,
+    },
+    KeywordHint {
+        keyword: "mavproxy",
+        label: "MAVProxy",
+        vendor: "ArduPilot",
+        role: DeviceRole::GroundStation,
+        autopilot: None,
+    },
+    KeywordHint {
+        keyword: "qgroundcontrol",
+        label: "QGroundControl",
+        vendor: "Dronecode",
+        role: DeviceRole::GroundStation,
+        autopilot: None,
+    },
+    KeywordHint {
+        keyword: "mission planner",
+        label: "Mission Planner",
+        vendor: "ArduPilot",
+        role: DeviceRole::GroundStation,
+        autopilot: None,
+    },
+    KeywordHint {
+        keyword: "flighthub",
+        label: "DJI FlightHub",
+        vendor: "DJI",
+        role: DeviceRole::GroundStation,
+        autopilot: None,
+    },
+    KeywordHint {
+        keyword: "dji",
+        label: "DJI device",
+        vendor: "DJI",
+        role: DeviceRole::Vehicle,
+        autopilot: None,
+    },
+    KeywordHint {
+        keyword: "parrot",
+        label: "Parrot device",
+        vendor: "Parrot",
+        role: DeviceRole::Vehicle,
+        autopilot: None,
+    },
+    KeywordHint {
+        keyword: "skydio",
+        label: "Skydio device",
+        vendor: "Skydio",
+        role: DeviceRole::Vehicle,
+        autopilot: None,
+    },
+    KeywordHint {
+        keyword: "jetson",
+        label: "NVIDIA Jetson",
+        vendor: "NVIDIA",
+        role: DeviceRole::CompanionComputer,
+        autopilot: None,
+    },
+    KeywordHint {
+        keyword: "raspberry",
+        label: "Raspberry Pi",
+        vendor: "Raspberry Pi",
+        role: DeviceRole::CompanionComputer,
+        autopilot: None,
+    },
+    KeywordHint {
+        keyword: "siyi",
+        label: "SIYI gimbal camera",
+        vendor: "SIYI",
+        role: DeviceRole::Payload,
+        autopilot: None,
+    },
+    KeywordHint {
+        keyword: "gimbal",
+        label: "gimbal camera",
+        vendor: "Unknown",
+        role: DeviceRole::Payload,
+        autopilot: None,
+    },
+];
+
+fn match_keywords(text: &str) -> Option<&'static KeywordHint> {
+    let text = text.to_lowercase();
+    KEYWORD_HINTS
+        .iter()
+        .find(|hint| text.contains(hint.keyword))
+}
+
+fn apply_hint(device: &mut UavDevice, hint: &KeywordHint) {
+    device.vendor = Some(hint.vendor.to_string());
+    if device.role == DeviceRole::Unknown || hint.role != DeviceRole::Vehicle {
+        device.role = hint.role;
+    }
+    if hint.autopilot.is_some() {
+        device.autopilot = hint.autopilot.clone();
+    }
+}
+
+/// Fields of a MAVLink HEARTBEAT needed for fingerprinting.
+#[derive(Debug, Clone, Copy)]
+struct Heartbeat {
+    system_id: u8,
+    component_id: u8,
+    mav_type: u8,
+    autopilot: u8,
+    version: MavlinkVersion,
+}
+
+fn classify_mavlink(addr: SocketAddr, transport: Transport, data: &[u8]) -> Option<UavDevice> {
+    let heartbeat = find_heartbeat(data)?;
+    let mut device = UavDevice::new(addr, transport, data);
+
+    device.role = match (heartbeat.mav_type, heartbeat.component_id) {
+        (6, _) => DeviceRole::GroundStation,
+        (18, _) | (_, 191..=194) => DeviceRole::CompanionComputer,
+        (26 | 30, _) => DeviceRole::Payload,
+        _ => DeviceRole::Vehicle,
+    };
+    device.autopilot = match heartbeat.autopilot {
+        3 => Some(AutopilotFamily::ArduPilot),
+        12 => Some(AutopilotFamily::Px4),
+        0 => Some(AutopilotFamily::Generic),
+        8 => None,
+        other => Some(AutopilotFamily::Other(other)),
+    };
+    device.vendor = match device.autopilot {
+        Some(AutopilotFamily::ArduPilot) => Some("ArduPilot".to_string()),
+        Some(AutopilotFamily::Px4) => Some("PX4".to_string()),
+        _ => None,
+    };
+    device.confidence = 95;
+    device.details = format!(
+        "MAVLink {:?} HEARTBEAT from sysid {} compid {} (MAV_TYPE {}, MAV_AUTOPILOT {})",
+        heartbeat.version,
+        heartbeat.system_id,
+        heartbeat.component_id,
+        heartbeat.mav_type,
+        heartbeat.autopilot
+    );
+    Some(device)
+}
+
+/// Finds the first CRC-valid HEARTBEAT in `data` that is not our own probe
+/// echoed back by a reflector.
+fn find_heartbeat(data: &[u8]) -> Option<Heartbeat> {
+    let dialect = Dialect::builtin();
+    mavlink::decode_stream(dialect, data)
+        .into_iter()
+        .filter(|decoded| decoded.parsed.crc == CrcCheck::Valid)
+        .find_map(|decoded| {
+            let message = decoded.message.filter(|m| m.name == "HEARTBEAT")?;
+            let frame = &decoded.parsed.frame;
+            let heartbeat = Heartbeat {
+                system_id: frame.system_id,
+                component_id: frame.component_id,
+                mav_type: message.get("type")?.as_u64()? as u8,
+                autopilot: message.get("autopilot")?.as_u64()? as u8,
+                version: frame.version,
+            };
+            let is_own_probe = heartbeat.system_id == 255
+                && heartbeat.component_id == 0
+                && heartbeat.mav_type == 6;
+            (!is_own_probe).then_some(heartbeat)
+        })
+}
@@ -0,0 +1,482 @@
+use crate::network::uav_detect::AutopilotFamily;
+use anyhow::{anyhow, bail, Context, Result};
+use data::{ScanType, VehicleArtifacts, VehicleParameter, VulnData, VulnSeverity};
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+const BUILTIN_RULES: &str = include_str!("../rules/params.toml");
+
+/// MAV_PARAM_TYPE_REAL32, assumed for `.param` formats that carry no type.
+const DEFAULT_PARAM_TYPE: u8 = 9;
+
+#[derive(Debug, Clone, Deserialize)]
+struct RuleFile {
+    #[serde(default)]
+    rules: Vec<RuleDef>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RuleDef {
+    id: String,
+    title: String,
+    severity: String,
+    autopilot: Option<String>,
+    cwe: Option<String>,
+    description: String,
+    remediation: Option<String>,
+    #[serde(default)]
+    references: Vec<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(flatten)]
+    condition: ConditionDef,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct ConditionDef {
+    param: Option<String>,
+    equals: Option<f64>,
+    not_equals: Option<f64>,
+    lt: Option<f64>,
+    le: Option<f64>,
+    gt: Option<f64>,
+    ge: Option<f64>,
+    #[serde(rename = "in")]
+    one_of: Option<Vec<f64>>,
+    not_in: Option<Vec<f64>>,
+    bits_set: Option<u64>,
+    bits_clear: Option<u64>,
+    #[serde(default)]
+    missing: bool,
+    fact: Option<String>,
+    is: Option<bool>,
+    #[serde(default)]
+    if_unknown: bool,
+    #[serde(default)]
+    all: Vec<ConditionDef>,
+    #[serde(default)]
+    any: Vec<ConditionDef>,
+}
+
+/// A compiled rule condition. See `rules/params.toml` for the format.
+#[derive(Debug, Clone)]
+pub struct Condition {
+    param: Option<Regex>,
+    def: ConditionDef,
+    all: Vec<Condition>,
+    any: Vec<Condition>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParamRule {
+    pub id: String,
+    pub title: String,
+    pub severity: VulnSeverity,
+    /// `ardupilot` or `px4`; `None` applies to every autopilot.
+    pub autopilot: Option<String>,
+    pub cwe: Option<String>,
+    pub description: String,
+    pub remediation: Option<String>,
+    pub references: Vec<String>,
+    pub tags: Vec<String>,
+    pub condition: Condition,
+}
+
+/// What the auditor knows about the vehicle besides its parameters.
+#[derive(Debug, Clone, Default)]
+pub struct AuditContext {
+    /// Asset id or address the findings are reported against.
+    pub affected: String,
+    /// Inferred from the parameter names when not set.
+    pub autopilot: Option<AutopilotFamily>,
+    /// The `.param` file the table was read from, if any.
+    pub source_file: Option<String>,
+    /// Facts rules can test, e.g. `mavlink_signing` from a signing assessment.
+    pub facts: HashMap<String, bool>,
+}
+
+impl AuditContext {
+    pub fn new(affected: impl Into<String>) -> Self {
+        Self {
+            affected: affected.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_autopilot(mut self, autopilot: AutopilotFamily) -> Self {
+        self.autopilot = Some(autopilot);
+        self
+    }
+
+    pub fn with_source_file(mut self, path: impl Into<String>) -> Self {
+        self.source_file = Some(path.into());
+        self
+    }
+
+    pub fn with_fact(mut self, name: impl Into<String>, value: bool) -> Self {
+        self.facts.insert(name.into(), value);
+        self
+    }
+}
+
+/// Evaluates ArduPilot/PX4 parameter tables against declarative rule packs
+/// from `rules/params.toml` plus any user-supplied TOML or JSON files.
+pub struct ParamAuditor {
+    rules: Vec<ParamRule>,
+}
+
+impl ParamAuditor {
+    pub fn new() -> Self {
+        let mut auditor = Self { rules: Vec::new() };
+        auditor
+            .add_rules(BUILTIN_RULES)
+            .expect("built-in parameter rules must be valid");
+        auditor
+    }
+
+    /// Loads a rule pack; `.json` files are parsed as JSON, anything else as
+    /// TOML. A rule with the id of an existing one replaces it.
+    pub fn load_rules(&mut self, path: &Path) -> Result<usize> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read rule file {}", path.display()))?;
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let result = if is_json {
+            self.add_rules_json(&content)
+        } else {
+            self.add_rules(&content)
+        };
+        result.with_context(|| format!("invalid rule file {}", path.display()))
+    }
+
+    pub fn add_rules(&mut self, content: &str) -> Result<usize> {
+        self.add_rule_file(toml::from_str(content)?)
+    }
+
+    pub fn add_rules_json(&mut self, content: &str) -> Result<usize> {
+        self.add_rule_file(serde_json::from_str(content)?)
+    }
+
+    fn add_rule_file(&mut self, file: RuleFile) -> Result<usize> {
+        let compiled = file
+            .rules
+            .into_iter()
+            .map(compile_rule)
+            .collect::<Result<Vec<_>>>()?;
+        let count = compiled.len();
+        for rule in compiled {
+            match self.rules.iter_mut().find(|r| r.id == rule.id) {
+                Some(existing) => *existing = rule,
+                None => self.rules.push(rule),
+            }
+        }
+        Ok(count)
+    }
+
+    pub fn rules(&self) -> &[ParamRule] {
+        &self.rules
+    }
+
+    /// Audits one downloaded parameter table. Facts such as signing state
+    /// still have to be supplied through `context`.
+    pub fn audit_artifacts(
+        &self,
+        artifacts: &VehicleArtifacts,
+        mut context: AuditContext,
+    ) -> Vec<VulnData> {
+        if context.affected.is_empty() {
+            context.affected = if artifacts.asset_id.is_empty() {
+                artifacts.target.clone()
+            } else {
+                artifacts.asset_id.clone()
+            };
+        }
+        self.audit(&artifacts.parameters, &context)
+    }
+
+    pub fn audit(&self, params: &[VehicleParameter], context: &AuditContext) -> Vec<VulnData> {
+        let autopilot = context
+            .autopilot
+            .clone()
+            .or_else(|| infer_autopilot(params));
+        let detection_time = chrono::Utc::now().to_rfc3339();
+
+        let mut findings = Vec::new();
+        for rule in &self.rules {
+            if let Some(wanted) = &rule.autopilot {
+                match autopilot.as_ref().and_then(family_name) {
+                    Some(family) if family.eq_ignore_ascii_case(wanted) => {}
+                    Some(_) => continue,
+                    // Unknown autopilot: parameter names are distinct enough.
+                    None if autopilot.is_none() => {}
+                    None => continue,
+                }
+            }
+
+            for hit in rule.condition.subject_hits(params, context) {
+                let mut vuln = VulnData::new(
+                    format!(
+                        "{}:{}:{}",
+                        rule.id,
+                        context.affected,
+                        hit.map_or("-", |p| p.name.as_str())
+                    ),
+                    rule.title.clone(),
+                    render(&rule.description, hit),
+                    rule.severity.clone(),
+                );
+                vuln.cwe = rule.cwe.clone();
+                vuln.affected = context.affected.clone();
+                vuln.affected_systems = autopilot
+                    .as_ref()
+                    .and_then(family_name)
+                    .map(|name| vec![name.to_string()])
+                    .unwrap_or_default();
+                vuln.detection_time = detection_time.clone();
+                vuln.detection_location.component = hit
+                    .map(|p| p.name.clone())
+                    .unwrap_or_else(|| "parameters".to_string());
+                vuln.detection_location.file_path = context.source_file.clone();
+                vuln.scan_type = ScanType::Protocol;
+                vuln.references = rule.references.clone();
+                vuln.tags = ["mavlink", "parameters"]
+                    .iter()
+                    .map(|t| t.to_string())
+                    .chain(rule.tags.iter().cloned())
+                    .collect();
+                vuln.remediation = rule.remediation.as_ref().map(|r| render(r, hit));
+                findings.push(vuln);
+            }
+        }
+
+        tracing::info!(
+            "Parameter audit of {}: {} parameters, {} findings",
+            context.affected,
+            params.len(),
+            findings.len()
+        );
+        findings
+    }
+}
+
+impl Default for ParamAuditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Condition {
+    /// Parameters the rule fires for. A rule whose subject is a fact or a
+    /// `missing` test fires once, without a parameter.
+    fn subject_hits<'a>(
+        &self,
+        params: &'a [VehicleParameter],
+        context: &AuditContext,
+    ) -> Vec<Option<&'a VehicleParameter>> {
+        if !self.extra_conditions_hold(params, context) {
+            return Vec::new();
+        }
+        match &self.param {
+            Some(pattern) if !self.def.missing => params
+                .iter()
+                .filter(|p| pattern.is_match(&p.name) && self.value_matches(p.value))
+                .map(Some)
+                .collect(),
+            _ if self.holds(params, context) => vec![None],
+            _ => Vec::new(),
+        }
+    }
+
+    fn holds(&self, params: &[VehicleParameter], context: &AuditContext) -> bool {
+        if let Some(fact) = &self.def.fact {
+            let wanted = self.def.is.unwrap_or(true);
+            let satisfied = match context.facts.get(fact) {
+                Some(value) => *value == wanted,
+                None => self.def.if_unknown,
+            };
+            if !satisfied {
+                return false;
+            }
+        }
+        if let Some(pattern) = &self.param {
+            let mut matching = params.iter().filter(|p| pattern.is_match(&p.name));
+            let satisfied = if self.def.missing {
+                matching.next().is_none()
+            } else {
+                matching.any(|p| self.value_matches(p.value))
+            };
+            if !satisfied {
+                return false;
+            }
+        }
+        self.extra_conditions_hold(params, context)
+    }
+
+    fn extra_conditions_hold(&self, params: &[VehicleParameter], context: &AuditContext) -> bool {
+        self.all.iter().all(|c| c.holds(params, context))
+            && (self.any.is_empty() || self.any.iter().any(|c| c.holds(params, context)))
+    }
+
+    fn value_matches(&self, value: f64) -> bool {
+        let def = &self.def;
+        let bits = value as u64;
+        def.equals.is_none_or(|v| value == v)
+            && def.not_equals.is_none_or(|v| value != v)
+            && def.lt.is_none_or(|v| value < v)
+            && def.le.is_none_or(|v| value <= v)
+            && def.gt.is_none_or(|v| value > v)
+            && def.ge.is_none_or(|v| value >= v)
+            && def.one_of.as_ref().is_none_or(|vs| vs.contains(&value))
+            && def.not_in.as_ref().is_none_or(|vs| !vs.contains(&value))
+            && def.bits_set.is_none_or(|mask| bits & mask == mask)
+            && def.bits_clear.is_none_or(|mask| bits & mask == 0)
+    }
+}
+
+fn compile_rule(def: RuleDef) -> Result<ParamRule> {
+    let condition = compile_condition(def.condition)
+        .with_context(|| format!("invalid condition in rule {}", def.id))?;
+    if condition.param.is_none()
+        && condition.def.fact.is_none()
+        && condition.all.is_empty()
+        && condition.any.is_empty()
+    {
+        bail!("rule {} has no condition", def.id);
+    }
+    Ok(ParamRule {
+        severity: parse_severity(&def.severity)
+            .with_context(|| format!("invalid severity in rule {}", def.id))?,
+        id: def.id,
+        title: def.title,
+        autopilot: def.autopilot,
+        cwe: def.cwe,
+        description: def.description,
+        remediation: def.remediation,
+        references: def.references,
+        tags: def.tags,
+        condition,
+    })
+}
+
+fn compile_condition(mut def: ConditionDef) -> Result<Condition> {
+    let param = def.param.as_deref().map(glob_regex).transpose()?;
+    let all = std::mem::take(&mut def.all)
+        .into_iter()
+        .map(compile_condition)
+        .collect::<Result<_>>()?;
+    let any = std::mem::take(&mut def.any)
+        .into_iter()
+        .map(compile_condition)
+        .collect::<Result<_>>()?;
+    Ok(Condition {
+        param,
+        def,
+        all,
+        any,
+    })
+}
+
+/// Parameter names are matched case-sensitively with `*` and `?` wildcards.
+fn glob_regex(glob: &str) -> Result<Regex> {
+    let mut pattern = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    Regex::new(&pattern).map_err(|e| anyhow!("invalid parameter pattern {}: {}", glob, e))
+}
+
+fn parse_severity(severity: &str) -> Result<VulnSeverity> {
+    Ok(match severity.to_ascii_lowercase().as_str() {
+        "critical" => VulnSeverity::Critical,
+        "high" => VulnSeverity::High,
+        "medium" => VulnSeverity::Medium,
+        "low" => VulnSeverity::Low,
+        "info" => VulnSeverity::Info,
+        other => bail!("unknown severity {}", other),
+    })
+}
+
+fn family_name(family: &AutopilotFamily) -> Option<&'static str> {
+    match family {
+        AutopilotFamily::ArduPilot => Some("ArduPilot"),
+        AutopilotFamily::Px4 => Some("PX4"),
+        _ => None,
+    }
+}
+
+fn infer_autopilot(params: &[VehicleParameter]) -> Option<AutopilotFamily> {
+    params.iter().find_map(|p| match p.name.as_str() {
+        "SYSID_THISMAV" => Some(AutopilotFamily::ArduPilot),
+        "MAV_SYS_ID" => Some(AutopilotFamily::Px4),
+        _ => None,
+    })
+}
+
+/// Fills `{param}` and `{value}` in rule text.
+fn render(template: &str, param: Option<&VehicleParameter>) -> String {
+    match param {
+        Some(param) => template
+            .replace("{param}", &param.name)
+            .replace("{value}", &format_value(param.value)),
+        None => template.to_string(),
+    }
+}
+
+fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        format!("{}", value)
+    }
+}
+
+/// Reads a saved parameter file: Mission Planner / MAVProxy `NAME,VALUE` or
+/// `NAME VALUE` lines, or QGroundControl's tab-separated
+/// `sysid compid NAME VALUE TYPE`. `#` starts a comment.
+pub fn parse_param_file(content: &str) -> Result<Vec<VehicleParameter>> {
+    let mut params = Vec::new();
+    for (line_no, line) in content.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|f| !f.is_empty())
+            .collect();
+
+        let (name, value, param_type) = match fields.as_slice() {
+            [_, _, name, value, param_type] => (*name, *value, param_type.parse().ok()),
+            [name, value] => (*name, *value, None),
+            _ => bail!(
+                "line {}: unrecognized parameter line: {}",
+                line_no + 1,
+                line
+            ),
+        };
+        let value: f64 = value
+            .parse()
+            .with_context(|| format!("line {}: invalid value for {}", line_no + 1, name))?;
+        params.push(VehicleParameter {
+            name: name.to_string(),
+            value,
+            param_type: param_type.unwrap_or(DEFAULT_PARAM_TYPE),
+            index: params.len() as u16,
+        });
+    }
+    Ok(params)
+}
+
+pub fn load_param_file(path: &Path) -> Result<Vec<VehicleParameter>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read parameter file {}", path.display()))?;
+    parse_param_file(&content).with_context(|| format!("invalid parameter file {}", path.display()))
+}
@@ -0,0 +1,282 @@
+//! Promotes scanner output into the `VulnData` records the Vulns view and
+//! repositories work with.
+
+use crate::{Finding, ScanResult, ScanType};
+use data::VulnData;
+use std::collections::HashMap;
+
+/// Collects scan results as `VulnData`, merging findings that describe the
+/// same issue at the same place (see `VulnData::dedup_key`).
+#[derive(Debug, Default)]
+pub struct VulnPipeline {
+    vulns: Vec<VulnData>,
+    keys: HashMap<String, usize>,
+}
+
+impl VulnPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts from vulnerabilities already recorded, so re-running a scan
+    /// updates them instead of adding copies. Their triage status is kept.
+    pub fn with_existing(vulns: Vec<VulnData>) -> Self {
+        let mut pipeline = Self::new();
+        for vuln in vulns {
+            pipeline.add(vuln);
+        }
+        pipeline
+    }
+
+    /// Adds every finding of `result`. Returns how many were new.
+    pub fn add_result(&mut self, result: &ScanResult) -> usize {
+        let detection_time = chrono::Utc::now().to_rfc3339();
+        result
+            .findings
+            .iter()
+            .map(|finding| {
+                let mut vuln = promote(finding, &result.scan_type, &result.target);
+                vuln.detection_time = detection_time.clone();
+                self.add(vuln)
+            })
+            .filter(|added| *added)
+            .count()
+    }
+
+    /// Adds `vuln`, or merges it into the one with the same dedup key:
+    /// the higher severity wins, and new evidence and affected systems
+    /// are appended. Returns whether it was new.
+    pub fn add(&mut self, vuln: VulnData) -> bool {
+        let key = vuln.dedup_key();
+        let Some(&index) = self.keys.get(&key) else {
+            self.keys.insert(key, self.vulns.len());
+            self.vulns.push(vuln);
+            return true;
+        };
+        let existing = &mut self.vulns[index];
+        // `VulnSeverity` orders Critical first.
+        if vuln.severity < existing.severity {
+            existing.severity = vuln.severity;
+        }
+        if existing.cve.is_none() {
+            existing.cve = vuln.cve;
+        }
+        if existing.cwe.is_none() {
+            existing.cwe = vuln.cwe;
+        }
+        for evidence in vuln.evidence {
+            if !existing.evidence.contains(&evidence) {
+                existing.evidence.push(evidence);
+            }
+        }
+        for system in vuln.affected_systems {
+            if !existing.affected_systems.contains(&system) {
+                existing.affected_systems.push(system);
+            }
+        }
+        false
+    }
+
+    pub fn vulns(&self) -> &[VulnData] {
+        &self.vulns
+    }
+
+    pub fn into_vulns(self) -> Vec<VulnData> {
+        self.vulns
+    }
+}
+
+/// One finding as `VulnData`. A finding without `affected` or `location`
+/// is attributed to the scan target. `Finding::try_from` turns the result
+/// back into the same finding.
+pub fn promote(finding: &Finding, scan_type: &ScanType, target: &str) -> VulnData {
+    let affected = finding
+        .affected
+        .clone()
+        .unwrap_or_else(|| target.to_string());
+    let prefix = match scan_type {
+        ScanType::Network => "NET",
+        ScanType::Protocol => "PROTO",
+        ScanType::Firmware => "FW",
+    };
+    let mut vuln = VulnData::new(
+        format!(
+            "{}:{}:{}",
+            prefix,
+            finding.cve.as_deref().unwrap_or(&finding.title),
+            affected
+        ),
+        finding.title.clone(),
+        finding.description.clone(),
+        finding.severity.clone().into(),
+    );
+    vuln.cve = finding.cve.clone();
+    vuln.cwe = finding.cwe.clone();
+    vuln.remediation = finding.remediation.clone();
+    vuln.affected = affected;
+    vuln.affected_systems = vec![target.to_string()];
+    match &finding.location {
+        Some(location) => vuln.detection_location = location.clone(),
+        None => vuln.detection_location.component = target.to_string(),
+    }
+    vuln.scan_type = scan_type.clone().into();
+    vuln.evidence = finding.evidence.clone();
+    vuln
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Severity;
+    use data::{DetectionSource, VulnSeverity, VulnStatus};
+
+    const SEVERITIES: [Severity; 4] = [
+        Severity::Low,
+        Severity::Medium,
+        Severity::High,
+        Severity::Critical,
+    ];
+
+    fn finding(severity: Severity, affected: &str) -> Finding {
+        Finding::new(
+            severity,
+            "Telnet enabled".to_string(),
+            "Telnet accepts logins in clear text.".to_string(),
+        )
+        .with_affected(affected)
+        .with_evidence("login: ")
+    }
+
+    fn result(findings: Vec<Finding>) -> ScanResult {
+        ScanResult {
+            scan_type: ScanType::Network,
+            target: "10.0.0.5".to_string(),
+            findings,
+            incomplete: false,
+        }
+    }
+
+    #[test]
+    fn severity_round_trips_through_data() {
+        for severity in SEVERITIES {
+            let data = VulnSeverity::from(severity.clone());
+            assert_eq!(Severity::try_from(data).unwrap(), severity);
+        }
+        assert!(Severity::try_from(VulnSeverity::Info).is_err());
+    }
+
+    #[test]
+    fn scan_type_round_trips_through_data() {
+        for scan_type in [ScanType::Network, ScanType::Protocol, ScanType::Firmware] {
+            let data = data::ScanType::from(scan_type.clone());
+            let back = ScanType::try_from(data).unwrap();
+            assert_eq!(format!("{:?}", back), format!("{:?}", scan_type));
+        }
+        assert!(ScanType::try_from(data::ScanType::PenetrationTest).is_err());
+    }
+
+    #[test]
+    fn finding_round_trips_through_vuln_data() {
+        for severity in SEVERITIES {
+            let original = finding(severity, "10.0.0.5:23/tcp")
+                .with_cve("CVE-2020-10188")
+                .with_cwe("CWE-319")
+                .with_remediation("Disable telnetd.");
+            let vuln = promote(&original, &ScanType::Network, "10.0.0.5");
+            assert_eq!(vuln.cwe.as_deref(), Some("CWE-319"));
+            let back = Finding::try_from(&vuln).unwrap();
+            assert_eq!(back.location.as_ref().unwrap().component, "10.0.0.5");
+            assert_eq!(
+                Finding {
+                    location: None,
+                    ..back
+                },
+                original
+            );
+        }
+    }
+
+    #[test]
+    fn vuln_data_round_trips_through_finding() {
+        let mut vuln = VulnData::new(
+            "FW:CVE-2018-1000517:busybox 1.27.2".to_string(),
+            "BusyBox wget buffer overflow".to_string(),
+            "BusyBox wget contains a buffer overflow.".to_string(),
+            VulnSeverity::Critical,
+        );
+        vuln.cve = Some("CVE-2018-1000517".to_string());
+        vuln.cwe = Some("CWE-120".to_string());
+        vuln.remediation = Some("Upgrade BusyBox to 1.29.0.".to_string());
+        vuln.affected = "busybox 1.27.2".to_string();
+        vuln.detection_location.component = "busybox".to_string();
+        vuln.detection_location.file_path = Some("rootfs/bin/busybox".to_string());
+        vuln.detection_location.source = DetectionSource::StaticAnalysis;
+        vuln.evidence = vec!["BusyBox v1.27.2".to_string()];
+
+        let finding = Finding::try_from(&vuln).unwrap();
+        let back = promote(&finding, &ScanType::Firmware, "fw.bin");
+        assert_eq!(back.severity, vuln.severity);
+        assert_eq!(back.title, vuln.title);
+        assert_eq!(back.description, vuln.description);
+        assert_eq!(back.cve, vuln.cve);
+        assert_eq!(back.cwe, vuln.cwe);
+        assert_eq!(back.remediation, vuln.remediation);
+        assert_eq!(back.affected, vuln.affected);
+        assert_eq!(back.detection_location, vuln.detection_location);
+        assert_eq!(back.evidence, vuln.evidence);
+
+        vuln.severity = VulnSeverity::Info;
+        let error = Finding::try_from(&vuln).unwrap_err();
+        assert!(format!("{:#}", error).contains("no Info severity"));
+    }
+
+    #[test]
+    fn finding_without_location_is_attributed_to_target() {
+        let mut original = finding(Severity::High, "");
+        original.affected = None;
+        let vuln = promote(&original, &ScanType::Protocol, "udp:14550");
+        assert_eq!(vuln.affected, "udp:14550");
+        assert_eq!(vuln.id, "PROTO:Telnet enabled:udp:14550");
+        assert_eq!(
+            Finding::try_from(&vuln).unwrap().affected.as_deref(),
+            Some("udp:14550")
+        );
+    }
+
+    #[test]
+    fn repeated_findings_are_merged() {
+        let mut pipeline = VulnPipeline::new();
+        let first = result(vec![
+            finding(Severity::Medium, "10.0.0.5:23/tcp"),
+            finding(Severity::Medium, "10.0.0.6:23/tcp"),
+        ]);
+        assert_eq!(pipeline.add_result(&first), 2);
+
+        let second = result(vec![
+            finding(Severity::High, "10.0.0.5:23/tcp").with_evidence("Password: ")
+        ]);
+        assert_eq!(pipeline.add_result(&second), 0);
+
+        let vulns = pipeline.into_vulns();
+        assert_eq!(vulns.len(), 2);
+        assert_eq!(second.vulnerabilities().len(), 1);
+        assert_eq!(vulns[0].severity, VulnSeverity::High);
+        assert_eq!(vulns[0].evidence, ["login: ", "Password: "]);
+        assert_eq!(vulns[1].severity, VulnSeverity::Medium);
+    }
+
+    #[test]
+    fn rescans_keep_triage_status() {
+        let mut triaged = promote(
+            &finding(Severity::Medium, "10.0.0.5:23/tcp"),
+            &ScanType::Network,
+            "10.0.0.5",
+        );
+        triaged.status = VulnStatus::FalsePositive;
+
+        let mut pipeline = VulnPipeline::with_existing(vec![triaged]);
+        let rescan = result(vec![finding(Severity::Medium, "10.0.0.5:23/tcp")]);
+        assert_eq!(pipeline.add_result(&rescan), 0);
+        assert_eq!(pipeline.vulns()[0].status, VulnStatus::FalsePositive);
+    }
+}
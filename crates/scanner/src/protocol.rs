@@ -1,11 +1,60 @@
+pub mod dissector;
+pub mod dji;
+pub mod mavlink;
+
+use crate::param_audit::{AuditContext, ParamAuditor};
 use crate::{ScanResult, ScanType, Finding};
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use data::{AssetNode, VehicleArtifacts, VulnData};
+use dissector::{DissectedFrame, DissectorRegistry, ProtocolDissector};
+use mavlink::{DecodedFrame, Dialect, LinkAddress, MavLink, RiskLevel};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Mirrors the `[mavlink]` section of `config.toml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MavlinkConfig {
+    /// Identity we send as; 255 is what ground stations conventionally use.
+    pub system_id: u8,
+    pub component_id: u8,
+    pub heartbeat_interval_ms: u64,
+    /// How long to passively collect traffic before sending anything.
+    pub listen_seconds: u64,
+    pub response_timeout_ms: u64,
+    pub connect_timeout_ms: u64,
+    /// Highest-risk command `test_command_injection` may send. Anything above
+    /// `read_only` must be opted into explicitly.
+    pub risk_level: RiskLevel,
+    /// The parameter the `write` level changes and restores. It must be one
+    /// nothing on the vehicle depends on; identity and storage parameters
+    /// are refused.
+    pub write_test_param: String,
+}
+
+impl Default for MavlinkConfig {
+    fn default() -> Self {
+        Self {
+            system_id: 255,
+            component_id: 0,
+            heartbeat_interval_ms: 1000,
+            listen_seconds: 5,
+            response_timeout_ms: 1500,
+            connect_timeout_ms: 1000,
+            risk_level: RiskLevel::ReadOnly,
+            write_test_param: "SCR_USER1".to_string(),
+        }
+    }
+}
 
 pub struct ProtocolAnalyzer {
     protocol_type: UavProtocol,
+    mavlink_dialect: Dialect,
+    mavlink_config: MavlinkConfig,
+    dissectors: DissectorRegistry,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum UavProtocol {
     MAVLink,
     DJI,
@@ -16,32 +65,275 @@ pub enum UavProtocol {
 
 impl ProtocolAnalyzer {
     pub fn new(protocol_type: UavProtocol) -> Self {
-        Self { protocol_type }
+        Self {
+            protocol_type,
+            mavlink_dialect: Dialect::builtin().clone(),
+            mavlink_config: MavlinkConfig::default(),
+            dissectors: DissectorRegistry::new(),
+        }
+    }
+
+    pub fn with_mavlink_config(mut self, config: MavlinkConfig) -> Self {
+        self.mavlink_config = config;
+        self
+    }
+
+    /// Uses a full dialect (e.g. `ardupilotmega.xml`) instead of the built-in
+    /// subset when decoding MAVLink.
+    pub fn with_mavlink_dialect(mut self, dialect: Dialect) -> Self {
+        self.mavlink_dialect = dialect;
+        self
+    }
+
+    pub fn mavlink_dialect(&self) -> &Dialect {
+        &self.mavlink_dialect
+    }
+
+    /// Decodes raw MAVLink bytes, e.g. from a capture or a serial dump.
+    pub fn decode_mavlink(&self, data: &[u8]) -> Vec<DecodedFrame> {
+        mavlink::decode_stream(&self.mavlink_dialect, data)
+    }
+
+    /// Registers an additional dissector, replacing any with the same name.
+    pub fn with_dissector(mut self, dissector: Box<dyn ProtocolDissector>) -> Self {
+        self.dissectors.register(dissector);
+        self
+    }
+
+    pub fn dissectors(&self) -> &DissectorRegistry {
+        &self.dissectors
+    }
+
+    /// Decodes a capture (USB-serial log, WiFi dump) with the dissectors for
+    /// this analyzer's protocol, falling back to every registered dissector
+    /// when the protocol is unknown or has none.
+    pub fn dissect(&self, data: &[u8]) -> Vec<DissectedFrame> {
+        let frames = self.dissectors.dissect_as(&self.protocol_type, data);
+        if frames.is_empty() {
+            self.dissectors.dissect(data)
+        } else {
+            frames
+        }
     }
 
+    /// Runs the live checks for this analyzer's protocol against `target`.
+    /// MAVLink targets get the signing assessment followed by command
+    /// injection up to `MavlinkConfig::risk_level`. Other protocols have no
+    /// live checks; their captures go through `dissect`, and the result is
+    /// marked incomplete.
     pub async fn analyze(&self, target: &str) -> Result<ScanResult> {
         tracing::info!("Analyzing protocol: {:?} on {}", self.protocol_type, target);
-        
-        // TODO: Implement protocol analysis
-        // - Parse protocol messages
-        // - Check for authentication weaknesses
-        // - Test command injection
-        // - Verify encryption
-        
+
+        let mut findings = Vec::new();
+        let incomplete = match self.protocol_type {
+            UavProtocol::MAVLink | UavProtocol::ArduPilot | UavProtocol::PX4 => {
+                let signing = self.assess_mavlink_signing(target).await?;
+                findings.extend(signing.findings(target));
+                let injection = self.verify_mavlink_injection(target).await?;
+                findings.extend(injection.findings(target));
+                if injection.vehicle.is_none() {
+                    tracing::warn!("No MAVLink vehicle answered on {}", target);
+                }
+                injection.vehicle.is_none()
+            }
+            _ => {
+                tracing::warn!(
+                    "No live analysis for {:?}; dissect a capture instead",
+                    self.protocol_type
+                );
+                true
+            }
+        };
+
         Ok(ScanResult {
             scan_type: ScanType::Protocol,
             target: target.to_string(),
-            findings: vec![],
+            findings,
+            incomplete,
         })
     }
 
-    pub async fn test_authentication(&self, _target: &str) -> Result<Vec<Finding>> {
-        // TODO: Test authentication mechanisms
-        Ok(vec![])
+    /// For MAVLink targets (`udp:host:port`, `tcp:host:port`, `udpin:addr:port`
+    /// or `host[:port]`), checks whether MAVLink 2 signing is enforced.
+    pub async fn test_authentication(&self, target: &str) -> Result<Vec<Finding>> {
+        match self.protocol_type {
+            UavProtocol::MAVLink | UavProtocol::ArduPilot | UavProtocol::PX4 => {
+                let assessment = self.assess_mavlink_signing(target).await?;
+                if !assessment.traffic_seen() && !assessment.unsigned_accepted() {
+                    tracing::warn!("No MAVLink traffic from {}", target);
+                }
+                Ok(assessment.findings(target))
+            }
+            _ => {
+                tracing::warn!(
+                    "Authentication testing is not supported for {:?}",
+                    self.protocol_type
+                );
+                Ok(vec![])
+            }
+        }
+    }
+
+    pub async fn assess_mavlink_signing(&self, target: &str) -> Result<mavlink::SigningAssessment> {
+        tracing::info!("Assessing MAVLink signing on {}", target);
+        let mut link = self.connect_mavlink(target).await?;
+        let config = &self.mavlink_config;
+        mavlink::signing::assess(
+            &mut link,
+            Duration::from_secs(config.listen_seconds),
+            Duration::from_millis(config.heartbeat_interval_ms),
+            Duration::from_millis(config.response_timeout_ms),
+        )
+        .await
+    }
+
+    /// Downloads parameters, mission/fence/rally plans and AUTOPILOT_VERSION
+    /// from the autopilot behind `target` and attaches them to `asset`.
+    pub async fn collect_vehicle_artifacts(
+        &self,
+        target: &str,
+        asset: &mut AssetNode,
+    ) -> Result<VehicleArtifacts> {
+        tracing::info!("Collecting vehicle artifacts from {}", target);
+        let config = &self.mavlink_config;
+        let mut link = self.connect_mavlink(target).await?;
+        let options = mavlink::exfil::ExfilOptions {
+            discovery: Duration::from_secs(config.listen_seconds),
+            heartbeat_interval: Duration::from_millis(config.heartbeat_interval_ms),
+            response_timeout: Duration::from_millis(config.response_timeout_ms),
+            retries: 3,
+        };
+        let artifacts = mavlink::exfil::collect(&mut link, &asset.id, target, options)
+            .await?
+            .ok_or_else(|| anyhow!("no autopilot heartbeat from {}", target))?;
+
+        if asset.firmware_version.is_none() {
+            asset.firmware_version = artifacts
+                .autopilot_version
+                .as_ref()
+                .map(|v| v.flight_version_string());
+        }
+        asset.vehicle_artifacts.push(artifacts.clone());
+        Ok(artifacts)
+    }
+
+    /// Downloads the vehicle's parameters and audits them with `auditor`.
+    /// Whether the link enforces signing is measured first so rules about
+    /// exposed telemetry ports can take it into account.
+    pub async fn audit_vehicle_parameters(
+        &self,
+        target: &str,
+        asset: &mut AssetNode,
+        auditor: &ParamAuditor,
+    ) -> Result<Vec<VulnData>> {
+        let mut context = AuditContext::new(asset.id.clone());
+        match self.assess_mavlink_signing(target).await {
+            Ok(signing) if signing.traffic_seen() => {
+                let enforced = signing.signing_in_use() && !signing.unsigned_accepted();
+                context = context.with_fact("mavlink_signing", enforced);
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Signing assessment of {} failed: {}", target, e),
+        }
+        let artifacts = self.collect_vehicle_artifacts(target, asset).await?;
+        Ok(auditor.audit_artifacts(&artifacts, context))
+    }
+
+    async fn connect_mavlink(&self, target: &str) -> Result<MavLink> {
+        let config = &self.mavlink_config;
+        MavLink::connect(
+            &LinkAddress::parse(target)?,
+            self.mavlink_dialect.clone(),
+            config.system_id,
+            config.component_id,
+            Duration::from_millis(config.connect_timeout_ms),
+        )
+        .await
+    }
+
+    /// For MAVLink targets, proves whether the vehicle executes commands from
+    /// our system id. Only read-only requests are sent unless
+    /// `MavlinkConfig::risk_level` allows more.
+    pub async fn test_command_injection(&self, target: &str) -> Result<Vec<Finding>> {
+        match self.protocol_type {
+            UavProtocol::MAVLink | UavProtocol::ArduPilot | UavProtocol::PX4 => {
+                let report = self.verify_mavlink_injection(target).await?;
+                Ok(report.findings(target))
+            }
+            _ => {
+                tracing::warn!(
+                    "Command injection testing is not supported for {:?}",
+                    self.protocol_type
+                );
+                Ok(vec![])
+            }
+        }
+    }
+
+    pub async fn verify_mavlink_injection(&self, target: &str) -> Result<mavlink::InjectionReport> {
+        let config = &self.mavlink_config;
+        tracing::info!(
+            "Verifying MAVLink command injection on {} (risk level {:?})",
+            target,
+            config.risk_level
+        );
+        let mut link = self.connect_mavlink(target).await?;
+        mavlink::injection::run(
+            &mut link,
+            config.risk_level,
+            &config.write_test_param,
+            Duration::from_secs(config.listen_seconds),
+            Duration::from_millis(config.heartbeat_interval_ms),
+            Duration::from_millis(config.response_timeout_ms),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mavlink::mock::MockAutopilot;
+    use super::*;
+
+    #[test]
+    fn analyze_runs_mavlink_checks() {
+        crate::test_runtime().block_on(async {
+            let LinkAddress::Udp(address) = MockAutopilot {
+                command_result: Some(0),
+                ..MockAutopilot::default()
+            }
+            .start()
+            .await
+            else {
+                unreachable!()
+            };
+            let analyzer =
+                ProtocolAnalyzer::new(UavProtocol::MAVLink).with_mavlink_config(MavlinkConfig {
+                    listen_seconds: 1,
+                    heartbeat_interval_ms: 100,
+                    response_timeout_ms: 300,
+                    ..MavlinkConfig::default()
+                });
+
+            let result = analyzer.analyze(&format!("udp:{}", address)).await.unwrap();
+            assert!(!result.incomplete);
+            let titles: Vec<_> = result.findings.iter().map(|f| f.title.as_str()).collect();
+            assert!(titles.contains(&"MAVLink endpoint accepts unsigned commands"));
+            assert!(
+                titles.contains(&"MAVLink vehicle accepts commands from an arbitrary system ID")
+            );
+        });
     }
 
-    pub async fn test_command_injection(&self, _target: &str) -> Result<Vec<Finding>> {
-        // TODO: Test for command injection vulnerabilities
-        Ok(vec![])
+    #[test]
+    fn analyze_without_live_checks_is_incomplete() {
+        crate::test_runtime().block_on(async {
+            let result = ProtocolAnalyzer::new(UavProtocol::DJI)
+                .analyze("192.168.2.1")
+                .await
+                .unwrap();
+            assert!(result.incomplete);
+            assert!(result.findings.is_empty());
+        });
     }
 }
@@ -0,0 +1,175 @@
+use super::mavlink::{self, Dialect};
+use super::{dji, UavProtocol};
+use serde::{Deserialize, Serialize};
+
+/// One frame recovered from a capture, in a protocol-neutral form.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DissectedFrame {
+    /// Name of the dissector that produced the frame.
+    pub dissector: String,
+    /// Offset of the first byte of the frame in the input.
+    pub offset: usize,
+    pub raw: Vec<u8>,
+    /// One-line description, e.g.
+    /// `FLYCONTROLLER -> MOBILE_APP response 03/43 (Flight Control)`.
+    pub summary: String,
+    /// Decoded header and payload fields in wire order.
+    pub fields: Vec<(String, String)>,
+    pub payload: Vec<u8>,
+}
+
+/// Splits raw bytes (serial dumps, UDP payloads, pcap bodies) into frames of
+/// one protocol. Implementations must only return frames whose checksums
+/// verify, so the registry can tell protocols apart by frame count.
+pub trait ProtocolDissector: Send + Sync {
+    /// Unique name used to select the dissector, e.g. `dji-duml`.
+    fn name(&self) -> &str;
+
+    fn protocol(&self) -> UavProtocol;
+
+    fn dissect(&self, data: &[u8]) -> Vec<DissectedFrame>;
+}
+
+/// Dissectors known to the scanner. Later registrations with the same name
+/// replace earlier ones, so encrypted or model-specific variants can
+/// override the built-in framing.
+pub struct DissectorRegistry {
+    dissectors: Vec<Box<dyn ProtocolDissector>>,
+}
+
+impl DissectorRegistry {
+    /// A registry without any dissectors.
+    pub fn empty() -> Self {
+        Self {
+            dissectors: Vec::new(),
+        }
+    }
+
+    /// The built-in dissectors: DJI DUML and MAVLink with the built-in dialect.
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+        registry.register(Box::new(dji::DumlDissector::new()));
+        registry.register(Box::new(MavlinkDissector::new(Dialect::builtin().clone())));
+        registry
+    }
+
+    pub fn register(&mut self, dissector: Box<dyn ProtocolDissector>) {
+        match self
+            .dissectors
+            .iter_mut()
+            .find(|d| d.name() == dissector.name())
+        {
+            Some(existing) => *existing = dissector,
+            None => self.dissectors.push(dissector),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn ProtocolDissector> {
+        self.dissectors
+            .iter()
+            .find(|d| d.name() == name)
+            .map(|d| d.as_ref())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.dissectors.iter().map(|d| d.name()).collect()
+    }
+
+    /// Dissectors registered for `protocol`.
+    pub fn for_protocol(&self, protocol: &UavProtocol) -> Vec<&dyn ProtocolDissector> {
+        self.dissectors
+            .iter()
+            .filter(|d| d.protocol() == *protocol)
+            .map(|d| d.as_ref())
+            .collect()
+    }
+
+    /// Runs every dissector over `data` and keeps the result of the one that
+    /// recovered the most bytes. Returns an empty vec if none matched.
+    pub fn dissect(&self, data: &[u8]) -> Vec<DissectedFrame> {
+        best_dissection(self.dissectors.iter().map(|d| d.as_ref()), data)
+    }
+
+    /// Like `dissect`, restricted to the dissectors for `protocol`.
+    pub fn dissect_as(&self, protocol: &UavProtocol, data: &[u8]) -> Vec<DissectedFrame> {
+        best_dissection(self.for_protocol(protocol), data)
+    }
+}
+
+fn best_dissection<'a>(
+    dissectors: impl IntoIterator<Item = &'a dyn ProtocolDissector>,
+    data: &[u8],
+) -> Vec<DissectedFrame> {
+    dissectors
+        .into_iter()
+        .map(|d| d.dissect(data))
+        .max_by_key(|frames| frames.iter().map(|f| f.raw.len()).sum::<usize>())
+        .unwrap_or_default()
+}
+
+impl Default for DissectorRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Adapts the MAVLink frame parser to the dissector interface.
+pub struct MavlinkDissector {
+    dialect: Dialect,
+}
+
+impl MavlinkDissector {
+    pub fn new(dialect: Dialect) -> Self {
+        Self { dialect }
+    }
+}
+
+impl ProtocolDissector for MavlinkDissector {
+    fn name(&self) -> &str {
+        "mavlink"
+    }
+
+    fn protocol(&self) -> UavProtocol {
+        UavProtocol::MAVLink
+    }
+
+    fn dissect(&self, data: &[u8]) -> Vec<DissectedFrame> {
+        mavlink::decode_stream(&self.dialect, data)
+            .into_iter()
+            // Without CRC_EXTRA a frame is only a plausible header.
+            .filter(|decoded| decoded.parsed.crc == mavlink::CrcCheck::Valid)
+            .map(|decoded| {
+                let frame = &decoded.parsed.frame;
+                let name = decoded
+                    .message
+                    .as_ref()
+                    .map(|m| m.name.clone())
+                    .unwrap_or_else(|| format!("MSG#{}", frame.message_id));
+                let mut fields = vec![
+                    ("version".to_string(), format!("{:?}", frame.version)),
+                    ("sequence".to_string(), frame.sequence.to_string()),
+                    ("system_id".to_string(), frame.system_id.to_string()),
+                    ("component_id".to_string(), frame.component_id.to_string()),
+                    ("message_id".to_string(), frame.message_id.to_string()),
+                    ("signed".to_string(), frame.signature.is_some().to_string()),
+                ];
+                if let Some(message) = &decoded.message {
+                    fields.extend(
+                        message
+                            .fields
+                            .iter()
+                            .map(|(name, value)| (name.clone(), value.to_string())),
+                    );
+                }
+                DissectedFrame {
+                    dissector: self.name().to_string(),
+                    offset: decoded.parsed.offset,
+                    raw: decoded.parsed.raw.clone(),
+                    summary: format!("{} from {}/{}", name, frame.system_id, frame.component_id),
+                    fields,
+                    payload: frame.payload.clone(),
+                }
+            })
+            .collect()
+    }
+}
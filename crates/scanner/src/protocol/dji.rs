@@ -0,0 +1,454 @@
+//! DJI DUML ("DJI Universal Markup Language") framing, as spoken between
+//! flight controller, gimbal, camera, remote and app over serial, USB and
+//! the WiFi/OcuSync links.
+//!
+//! ```text
+//! 0      1..3           3     4      5      6..8  8     9    10   11..n-2  n-2..n
+//! 0x55   len:10 ver:6   crc8  src    dst    seq   attr  set  id   payload  crc16
+//! ```
+//!
+//! `len` counts the whole frame. `src`/`dst` hold a device type in the low
+//! five bits and an instance index in the upper three. `attr` holds the
+//! request/response bit, the ack type and the payload encryption type.
+
+use super::dissector::{DissectedFrame, ProtocolDissector};
+use super::mavlink::frame::crc_accumulate;
+use super::UavProtocol;
+use serde::{Deserialize, Serialize};
+
+pub const DUML_MAGIC: u8 = 0x55;
+pub const HEADER_LEN: usize = 11;
+/// Header plus CRC16 and an empty payload.
+pub const MIN_FRAME_LEN: usize = HEADER_LEN + 2;
+
+const CRC8_SEED: u8 = 0x77;
+const CRC16_SEED: u16 = 0x3692;
+
+/// A device on the DUML bus, e.g. `FLYCONTROLLER` index 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DumlDevice {
+    pub kind: u8,
+    pub index: u8,
+}
+
+impl DumlDevice {
+    pub fn new(kind: u8, index: u8) -> Self {
+        Self {
+            kind: kind & 0x1f,
+            index: index & 0x07,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Self {
+        Self::new(byte & 0x1f, byte >> 5)
+    }
+
+    fn to_byte(self) -> u8 {
+        (self.index << 5) | (self.kind & 0x1f)
+    }
+
+    pub fn kind_name(&self) -> &'static str {
+        device_name(self.kind)
+    }
+}
+
+impl std::fmt::Display for DumlDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.index == 0 {
+            write!(f, "{}", self.kind_name())
+        } else {
+            write!(f, "{}{}", self.kind_name(), self.index)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DumlFrame {
+    /// Protocol version from the upper six bits of the length field; 1 on
+    /// every known device.
+    pub version: u8,
+    pub sender: DumlDevice,
+    pub receiver: DumlDevice,
+    pub sequence: u16,
+    pub is_response: bool,
+    /// 0 = no ack, 1 = ack after receipt, 2 = ack after execution.
+    pub ack_type: u8,
+    /// 0 = plaintext. Other values are model-specific and need a variant
+    /// dissector that knows the keys.
+    pub encryption: u8,
+    pub cmd_set: u8,
+    pub cmd_id: u8,
+    pub payload: Vec<u8>,
+}
+
+impl DumlFrame {
+    pub fn new(
+        sender: DumlDevice,
+        receiver: DumlDevice,
+        sequence: u16,
+        cmd_set: u8,
+        cmd_id: u8,
+        payload: Vec<u8>,
+    ) -> Self {
+        Self {
+            version: 1,
+            sender,
+            receiver,
+            sequence,
+            is_response: false,
+            ack_type: 0,
+            encryption: 0,
+            cmd_set,
+            cmd_id,
+            payload,
+        }
+    }
+
+    /// Serializes the frame with both CRCs filled in.
+    pub fn encode(&self) -> Vec<u8> {
+        let len = MIN_FRAME_LEN + self.payload.len();
+        let mut bytes = Vec::with_capacity(len);
+        bytes.push(DUML_MAGIC);
+        bytes.push((len & 0xff) as u8);
+        bytes.push((((len >> 8) & 0x03) as u8) | (self.version << 2));
+        bytes.push(crc8(&bytes));
+        bytes.push(self.sender.to_byte());
+        bytes.push(self.receiver.to_byte());
+        bytes.extend_from_slice(&self.sequence.to_le_bytes());
+        bytes.push(
+            (u8::from(self.is_response) << 7)
+                | ((self.ack_type & 0x03) << 5)
+                | (self.encryption & 0x07),
+        );
+        bytes.push(self.cmd_set);
+        bytes.push(self.cmd_id);
+        bytes.extend_from_slice(&self.payload);
+        let crc = crc16(&bytes);
+        bytes.extend_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    fn decode(raw: &[u8]) -> Self {
+        let attr = raw[8];
+        Self {
+            version: raw[2] >> 2,
+            sender: DumlDevice::from_byte(raw[4]),
+            receiver: DumlDevice::from_byte(raw[5]),
+            sequence: u16::from_le_bytes([raw[6], raw[7]]),
+            is_response: attr & 0x80 != 0,
+            ack_type: (attr >> 5) & 0x03,
+            encryption: attr & 0x07,
+            cmd_set: raw[9],
+            cmd_id: raw[10],
+            payload: raw[HEADER_LEN..raw.len() - 2].to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParsedDumlFrame {
+    pub frame: DumlFrame,
+    /// Offset of the first byte of the frame in the input.
+    pub offset: usize,
+    pub raw: Vec<u8>,
+}
+
+/// Extracts every DUML frame whose header CRC8 and frame CRC16 verify.
+/// Bytes between frames are skipped one at a time so the scan
+/// resynchronizes after garbage or a truncated frame.
+pub fn parse_frames(data: &[u8]) -> Vec<ParsedDumlFrame> {
+    let mut frames = Vec::new();
+    let mut pos = 0;
+    while pos + MIN_FRAME_LEN <= data.len() {
+        match frame_len_at(&data[pos..]) {
+            Some(len) => {
+                let raw = &data[pos..pos + len];
+                frames.push(ParsedDumlFrame {
+                    frame: DumlFrame::decode(raw),
+                    offset: pos,
+                    raw: raw.to_vec(),
+                });
+                pos += len;
+            }
+            None => pos += 1,
+        }
+    }
+    frames
+}
+
+/// Length of the valid frame starting at `data[0]`, if there is one.
+fn frame_len_at(data: &[u8]) -> Option<usize> {
+    if data.len() < MIN_FRAME_LEN || data[0] != DUML_MAGIC || crc8(&data[..3]) != data[3] {
+        return None;
+    }
+    let len = usize::from(data[1]) | (usize::from(data[2] & 0x03) << 8);
+    if len < MIN_FRAME_LEN || len > data.len() {
+        return None;
+    }
+    let expected = u16::from_le_bytes([data[len - 2], data[len - 1]]);
+    (crc16(&data[..len - 2]) == expected).then_some(len)
+}
+
+/// Header checksum: reflected CRC-8 (poly 0x31) seeded with 0x77.
+pub fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(CRC8_SEED, |mut crc, byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x8c
+            } else {
+                crc >> 1
+            };
+        }
+        crc
+    })
+}
+
+/// Frame checksum: the MAVLink/X.25 CRC-16 seeded with 0x3692.
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter()
+        .fold(CRC16_SEED, |crc, byte| crc_accumulate(crc, *byte))
+}
+
+pub fn device_name(kind: u8) -> &'static str {
+    match kind {
+        0 => "ANY",
+        1 => "CAMERA",
+        2 => "MOBILE_APP",
+        3 => "FLYCONTROLLER",
+        4 => "GIMBAL",
+        5 => "CENTER_BOARD",
+        6 => "REMOTE_RADIO",
+        7 => "WIFI",
+        8 => "LB_DM3XX_SKY",
+        9 => "LB_MCU_SKY",
+        10 => "PC",
+        11 => "BATTERY",
+        12 => "ESC",
+        13 => "DM368_GROUND",
+        14 => "OFDM_GROUND",
+        15 => "LB_68013_SKY",
+        16 => "SER_68013_GROUND",
+        17 => "MVO",
+        18 => "SVO",
+        19 => "LB_FPGA_SKY",
+        20 => "FPGA_GROUND",
+        21 => "FPGA_SIM",
+        22 => "STATION",
+        23 => "XU",
+        24 => "WTF",
+        25 => "IMU",
+        26 => "GPS",
+        27 => "WIFI_GROUND",
+        28 => "SIG_CVT",
+        29 => "PMU",
+        _ => "UNKNOWN",
+    }
+}
+
+pub fn cmd_set_name(cmd_set: u8) -> Option<&'static str> {
+    Some(match cmd_set {
+        0x00 => "General",
+        0x01 => "Special",
+        0x02 => "Camera",
+        0x03 => "Flight Control",
+        0x04 => "Gimbal",
+        0x05 => "Center Board",
+        0x06 => "Remote Control",
+        0x07 => "WiFi",
+        0x08 => "DM36x",
+        0x09 => "HD Link",
+        0x0a => "Mono/Binocular",
+        0x0b => "Simulation",
+        0x0c => "ESC",
+        0x0d => "Battery",
+        0x0e => "Data Logger",
+        0x0f => "RTK",
+        0x10 => "Automation",
+        _ => return None,
+    })
+}
+
+/// Plaintext DUML framing. Encrypted payloads are passed through untouched
+/// and flagged in the summary.
+#[derive(Debug, Clone, Default)]
+pub struct DumlDissector;
+
+impl DumlDissector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl ProtocolDissector for DumlDissector {
+    fn name(&self) -> &str {
+        "dji-duml"
+    }
+
+    fn protocol(&self) -> UavProtocol {
+        UavProtocol::DJI
+    }
+
+    fn dissect(&self, data: &[u8]) -> Vec<DissectedFrame> {
+        parse_frames(data)
+            .into_iter()
+            .map(|parsed| {
+                let frame = parsed.frame;
+                let set_name = cmd_set_name(frame.cmd_set).unwrap_or("Unknown");
+                let mut summary = format!(
+                    "{} -> {} {} {:02x}/{:02x} ({})",
+                    frame.sender,
+                    frame.receiver,
+                    if frame.is_response {
+                        "response"
+                    } else {
+                        "request"
+                    },
+                    frame.cmd_set,
+                    frame.cmd_id,
+                    set_name
+                );
+                if frame.encryption != 0 {
+                    summary.push_str(&format!(", encrypted type {}", frame.encryption));
+                }
+                let fields = vec![
+                    ("version".to_string(), frame.version.to_string()),
+                    ("sender".to_string(), frame.sender.to_string()),
+                    ("receiver".to_string(), frame.receiver.to_string()),
+                    ("sequence".to_string(), frame.sequence.to_string()),
+                    ("is_response".to_string(), frame.is_response.to_string()),
+                    ("ack_type".to_string(), frame.ack_type.to_string()),
+                    ("encryption".to_string(), frame.encryption.to_string()),
+                    (
+                        "cmd_set".to_string(),
+                        format!("0x{:02x} ({})", frame.cmd_set, set_name),
+                    ),
+                    ("cmd_id".to_string(), format!("0x{:02x}", frame.cmd_id)),
+                ];
+                DissectedFrame {
+                    dissector: self.name().to_string(),
+                    offset: parsed.offset,
+                    raw: parsed.raw,
+                    summary,
+                    fields,
+                    payload: frame.payload,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// PC -> LB_DM3XX_SKY1 "enter upgrade mode" (00/07), as sent by public
+    /// firmware tools.
+    const CAPTURED: [u8; 22] = [
+        0x55, 0x16, 0x04, 0xfc, 0x2a, 0x28, 0x65, 0x57, 0x40, 0x00, 0x07, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x27, 0xd3,
+    ];
+
+    fn frame(sequence: u16, payload: &[u8]) -> DumlFrame {
+        DumlFrame::new(
+            DumlDevice::new(2, 0),
+            DumlDevice::new(3, 0),
+            sequence,
+            0x03,
+            0x43,
+            payload.to_vec(),
+        )
+    }
+
+    #[test]
+    fn checksums_match_captured_frame() {
+        assert_eq!(crc8(&CAPTURED[..3]), CAPTURED[3]);
+        assert_eq!(crc16(&CAPTURED[..20]).to_le_bytes(), [0x27, 0xd3]);
+        // Header checksums of the 13- and 14-byte frames seen on the bus.
+        assert_eq!(crc8(&[0x55, 0x0d, 0x04]), 0x33);
+        assert_eq!(crc8(&[0x55, 0x0e, 0x04]), 0x66);
+    }
+
+    #[test]
+    fn decodes_and_re_encodes_captured_frame() {
+        let parsed = parse_frames(&CAPTURED);
+        assert_eq!(parsed.len(), 1);
+        let frame = &parsed[0].frame;
+        assert_eq!(frame.version, 1);
+        assert_eq!(frame.sender, DumlDevice::new(10, 1));
+        assert_eq!(frame.receiver, DumlDevice::new(8, 1));
+        assert_eq!(frame.sender.to_string(), "PC1");
+        assert_eq!(frame.sequence, 0x5765);
+        assert!(!frame.is_response);
+        assert_eq!(frame.ack_type, 2);
+        assert_eq!(frame.encryption, 0);
+        assert_eq!((frame.cmd_set, frame.cmd_id), (0x00, 0x07));
+        assert_eq!(frame.payload, [0; 9]);
+        assert_eq!(frame.encode(), CAPTURED);
+    }
+
+    #[test]
+    fn round_trips_frames() {
+        for (sequence, payload) in [(0, &[][..]), (1, &[0xaa][..]), (0xffff, &[7; 300][..])] {
+            let mut original = frame(sequence, payload);
+            original.is_response = true;
+            original.ack_type = 1;
+            original.encryption = 3;
+            let bytes = original.encode();
+            assert_eq!(bytes.len(), MIN_FRAME_LEN + payload.len());
+
+            let parsed = parse_frames(&bytes);
+            assert_eq!(parsed.len(), 1);
+            assert_eq!(parsed[0].frame, original);
+            assert_eq!(parsed[0].raw, bytes);
+        }
+    }
+
+    #[test]
+    fn resyncs_after_garbage_and_truncation() {
+        let first = frame(1, b"abc").encode();
+        let second = frame(2, b"defg").encode();
+        let mut corrupt = frame(3, b"xyz").encode();
+        corrupt[12] ^= 0x01;
+
+        let mut data = vec![0x55, 0x55, 0x00, 0x13];
+        data.extend_from_slice(&first);
+        data.extend_from_slice(&corrupt);
+        data.extend_from_slice(&first[..first.len() - 3]);
+        let second_offset = data.len();
+        data.extend_from_slice(&second);
+        data.extend_from_slice(&second[..5]);
+
+        let parsed = parse_frames(&data);
+        let sequences: Vec<_> = parsed.iter().map(|p| p.frame.sequence).collect();
+        assert_eq!(sequences, [1, 2]);
+        assert_eq!(parsed[0].offset, 4);
+        assert_eq!(parsed[1].offset, second_offset);
+    }
+
+    #[test]
+    fn registry_finds_duml_dissector() {
+        let registry = super::super::dissector::DissectorRegistry::new();
+        assert_eq!(
+            registry.get("dji-duml").map(|d| d.protocol()),
+            Some(UavProtocol::DJI)
+        );
+        let for_dji: Vec<_> = registry
+            .for_protocol(&UavProtocol::DJI)
+            .iter()
+            .map(|d| d.name().to_string())
+            .collect();
+        assert_eq!(for_dji, ["dji-duml"]);
+
+        let frames = registry.dissect(&CAPTURED);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].dissector, "dji-duml");
+        assert_eq!(
+            frames[0].summary,
+            "PC1 -> LB_DM3XX_SKY1 request 00/07 (General)"
+        );
+        assert!(registry
+            .dissect_as(&UavProtocol::MAVLink, &CAPTURED)
+            .is_empty());
+    }
+}
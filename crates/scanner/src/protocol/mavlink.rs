@@ -0,0 +1,44 @@
+pub mod dialect;
+pub mod exfil;
+pub mod frame;
+pub mod injection;
+pub mod link;
+#[cfg(test)]
+pub(crate) mod mock;
+pub mod signing;
+
+pub use dialect::{Dialect, FieldValue, MavMessage, MessageDef};
+pub use frame::{CrcCheck, FrameParser, MavFrame, MavSignature, MavlinkVersion, ParsedFrame};
+pub use injection::{InjectionReport, RiskLevel};
+pub use link::{LinkAddress, MavLink, ProbeExchange};
+pub use signing::SigningAssessment;
+
+use serde::{Deserialize, Serialize};
+
+/// A frame together with its decoded message, when the dialect knows it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecodedFrame {
+    pub parsed: ParsedFrame,
+    pub message: Option<MavMessage>,
+}
+
+/// Splits raw capture bytes into frames and decodes the ones `dialect`
+/// knows. Garbage between frames is skipped.
+pub fn decode_stream(dialect: &Dialect, data: &[u8]) -> Vec<DecodedFrame> {
+    frame::parse_frames(data, |id| dialect.crc_extra(id))
+        .into_iter()
+        .map(|parsed| {
+            let message = dialect.decode(&parsed.frame).ok();
+            DecodedFrame { parsed, message }
+        })
+        .collect()
+}
+
+/// Space-separated hex, the form frames are quoted in as finding evidence.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
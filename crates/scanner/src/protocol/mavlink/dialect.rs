@@ -0,0 +1,648 @@
+use super::frame::{crc_accumulate, crc_calculate, MavFrame};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Messages the scanner itself sends and inspects, taken from common.xml and
+/// ardupilotmega.xml. Full dialects are loaded at runtime with `Dialect::load`.
+const BUILTIN_DIALECT: &str = include_str!("../../../dialects/scanner.xml");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FieldType {
+    Char,
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    F32,
+    F64,
+}
+
+impl FieldType {
+    fn parse(name: &str) -> Option<Self> {
+        let ty = match name {
+            "char" => FieldType::Char,
+            "uint8_t" | "uint8_t_mavlink_version" => FieldType::U8,
+            "int8_t" => FieldType::I8,
+            "uint16_t" => FieldType::U16,
+            "int16_t" => FieldType::I16,
+            "uint32_t" => FieldType::U32,
+            "int32_t" => FieldType::I32,
+            "uint64_t" => FieldType::U64,
+            "int64_t" => FieldType::I64,
+            "float" => FieldType::F32,
+            "double" => FieldType::F64,
+            _ => return None,
+        };
+        Some(ty)
+    }
+
+    pub fn size(self) -> usize {
+        match self {
+            FieldType::Char | FieldType::U8 | FieldType::I8 => 1,
+            FieldType::U16 | FieldType::I16 => 2,
+            FieldType::U32 | FieldType::I32 | FieldType::F32 => 4,
+            FieldType::U64 | FieldType::I64 | FieldType::F64 => 8,
+        }
+    }
+
+    /// Name used when computing CRC_EXTRA.
+    fn c_name(self) -> &'static str {
+        match self {
+            FieldType::Char => "char",
+            FieldType::U8 => "uint8_t",
+            FieldType::I8 => "int8_t",
+            FieldType::U16 => "uint16_t",
+            FieldType::I16 => "int16_t",
+            FieldType::U32 => "uint32_t",
+            FieldType::I32 => "int32_t",
+            FieldType::U64 => "uint64_t",
+            FieldType::I64 => "int64_t",
+            FieldType::F32 => "float",
+            FieldType::F64 => "double",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldDef {
+    pub name: String,
+    pub field_type: FieldType,
+    pub array_len: Option<usize>,
+    pub enum_name: Option<String>,
+    pub units: Option<String>,
+    pub is_extension: bool,
+}
+
+impl FieldDef {
+    pub fn wire_size(&self) -> usize {
+        self.field_type.size() * self.array_len.unwrap_or(1)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageDef {
+    pub id: u32,
+    pub name: String,
+    /// Fields in wire order: base fields sorted by type size, then extensions.
+    pub fields: Vec<FieldDef>,
+    pub crc_extra: u8,
+}
+
+impl MessageDef {
+    pub fn payload_len(&self) -> usize {
+        self.fields.iter().map(FieldDef::wire_size).sum()
+    }
+
+    pub fn field(&self, name: &str) -> Option<&FieldDef> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnumDef {
+    pub name: String,
+    pub bitmask: bool,
+    pub entries: Vec<(u64, String)>,
+}
+
+impl EnumDef {
+    pub fn entry_name(&self, value: u64) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(v, _)| *v == value)
+            .map(|(_, name)| name.as_str())
+    }
+
+    pub fn value_of(&self, name: &str) -> Option<u64> {
+        self.entries
+            .iter()
+            .find(|(_, n)| n == name)
+            .map(|(value, _)| *value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FieldValue {
+    UInt(u64),
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Array(Vec<FieldValue>),
+}
+
+impl FieldValue {
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            FieldValue::UInt(v) => Some(*v),
+            FieldValue::Int(v) => u64::try_from(*v).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            FieldValue::UInt(v) => i64::try_from(*v).ok(),
+            FieldValue::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            FieldValue::UInt(v) => Some(*v as f64),
+            FieldValue::Int(v) => Some(*v as f64),
+            FieldValue::Float(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            FieldValue::Text(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<Vec<u8>> {
+        match self {
+            FieldValue::Array(values) => values
+                .iter()
+                .map(|v| v.as_u64().and_then(|v| u8::try_from(v).ok()))
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldValue::UInt(v) => write!(f, "{}", v),
+            FieldValue::Int(v) => write!(f, "{}", v),
+            FieldValue::Float(v) => write!(f, "{}", v),
+            FieldValue::Text(v) => write!(f, "{:?}", v),
+            FieldValue::Array(values) => {
+                write!(f, "[")?;
+                for (i, v) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", v)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+/// A decoded message with its fields in wire order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MavMessage {
+    pub id: u32,
+    pub name: String,
+    pub fields: Vec<(String, FieldValue)>,
+}
+
+impl MavMessage {
+    pub fn get(&self, name: &str) -> Option<&FieldValue> {
+        self.fields.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+}
+
+/// A set of MAVLink message and enum definitions built from dialect XML.
+#[derive(Debug, Clone, Default)]
+pub struct Dialect {
+    messages: HashMap<u32, MessageDef>,
+    names: HashMap<String, u32>,
+    enums: HashMap<String, EnumDef>,
+}
+
+impl Dialect {
+    /// The small built-in dialect covering the messages the scanner uses.
+    pub fn builtin() -> &'static Dialect {
+        static BUILTIN: OnceLock<Dialect> = OnceLock::new();
+        BUILTIN.get_or_init(|| {
+            let mut dialect = Self::default();
+            dialect
+                .add_xml(BUILTIN_DIALECT, None, &mut HashSet::new())
+                .expect("built-in MAVLink dialect must be valid");
+            dialect
+        })
+    }
+
+    /// Loads a dialect XML file such as `ardupilotmega.xml`, following its
+    /// `<include>` elements relative to the file's directory.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut dialect = Self::default();
+        dialect.load_file(path, &mut HashSet::new())?;
+        Ok(dialect)
+    }
+
+    /// Adds the definitions from another dialect file on top of this one.
+    pub fn extend_from_file(&mut self, path: &Path) -> Result<()> {
+        self.load_file(path, &mut HashSet::new())
+    }
+
+    pub fn message(&self, id: u32) -> Option<&MessageDef> {
+        self.messages.get(&id)
+    }
+
+    pub fn message_by_name(&self, name: &str) -> Option<&MessageDef> {
+        self.names.get(name).and_then(|id| self.messages.get(id))
+    }
+
+    pub fn enum_def(&self, name: &str) -> Option<&EnumDef> {
+        self.enums.get(name)
+    }
+
+    pub fn messages(&self) -> impl Iterator<Item = &MessageDef> {
+        self.messages.values()
+    }
+
+    pub fn crc_extra(&self, id: u32) -> Option<u8> {
+        self.messages.get(&id).map(|m| m.crc_extra)
+    }
+
+    /// Looks up the symbolic name of `value` in the enum attached to `field`.
+    pub fn enum_entry(&self, message: &MessageDef, field: &str, value: u64) -> Option<&str> {
+        let enum_name = message.field(field)?.enum_name.as_deref()?;
+        self.enums.get(enum_name)?.entry_name(value)
+    }
+
+    /// Decodes the payload of `frame`. Zero-trimmed MAVLink 2 payloads are
+    /// padded back to full length before decoding.
+    pub fn decode(&self, frame: &MavFrame) -> Result<MavMessage> {
+        let def = self
+            .messages
+            .get(&frame.message_id)
+            .ok_or_else(|| anyhow!("unknown message id {}", frame.message_id))?;
+
+        let mut payload = frame.payload.clone();
+        let full_len = def.payload_len();
+        if payload.len() > full_len {
+            bail!(
+                "{} payload is {} bytes, expected at most {}",
+                def.name,
+                payload.len(),
+                full_len
+            );
+        }
+        payload.resize(full_len, 0);
+
+        let mut offset = 0;
+        let mut fields = Vec::with_capacity(def.fields.len());
+        for field in &def.fields {
+            let value = decode_field(field, &payload[offset..offset + field.wire_size()]);
+            offset += field.wire_size();
+            fields.push((field.name.clone(), value));
+        }
+
+        Ok(MavMessage {
+            id: def.id,
+            name: def.name.clone(),
+            fields,
+        })
+    }
+
+    /// Builds the payload for message `name`. Fields that are not given are
+    /// zero-filled.
+    pub fn encode(
+        &self,
+        name: &str,
+        values: &[(&str, FieldValue)],
+    ) -> Result<(&MessageDef, Vec<u8>)> {
+        let def = self
+            .message_by_name(name)
+            .ok_or_else(|| anyhow!("unknown message {}", name))?;
+
+        for (field, _) in values {
+            if def.field(field).is_none() {
+                bail!("{} has no field {}", name, field);
+            }
+        }
+
+        let mut payload = Vec::with_capacity(def.payload_len());
+        for field in &def.fields {
+            let value = values
+                .iter()
+                .find(|(n, _)| *n == field.name)
+                .map(|(_, v)| v);
+            encode_field(field, value, &mut payload)
+                .with_context(|| format!("invalid value for {}.{}", name, field.name))?;
+        }
+        Ok((def, payload))
+    }
+
+    fn load_file(&mut self, path: &Path, visited: &mut HashSet<PathBuf>) -> Result<()> {
+        let canonical = path
+            .canonicalize()
+            .with_context(|| format!("failed to open dialect {}", path.display()))?;
+        if !visited.insert(canonical.clone()) {
+            return Ok(());
+        }
+        let content = std::fs::read_to_string(&canonical)
+            .with_context(|| format!("failed to read dialect {}", path.display()))?;
+        self.add_xml(&content, canonical.parent(), visited)
+            .with_context(|| format!("invalid dialect {}", path.display()))
+    }
+
+    fn add_xml(
+        &mut self,
+        content: &str,
+        base_dir: Option<&Path>,
+        visited: &mut HashSet<PathBuf>,
+    ) -> Result<()> {
+        let doc = roxmltree::Document::parse(content)?;
+        let root = doc.root_element();
+        if root.tag_name().name() != "mavlink" {
+            bail!(
+                "root element is <{}>, expected <mavlink>",
+                root.tag_name().name()
+            );
+        }
+
+        for include in root.children().filter(|n| n.has_tag_name("include")) {
+            let file = include.text().unwrap_or_default().trim();
+            let dir = base_dir.ok_or_else(|| anyhow!("cannot resolve include {}", file))?;
+            self.load_file(&dir.join(file), visited)?;
+        }
+
+        for enums in root.children().filter(|n| n.has_tag_name("enums")) {
+            for node in enums.children().filter(|n| n.has_tag_name("enum")) {
+                self.add_enum(node)?;
+            }
+        }
+
+        for messages in root.children().filter(|n| n.has_tag_name("messages")) {
+            for node in messages.children().filter(|n| n.has_tag_name("message")) {
+                let def = parse_message(node)?;
+                self.names.insert(def.name.clone(), def.id);
+                self.messages.insert(def.id, def);
+            }
+        }
+        Ok(())
+    }
+
+    /// Enums with the same name across files are merged, as ardupilotmega.xml
+    /// does for MAV_CMD.
+    fn add_enum(&mut self, node: roxmltree::Node) -> Result<()> {
+        let name = node
+            .attribute("name")
+            .ok_or_else(|| anyhow!("<enum> without a name"))?;
+        let def = self
+            .enums
+            .entry(name.to_string())
+            .or_insert_with(|| EnumDef {
+                name: name.to_string(),
+                bitmask: false,
+                entries: Vec::new(),
+            });
+        def.bitmask |= node.attribute("bitmask") == Some("true");
+
+        let mut next_value = def.entries.iter().map(|(v, _)| v + 1).max().unwrap_or(0);
+        for entry in node.children().filter(|n| n.has_tag_name("entry")) {
+            let entry_name = entry
+                .attribute("name")
+                .ok_or_else(|| anyhow!("entry without a name in enum {}", name))?;
+            let value = match entry.attribute("value") {
+                Some(value) => parse_enum_value(value)
+                    .ok_or_else(|| anyhow!("bad value {:?} for {}", value, entry_name))?,
+                None => next_value,
+            };
+            next_value = value + 1;
+            def.entries.retain(|(_, n)| n != entry_name);
+            def.entries.push((value, entry_name.to_string()));
+        }
+        Ok(())
+    }
+}
+
+fn parse_message(node: roxmltree::Node) -> Result<MessageDef> {
+    let name = node
+        .attribute("name")
+        .ok_or_else(|| anyhow!("<message> without a name"))?
+        .to_string();
+    let id: u32 = node
+        .attribute("id")
+        .ok_or_else(|| anyhow!("message {} has no id", name))?
+        .parse()
+        .with_context(|| format!("message {} has an invalid id", name))?;
+    if id > 0x00ff_ffff {
+        bail!("message {} id {} does not fit in 24 bits", name, id);
+    }
+
+    let mut base = Vec::new();
+    let mut extensions = Vec::new();
+    let mut in_extensions = false;
+    for child in node.children().filter(|n| n.is_element()) {
+        match child.tag_name().name() {
+            "extensions" => in_extensions = true,
+            "field" => {
+                let field = parse_field(child, in_extensions)
+                    .with_context(|| format!("in message {}", name))?;
+                if in_extensions {
+                    extensions.push(field);
+                } else {
+                    base.push(field);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Base fields are reordered by type size (largest first, stable);
+    // extension fields keep their declaration order.
+    base.sort_by_key(|f| std::cmp::Reverse(f.field_type.size()));
+
+    let mut crc = crc_calculate(format!("{} ", name).as_bytes());
+    for field in &base {
+        for byte in format!("{} ", field.field_type.c_name()).bytes() {
+            crc = crc_accumulate(crc, byte);
+        }
+        for byte in format!("{} ", field.name).bytes() {
+            crc = crc_accumulate(crc, byte);
+        }
+        if let Some(len) = field.array_len {
+            crc = crc_accumulate(crc, len as u8);
+        }
+    }
+    let crc_extra = ((crc & 0xff) ^ (crc >> 8)) as u8;
+
+    let mut fields = base;
+    fields.extend(extensions);
+    let def = MessageDef {
+        id,
+        name,
+        fields,
+        crc_extra,
+    };
+    if def.payload_len() > 255 {
+        bail!("message {} payload exceeds 255 bytes", def.name);
+    }
+    Ok(def)
+}
+
+fn parse_field(node: roxmltree::Node, is_extension: bool) -> Result<FieldDef> {
+    let name = node
+        .attribute("name")
+        .ok_or_else(|| anyhow!("<field> without a name"))?;
+    let type_attr = node
+        .attribute("type")
+        .ok_or_else(|| anyhow!("field {} has no type", name))?;
+
+    let (base_type, array_len) = match type_attr.split_once('[') {
+        Some((base, len)) => {
+            let len: usize = len
+                .trim_end_matches(']')
+                .parse()
+                .with_context(|| format!("field {} has an invalid array length", name))?;
+            if len == 0 || len > 255 {
+                bail!("field {} array length {} out of range", name, len);
+            }
+            (base, Some(len))
+        }
+        None => (type_attr, None),
+    };
+    let field_type = FieldType::parse(base_type)
+        .ok_or_else(|| anyhow!("field {} has unknown type {}", name, type_attr))?;
+
+    Ok(FieldDef {
+        name: name.to_string(),
+        field_type,
+        array_len,
+        enum_name: node.attribute("enum").map(str::to_string),
+        units: node.attribute("units").map(str::to_string),
+        is_extension,
+    })
+}
+
+fn parse_enum_value(value: &str) -> Option<u64> {
+    let value = value.trim();
+    if let Some(hex) = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        return u64::from_str_radix(hex, 16).ok();
+    }
+    if let Some(exp) = value.strip_prefix("2**") {
+        return 1u64.checked_shl(exp.parse().ok()?);
+    }
+    value.parse().ok()
+}
+
+fn decode_field(field: &FieldDef, bytes: &[u8]) -> FieldValue {
+    if field.field_type == FieldType::Char && field.array_len.is_some() {
+        let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+        return FieldValue::Text(String::from_utf8_lossy(&bytes[..end]).into_owned());
+    }
+
+    let size = field.field_type.size();
+    let mut values = bytes
+        .chunks_exact(size)
+        .map(|chunk| decode_scalar(field.field_type, chunk));
+    match field.array_len {
+        Some(_) => FieldValue::Array(values.collect()),
+        None => values.next().unwrap_or(FieldValue::UInt(0)),
+    }
+}
+
+fn decode_scalar(ty: FieldType, b: &[u8]) -> FieldValue {
+    match ty {
+        FieldType::Char | FieldType::U8 => FieldValue::UInt(u64::from(b[0])),
+        FieldType::I8 => FieldValue::Int(i64::from(b[0] as i8)),
+        FieldType::U16 => FieldValue::UInt(u64::from(u16::from_le_bytes([b[0], b[1]]))),
+        FieldType::I16 => FieldValue::Int(i64::from(i16::from_le_bytes([b[0], b[1]]))),
+        FieldType::U32 => FieldValue::UInt(u64::from(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))),
+        FieldType::I32 => FieldValue::Int(i64::from(i32::from_le_bytes([b[0], b[1], b[2], b[3]]))),
+        FieldType::F32 => {
+            FieldValue::Float(f64::from(f32::from_le_bytes([b[0], b[1], b[2], b[3]])))
+        }
+        FieldType::U64 => {
+            FieldValue::UInt(u64::from_le_bytes(b[..8].try_into().unwrap_or_default()))
+        }
+        FieldType::I64 => {
+            FieldValue::Int(i64::from_le_bytes(b[..8].try_into().unwrap_or_default()))
+        }
+        FieldType::F64 => {
+            FieldValue::Float(f64::from_le_bytes(b[..8].try_into().unwrap_or_default()))
+        }
+    }
+}
+
+fn encode_field(field: &FieldDef, value: Option<&FieldValue>, out: &mut Vec<u8>) -> Result<()> {
+    let size = field.wire_size();
+    let start = out.len();
+
+    match (value, field.array_len) {
+        (None, _) => out.resize(start + size, 0),
+        (Some(FieldValue::Text(text)), Some(len)) if field.field_type == FieldType::Char => {
+            if text.len() > len {
+                bail!("text is longer than {} bytes", len);
+            }
+            out.extend_from_slice(text.as_bytes());
+            out.resize(start + size, 0);
+        }
+        (Some(FieldValue::Array(values)), Some(len)) => {
+            if values.len() > len {
+                bail!(
+                    "array has {} elements, at most {} allowed",
+                    values.len(),
+                    len
+                );
+            }
+            for value in values {
+                encode_scalar(field.field_type, value, out)?;
+            }
+            out.resize(start + size, 0);
+        }
+        (Some(value), None) => encode_scalar(field.field_type, value, out)?,
+        (Some(_), Some(_)) => bail!("expected an array value"),
+    }
+    Ok(())
+}
+
+fn encode_scalar(ty: FieldType, value: &FieldValue, out: &mut Vec<u8>) -> Result<()> {
+    let int = || -> Result<i128> {
+        match value {
+            FieldValue::UInt(v) => Ok(i128::from(*v)),
+            FieldValue::Int(v) => Ok(i128::from(*v)),
+            FieldValue::Float(v) if v.fract() == 0.0 => Ok(*v as i128),
+            _ => bail!("expected an integer, got {}", value),
+        }
+    };
+    let float = || -> Result<f64> {
+        value
+            .as_f64()
+            .ok_or_else(|| anyhow!("expected a number, got {}", value))
+    };
+
+    macro_rules! put {
+        ($t:ty) => {{
+            let v = <$t>::try_from(int()?)
+                .map_err(|_| anyhow!("{} out of range for {}", value, stringify!($t)))?;
+            out.extend_from_slice(&v.to_le_bytes());
+        }};
+    }
+
+    match ty {
+        FieldType::Char | FieldType::U8 => put!(u8),
+        FieldType::I8 => put!(i8),
+        FieldType::U16 => put!(u16),
+        FieldType::I16 => put!(i16),
+        FieldType::U32 => put!(u32),
+        FieldType::I32 => put!(i32),
+        FieldType::U64 => put!(u64),
+        FieldType::I64 => put!(i64),
+        FieldType::F32 => out.extend_from_slice(&(float()? as f32).to_le_bytes()),
+        FieldType::F64 => out.extend_from_slice(&float()?.to_le_bytes()),
+    }
+    Ok(())
+}
@@ -0,0 +1,339 @@
+use super::dialect::{FieldValue, MavMessage};
+use super::frame::MavlinkVersion;
+use super::link::MavLink;
+use anyhow::Result;
+use data::{
+    AutopilotVersionInfo, MissionItem, MissionPlan, MissionType, VehicleArtifacts, VehicleParameter,
+};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::time::Instant;
+
+const MAV_CMD_REQUEST_MESSAGE: u64 = 512;
+const MAV_CMD_REQUEST_AUTOPILOT_CAPABILITIES: u64 = 520;
+const AUTOPILOT_VERSION_ID: u64 = 148;
+const MAV_PROTOCOL_CAPABILITY_PARAM_ENCODE_BYTEWISE: u64 = 16;
+const MAV_MISSION_ACCEPTED: u64 = 0;
+
+/// MAV_PARAM_TYPE values up to INT32 can be carried bytewise in the float.
+const MAV_PARAM_TYPE_INT32: u8 = 6;
+const MAV_PARAM_TYPE_UINT8: u8 = 1;
+
+/// Timing for a download. Individual parameters and mission items that go
+/// missing are requested again up to `retries` times.
+#[derive(Debug, Clone, Copy)]
+pub struct ExfilOptions {
+    pub discovery: Duration,
+    pub heartbeat_interval: Duration,
+    pub response_timeout: Duration,
+    pub retries: usize,
+}
+
+/// Downloads AUTOPILOT_VERSION, the parameter table and the mission, fence
+/// and rally plans from the first autopilot on `link`. Returns `None` when no
+/// autopilot announces itself. Everything used here is a read request.
+pub async fn collect(
+    link: &mut MavLink,
+    asset_id: &str,
+    target: &str,
+    options: ExfilOptions,
+) -> Result<Option<VehicleArtifacts>> {
+    let Some((heartbeat, _)) = link
+        .discover_vehicle(options.discovery, options.heartbeat_interval)
+        .await?
+    else {
+        return Ok(None);
+    };
+    let vehicle = (heartbeat.frame.system_id, heartbeat.frame.component_id);
+
+    let mut artifacts = VehicleArtifacts::new(
+        asset_id.to_string(),
+        target.to_string(),
+        chrono::Utc::now().to_rfc3339(),
+    );
+    artifacts.system_id = vehicle.0;
+    artifacts.component_id = vehicle.1;
+
+    artifacts.autopilot_version = autopilot_version(link, vehicle, &options).await?;
+    let bytewise = artifacts
+        .autopilot_version
+        .as_ref()
+        .is_some_and(|v| v.capabilities & MAV_PROTOCOL_CAPABILITY_PARAM_ENCODE_BYTEWISE != 0);
+
+    let (count, parameters) = parameters(link, vehicle, bytewise, &options).await?;
+    artifacts.parameter_count = count;
+    artifacts.parameters = parameters;
+
+    for mission_type in MissionType::ALL {
+        if let Some(plan) = plan(link, vehicle, mission_type, &options).await? {
+            artifacts.plans.push(plan);
+        }
+    }
+
+    tracing::info!(
+        "Collected {}/{} parameters and {} plans from system {}",
+        artifacts.parameters.len(),
+        artifacts.parameter_count,
+        artifacts.plans.len(),
+        vehicle.0
+    );
+    Ok(Some(artifacts))
+}
+
+fn target_fields((system, component): (u8, u8)) -> [(&'static str, FieldValue); 2] {
+    [
+        ("target_system", FieldValue::UInt(u64::from(system))),
+        ("target_component", FieldValue::UInt(u64::from(component))),
+    ]
+}
+
+/// Asks for AUTOPILOT_VERSION with REQUEST_MESSAGE, falling back to the
+/// older REQUEST_AUTOPILOT_CAPABILITIES.
+async fn autopilot_version(
+    link: &mut MavLink,
+    vehicle: (u8, u8),
+    options: &ExfilOptions,
+) -> Result<Option<AutopilotVersionInfo>> {
+    let requests = [
+        (MAV_CMD_REQUEST_MESSAGE, AUTOPILOT_VERSION_ID as f64),
+        (MAV_CMD_REQUEST_AUTOPILOT_CAPABILITIES, 1.0),
+    ];
+    for (command, param1) in requests {
+        let mut fields = target_fields(vehicle).to_vec();
+        fields.push(("command", FieldValue::UInt(command)));
+        fields.push(("param1", FieldValue::Float(param1)));
+        link.send_message(MavlinkVersion::V2, "COMMAND_LONG", &fields)
+            .await?;
+
+        let (reply, _) = link
+            .wait_for(options.response_timeout, |parsed, m| {
+                parsed.frame.system_id == vehicle.0 && m.name == "AUTOPILOT_VERSION"
+            })
+            .await;
+        if let Some((_, message)) = reply {
+            return Ok(autopilot_version_info(&message));
+        }
+    }
+    Ok(None)
+}
+
+fn autopilot_version_info(message: &MavMessage) -> Option<AutopilotVersionInfo> {
+    let u64_field = |name: &str| message.get(name).and_then(FieldValue::as_u64);
+    let custom = message
+        .get("flight_custom_version")
+        .and_then(FieldValue::as_bytes)
+        .unwrap_or_default();
+    Some(AutopilotVersionInfo {
+        capabilities: u64_field("capabilities")?,
+        flight_sw_version: u64_field("flight_sw_version")? as u32,
+        middleware_sw_version: u64_field("middleware_sw_version")? as u32,
+        os_sw_version: u64_field("os_sw_version")? as u32,
+        board_version: u64_field("board_version")? as u32,
+        flight_custom_version: custom.iter().map(|b| format!("{:02x}", b)).collect(),
+        vendor_id: u64_field("vendor_id")? as u16,
+        product_id: u64_field("product_id")? as u16,
+        uid: u64_field("uid")?,
+    })
+}
+
+/// PARAM_REQUEST_LIST, then PARAM_REQUEST_READ for every index the stream
+/// skipped. Returns the announced count and the parameters in index order.
+async fn parameters(
+    link: &mut MavLink,
+    vehicle: (u8, u8),
+    bytewise: bool,
+    options: &ExfilOptions,
+) -> Result<(u16, Vec<VehicleParameter>)> {
+    let mut received = BTreeMap::new();
+    let mut count = None;
+
+    link.send_message(
+        MavlinkVersion::V2,
+        "PARAM_REQUEST_LIST",
+        &target_fields(vehicle),
+    )
+    .await?;
+    receive_params(link, vehicle, bytewise, options, &mut received, &mut count).await;
+
+    for _ in 0..options.retries {
+        let Some(total) = count else { break };
+        let missing: Vec<u16> = (0..total).filter(|i| !received.contains_key(i)).collect();
+        if missing.is_empty() {
+            break;
+        }
+        tracing::debug!("Re-requesting {} missing parameters", missing.len());
+        for index in missing {
+            let mut fields = target_fields(vehicle).to_vec();
+            fields.push(("param_index", FieldValue::Int(i64::from(index))));
+            link.send_message(MavlinkVersion::V2, "PARAM_REQUEST_READ", &fields)
+                .await?;
+        }
+        receive_params(link, vehicle, bytewise, options, &mut received, &mut count).await;
+    }
+
+    Ok((count.unwrap_or(0), received.into_values().collect()))
+}
+
+/// Collects PARAM_VALUE until none has arrived for `response_timeout` or
+/// every announced parameter is in.
+async fn receive_params(
+    link: &mut MavLink,
+    vehicle: (u8, u8),
+    bytewise: bool,
+    options: &ExfilOptions,
+    received: &mut BTreeMap<u16, VehicleParameter>,
+    count: &mut Option<u16>,
+) {
+    let mut deadline = Instant::now() + options.response_timeout;
+    while let Some((parsed, message)) = link.recv_frame(deadline).await {
+        let Some(message) = message.filter(|m| m.name == "PARAM_VALUE") else {
+            continue;
+        };
+        if parsed.frame.system_id != vehicle.0 {
+            continue;
+        }
+        let Some(param) = parameter(&message, bytewise) else {
+            continue;
+        };
+        deadline = Instant::now() + options.response_timeout;
+        let total = message.get("param_count").and_then(FieldValue::as_u64);
+        *count = total.map(|c| c as u16).or(*count);
+        // Unsolicited PARAM_VALUE after a set uses index 65535.
+        if param.index != u16::MAX {
+            received.insert(param.index, param);
+        }
+        if count.is_some_and(|c| received.len() >= usize::from(c)) {
+            return;
+        }
+    }
+}
+
+fn parameter(message: &MavMessage, bytewise: bool) -> Option<VehicleParameter> {
+    let raw = message.get("param_value")?.as_f64()?;
+    let param_type = message.get("param_type")?.as_u64()? as u8;
+    let value = if bytewise && (MAV_PARAM_TYPE_UINT8..=MAV_PARAM_TYPE_INT32).contains(&param_type) {
+        bytewise_integer(raw as f32, param_type)
+    } else {
+        raw
+    };
+    Some(VehicleParameter {
+        name: message.get("param_id")?.as_str()?.to_string(),
+        value,
+        param_type,
+        index: message.get("param_index")?.as_u64()? as u16,
+    })
+}
+
+/// PX4 sends integer parameters as their raw bytes reinterpreted as a float.
+fn bytewise_integer(value: f32, param_type: u8) -> f64 {
+    let bytes = value.to_le_bytes();
+    match param_type {
+        1 => f64::from(bytes[0]),
+        2 => f64::from(bytes[0] as i8),
+        3 => f64::from(u16::from_le_bytes([bytes[0], bytes[1]])),
+        4 => f64::from(i16::from_le_bytes([bytes[0], bytes[1]])),
+        5 => f64::from(u32::from_le_bytes(bytes)),
+        _ => f64::from(i32::from_le_bytes(bytes)),
+    }
+}
+
+/// Downloads one plan with the MISSION_REQUEST_INT handshake. Returns `None`
+/// when the vehicle does not answer for this mission type.
+async fn plan(
+    link: &mut MavLink,
+    vehicle: (u8, u8),
+    mission_type: MissionType,
+    options: &ExfilOptions,
+) -> Result<Option<MissionPlan>> {
+    let type_value = u64::from(mission_type.mav_value());
+    let mut fields = target_fields(vehicle).to_vec();
+    fields.push(("mission_type", FieldValue::UInt(type_value)));
+
+    let mut reply = None;
+    for _ in 0..=options.retries {
+        link.send_message(MavlinkVersion::V2, "MISSION_REQUEST_LIST", &fields)
+            .await?;
+        (reply, _) = link
+            .wait_for(options.response_timeout, |parsed, m| {
+                parsed.frame.system_id == vehicle.0
+                    && m.name == "MISSION_COUNT"
+                    && m.get("mission_type").and_then(FieldValue::as_u64) == Some(type_value)
+            })
+            .await;
+        if reply.is_some() {
+            break;
+        }
+    }
+    let Some((_, count_message)) = reply else {
+        return Ok(None);
+    };
+    let expected_count = count_message
+        .get("count")
+        .and_then(FieldValue::as_u64)
+        .unwrap_or(0) as u16;
+
+    let mut items = Vec::with_capacity(usize::from(expected_count));
+    'items: for seq in 0..expected_count {
+        for _ in 0..=options.retries {
+            let mut request = fields.clone();
+            request.push(("seq", FieldValue::UInt(u64::from(seq))));
+            link.send_message(MavlinkVersion::V2, "MISSION_REQUEST_INT", &request)
+                .await?;
+            let (item, _) = link
+                .wait_for(options.response_timeout, |parsed, m| {
+                    parsed.frame.system_id == vehicle.0
+                        && m.name == "MISSION_ITEM_INT"
+                        && m.get("seq").and_then(FieldValue::as_u64) == Some(u64::from(seq))
+                        && m.get("mission_type").and_then(FieldValue::as_u64) == Some(type_value)
+                })
+                .await;
+            if let Some(item) = item.and_then(|(_, m)| mission_item(&m)) {
+                items.push(item);
+                continue 'items;
+            }
+        }
+        tracing::warn!(
+            "{:?} item {} did not arrive, plan is incomplete",
+            mission_type,
+            seq
+        );
+        break;
+    }
+
+    let mut ack = fields;
+    ack.push(("type", FieldValue::UInt(MAV_MISSION_ACCEPTED)));
+    link.send_message(MavlinkVersion::V2, "MISSION_ACK", &ack)
+        .await?;
+
+    Ok(Some(MissionPlan {
+        mission_type,
+        expected_count,
+        items,
+    }))
+}
+
+fn mission_item(message: &MavMessage) -> Option<MissionItem> {
+    let u64_field = |name: &str| message.get(name).and_then(FieldValue::as_u64);
+    let f32_field = |name: &str| {
+        message
+            .get(name)
+            .and_then(FieldValue::as_f64)
+            .map(|v| v as f32)
+    };
+    Some(MissionItem {
+        seq: u64_field("seq")? as u16,
+        frame: u64_field("frame")? as u8,
+        command: u64_field("command")? as u16,
+        current: u64_field("current")? != 0,
+        autocontinue: u64_field("autocontinue")? != 0,
+        params: [
+            f32_field("param1")?,
+            f32_field("param2")?,
+            f32_field("param3")?,
+            f32_field("param4")?,
+        ],
+        x: message.get("x")?.as_i64()? as i32,
+        y: message.get("y")?.as_i64()? as i32,
+        z: f32_field("z")?,
+    })
+}
@@ -0,0 +1,603 @@
+use serde::{Deserialize, Serialize};
+
+pub const STX_V1: u8 = 0xfe;
+pub const STX_V2: u8 = 0xfd;
+
+/// The only incompatibility flag defined by MAVLink 2: a signature follows the CRC.
+pub const INCOMPAT_FLAG_SIGNED: u8 = 0x01;
+
+pub const SIGNATURE_LEN: usize = 13;
+const V1_HEADER_LEN: usize = 6;
+const V2_HEADER_LEN: usize = 10;
+const CRC_LEN: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MavlinkVersion {
+    V1,
+    V2,
+}
+
+/// MAVLink 2 signature block appended to signed frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MavSignature {
+    pub link_id: u8,
+    /// 48-bit timestamp in units of 10us since 2015-01-01.
+    pub timestamp: u64,
+    pub signature: [u8; 6],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MavFrame {
+    pub version: MavlinkVersion,
+    pub incompat_flags: u8,
+    pub compat_flags: u8,
+    pub sequence: u8,
+    pub system_id: u8,
+    pub component_id: u8,
+    pub message_id: u32,
+    /// Payload as received. MAVLink 2 senders trim trailing zero bytes.
+    pub payload: Vec<u8>,
+    pub checksum: u16,
+    pub signature: Option<MavSignature>,
+}
+
+impl MavFrame {
+    pub fn new(
+        version: MavlinkVersion,
+        sequence: u8,
+        system_id: u8,
+        component_id: u8,
+        message_id: u32,
+        payload: Vec<u8>,
+    ) -> Self {
+        Self {
+            version,
+            incompat_flags: 0,
+            compat_flags: 0,
+            sequence,
+            system_id,
+            component_id,
+            message_id,
+            payload,
+            checksum: 0,
+            signature: None,
+        }
+    }
+
+    pub fn is_signed(&self) -> bool {
+        self.incompat_flags & INCOMPAT_FLAG_SIGNED != 0
+    }
+
+    /// Serializes the frame, computing the checksum with `crc_extra`. MAVLink 2
+    /// payloads are zero-trimmed. Signed frames keep their existing signature
+    /// block.
+    pub fn encode(&self, crc_extra: u8) -> Vec<u8> {
+        let mut out = self.encode_unsigned(crc_extra);
+        if let Some(signature) = &self.signature {
+            out.push(signature.link_id);
+            out.extend_from_slice(&signature.timestamp.to_le_bytes()[..6]);
+            out.extend_from_slice(&signature.signature);
+        }
+        out
+    }
+
+    /// Header, payload and CRC without the signature block. This is also the
+    /// data a MAVLink 2 signature is computed over.
+    pub fn encode_unsigned(&self, crc_extra: u8) -> Vec<u8> {
+        let mut payload = self.payload.as_slice();
+        let mut out = Vec::with_capacity(V2_HEADER_LEN + payload.len() + CRC_LEN + SIGNATURE_LEN);
+
+        match self.version {
+            MavlinkVersion::V1 => {
+                out.extend_from_slice(&[
+                    STX_V1,
+                    payload.len() as u8,
+                    self.sequence,
+                    self.system_id,
+                    self.component_id,
+                    self.message_id as u8,
+                ]);
+            }
+            MavlinkVersion::V2 => {
+                while let [rest @ .., 0] = payload {
+                    if rest.is_empty() {
+                        break;
+                    }
+                    payload = rest;
+                }
+                let id = self.message_id.to_le_bytes();
+                out.extend_from_slice(&[
+                    STX_V2,
+                    payload.len() as u8,
+                    self.incompat_flags,
+                    self.compat_flags,
+                    self.sequence,
+                    self.system_id,
+                    self.component_id,
+                    id[0],
+                    id[1],
+                    id[2],
+                ]);
+            }
+        }
+        out.extend_from_slice(payload);
+
+        let crc = crc_accumulate(crc_calculate(&out[1..]), crc_extra);
+        out.extend_from_slice(&crc.to_le_bytes());
+        out
+    }
+}
+
+/// X.25 / CRC-16-MCRF4XX as used by MAVLink.
+pub fn crc_calculate(data: &[u8]) -> u16 {
+    data.iter()
+        .fold(0xffff, |crc, byte| crc_accumulate(crc, *byte))
+}
+
+pub fn crc_accumulate(crc: u16, byte: u8) -> u16 {
+    let mut tmp = byte ^ (crc & 0xff) as u8;
+    tmp ^= tmp << 4;
+    let tmp = u16::from(tmp);
+    (crc >> 8) ^ (tmp << 8) ^ (tmp << 3) ^ (tmp >> 4)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CrcCheck {
+    Valid,
+    /// The message id is not in the dialect, so CRC_EXTRA is unknown.
+    Unverified,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParsedFrame {
+    pub frame: MavFrame,
+    pub crc: CrcCheck,
+    /// Offset of the first byte of the frame in the input stream.
+    pub offset: usize,
+    /// The frame exactly as received, signature included.
+    pub raw: Vec<u8>,
+}
+
+/// Incremental MAVLink v1/v2 frame parser.
+///
+/// Bytes can be pushed in arbitrary chunks. Frames with a bad CRC, unknown
+/// incompatibility flags or a truncated tail are skipped one byte at a time
+/// so the parser resynchronizes on the next start-of-frame marker. A frame
+/// whose CRC cannot be checked is only accepted if no frame with a verified
+/// CRC starts inside it, so a stray STX with a large length byte cannot
+/// swallow the real frames behind it.
+#[derive(Debug, Default)]
+pub struct FrameParser {
+    buf: Vec<u8>,
+    /// Read position in `buf`; everything before it has been handled.
+    pos: usize,
+    consumed: usize,
+    skipped: usize,
+}
+
+impl FrameParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.drain(..self.pos);
+        self.pos = 0;
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Number of bytes discarded as garbage so far.
+    pub fn skipped_bytes(&self) -> usize {
+        self.skipped
+    }
+
+    /// Returns the next complete frame, or `None` when more data is needed.
+    /// `crc_extra` maps a message id to its CRC_EXTRA byte.
+    pub fn next_frame(&mut self, crc_extra: impl Fn(u32) -> Option<u8>) -> Option<ParsedFrame> {
+        self.parse_next(&crc_extra, false)
+    }
+
+    /// Treats the input as complete: drains every remaining frame and drops
+    /// whatever partial frame is left at the end.
+    pub fn finish(&mut self, crc_extra: impl Fn(u32) -> Option<u8>) -> Vec<ParsedFrame> {
+        let mut frames = Vec::new();
+        while let Some(frame) = self.parse_next(&crc_extra, true) {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    fn parse_next(
+        &mut self,
+        crc_extra: &impl Fn(u32) -> Option<u8>,
+        at_eof: bool,
+    ) -> Option<ParsedFrame> {
+        loop {
+            let Some(start) = self.buf[self.pos..]
+                .iter()
+                .position(|b| *b == STX_V1 || *b == STX_V2)
+            else {
+                self.skip(self.buf.len() - self.pos);
+                return None;
+            };
+            self.skip(start);
+
+            match self.check(self.pos, crc_extra) {
+                Check::Frame(len, CrcCheck::Unverified) => {
+                    match self.verified_frame_within(len, crc_extra) {
+                        Some(true) => self.skip(1),
+                        None if !at_eof => return None,
+                        _ => return Some(self.take(len, CrcCheck::Unverified)),
+                    }
+                }
+                Check::Frame(len, crc) => return Some(self.take(len, crc)),
+                Check::Invalid => self.skip(1),
+                Check::Incomplete if at_eof => self.skip(1),
+                Check::Incomplete => return None,
+            }
+        }
+    }
+
+    fn skip(&mut self, count: usize) {
+        self.pos += count;
+        self.consumed += count;
+        self.skipped += count;
+    }
+
+    /// Whether a frame with a verified CRC starts within the `len` bytes of
+    /// the candidate at the read position. `None` if one might, but more
+    /// data is needed to tell.
+    fn verified_frame_within(
+        &self,
+        len: usize,
+        crc_extra: &impl Fn(u32) -> Option<u8>,
+    ) -> Option<bool> {
+        let mut pending = false;
+        for at in self.pos + 1..self.pos + len {
+            if self.buf[at] != STX_V1 && self.buf[at] != STX_V2 {
+                continue;
+            }
+            match self.check(at, crc_extra) {
+                Check::Frame(_, CrcCheck::Valid) => return Some(true),
+                Check::Incomplete => pending = true,
+                _ => {}
+            }
+        }
+        (!pending).then_some(false)
+    }
+
+    /// Validates the frame starting at `at` without consuming it.
+    fn check(&self, at: usize, crc_extra: &impl Fn(u32) -> Option<u8>) -> Check {
+        let buf = &self.buf[at..];
+        let (header_len, message_id, incompat_flags) = if buf[0] == STX_V1 {
+            if buf.len() < V1_HEADER_LEN {
+                return Check::Incomplete;
+            }
+            (V1_HEADER_LEN, u32::from(buf[5]), 0)
+        } else {
+            if buf.len() < V2_HEADER_LEN {
+                return Check::Incomplete;
+            }
+            (
+                V2_HEADER_LEN,
+                u32::from_le_bytes([buf[7], buf[8], buf[9], 0]),
+                buf[2],
+            )
+        };
+        if incompat_flags & !INCOMPAT_FLAG_SIGNED != 0 {
+            return Check::Invalid;
+        }
+        let signed = incompat_flags & INCOMPAT_FLAG_SIGNED != 0;
+
+        let crc_end = header_len + usize::from(buf[1]) + CRC_LEN;
+        let frame_len = crc_end + if signed { SIGNATURE_LEN } else { 0 };
+        if buf.len() < frame_len {
+            return Check::Incomplete;
+        }
+
+        match crc_extra(message_id) {
+            Some(extra) => {
+                let checksum = u16::from_le_bytes([buf[crc_end - 2], buf[crc_end - 1]]);
+                let computed = crc_accumulate(crc_calculate(&buf[1..crc_end - 2]), extra);
+                if computed != checksum {
+                    return Check::Invalid;
+                }
+                Check::Frame(frame_len, CrcCheck::Valid)
+            }
+            None => Check::Frame(frame_len, CrcCheck::Unverified),
+        }
+    }
+
+    /// Decodes and consumes the checked frame of `len` bytes at the read
+    /// position.
+    fn take(&mut self, len: usize, crc: CrcCheck) -> ParsedFrame {
+        let buf = &self.buf[self.pos..self.pos + len];
+        let version = if buf[0] == STX_V1 {
+            MavlinkVersion::V1
+        } else {
+            MavlinkVersion::V2
+        };
+        let (
+            header_len,
+            incompat_flags,
+            compat_flags,
+            sequence,
+            system_id,
+            component_id,
+            message_id,
+        ) = match version {
+            MavlinkVersion::V1 => (
+                V1_HEADER_LEN,
+                0,
+                0,
+                buf[2],
+                buf[3],
+                buf[4],
+                u32::from(buf[5]),
+            ),
+            MavlinkVersion::V2 => (
+                V2_HEADER_LEN,
+                buf[2],
+                buf[3],
+                buf[4],
+                buf[5],
+                buf[6],
+                u32::from_le_bytes([buf[7], buf[8], buf[9], 0]),
+            ),
+        };
+        let payload_len = usize::from(buf[1]);
+        let crc_end = header_len + payload_len + CRC_LEN;
+
+        let signature = (incompat_flags & INCOMPAT_FLAG_SIGNED != 0).then(|| {
+            let sig = &buf[crc_end..len];
+            let mut timestamp = [0u8; 8];
+            timestamp[..6].copy_from_slice(&sig[1..7]);
+            let mut signature = [0u8; 6];
+            signature.copy_from_slice(&sig[7..13]);
+            MavSignature {
+                link_id: sig[0],
+                timestamp: u64::from_le_bytes(timestamp),
+                signature,
+            }
+        });
+
+        let frame = MavFrame {
+            version,
+            incompat_flags,
+            compat_flags,
+            sequence,
+            system_id,
+            component_id,
+            message_id,
+            payload: buf[header_len..header_len + payload_len].to_vec(),
+            checksum: u16::from_le_bytes([buf[crc_end - 2], buf[crc_end - 1]]),
+            signature,
+        };
+        let parsed = ParsedFrame {
+            frame,
+            crc,
+            offset: self.consumed,
+            raw: buf.to_vec(),
+        };
+        self.pos += len;
+        self.consumed += len;
+        parsed
+    }
+}
+
+enum Check {
+    /// A complete frame of this many bytes.
+    Frame(usize, CrcCheck),
+    Invalid,
+    Incomplete,
+}
+
+/// Parses every frame in a complete buffer, e.g. a capture file.
+pub fn parse_frames(data: &[u8], crc_extra: impl Fn(u32) -> Option<u8>) -> Vec<ParsedFrame> {
+    let mut parser = FrameParser::new();
+    parser.push(data);
+    parser.finish(crc_extra)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEARTBEAT: u32 = 0;
+    const ATTITUDE: u32 = 30;
+
+    fn crc_extra(id: u32) -> Option<u8> {
+        match id {
+            HEARTBEAT => Some(50),
+            ATTITUDE => Some(39),
+            _ => None,
+        }
+    }
+
+    /// Deterministic xorshift so failures are reproducible.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn bytes(&mut self, len: usize) -> Vec<u8> {
+            (0..len).map(|_| self.next() as u8).collect()
+        }
+    }
+
+    fn heartbeat(version: MavlinkVersion, sequence: u8) -> Vec<u8> {
+        let payload = vec![0, 0, 0, 0, 2, 3, 0x51, 4, 3];
+        MavFrame::new(version, sequence, 1, 1, HEARTBEAT, payload).encode(50)
+    }
+
+    fn attitude(rng: &mut Rng, sequence: u8) -> Vec<u8> {
+        MavFrame::new(MavlinkVersion::V2, sequence, 1, 1, ATTITUDE, rng.bytes(28)).encode(39)
+    }
+
+    fn verified(frames: &[ParsedFrame]) -> Vec<Vec<u8>> {
+        frames
+            .iter()
+            .filter(|parsed| parsed.crc == CrcCheck::Valid)
+            .map(|parsed| parsed.raw.clone())
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_v1_v2_and_signed_frames() {
+        let mut signed = MavFrame::new(MavlinkVersion::V2, 7, 1, 1, HEARTBEAT, vec![1; 9]);
+        signed.incompat_flags = INCOMPAT_FLAG_SIGNED;
+        signed.signature = Some(MavSignature {
+            link_id: 2,
+            timestamp: 0x0102_0304_0506,
+            signature: [9; 6],
+        });
+        let frames = [
+            heartbeat(MavlinkVersion::V1, 1),
+            heartbeat(MavlinkVersion::V2, 2),
+            signed.encode(50),
+        ];
+
+        let parsed = parse_frames(&frames.concat(), crc_extra);
+        assert_eq!(verified(&parsed), frames);
+        assert_eq!(parsed[2].frame.signature, signed.signature);
+        assert_eq!(parsed[1].offset, frames[0].len());
+    }
+
+    #[test]
+    fn unverified_frame_does_not_swallow_the_next_one() {
+        let mut data = vec![0xfd, 0x20, 0x00, 0x00, 0x00, 0x01, 0x01, 0x55, 0x55, 0x00];
+        let valid = heartbeat(MavlinkVersion::V2, 0);
+        data.extend_from_slice(&valid);
+
+        let parsed = parse_frames(&data, crc_extra);
+        assert_eq!(verified(&parsed), [valid]);
+        assert!(parsed.iter().all(|p| p.crc == CrcCheck::Valid));
+    }
+
+    #[test]
+    fn unverified_frame_is_kept_when_nothing_verifies_inside_it() {
+        let frame = MavFrame::new(MavlinkVersion::V2, 0, 1, 1, 0x5555, vec![7; 4]).encode(0);
+        let parsed = parse_frames(&frame, crc_extra);
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].crc, CrcCheck::Unverified);
+        assert_eq!(parsed[0].frame.message_id, 0x5555);
+    }
+
+    #[test]
+    fn recovers_every_frame_from_byte_soup() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..200 {
+            let mut data = Vec::new();
+            let mut expected = Vec::new();
+            for sequence in 0..8u8 {
+                let garbage_len = (rng.next() % 64) as usize;
+                data.extend(rng.bytes(garbage_len));
+                let frame = match rng.next() % 3 {
+                    0 => heartbeat(MavlinkVersion::V1, sequence),
+                    1 => heartbeat(MavlinkVersion::V2, sequence),
+                    _ => attitude(&mut rng, sequence),
+                };
+                data.extend_from_slice(&frame);
+                expected.push(frame);
+            }
+            let garbage_len = (rng.next() % 64) as usize;
+            data.extend(rng.bytes(garbage_len));
+
+            let parsed = parse_frames(&data, crc_extra);
+            let found = verified(&parsed);
+            let mut remaining = found.iter();
+            for frame in &expected {
+                assert!(remaining.any(|raw| raw == frame), "frame lost in soup");
+            }
+
+            // Feeding the same bytes in random chunks gives the same frames.
+            let mut parser = FrameParser::new();
+            let mut chunked = Vec::new();
+            let mut rest = data.as_slice();
+            while !rest.is_empty() {
+                let len = 1 + (rng.next() % 40) as usize;
+                let (chunk, tail) = rest.split_at(len.min(rest.len()));
+                parser.push(chunk);
+                while let Some(frame) = parser.next_frame(crc_extra) {
+                    chunked.push(frame);
+                }
+                rest = tail;
+            }
+            chunked.extend(parser.finish(crc_extra));
+            assert_eq!(chunked, parsed);
+        }
+    }
+
+    #[test]
+    fn truncated_input_yields_only_complete_frames() {
+        let mut rng = Rng(42);
+        let frames = [
+            heartbeat(MavlinkVersion::V2, 0),
+            attitude(&mut rng, 1),
+            heartbeat(MavlinkVersion::V1, 2),
+        ];
+        let data = frames.concat();
+        for len in 0..=data.len() {
+            let mut complete = 0;
+            let mut end = 0;
+            for frame in &frames {
+                if end + frame.len() > len {
+                    break;
+                }
+                end += frame.len();
+                complete += 1;
+            }
+
+            let mut parser = FrameParser::new();
+            parser.push(&data[..len]);
+            let mut parsed = Vec::new();
+            while let Some(frame) = parser.next_frame(crc_extra) {
+                parsed.push(frame.raw);
+            }
+            assert_eq!(parsed, frames[..complete]);
+
+            // The rest of the stream completes the partial frame.
+            parser.push(&data[len..]);
+            parsed.extend(parser.finish(crc_extra).into_iter().map(|p| p.raw));
+            assert_eq!(parsed, frames);
+        }
+    }
+
+    #[test]
+    fn bit_flips_never_yield_a_corrupted_verified_frame() {
+        let mut rng = Rng(7);
+        let first = attitude(&mut rng, 0);
+        let second = heartbeat(MavlinkVersion::V2, 1);
+        for bit in 0..first.len() * 8 {
+            let mut data = first.clone();
+            data[bit / 8] ^= 1 << (bit % 8);
+            data.extend_from_slice(&second);
+
+            let found = verified(&parse_frames(&data, crc_extra));
+            assert_eq!(found, std::slice::from_ref(&second), "bit {bit}");
+        }
+    }
+
+    #[test]
+    fn garbage_is_parsed_in_linear_time() {
+        let mut rng = Rng(1);
+        let mut data = rng.bytes(1 << 20);
+        // Dense start-of-frame markers are the worst case for resyncing.
+        for byte in data.iter_mut().step_by(3) {
+            *byte = STX_V2;
+        }
+        let start = std::time::Instant::now();
+        let mut parser = FrameParser::new();
+        for chunk in data.chunks(4096) {
+            parser.push(chunk);
+            while parser.next_frame(crc_extra).is_some() {}
+        }
+        parser.finish(crc_extra);
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,780 @@
+use super::dialect::{FieldValue, MavMessage};
+use super::frame::MavlinkVersion;
+use super::link::{MavLink, ProbeExchange};
+use crate::{Finding, Severity};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+const MAV_CMD_COMPONENT_ARM_DISARM: u64 = 400;
+const MAV_CMD_REQUEST_MESSAGE: u64 = 512;
+const MAV_RESULT_ACCEPTED: u64 = 0;
+const MAV_MODE_FLAG_SAFETY_ARMED: u64 = 0x80;
+const AUTOPILOT_VERSION_ID: u64 = 148;
+
+/// Parameters the write test refuses to change whatever it is configured
+/// with: they renumber the vehicle or make the autopilot reset its
+/// parameter storage on the next boot.
+const PROTECTED_PARAMS: [&str; 5] = [
+    "FORMAT_VERSION",
+    "SYSID_THISMAV",
+    "MAV_SYS_ID",
+    "SYS_AUTOSTART",
+    "SYS_AUTOCONFIG",
+];
+
+/// How far command-injection testing may go. Each level includes the ones
+/// below it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskLevel {
+    /// Requests that only make the vehicle report state.
+    #[default]
+    ReadOnly,
+    /// Changes the configured test parameter, reads it back and restores
+    /// the original value.
+    Write,
+    /// Arms a disarmed vehicle and disarms it straight away. Propellers may
+    /// spin; never used on a vehicle that is already armed.
+    Actuate,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandOutcome {
+    Accepted,
+    /// The vehicle processed the request but refused it, e.g. `MAV_RESULT_DENIED`.
+    Rejected(String),
+    NoResponse,
+    /// The vehicle answered, but nothing shows the request took effect.
+    Unconfirmed(String),
+    /// Not sent, with the reason.
+    Skipped(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandAttempt {
+    pub name: String,
+    pub risk: RiskLevel,
+    pub outcome: CommandOutcome,
+    pub exchanges: Vec<ProbeExchange>,
+}
+
+impl CommandAttempt {
+    fn evidence(&self) -> Vec<String> {
+        self.exchanges
+            .iter()
+            .flat_map(ProbeExchange::evidence)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct InjectionReport {
+    /// System and component id of the autopilot, from its HEARTBEAT.
+    pub vehicle: Option<(u8, u8)>,
+    /// The system id the commands were sent from.
+    pub system_id: u8,
+    pub attempts: Vec<CommandAttempt>,
+    /// Parameters the write test changed and could not confirm it set back.
+    pub unrestored: Vec<UnrestoredParam>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnrestoredParam {
+    pub id: String,
+    pub original: f64,
+    /// What the vehicle reported after the restore, if it answered.
+    pub last_read: Option<f64>,
+}
+
+impl InjectionReport {
+    pub fn accepted(&self, risk: RiskLevel) -> impl Iterator<Item = &CommandAttempt> {
+        self.attempts
+            .iter()
+            .filter(move |a| a.risk == risk && a.outcome == CommandOutcome::Accepted)
+    }
+
+    pub fn findings(&self, affected: &str) -> Vec<Finding> {
+        let mut findings = Vec::new();
+
+        let read_only: Vec<_> = self.accepted(RiskLevel::ReadOnly).collect();
+        if !read_only.is_empty() {
+            let names: Vec<_> = read_only.iter().map(|a| a.name.as_str()).collect();
+            let mut finding = Finding::new(
+                Severity::Critical,
+                "MAVLink vehicle accepts commands from an arbitrary system ID".to_string(),
+                format!(
+                    "Unsigned requests sent as system {} were executed: {}. Any host on the \
+                     link can command the vehicle (UAV-001).",
+                    self.system_id,
+                    names.join(", ")
+                ),
+            )
+            .with_affected(affected);
+            finding.evidence = read_only.iter().flat_map(|a| a.evidence()).collect();
+            findings.push(finding);
+        }
+
+        for attempt in self.accepted(RiskLevel::Write) {
+            let restore = if self.unrestored.is_empty() {
+                "The original value was written back and read back afterwards."
+            } else {
+                "Restoring the original value could not be confirmed."
+            };
+            let mut finding = Finding::new(
+                Severity::Critical,
+                "Vehicle parameters writable without authentication".to_string(),
+                format!(
+                    "{} from system {} was applied and read back from the vehicle. {}",
+                    attempt.name, self.system_id, restore
+                ),
+            )
+            .with_affected(affected);
+            finding.evidence = attempt.evidence();
+            findings.push(finding);
+        }
+
+        for param in &self.unrestored {
+            let last_read = param
+                .last_read
+                .map_or_else(|| "no answer".to_string(), |v| v.to_string());
+            findings.push(
+                Finding::new(
+                    Severity::High,
+                    "Vehicle parameter may have been left modified".to_string(),
+                    format!(
+                        "The write test changed {} and could not confirm it was restored \
+                         (last read: {}). Set it back to {} before flight.",
+                        param.id, last_read, param.original
+                    ),
+                )
+                .with_affected(affected),
+            );
+        }
+
+        for attempt in self.accepted(RiskLevel::Actuate) {
+            let mut finding = Finding::new(
+                Severity::Critical,
+                "Vehicle can be armed without authentication".to_string(),
+                format!(
+                    "{} from system {} was accepted; the vehicle was disarmed again \
+                     immediately.",
+                    attempt.name, self.system_id
+                ),
+            )
+            .with_affected(affected);
+            finding.evidence = attempt.evidence();
+            findings.push(finding);
+        }
+
+        findings
+    }
+}
+
+/// Sends commands up to `risk` to the vehicle on `link` and records what it
+/// did with them. Nothing sent moves the aircraft: arming is only tried on a
+/// disarmed vehicle and followed by a disarm, and takeoff is never sent.
+/// The write level only touches `test_param`.
+pub async fn run(
+    link: &mut MavLink,
+    risk: RiskLevel,
+    test_param: &str,
+    discovery: Duration,
+    heartbeat_interval: Duration,
+    response_timeout: Duration,
+) -> Result<InjectionReport> {
+    let mut report = InjectionReport {
+        system_id: link.system_id(),
+        ..Default::default()
+    };
+
+    let Some((heartbeat_frame, heartbeat)) =
+        link.discover_vehicle(discovery, heartbeat_interval).await?
+    else {
+        tracing::warn!("No autopilot heartbeat seen, skipping command injection");
+        return Ok(report);
+    };
+    let target = (
+        heartbeat_frame.frame.system_id,
+        heartbeat_frame.frame.component_id,
+    );
+    report.vehicle = Some(target);
+
+    report
+        .attempts
+        .push(request_message(link, target, response_timeout).await?);
+    report
+        .attempts
+        .push(read_param(link, target, response_timeout).await?);
+    report
+        .attempts
+        .push(request_mission_list(link, target, response_timeout).await?);
+
+    if risk >= RiskLevel::Write {
+        let name = format!("PARAM_SET {}", test_param);
+        let attempt = if test_param.is_empty() {
+            skipped(&name, RiskLevel::Write, "no test parameter configured")
+        } else if PROTECTED_PARAMS.contains(&test_param) {
+            skipped(&name, RiskLevel::Write, "parameter is not safe to change")
+        } else {
+            match read_named(link, target, test_param, response_timeout).await? {
+                (Some(param), _) => {
+                    let (attempt, unrestored) =
+                        write_param(link, target, &param, response_timeout).await?;
+                    report.unrestored.extend(unrestored);
+                    attempt
+                }
+                (None, _) => skipped(&name, RiskLevel::Write, "parameter could not be read"),
+            }
+        };
+        report.attempts.push(attempt);
+    }
+
+    if risk >= RiskLevel::Actuate {
+        let base_mode = heartbeat.get("base_mode").and_then(FieldValue::as_u64);
+        let attempt = match base_mode {
+            Some(mode) if mode & MAV_MODE_FLAG_SAFETY_ARMED == 0 => {
+                arm_and_disarm(link, target, response_timeout).await?
+            }
+            Some(_) => skipped(
+                "COMMAND_LONG COMPONENT_ARM_DISARM",
+                RiskLevel::Actuate,
+                "vehicle is already armed",
+            ),
+            None => skipped(
+                "COMMAND_LONG COMPONENT_ARM_DISARM",
+                RiskLevel::Actuate,
+                "arming state unknown",
+            ),
+        };
+        report.attempts.push(attempt);
+    }
+
+    for attempt in &report.attempts {
+        tracing::info!(
+            "MAVLink injection: {} -> {:?}",
+            attempt.name,
+            attempt.outcome
+        );
+    }
+    Ok(report)
+}
+
+fn skipped(name: &str, risk: RiskLevel, reason: &str) -> CommandAttempt {
+    CommandAttempt {
+        name: name.to_string(),
+        risk,
+        outcome: CommandOutcome::Skipped(reason.to_string()),
+        exchanges: Vec::new(),
+    }
+}
+
+/// Sends a COMMAND_LONG and waits for the matching COMMAND_ACK.
+async fn command_long(
+    link: &mut MavLink,
+    (system, component): (u8, u8),
+    command: u64,
+    params: &[f64],
+    response_timeout: Duration,
+) -> Result<(CommandOutcome, ProbeExchange)> {
+    let param_names = [
+        "param1", "param2", "param3", "param4", "param5", "param6", "param7",
+    ];
+    let mut fields = vec![
+        ("target_system", FieldValue::UInt(u64::from(system))),
+        ("target_component", FieldValue::UInt(u64::from(component))),
+        ("command", FieldValue::UInt(command)),
+    ];
+    fields.extend(
+        param_names
+            .iter()
+            .zip(params)
+            .map(|(name, value)| (*name, FieldValue::Float(*value))),
+    );
+    let sent = link
+        .send_message(MavlinkVersion::V2, "COMMAND_LONG", &fields)
+        .await?;
+
+    let our_id = u64::from(link.system_id());
+    let (reply, _) = link
+        .wait_for(response_timeout, |parsed, m| {
+            // target_system is an extension field; older autopilots leave it 0.
+            let recipient = m.get("target_system").and_then(FieldValue::as_u64);
+            parsed.frame.system_id == system
+                && m.name == "COMMAND_ACK"
+                && m.get("command").and_then(FieldValue::as_u64) == Some(command)
+                && (recipient == Some(0) || recipient == Some(our_id))
+        })
+        .await;
+
+    let outcome = match &reply {
+        Some((_, ack)) => ack_outcome(link, ack),
+        None => CommandOutcome::NoResponse,
+    };
+    let command_name = link
+        .dialect()
+        .enum_def("MAV_CMD")
+        .and_then(|e| e.entry_name(command))
+        .map(|name| name.trim_start_matches("MAV_CMD_").to_string())
+        .unwrap_or_else(|| command.to_string());
+    Ok((
+        outcome,
+        ProbeExchange {
+            description: format!("COMMAND_LONG {}", command_name),
+            sent,
+            reply,
+        },
+    ))
+}
+
+fn ack_outcome(link: &MavLink, ack: &MavMessage) -> CommandOutcome {
+    let result = ack.get("result").and_then(FieldValue::as_u64);
+    if result == Some(MAV_RESULT_ACCEPTED) {
+        return CommandOutcome::Accepted;
+    }
+    let name = result
+        .and_then(|r| {
+            let def = link.dialect().message_by_name("COMMAND_ACK")?;
+            link.dialect().enum_entry(def, "result", r)
+        })
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("{:?}", result));
+    CommandOutcome::Rejected(name)
+}
+
+async fn request_message(
+    link: &mut MavLink,
+    target: (u8, u8),
+    response_timeout: Duration,
+) -> Result<CommandAttempt> {
+    let (outcome, exchange) = command_long(
+        link,
+        target,
+        MAV_CMD_REQUEST_MESSAGE,
+        &[AUTOPILOT_VERSION_ID as f64],
+        response_timeout,
+    )
+    .await?;
+    Ok(CommandAttempt {
+        name: "COMMAND_LONG REQUEST_MESSAGE(AUTOPILOT_VERSION)".to_string(),
+        risk: RiskLevel::ReadOnly,
+        outcome,
+        exchanges: vec![exchange],
+    })
+}
+
+/// A parameter as reported by PARAM_VALUE.
+#[derive(Debug, Clone)]
+struct ParamValue {
+    id: String,
+    value: f64,
+    param_type: u64,
+}
+
+/// Reads parameter index 0, which every autopilot with parameters has.
+async fn read_param(
+    link: &mut MavLink,
+    (system, component): (u8, u8),
+    response_timeout: Duration,
+) -> Result<CommandAttempt> {
+    let sent = link
+        .send_message(
+            MavlinkVersion::V2,
+            "PARAM_REQUEST_READ",
+            &[
+                ("target_system", FieldValue::UInt(u64::from(system))),
+                ("target_component", FieldValue::UInt(u64::from(component))),
+                ("param_index", FieldValue::Int(0)),
+            ],
+        )
+        .await?;
+    let (reply, _) = link
+        .wait_for(response_timeout, |parsed, m| {
+            parsed.frame.system_id == system
+                && m.name == "PARAM_VALUE"
+                && m.get("param_index").and_then(FieldValue::as_u64) == Some(0)
+        })
+        .await;
+
+    Ok(CommandAttempt {
+        name: "PARAM_REQUEST_READ".to_string(),
+        risk: RiskLevel::ReadOnly,
+        outcome: if reply.is_some() {
+            CommandOutcome::Accepted
+        } else {
+            CommandOutcome::NoResponse
+        },
+        exchanges: vec![ProbeExchange {
+            description: "PARAM_REQUEST_READ index 0".to_string(),
+            sent,
+            reply,
+        }],
+    })
+}
+
+fn param_value(message: &MavMessage) -> Option<ParamValue> {
+    Some(ParamValue {
+        id: message.get("param_id")?.as_str()?.to_string(),
+        value: message.get("param_value")?.as_f64()?,
+        param_type: message.get("param_type")?.as_u64()?,
+    })
+}
+
+/// Asks for the mission item count. The download is closed with a
+/// MISSION_ACK straight away so the vehicle does not wait for item requests.
+async fn request_mission_list(
+    link: &mut MavLink,
+    (system, component): (u8, u8),
+    response_timeout: Duration,
+) -> Result<CommandAttempt> {
+    let target = [
+        ("target_system", FieldValue::UInt(u64::from(system))),
+        ("target_component", FieldValue::UInt(u64::from(component))),
+    ];
+    let sent = link
+        .send_message(MavlinkVersion::V2, "MISSION_REQUEST_LIST", &target)
+        .await?;
+    let (reply, _) = link
+        .wait_for(response_timeout, |parsed, m| {
+            parsed.frame.system_id == system && m.name == "MISSION_COUNT"
+        })
+        .await;
+
+    let outcome = match &reply {
+        Some(_) => {
+            link.send_message(MavlinkVersion::V2, "MISSION_ACK", &target)
+                .await?;
+            CommandOutcome::Accepted
+        }
+        None => CommandOutcome::NoResponse,
+    };
+    Ok(CommandAttempt {
+        name: "MISSION_REQUEST_LIST".to_string(),
+        risk: RiskLevel::ReadOnly,
+        outcome,
+        exchanges: vec![ProbeExchange {
+            description: "MISSION_REQUEST_LIST".to_string(),
+            sent,
+            reply,
+        }],
+    })
+}
+
+/// Sets `param` to a different value, confirms the change with a
+/// PARAM_REQUEST_READ and writes the original value back, reading it again
+/// to confirm. The PARAM_VALUE answering a PARAM_SET proves nothing by
+/// itself: autopilots send one with the current value when they refuse the
+/// set too.
+async fn write_param(
+    link: &mut MavLink,
+    target: (u8, u8),
+    param: &ParamValue,
+    response_timeout: Duration,
+) -> Result<(CommandAttempt, Option<UnrestoredParam>)> {
+    // PARAM_VALUE carries a float, so compare at that precision.
+    let same = |read: &Option<ParamValue>, expected: f64| {
+        read.as_ref().map(|p| p.value as f32) == Some(expected as f32)
+    };
+
+    let probe = param.value + 1.0;
+    let (set_reply, set_exchange) = set_param(
+        link,
+        target,
+        param,
+        probe,
+        format!("PARAM_SET {} = {} (was {})", param.id, probe, param.value),
+        response_timeout,
+    )
+    .await?;
+    let (read_back, read_exchange) = read_named(link, target, &param.id, response_timeout).await?;
+    let mut exchanges = vec![set_exchange, read_exchange];
+
+    let outcome = if same(&read_back, probe) {
+        CommandOutcome::Accepted
+    } else if let Some(read) = &read_back {
+        CommandOutcome::Unconfirmed(format!("{} still reads {}", param.id, read.value))
+    } else if set_reply.is_some() {
+        CommandOutcome::Unconfirmed(format!("{} could not be read back", param.id))
+    } else {
+        CommandOutcome::NoResponse
+    };
+
+    let mut unrestored = None;
+    if !same(&read_back, param.value) {
+        let (_, restore_exchange) = set_param(
+            link,
+            target,
+            param,
+            param.value,
+            format!("PARAM_SET {} = {} (restore)", param.id, param.value),
+            response_timeout,
+        )
+        .await?;
+        let (restored, check_exchange) =
+            read_named(link, target, &param.id, response_timeout).await?;
+        exchanges.extend([restore_exchange, check_exchange]);
+        // A vehicle that never answered most likely never applied the set.
+        if !same(&restored, param.value) && outcome != CommandOutcome::NoResponse {
+            tracing::warn!(
+                "Could not confirm {} was restored to {} on system {}",
+                param.id,
+                param.value,
+                target.0
+            );
+            unrestored = Some(UnrestoredParam {
+                id: param.id.clone(),
+                original: param.value,
+                last_read: restored.map(|p| p.value),
+            });
+        }
+    }
+
+    let attempt = CommandAttempt {
+        name: format!("PARAM_SET {}", param.id),
+        risk: RiskLevel::Write,
+        outcome,
+        exchanges,
+    };
+    Ok((attempt, unrestored))
+}
+
+async fn set_param(
+    link: &mut MavLink,
+    target: (u8, u8),
+    param: &ParamValue,
+    value: f64,
+    description: String,
+    response_timeout: Duration,
+) -> Result<(Option<ParamValue>, ProbeExchange)> {
+    let mut fields = param_target(target, &param.id);
+    fields.push(("param_value", FieldValue::Float(value)));
+    fields.push(("param_type", FieldValue::UInt(param.param_type)));
+    param_exchange(
+        link,
+        target.0,
+        &param.id,
+        "PARAM_SET",
+        &fields,
+        description,
+        response_timeout,
+    )
+    .await
+}
+
+/// Reads one parameter by name.
+async fn read_named(
+    link: &mut MavLink,
+    target: (u8, u8),
+    id: &str,
+    response_timeout: Duration,
+) -> Result<(Option<ParamValue>, ProbeExchange)> {
+    let mut fields = param_target(target, id);
+    fields.push(("param_index", FieldValue::Int(-1)));
+    param_exchange(
+        link,
+        target.0,
+        id,
+        "PARAM_REQUEST_READ",
+        &fields,
+        format!("PARAM_REQUEST_READ {}", id),
+        response_timeout,
+    )
+    .await
+}
+
+fn param_target((system, component): (u8, u8), id: &str) -> Vec<(&'static str, FieldValue)> {
+    vec![
+        ("target_system", FieldValue::UInt(u64::from(system))),
+        ("target_component", FieldValue::UInt(u64::from(component))),
+        ("param_id", FieldValue::Text(id.to_string())),
+    ]
+}
+
+/// Sends `message` and waits for the PARAM_VALUE for `id` it should cause.
+async fn param_exchange(
+    link: &mut MavLink,
+    system: u8,
+    id: &str,
+    message: &str,
+    fields: &[(&str, FieldValue)],
+    description: String,
+    response_timeout: Duration,
+) -> Result<(Option<ParamValue>, ProbeExchange)> {
+    let sent = link
+        .send_message(MavlinkVersion::V2, message, fields)
+        .await?;
+    let (reply, _) = link
+        .wait_for(response_timeout, |parsed, m| {
+            parsed.frame.system_id == system
+                && m.name == "PARAM_VALUE"
+                && m.get("param_id").and_then(FieldValue::as_str) == Some(id)
+        })
+        .await;
+    let param = reply.as_ref().and_then(|(_, m)| param_value(m));
+    Ok((
+        param,
+        ProbeExchange {
+            description,
+            sent,
+            reply,
+        },
+    ))
+}
+
+/// Arms without force and disarms at once unless the arm was refused. The
+/// disarm is also sent when the arm ACK was lost, since the vehicle may have
+/// armed anyway.
+async fn arm_and_disarm(
+    link: &mut MavLink,
+    target: (u8, u8),
+    response_timeout: Duration,
+) -> Result<CommandAttempt> {
+    let (outcome, arm) = command_long(
+        link,
+        target,
+        MAV_CMD_COMPONENT_ARM_DISARM,
+        &[1.0],
+        response_timeout,
+    )
+    .await?;
+    let mut exchanges = vec![arm];
+    if !matches!(outcome, CommandOutcome::Rejected(_)) {
+        let (_, disarm) = command_long(
+            link,
+            target,
+            MAV_CMD_COMPONENT_ARM_DISARM,
+            &[0.0],
+            response_timeout,
+        )
+        .await?;
+        exchanges.push(disarm);
+    }
+    Ok(CommandAttempt {
+        name: "COMMAND_LONG COMPONENT_ARM_DISARM".to_string(),
+        risk: RiskLevel::Actuate,
+        outcome,
+        exchanges,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::dialect::Dialect;
+    use super::super::mock::{self, MockAutopilot};
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_millis(300);
+    const TARGET: (u8, u8) = (mock::SYSTEM_ID, mock::COMPONENT_ID);
+    const WRITABLE: &str = "Vehicle parameters writable without authentication";
+    const LEFT_MODIFIED: &str = "Vehicle parameter may have been left modified";
+
+    async fn connect(param_writes: usize) -> MavLink {
+        let address = MockAutopilot {
+            command_result: Some(MAV_RESULT_ACCEPTED),
+            params: vec![("SYSID_THISMAV", 1.0), ("SCR_USER1", 5.0)],
+            param_writes,
+            ..MockAutopilot::default()
+        }
+        .start()
+        .await;
+        MavLink::connect(&address, Dialect::builtin().clone(), 250, 190, TIMEOUT)
+            .await
+            .unwrap()
+    }
+
+    async fn write_test(link: &mut MavLink, test_param: &str) -> InjectionReport {
+        run(
+            link,
+            RiskLevel::Write,
+            test_param,
+            Duration::from_millis(500),
+            Duration::from_millis(100),
+            TIMEOUT,
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn value(link: &mut MavLink, id: &str) -> Option<f64> {
+        let (param, _) = read_named(link, TARGET, id, TIMEOUT).await.unwrap();
+        param.map(|p| p.value)
+    }
+
+    fn write_outcome(report: &InjectionReport) -> &CommandOutcome {
+        let attempt = report
+            .attempts
+            .iter()
+            .find(|a| a.risk == RiskLevel::Write)
+            .expect("write attempted");
+        &attempt.outcome
+    }
+
+    fn titles(report: &InjectionReport) -> Vec<String> {
+        report
+            .findings("udp:127.0.0.1")
+            .into_iter()
+            .map(|f| f.title)
+            .collect()
+    }
+
+    #[test]
+    fn confirmed_write_is_reported_and_restored() {
+        crate::test_runtime().block_on(async {
+            let mut link = connect(usize::MAX).await;
+            let report = write_test(&mut link, "SCR_USER1").await;
+
+            assert_eq!(write_outcome(&report), &CommandOutcome::Accepted);
+            assert!(report.unrestored.is_empty());
+            assert_eq!(value(&mut link, "SCR_USER1").await, Some(5.0));
+            assert_eq!(value(&mut link, "SYSID_THISMAV").await, Some(1.0));
+            assert!(titles(&report).iter().any(|t| t == WRITABLE));
+        });
+    }
+
+    #[test]
+    fn echoed_value_without_change_is_unconfirmed() {
+        crate::test_runtime().block_on(async {
+            let mut link = connect(0).await;
+            let report = write_test(&mut link, "SCR_USER1").await;
+
+            assert_eq!(
+                write_outcome(&report),
+                &CommandOutcome::Unconfirmed("SCR_USER1 still reads 5".to_string())
+            );
+            assert!(report.unrestored.is_empty());
+            assert!(titles(&report).iter().all(|t| t != WRITABLE));
+        });
+    }
+
+    #[test]
+    fn failed_restore_is_reported() {
+        crate::test_runtime().block_on(async {
+            let mut link = connect(1).await;
+            let report = write_test(&mut link, "SCR_USER1").await;
+
+            assert_eq!(write_outcome(&report), &CommandOutcome::Accepted);
+            assert_eq!(
+                report.unrestored,
+                [UnrestoredParam {
+                    id: "SCR_USER1".to_string(),
+                    original: 5.0,
+                    last_read: Some(6.0),
+                }]
+            );
+            let titles = titles(&report);
+            assert!(titles.iter().any(|t| t == WRITABLE));
+            assert!(titles.iter().any(|t| t == LEFT_MODIFIED));
+        });
+    }
+
+    #[test]
+    fn protected_parameters_are_never_written() {
+        crate::test_runtime().block_on(async {
+            let mut link = connect(usize::MAX).await;
+            let report = write_test(&mut link, "SYSID_THISMAV").await;
+
+            assert!(matches!(write_outcome(&report), CommandOutcome::Skipped(_)));
+            assert_eq!(value(&mut link, "SYSID_THISMAV").await, Some(1.0));
+        });
+    }
+}
@@ -0,0 +1,334 @@
+use super::dialect::{Dialect, FieldValue, MavMessage};
+use super::frame::{FrameParser, MavFrame, MavlinkVersion, ParsedFrame};
+use anyhow::{anyhow, bail, Context, Result};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time::{timeout_at, Instant};
+
+pub const MAV_AUTOPILOT_INVALID: u64 = 8;
+
+/// One request sent to an endpoint and the reply it drew, if any.
+#[derive(Debug, Clone)]
+pub struct ProbeExchange {
+    pub description: String,
+    pub sent: Vec<u8>,
+    pub reply: Option<(ParsedFrame, MavMessage)>,
+}
+
+impl ProbeExchange {
+    pub fn accepted(&self) -> bool {
+        self.reply.is_some()
+    }
+
+    /// Hex dumps of the request and reply, for `Finding::evidence`.
+    pub fn evidence(&self) -> Vec<String> {
+        let mut evidence = vec![format!(
+            "{} sent: {}",
+            self.description,
+            super::hex(&self.sent)
+        )];
+        if let Some((parsed, message)) = &self.reply {
+            evidence.push(format!(
+                "{} reply from {}/{}: {}",
+                message.name,
+                parsed.frame.system_id,
+                parsed.frame.component_id,
+                super::hex(&parsed.raw)
+            ));
+        }
+        evidence
+    }
+}
+
+/// Where and how to reach a MAVLink endpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkAddress {
+    /// Send to a UDP endpoint (e.g. an autopilot or MAVProxy `udpin`).
+    Udp(String),
+    /// Listen on a local UDP port and talk to whoever sends first, for
+    /// vehicles configured with `udpout` towards the GCS.
+    UdpIn(String),
+    Tcp(String),
+}
+
+impl LinkAddress {
+    /// Parses `udp:host:port`, `udpin:addr:port`, `tcp:host:port`,
+    /// `host:port` or a bare host. Bare hosts use UDP 14550; ports 5760-5763
+    /// default to TCP as used by SITL and serial-over-TCP bridges.
+    pub fn parse(target: &str) -> Result<Self> {
+        let (scheme, rest) = match target.split_once(':') {
+            Some((scheme @ ("udp" | "udpin" | "tcp"), rest)) => (Some(scheme), rest),
+            _ => (None, target),
+        };
+        if rest.is_empty() {
+            bail!("empty MAVLink target");
+        }
+
+        let has_port = rest
+            .rsplit_once(':')
+            .is_some_and(|(_, port)| port.parse::<u16>().is_ok())
+            && !rest.ends_with(']');
+        let address = if has_port {
+            rest.to_string()
+        } else {
+            format!("{}:14550", rest)
+        };
+        let port = address
+            .rsplit_once(':')
+            .and_then(|(_, port)| port.parse::<u16>().ok())
+            .ok_or_else(|| anyhow!("invalid MAVLink target: {}", target))?;
+
+        Ok(match scheme {
+            Some("udp") => LinkAddress::Udp(address),
+            Some("udpin") => LinkAddress::UdpIn(address),
+            Some("tcp") => LinkAddress::Tcp(address),
+            _ if (5760..=5763).contains(&port) => LinkAddress::Tcp(address),
+            _ => LinkAddress::Udp(address),
+        })
+    }
+}
+
+enum Transport {
+    Udp {
+        socket: UdpSocket,
+        peer: Option<SocketAddr>,
+    },
+    Tcp(TcpStream),
+}
+
+/// A MAVLink connection that frames, decodes and sends messages as our own
+/// system/component id.
+pub struct MavLink {
+    transport: Transport,
+    parser: FrameParser,
+    dialect: Dialect,
+    system_id: u8,
+    component_id: u8,
+    sequence: u8,
+}
+
+impl MavLink {
+    pub async fn connect(
+        address: &LinkAddress,
+        dialect: Dialect,
+        system_id: u8,
+        component_id: u8,
+        connect_timeout: Duration,
+    ) -> Result<Self> {
+        let transport = match address {
+            LinkAddress::Udp(addr) => {
+                let peer = tokio::net::lookup_host(addr)
+                    .await?
+                    .next()
+                    .ok_or_else(|| anyhow!("{} did not resolve", addr))?;
+                let bind: SocketAddr = if peer.is_ipv4() {
+                    ([0, 0, 0, 0], 0).into()
+                } else {
+                    (std::net::Ipv6Addr::UNSPECIFIED, 0).into()
+                };
+                let socket = UdpSocket::bind(bind).await?;
+                Transport::Udp {
+                    socket,
+                    peer: Some(peer),
+                }
+            }
+            LinkAddress::UdpIn(addr) => {
+                let socket = UdpSocket::bind(addr)
+                    .await
+                    .with_context(|| format!("failed to listen on {}", addr))?;
+                Transport::Udp { socket, peer: None }
+            }
+            LinkAddress::Tcp(addr) => {
+                let stream = tokio::time::timeout(connect_timeout, TcpStream::connect(addr))
+                    .await
+                    .map_err(|_| anyhow!("connection to {} timed out", addr))?
+                    .with_context(|| format!("failed to connect to {}", addr))?;
+                Transport::Tcp(stream)
+            }
+        };
+
+        Ok(Self {
+            transport,
+            parser: FrameParser::new(),
+            dialect,
+            system_id,
+            component_id,
+            sequence: 0,
+        })
+    }
+
+    pub fn dialect(&self) -> &Dialect {
+        &self.dialect
+    }
+
+    pub fn system_id(&self) -> u8 {
+        self.system_id
+    }
+
+    pub fn component_id(&self) -> u8 {
+        self.component_id
+    }
+
+    /// False for a `udpin` link nobody has sent to yet.
+    pub fn has_peer(&self) -> bool {
+        !matches!(self.transport, Transport::Udp { peer: None, .. })
+    }
+
+    /// Encodes and sends `name` as an unsigned frame. Returns the bytes sent.
+    pub async fn send_message(
+        &mut self,
+        version: MavlinkVersion,
+        name: &str,
+        fields: &[(&str, FieldValue)],
+    ) -> Result<Vec<u8>> {
+        let (def, payload) = self.dialect.encode(name, fields)?;
+        if version == MavlinkVersion::V1 && def.id > 255 {
+            bail!("{} cannot be sent over MAVLink 1", name);
+        }
+        let frame = MavFrame::new(
+            version,
+            self.sequence,
+            self.system_id,
+            self.component_id,
+            def.id,
+            payload,
+        );
+        let bytes = frame.encode(def.crc_extra);
+        self.sequence = self.sequence.wrapping_add(1);
+        self.send_raw(&bytes).await?;
+        Ok(bytes)
+    }
+
+    /// Sends pre-encoded bytes, e.g. a captured frame being replayed.
+    pub async fn send_raw(&mut self, bytes: &[u8]) -> Result<()> {
+        match &mut self.transport {
+            Transport::Udp { socket, peer } => {
+                let peer = peer.ok_or_else(|| anyhow!("no UDP peer has connected yet"))?;
+                socket.send_to(bytes, peer).await?;
+            }
+            Transport::Tcp(stream) => stream.write_all(bytes).await?,
+        }
+        Ok(())
+    }
+
+    /// Sends a GCS HEARTBEAT; many endpoints only start streaming to a UDP
+    /// peer after they have heard from it.
+    pub async fn send_heartbeat(&mut self) -> Result<Vec<u8>> {
+        self.send_message(
+            MavlinkVersion::V2,
+            "HEARTBEAT",
+            &[
+                ("type", FieldValue::UInt(6)),
+                ("autopilot", FieldValue::UInt(8)),
+                ("mavlink_version", FieldValue::UInt(3)),
+            ],
+        )
+        .await
+    }
+
+    /// Sends heartbeats until an autopilot announces itself, returning its
+    /// HEARTBEAT. GCS and companion heartbeats (MAV_AUTOPILOT_INVALID) are
+    /// ignored.
+    pub async fn discover_vehicle(
+        &mut self,
+        window: Duration,
+        heartbeat_interval: Duration,
+    ) -> Result<Option<(ParsedFrame, MavMessage)>> {
+        let deadline = Instant::now() + window;
+        let our_id = self.system_id;
+        while Instant::now() < deadline {
+            if self.has_peer() {
+                self.send_heartbeat().await?;
+            }
+            let tick = (Instant::now() + heartbeat_interval).min(deadline);
+            while let Some((parsed, message)) = self.recv_frame(tick).await {
+                let is_autopilot = parsed.frame.system_id != our_id
+                    && message.as_ref().is_some_and(|m| {
+                        m.name == "HEARTBEAT"
+                            && m.get("autopilot").and_then(FieldValue::as_u64)
+                                != Some(MAV_AUTOPILOT_INVALID)
+                    });
+                if is_autopilot {
+                    return Ok(message.map(|m| (parsed, m)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the next frame, or `None` once `deadline` passes.
+    pub async fn recv_frame(
+        &mut self,
+        deadline: Instant,
+    ) -> Option<(ParsedFrame, Option<MavMessage>)> {
+        loop {
+            let dialect = &self.dialect;
+            if let Some(parsed) = self.parser.next_frame(|id| dialect.crc_extra(id)) {
+                let message = dialect.decode(&parsed.frame).ok();
+                return Some((parsed, message));
+            }
+
+            let mut buf = [0u8; 2048];
+            let len = match &mut self.transport {
+                Transport::Udp { socket, peer } => {
+                    let (len, from) = timeout_at(deadline, socket.recv_from(&mut buf))
+                        .await
+                        .ok()?
+                        .ok()?;
+                    match peer {
+                        Some(peer) if *peer != from => continue,
+                        Some(_) => {}
+                        None => *peer = Some(from),
+                    }
+                    len
+                }
+                Transport::Tcp(stream) => {
+                    let len = timeout_at(deadline, stream.read(&mut buf))
+                        .await
+                        .ok()?
+                        .ok()?;
+                    if len == 0 {
+                        return None;
+                    }
+                    len
+                }
+            };
+            self.parser.push(&buf[..len]);
+        }
+    }
+
+    /// Collects every frame received within `window`.
+    pub async fn collect(&mut self, window: Duration) -> Vec<(ParsedFrame, Option<MavMessage>)> {
+        let deadline = Instant::now() + window;
+        let mut frames = Vec::new();
+        while let Some(frame) = self.recv_frame(deadline).await {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    /// Waits until a frame satisfies `matches`, returning it together with
+    /// everything else received in the meantime.
+    pub async fn wait_for(
+        &mut self,
+        window: Duration,
+        matches: impl Fn(&ParsedFrame, &MavMessage) -> bool,
+    ) -> (
+        Option<(ParsedFrame, MavMessage)>,
+        Vec<(ParsedFrame, Option<MavMessage>)>,
+    ) {
+        let deadline = Instant::now() + window;
+        let mut others = Vec::new();
+        while let Some((parsed, message)) = self.recv_frame(deadline).await {
+            match message {
+                Some(message) if matches(&parsed, &message) => {
+                    return (Some((parsed, message)), others);
+                }
+                message => others.push((parsed, message)),
+            }
+        }
+        (None, others)
+    }
+}
@@ -0,0 +1,165 @@
+//! A scripted autopilot on a local UDP socket for link-level tests.
+
+use super::dialect::{Dialect, FieldValue, MavMessage};
+use super::frame::{FrameParser, MavFrame, MavlinkVersion};
+use super::link::LinkAddress;
+use tokio::net::UdpSocket;
+
+pub const SYSTEM_ID: u8 = 1;
+pub const COMPONENT_ID: u8 = 1;
+
+/// A system that is not the vehicle but shares the link, e.g. a second GCS.
+pub const BYSTANDER_ID: u8 = 42;
+
+const MAV_CMD_REQUEST_AUTOPILOT_CAPABILITIES: u64 = 520;
+const MAV_PARAM_TYPE_REAL32: u64 = 9;
+
+type Fields = Vec<(&'static str, FieldValue)>;
+
+/// How the mock answers. It always replies to a HEARTBEAT with its own, as
+/// a disarmed ArduPilot vehicle.
+#[derive(Debug, Clone, Default)]
+pub struct MockAutopilot {
+    /// COMMAND_ACK result for every COMMAND_LONG; `None` ignores commands.
+    pub command_result: Option<u64>,
+    /// Sends AUTOPILOT_VERSION when asked for capabilities, whatever the
+    /// command result, like a periodic broadcast that happens to coincide.
+    pub sends_version: bool,
+    /// `BYSTANDER_ID` sends AUTOPILOT_VERSION whenever the vehicle is asked
+    /// for its capabilities.
+    pub bystander: bool,
+    /// Parameters by index, all REAL32.
+    pub params: Vec<(&'static str, f64)>,
+    /// How many PARAM_SETs change a value; later ones are refused. Either
+    /// way the vehicle answers with the value the parameter now has, as
+    /// ArduPilot and PX4 do.
+    pub param_writes: usize,
+}
+
+impl MockAutopilot {
+    /// Binds to 127.0.0.1 and serves until the test's runtime shuts down.
+    pub async fn start(self) -> LinkAddress {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(self.serve(socket));
+        LinkAddress::Udp(addr.to_string())
+    }
+
+    async fn serve(mut self, socket: UdpSocket) {
+        let dialect = Dialect::builtin();
+        let mut parser = FrameParser::new();
+        let mut sequence = 0u8;
+        let mut buf = [0u8; 2048];
+        while let Ok((len, peer)) = socket.recv_from(&mut buf).await {
+            parser.push(&buf[..len]);
+            while let Some(parsed) = parser.next_frame(|id| dialect.crc_extra(id)) {
+                let Ok(message) = dialect.decode(&parsed.frame) else {
+                    continue;
+                };
+                let sender = parsed.frame.system_id;
+                for (system_id, name, fields) in self.replies(&message, sender) {
+                    let (def, payload) = dialect.encode(name, &fields).unwrap();
+                    let frame = MavFrame::new(
+                        MavlinkVersion::V2,
+                        sequence,
+                        system_id,
+                        COMPONENT_ID,
+                        def.id,
+                        payload,
+                    );
+                    sequence = sequence.wrapping_add(1);
+                    let _ = socket.send_to(&frame.encode(def.crc_extra), peer).await;
+                }
+            }
+        }
+    }
+
+    fn replies(&mut self, message: &MavMessage, sender: u8) -> Vec<(u8, &'static str, Fields)> {
+        let get = |name| message.get(name).and_then(FieldValue::as_u64);
+        let mut replies = Vec::new();
+        match message.name.as_str() {
+            "HEARTBEAT" => replies.push((
+                SYSTEM_ID,
+                "HEARTBEAT",
+                vec![
+                    ("type", FieldValue::UInt(2)),
+                    ("autopilot", FieldValue::UInt(3)),
+                    ("base_mode", FieldValue::UInt(0x51)),
+                    ("system_status", FieldValue::UInt(3)),
+                    ("mavlink_version", FieldValue::UInt(3)),
+                ],
+            )),
+            "COMMAND_LONG" => {
+                let command = get("command").unwrap_or_default();
+                if command == MAV_CMD_REQUEST_AUTOPILOT_CAPABILITIES {
+                    if self.bystander {
+                        replies.push((BYSTANDER_ID, "AUTOPILOT_VERSION", version_fields()));
+                    }
+                    if self.sends_version {
+                        replies.push((SYSTEM_ID, "AUTOPILOT_VERSION", version_fields()));
+                    }
+                }
+                if let Some(result) = self.command_result {
+                    replies.push((
+                        SYSTEM_ID,
+                        "COMMAND_ACK",
+                        vec![
+                            ("command", FieldValue::UInt(command)),
+                            ("result", FieldValue::UInt(result)),
+                            ("target_system", FieldValue::UInt(u64::from(sender))),
+                        ],
+                    ));
+                }
+            }
+            "PARAM_REQUEST_READ" => {
+                let index = message.get("param_index").and_then(FieldValue::as_i64);
+                let index = match index {
+                    Some(index) if index >= 0 => Some(index as usize),
+                    _ => self.param_index(message),
+                };
+                if let Some(index) = index.filter(|&i| i < self.params.len()) {
+                    replies.push(self.param_value(index));
+                }
+            }
+            "PARAM_SET" => {
+                if let Some(index) = self.param_index(message) {
+                    if self.param_writes > 0 {
+                        self.param_writes -= 1;
+                        let value = message.get("param_value").and_then(FieldValue::as_f64);
+                        self.params[index].1 = value.unwrap_or_default();
+                    }
+                    replies.push(self.param_value(index));
+                }
+            }
+            _ => {}
+        }
+        replies
+    }
+
+    fn param_index(&self, message: &MavMessage) -> Option<usize> {
+        let id = message.get("param_id").and_then(FieldValue::as_str)?;
+        self.params.iter().position(|(name, _)| *name == id)
+    }
+
+    fn param_value(&self, index: usize) -> (u8, &'static str, Fields) {
+        let (id, value) = self.params[index];
+        (
+            SYSTEM_ID,
+            "PARAM_VALUE",
+            vec![
+                ("param_id", FieldValue::Text(id.to_string())),
+                ("param_value", FieldValue::Float(value)),
+                ("param_type", FieldValue::UInt(MAV_PARAM_TYPE_REAL32)),
+                ("param_count", FieldValue::UInt(self.params.len() as u64)),
+                ("param_index", FieldValue::UInt(index as u64)),
+            ],
+        )
+    }
+}
+
+fn version_fields() -> Fields {
+    vec![
+        ("capabilities", FieldValue::UInt(0xffef)),
+        ("flight_sw_version", FieldValue::UInt(0x0405_00ff)),
+    ]
+}
@@ -0,0 +1,513 @@
+use super::dialect::{FieldValue, MavMessage};
+use super::frame::{MavlinkVersion, ParsedFrame};
+use super::link::{MavLink, ProbeExchange, MAV_AUTOPILOT_INVALID};
+use crate::{Finding, Severity};
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::Instant;
+
+/// Signing timestamps count 10us ticks since 2015-01-01 00:00:00 UTC.
+const SIGNING_EPOCH_UNIX_SECS: u64 = 1_420_070_400;
+const TICKS_PER_SEC: u64 = 100_000;
+
+/// Signing timestamps further than this from wall-clock time are not GPS or
+/// RTC backed, which widens the replay window after a reboot.
+const MAX_CLOCK_SKEW: Duration = Duration::from_secs(60);
+
+const MAV_CMD_REQUEST_AUTOPILOT_CAPABILITIES: u64 = 520;
+const MAV_RESULT_ACCEPTED: u64 = 0;
+
+/// Current time as a MAVLink 2 signing timestamp.
+pub fn signing_timestamp_now() -> u64 {
+    let since_unix = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let since_epoch = since_unix.saturating_sub(Duration::from_secs(SIGNING_EPOCH_UNIX_SECS));
+    since_epoch.as_micros() as u64 / 10
+}
+
+/// What was learned about MAVLink 2 signing on one link.
+#[derive(Debug, Clone, Default)]
+pub struct SigningAssessment {
+    /// System and component id of the autopilot, from its HEARTBEAT.
+    pub vehicle: Option<(u8, u8)>,
+    pub v1_frames: usize,
+    pub unsigned_v2_frames: usize,
+    pub signed_frames: usize,
+    pub unsigned_sample: Option<Vec<u8>>,
+    pub signed_sample: Option<Vec<u8>>,
+    /// Unsigned MAVLink 2 requests.
+    pub unsigned_probes: Vec<ProbeExchange>,
+    /// The same PING request framed as MAVLink 1, which cannot carry a signature.
+    pub v1_probe: Option<ProbeExchange>,
+    /// A captured signed PING from another system, sent again verbatim.
+    pub replay_probe: Option<ProbeExchange>,
+    pub timestamp_issues: Vec<String>,
+}
+
+impl SigningAssessment {
+    pub fn traffic_seen(&self) -> bool {
+        self.v1_frames + self.unsigned_v2_frames + self.signed_frames > 0
+    }
+
+    pub fn signing_in_use(&self) -> bool {
+        self.signed_frames > 0
+    }
+
+    pub fn unsigned_accepted(&self) -> bool {
+        self.unsigned_probes.iter().any(ProbeExchange::accepted)
+    }
+
+    /// Turns the assessment into findings against `affected`.
+    pub fn findings(&self, affected: &str) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let traffic = format!(
+            "Observed {} MAVLink 1, {} unsigned MAVLink 2 and {} signed MAVLink 2 frames.",
+            self.v1_frames, self.unsigned_v2_frames, self.signed_frames
+        );
+
+        if self.unsigned_accepted() {
+            let description = if self.signing_in_use() {
+                format!(
+                    "The endpoint signs its own traffic but still processed unsigned requests, \
+                     so signing does not stop command injection. {}",
+                    traffic
+                )
+            } else {
+                format!(
+                    "The endpoint processed unsigned requests from an arbitrary system id; any \
+                     host that can reach the link can send commands (UAV-001). {}",
+                    traffic
+                )
+            };
+            let mut finding = Finding::new(
+                Severity::Critical,
+                "MAVLink endpoint accepts unsigned commands".to_string(),
+                description,
+            )
+            .with_affected(affected);
+            for probe in self.unsigned_probes.iter().filter(|p| p.accepted()) {
+                finding.evidence.extend(probe.evidence());
+            }
+            findings.push(finding);
+        } else if self.traffic_seen() && !self.signing_in_use() {
+            let mut finding = Finding::new(
+                Severity::High,
+                "MAVLink 2 message signing not in use".to_string(),
+                format!(
+                    "No signed frames were seen, so messages on this link are not \
+                     authenticated (UAV-001). The unsigned test requests got no reply, which \
+                     may only mean the endpoint ignores them. {}",
+                    traffic
+                ),
+            )
+            .with_affected(affected);
+            if let Some(sample) = &self.unsigned_sample {
+                finding = finding.with_evidence(format!("unsigned frame: {}", super::hex(sample)));
+            }
+            findings.push(finding);
+        }
+
+        if self.signing_in_use() {
+            if let Some(probe) = self.v1_probe.as_ref().filter(|p| p.accepted()) {
+                let mut finding = Finding::new(
+                    Severity::High,
+                    "MAVLink 1 frames accepted on a signed link".to_string(),
+                    "A MAVLink 1 PING, which cannot carry a signature, was answered although \
+                     the endpoint signs its traffic. Downgrading to MAVLink 1 bypasses signing."
+                        .to_string(),
+                )
+                .with_affected(affected);
+                finding.evidence = probe.evidence();
+                findings.push(finding);
+            }
+        }
+
+        if let Some(probe) = self.replay_probe.as_ref().filter(|p| p.accepted()) {
+            let mut finding = Finding::new(
+                Severity::High,
+                "Signed MAVLink messages can be replayed".to_string(),
+                "A signed PING captured from another system was answered again when resent \
+                 verbatim. The receiver does not enforce increasing signing timestamps."
+                    .to_string(),
+            )
+            .with_affected(affected);
+            finding.evidence = probe.evidence();
+            findings.push(finding);
+        }
+
+        if !self.timestamp_issues.is_empty() {
+            let mut finding = Finding::new(
+                Severity::Medium,
+                "Weak MAVLink signing timestamps".to_string(),
+                "Signing timestamps are not tied to real time or are not strictly increasing. \
+                 Frames captured earlier may be accepted again, e.g. after the vehicle reboots."
+                    .to_string(),
+            )
+            .with_affected(affected);
+            finding.evidence = self.timestamp_issues.clone();
+            if let Some(sample) = &self.signed_sample {
+                finding = finding.with_evidence(format!("signed frame: {}", super::hex(sample)));
+            }
+            findings.push(finding);
+        }
+
+        findings
+    }
+}
+
+/// Listens on `link` for `listen`, then sends harmless unsigned requests and,
+/// when possible, replays a captured signed PING. Nothing sent changes
+/// vehicle state.
+pub async fn assess(
+    link: &mut MavLink,
+    listen: Duration,
+    heartbeat_interval: Duration,
+    response_timeout: Duration,
+) -> Result<SigningAssessment> {
+    let mut assessment = SigningAssessment::default();
+    let our_id = link.system_id();
+
+    let mut observed = Vec::new();
+    let deadline = Instant::now() + listen;
+    while Instant::now() < deadline {
+        if link.has_peer() {
+            link.send_heartbeat().await?;
+        }
+        let tick = (Instant::now() + heartbeat_interval).min(deadline);
+        while let Some((parsed, message)) = link.recv_frame(tick).await {
+            if parsed.frame.system_id != our_id {
+                observed.push((parsed, message));
+            }
+        }
+    }
+
+    for (parsed, message) in &observed {
+        let frame = &parsed.frame;
+        match (frame.version, frame.is_signed()) {
+            (MavlinkVersion::V1, _) => assessment.v1_frames += 1,
+            (MavlinkVersion::V2, false) => {
+                assessment.unsigned_v2_frames += 1;
+                assessment
+                    .unsigned_sample
+                    .get_or_insert_with(|| parsed.raw.clone());
+            }
+            (MavlinkVersion::V2, true) => {
+                assessment.signed_frames += 1;
+                assessment
+                    .signed_sample
+                    .get_or_insert_with(|| parsed.raw.clone());
+            }
+        }
+        if assessment.vehicle.is_none() {
+            let is_autopilot = message.as_ref().is_some_and(|m| {
+                m.name == "HEARTBEAT"
+                    && m.get("autopilot").and_then(FieldValue::as_u64)
+                        != Some(MAV_AUTOPILOT_INVALID)
+            });
+            if is_autopilot {
+                assessment.vehicle = Some((frame.system_id, frame.component_id));
+            }
+        }
+    }
+    assessment.timestamp_issues = timestamp_issues(&observed);
+
+    let (target_system, target_component) = assessment.vehicle.unwrap_or((0, 0));
+    tracing::info!(
+        "MAVLink signing: {} frames observed, vehicle {:?}",
+        observed.len(),
+        assessment.vehicle
+    );
+
+    assessment
+        .unsigned_probes
+        .push(ping(link, MavlinkVersion::V2, response_timeout).await?);
+    assessment
+        .unsigned_probes
+        .push(request_capabilities(link, target_system, target_component, response_timeout).await?);
+    assessment.v1_probe = Some(ping(link, MavlinkVersion::V1, response_timeout).await?);
+
+    let replayable = observed.iter().rev().find(|(parsed, message)| {
+        parsed.frame.is_signed()
+            && parsed.frame.system_id != target_system
+            && message.as_ref().is_some_and(|m| {
+                m.name == "PING" && m.get("target_system").and_then(FieldValue::as_u64) == Some(0)
+            })
+    });
+    if let Some((parsed, Some(message))) = replayable {
+        assessment.replay_probe = Some(replay_ping(link, parsed, message, response_timeout).await?);
+    }
+
+    Ok(assessment)
+}
+
+/// Broadcast PING request; replies are addressed back to our system id.
+async fn ping(
+    link: &mut MavLink,
+    version: MavlinkVersion,
+    response_timeout: Duration,
+) -> Result<ProbeExchange> {
+    let now = signing_timestamp_now();
+    let seq = (now & 0xffff_ffff) as u32;
+    let sent = link
+        .send_message(
+            version,
+            "PING",
+            &[
+                ("time_usec", FieldValue::UInt(now * 10)),
+                ("seq", FieldValue::UInt(u64::from(seq))),
+            ],
+        )
+        .await?;
+    let our_id = u64::from(link.system_id());
+    let (reply, _) = link
+        .wait_for(response_timeout, |_, m| {
+            m.name == "PING"
+                && m.get("seq").and_then(FieldValue::as_u64) == Some(u64::from(seq))
+                && m.get("target_system").and_then(FieldValue::as_u64) == Some(our_id)
+        })
+        .await;
+    Ok(ProbeExchange {
+        description: format!("unsigned {:?} PING", version),
+        sent,
+        reply,
+    })
+}
+
+/// MAV_CMD_REQUEST_AUTOPILOT_CAPABILITIES only makes the autopilot report
+/// its AUTOPILOT_VERSION.
+///
+/// Only frames from `target_system` count: an AUTOPILOT_VERSION requested
+/// by another GCS or broadcast periodically says nothing about ours. An
+/// accepted COMMAND_ACK addressed to us is the answer; an AUTOPILOT_VERSION
+/// from the vehicle is only used for autopilots that never acknowledge, and
+/// a refusing ACK overrides it.
+async fn request_capabilities(
+    link: &mut MavLink,
+    target_system: u8,
+    target_component: u8,
+    response_timeout: Duration,
+) -> Result<ProbeExchange> {
+    let sent = link
+        .send_message(
+            MavlinkVersion::V2,
+            "COMMAND_LONG",
+            &[
+                ("target_system", FieldValue::UInt(u64::from(target_system))),
+                (
+                    "target_component",
+                    FieldValue::UInt(u64::from(target_component)),
+                ),
+                (
+                    "command",
+                    FieldValue::UInt(MAV_CMD_REQUEST_AUTOPILOT_CAPABILITIES),
+                ),
+                ("param1", FieldValue::Float(1.0)),
+            ],
+        )
+        .await?;
+    let our_id = u64::from(link.system_id());
+    let deadline = Instant::now() + response_timeout;
+    let mut version = None;
+    let mut ack = None;
+    while let Some((parsed, message)) = link.recv_frame(deadline).await {
+        let Some(message) = message else {
+            continue;
+        };
+        if parsed.frame.system_id != target_system {
+            continue;
+        }
+        match message.name.as_str() {
+            "COMMAND_ACK"
+                if message.get("command").and_then(FieldValue::as_u64)
+                    == Some(MAV_CMD_REQUEST_AUTOPILOT_CAPABILITIES) =>
+            {
+                // target_system is an extension field; older autopilots leave it 0.
+                let recipient = message.get("target_system").and_then(FieldValue::as_u64);
+                if recipient.is_some_and(|r| r != 0 && r != our_id) {
+                    continue;
+                }
+                ack = Some((parsed, message));
+                break;
+            }
+            "AUTOPILOT_VERSION" => {
+                version.get_or_insert((parsed, message));
+            }
+            _ => {}
+        }
+    }
+    let reply = match ack {
+        Some((_, ref m))
+            if m.get("result").and_then(FieldValue::as_u64) != Some(MAV_RESULT_ACCEPTED) =>
+        {
+            None
+        }
+        Some(ack) => Some(ack),
+        None => version,
+    };
+    Ok(ProbeExchange {
+        description: "unsigned COMMAND_LONG REQUEST_AUTOPILOT_CAPABILITIES".to_string(),
+        sent,
+        reply,
+    })
+}
+
+/// Resends a captured signed PING. A reply echoing its `seq` and `time_usec`
+/// means the stale signature was accepted.
+async fn replay_ping(
+    link: &mut MavLink,
+    captured: &ParsedFrame,
+    message: &MavMessage,
+    response_timeout: Duration,
+) -> Result<ProbeExchange> {
+    link.send_raw(&captured.raw).await?;
+    let seq = message.get("seq").and_then(FieldValue::as_u64);
+    let time_usec = message.get("time_usec").and_then(FieldValue::as_u64);
+    let sender = u64::from(captured.frame.system_id);
+    let (reply, _) = link
+        .wait_for(response_timeout, |_, m| {
+            m.name == "PING"
+                && m.get("seq").and_then(FieldValue::as_u64) == seq
+                && m.get("time_usec").and_then(FieldValue::as_u64) == time_usec
+                && m.get("target_system").and_then(FieldValue::as_u64) == Some(sender)
+        })
+        .await;
+    Ok(ProbeExchange {
+        description: format!("replayed signed PING from system {}", sender),
+        sent: captured.raw.clone(),
+        reply,
+    })
+}
+
+/// Checks that every signing stream (system, component, link id) uses
+/// increasing timestamps close to wall-clock time.
+fn timestamp_issues(observed: &[(ParsedFrame, Option<MavMessage>)]) -> Vec<String> {
+    let now = signing_timestamp_now();
+    let max_skew = MAX_CLOCK_SKEW.as_secs() * TICKS_PER_SEC;
+    let mut last_seen: HashMap<(u8, u8, u8), u64> = HashMap::new();
+    let mut reported = HashSet::new();
+    let mut issues = Vec::new();
+
+    for (parsed, _) in observed {
+        let frame = &parsed.frame;
+        let Some(signature) = &frame.signature else {
+            continue;
+        };
+        let stream = (frame.system_id, frame.component_id, signature.link_id);
+
+        if let Some(previous) = last_seen.insert(stream, signature.timestamp) {
+            if signature.timestamp <= previous && reported.insert(stream) {
+                issues.push(format!(
+                    "system {} component {} link {}: timestamp {} does not increase (previous {})",
+                    stream.0, stream.1, stream.2, signature.timestamp, previous
+                ));
+            }
+            continue;
+        }
+
+        if now.abs_diff(signature.timestamp) > max_skew {
+            let skew_secs = (now as i128 - signature.timestamp as i128) / TICKS_PER_SEC as i128;
+            issues.push(format!(
+                "system {} component {} link {}: timestamp {} is {}s {} wall-clock time",
+                stream.0,
+                stream.1,
+                stream.2,
+                signature.timestamp,
+                skew_secs.abs(),
+                if skew_secs > 0 { "behind" } else { "ahead of" }
+            ));
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::dialect::Dialect;
+    use super::super::mock::{self, MockAutopilot};
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_millis(300);
+
+    async fn capabilities_reply(autopilot: MockAutopilot) -> Option<String> {
+        let address = autopilot.start().await;
+        let mut link = MavLink::connect(&address, Dialect::builtin().clone(), 250, 190, TIMEOUT)
+            .await
+            .unwrap();
+        let probe = request_capabilities(&mut link, mock::SYSTEM_ID, mock::COMPONENT_ID, TIMEOUT)
+            .await
+            .unwrap();
+        probe.reply.map(|(_, message)| message.name)
+    }
+
+    #[test]
+    fn accepted_command_ack_counts() {
+        crate::test_runtime().block_on(async {
+            let reply = capabilities_reply(MockAutopilot {
+                command_result: Some(MAV_RESULT_ACCEPTED),
+                sends_version: true,
+                ..MockAutopilot::default()
+            })
+            .await;
+            assert_eq!(reply.as_deref(), Some("COMMAND_ACK"));
+        });
+    }
+
+    #[test]
+    fn version_from_another_system_does_not_count() {
+        crate::test_runtime().block_on(async {
+            let reply = capabilities_reply(MockAutopilot {
+                bystander: true,
+                ..MockAutopilot::default()
+            })
+            .await;
+            assert_eq!(reply, None);
+        });
+    }
+
+    #[test]
+    fn refused_command_overrides_a_version_broadcast() {
+        crate::test_runtime().block_on(async {
+            let reply = capabilities_reply(MockAutopilot {
+                command_result: Some(4),
+                sends_version: true,
+                bystander: true,
+                ..MockAutopilot::default()
+            })
+            .await;
+            assert_eq!(reply, None);
+        });
+    }
+
+    #[test]
+    fn assessment_reports_unsigned_commands_from_the_vehicle() {
+        crate::test_runtime().block_on(async {
+            let address = MockAutopilot {
+                command_result: Some(MAV_RESULT_ACCEPTED),
+                ..MockAutopilot::default()
+            }
+            .start()
+            .await;
+            let mut link =
+                MavLink::connect(&address, Dialect::builtin().clone(), 250, 190, TIMEOUT)
+                    .await
+                    .unwrap();
+            let assessment = assess(&mut link, TIMEOUT, Duration::from_millis(100), TIMEOUT)
+                .await
+                .unwrap();
+
+            assert_eq!(
+                assessment.vehicle,
+                Some((mock::SYSTEM_ID, mock::COMPONENT_ID))
+            );
+            assert!(!assessment.signing_in_use());
+            assert!(assessment.unsigned_accepted());
+            let findings = assessment.findings("udp:127.0.0.1");
+            assert_eq!(
+                findings[0].title,
+                "MAVLink endpoint accepts unsigned commands"
+            );
+        });
+    }
+}
@@ -0,0 +1,905 @@
+pub mod auth;
+pub mod sdp;
+
+pub use auth::{AuthScheme, Challenge};
+pub use sdp::{MediaDescription, SessionDescription};
+
+use crate::credentials::{CredentialList, DefaultCredential};
+use crate::SecurityConfig;
+use anyhow::{anyhow, bail, Context, Result};
+use data::{AssetNode, ScanType, VulnData, VulnSeverity};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, timeout_at, Instant};
+
+const BUILTIN_PATHS: &str = include_str!("../signatures/rtsp_paths.txt");
+
+/// Responses larger than this are cut; an SDP is a few hundred bytes.
+const MAX_RESPONSE_BYTES: usize = 64 * 1024;
+
+/// Consecutive connection failures after which path enumeration gives up.
+const MAX_CONNECT_FAILURES: usize = 3;
+
+/// Mirrors the `[rtsp]` section of `config.toml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RtspConfig {
+    pub user_agent: String,
+    pub connect_timeout_ms: u64,
+    pub read_timeout_ms: u64,
+    /// Stream paths tried per server, from the top of the path list.
+    pub max_paths: usize,
+}
+
+impl Default for RtspConfig {
+    fn default() -> Self {
+        Self {
+            user_agent: "uavred".to_string(),
+            connect_timeout_ms: 3000,
+            read_timeout_ms: 5000,
+            max_paths: 64,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RtspResponse {
+    pub status: u16,
+    pub reason: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl RtspResponse {
+    /// First header named `name`, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Every `WWW-Authenticate` challenge the response carries.
+    pub fn challenges(&self) -> Vec<Challenge> {
+        self.headers
+            .iter()
+            .filter(|(n, _)| n.eq_ignore_ascii_case("www-authenticate"))
+            .filter_map(|(_, v)| Challenge::parse(v))
+            .collect()
+    }
+
+    fn has_sdp(&self) -> bool {
+        self.header("content-type")
+            .is_some_and(|ct| ct.to_ascii_lowercase().contains("sdp"))
+            || self.body.contains("\nm=")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StreamAccess {
+    /// `DESCRIBE` succeeded without credentials.
+    Open,
+    /// The server asked for credentials and none of the defaults worked.
+    Protected,
+    /// `DESCRIBE` succeeded with a default credential.
+    DefaultCredential,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RtspStream {
+    pub path: String,
+    pub url: String,
+    pub access: StreamAccess,
+    pub credential: Option<DefaultCredential>,
+    pub sdp: Option<SessionDescription>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RtspReport {
+    pub target: String,
+    pub server: Option<String>,
+    /// Methods from the `Public` header of the `OPTIONS` reply.
+    pub methods: Vec<String>,
+    /// The server answers `DESCRIBE` for any path, so paths were not
+    /// enumerated.
+    pub wildcard: bool,
+    pub streams: Vec<RtspStream>,
+    /// Authentication schemes offered in `WWW-Authenticate` challenges.
+    pub auth_schemes: Vec<AuthScheme>,
+    pub logins: Vec<DefaultCredential>,
+    pub attempts: usize,
+    /// The server started refusing requests during credential testing.
+    pub locked_out: bool,
+    /// Credential testing stopped at the rejected-login budget.
+    pub stopped_early: bool,
+}
+
+impl RtspReport {
+    fn affected(&self) -> String {
+        format!("{}/tcp", self.target)
+    }
+
+    pub fn open_streams(&self) -> impl Iterator<Item = &RtspStream> {
+        self.streams
+            .iter()
+            .filter(|s| s.access == StreamAccess::Open)
+    }
+
+    /// Findings as `VulnData`: CWE-306 for streams served without
+    /// authentication, CWE-798 for accepted default credentials and
+    /// CWE-523 for Basic authentication over plain RTSP.
+    pub fn vulnerabilities(&self) -> Vec<VulnData> {
+        let affected = self.affected();
+        let detection_time = chrono::Utc::now().to_rfc3339();
+        let mut vulns = Vec::new();
+
+        for stream in self.open_streams() {
+            let mut vuln = VulnData::new(
+                format!("RTSP-NOAUTH:{}:{}", affected, stream.path),
+                "Unauthenticated RTSP video stream".to_string(),
+                format!(
+                    "{} serves the stream {} without authentication{}. Anyone on \
+                     the link can watch the camera feed.",
+                    affected,
+                    stream.url,
+                    codec_summary(stream)
+                ),
+                VulnSeverity::High,
+            );
+            vuln.cwe = Some("CWE-306".to_string());
+            vuln.references = vec!["https://cwe.mitre.org/data/definitions/306.html".to_string()];
+            vuln.remediation = Some(
+                "Enable authentication on the RTSP server, or bind it to the \
+                 companion computer's internal interface only."
+                    .to_string(),
+            );
+            vulns.push(self.fill(vuln, &detection_time));
+        }
+
+        for credential in &self.logins {
+            let paths: Vec<&str> = self
+                .streams
+                .iter()
+                .filter(|s| s.credential.as_ref() == Some(credential))
+                .map(|s| s.path.as_str())
+                .collect();
+            let vendor = credential
+                .vendor
+                .as_ref()
+                .map(|v| format!(" ({} default)", v))
+                .unwrap_or_default();
+            let mut vuln = VulnData::new(
+                format!("RTSP-DEFAULT-CRED:{}:{}", affected, credential.username),
+                "Default RTSP credentials accepted".to_string(),
+                format!(
+                    "{} accepts the default login {}{} for {}.",
+                    affected,
+                    credential.display(),
+                    vendor,
+                    paths.join(", ")
+                ),
+                VulnSeverity::High,
+            );
+            vuln.cwe = Some("CWE-798".to_string());
+            vuln.affected_systems = credential.vendor.iter().cloned().collect();
+            vuln.references = vec!["https://cwe.mitre.org/data/definitions/798.html".to_string()];
+            vuln.remediation =
+                Some("Change the camera's factory password before flight.".to_string());
+            vulns.push(self.fill(vuln, &detection_time));
+        }
+
+        if self.auth_schemes.contains(&AuthScheme::Basic) {
+            let mut vuln = VulnData::new(
+                format!("RTSP-BASIC-AUTH:{}", affected),
+                "RTSP Basic authentication over cleartext".to_string(),
+                format!(
+                    "{} offers Basic authentication on plain RTSP, so the \
+                     camera password crosses the link base64-encoded.",
+                    affected
+                ),
+                VulnSeverity::Medium,
+            );
+            vuln.cwe = Some("CWE-523".to_string());
+            vuln.references = vec!["https://cwe.mitre.org/data/definitions/523.html".to_string()];
+            vuln.remediation = Some("Allow Digest authentication only.".to_string());
+            vulns.push(self.fill(vuln, &detection_time));
+        }
+
+        let publishing: Vec<&str> = self
+            .methods
+            .iter()
+            .map(String::as_str)
+            .filter(|m| m.eq_ignore_ascii_case("ANNOUNCE") || m.eq_ignore_ascii_case("RECORD"))
+            .collect();
+        if !publishing.is_empty() && self.open_streams().next().is_some() {
+            let mut vuln = VulnData::new(
+                format!("RTSP-PUBLISH:{}", affected),
+                "RTSP server advertises unauthenticated publishing".to_string(),
+                format!(
+                    "{} serves streams without authentication and advertises {}. \
+                     If publishing is equally unprotected, a client on the link \
+                     can replace the video feed the operator sees.",
+                    affected,
+                    publishing.join(" and ")
+                ),
+                VulnSeverity::Medium,
+            );
+            vuln.cwe = Some("CWE-306".to_string());
+            vuln.remediation = Some(
+                "Require authentication for ANNOUNCE and RECORD, or disable \
+                 publishing on the video server."
+                    .to_string(),
+            );
+            vulns.push(self.fill(vuln, &detection_time));
+        }
+        vulns
+    }
+
+    fn fill(&self, mut vuln: VulnData, detection_time: &str) -> VulnData {
+        vuln.affected = self.affected();
+        if let Some(server) = &self.server {
+            vuln.affected_systems.push(server.clone());
+        }
+        vuln.detection_time = detection_time.to_string();
+        vuln.detection_location.component = "rtsp".to_string();
+        vuln.scan_type = ScanType::Network;
+        vuln.tags = vec!["rtsp".to_string(), "video".to_string()];
+        vuln
+    }
+
+    /// Working logins as `data::Credential` with `auth_type = "rtsp"`.
+    pub fn credentials(&self) -> Vec<data::Credential> {
+        self.logins
+            .iter()
+            .map(|credential| credential.to_credential("rtsp"))
+            .collect()
+    }
+
+    /// Records working logins on `asset`, skipping ones already present.
+    pub fn apply_to(&self, asset: &mut AssetNode) {
+        for credential in self.credentials() {
+            let known = asset.credentials.iter().any(|c| {
+                c.auth_type == credential.auth_type
+                    && c.username == credential.username
+                    && c.password == credential.password
+            });
+            if !known {
+                asset.credentials.push(credential);
+            }
+        }
+    }
+}
+
+/// `, H264/90000 video and PCMA/8000 audio`-style suffix for descriptions.
+fn codec_summary(stream: &RtspStream) -> String {
+    let codecs = stream
+        .sdp
+        .as_ref()
+        .map(SessionDescription::codecs)
+        .unwrap_or_default();
+    if codecs.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", codecs.join(", "))
+    }
+}
+
+enum LoginOutcome {
+    Success(RtspResponse),
+    Rejected(Vec<Challenge>),
+    /// Anything but 200 or 401, or no answer: the server is throttling us.
+    Refused,
+}
+
+/// Tests an RTSP server for streams that play without authentication,
+/// default credentials and weak authentication schemes.
+pub struct RtspChecker {
+    config: RtspConfig,
+    credentials: CredentialList,
+    max_failed_attempts: usize,
+    paths: Vec<String>,
+    cseq: AtomicU32,
+}
+
+impl RtspChecker {
+    pub fn new() -> Self {
+        Self::with_config(RtspConfig::default())
+    }
+
+    pub fn with_config(config: RtspConfig) -> Self {
+        let mut checker = Self {
+            config,
+            credentials: CredentialList::new(),
+            max_failed_attempts: SecurityConfig::default().max_failed_attempts,
+            paths: Vec::new(),
+            cseq: AtomicU32::new(1),
+        };
+        checker.add_paths(BUILTIN_PATHS);
+        checker
+    }
+
+    /// Replaces the default-credential list, e.g. with one that also loaded
+    /// vendor-specific pairs.
+    pub fn with_credentials(mut self, credentials: CredentialList) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    /// Takes the rejected-login budget from `[security]`.
+    pub fn with_security(mut self, security: &SecurityConfig) -> Self {
+        self.max_failed_attempts = security.max_failed_attempts;
+        self
+    }
+
+    pub fn paths(&self) -> &[String] {
+        &self.paths
+    }
+
+    pub fn load_paths(&mut self, path: &Path) -> Result<usize> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read RTSP path list {}", path.display()))?;
+        Ok(self.add_paths(&content))
+    }
+
+    /// Appends stream paths (one per line, `#` comments).
+    pub fn add_paths(&mut self, content: &str) -> usize {
+        let before = self.paths.len();
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let path = if line.starts_with('/') {
+                line.to_string()
+            } else {
+                format!("/{}", line)
+            };
+            if !self.paths.contains(&path) {
+                self.paths.push(path);
+            }
+        }
+        self.paths.len() - before
+    }
+
+    pub async fn check(&self, addr: SocketAddr) -> Result<RtspReport> {
+        tracing::info!("Checking RTSP service on {}", addr);
+        let options = self.request(addr, "OPTIONS", "/", &[]).await?;
+        let mut report = RtspReport {
+            target: addr.to_string(),
+            server: options.header("server").map(str::to_string),
+            methods: options
+                .header("public")
+                .map(|p| {
+                    p.split(',')
+                        .map(|m| m.trim().to_ascii_uppercase())
+                        .filter(|m| !m.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            wildcard: false,
+            streams: Vec::new(),
+            auth_schemes: Vec::new(),
+            logins: Vec::new(),
+            attempts: 0,
+            locked_out: false,
+            stopped_early: false,
+        };
+
+        // A path that cannot exist tells whether answers mean anything.
+        let probe = format!("/uavred-{}", chrono::Utc::now().timestamp_millis());
+        let calibration = self.describe(addr, &probe, &[]).await.ok();
+        let catch_all = calibration
+            .as_ref()
+            .is_some_and(|r| r.status == 200 || r.status == 401);
+        report.wildcard = calibration.as_ref().is_some_and(|r| r.status == 200);
+        let paths: Vec<&String> = if catch_all {
+            self.paths.iter().take(1).collect()
+        } else {
+            self.paths.iter().take(self.config.max_paths).collect()
+        };
+
+        let mut challenges = Vec::new();
+        let mut connect_failures = 0;
+        for path in paths {
+            let response = match self.describe(addr, path, &[]).await {
+                Ok(response) => response,
+                Err(e) => {
+                    tracing::debug!("RTSP DESCRIBE {} on {} failed: {}", path, addr, e);
+                    connect_failures += 1;
+                    if connect_failures >= MAX_CONNECT_FAILURES {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            connect_failures = 0;
+            let access = match response.status {
+                200 if response.has_sdp() => StreamAccess::Open,
+                401 => {
+                    for challenge in response.challenges() {
+                        if !report.auth_schemes.contains(&challenge.scheme) {
+                            report.auth_schemes.push(challenge.scheme);
+                        }
+                        challenges.push(challenge);
+                    }
+                    StreamAccess::Protected
+                }
+                _ => continue,
+            };
+            report.streams.push(RtspStream {
+                path: path.clone(),
+                url: self.url(addr, path),
+                access,
+                credential: None,
+                sdp: (access == StreamAccess::Open)
+                    .then(|| SessionDescription::parse(&response.body)),
+            });
+        }
+
+        if let Some(index) = report
+            .streams
+            .iter()
+            .position(|s| s.access == StreamAccess::Protected)
+        {
+            self.try_credentials(addr, index, challenges, &mut report)
+                .await;
+        }
+
+        tracing::info!(
+            "RTSP check of {}: {} open streams, {} protected, {} default logins, {} attempts",
+            addr,
+            report.open_streams().count(),
+            report
+                .streams
+                .iter()
+                .filter(|s| s.access == StreamAccess::Protected)
+                .count(),
+            report.logins.len(),
+            report.attempts
+        );
+        Ok(report)
+    }
+
+    /// Tries default credentials against the stream at `index`, then opens
+    /// the other protected streams with the first pair that works.
+    async fn try_credentials(
+        &self,
+        addr: SocketAddr,
+        index: usize,
+        mut challenges: Vec<Challenge>,
+        report: &mut RtspReport,
+    ) {
+        let path = report.streams[index].path.clone();
+        let mut failures = 0;
+        let mut working = None;
+        for credential in self.credentials.for_service("rtsp") {
+            if self.max_failed_attempts > 0 && failures >= self.max_failed_attempts {
+                tracing::info!(
+                    "Stopping RTSP credential tests on {} after {} failures",
+                    addr,
+                    failures
+                );
+                report.stopped_early = true;
+                break;
+            }
+            report.attempts += 1;
+            match self.try_login(addr, &path, &challenges, credential).await {
+                LoginOutcome::Success(response) => {
+                    let stream = &mut report.streams[index];
+                    stream.access = StreamAccess::DefaultCredential;
+                    stream.credential = Some(credential.clone());
+                    stream.sdp = Some(SessionDescription::parse(&response.body));
+                    working = Some(credential.clone());
+                    break;
+                }
+                LoginOutcome::Rejected(fresh) => {
+                    failures += 1;
+                    if !fresh.is_empty() {
+                        challenges = fresh;
+                    }
+                }
+                LoginOutcome::Refused => {
+                    report.locked_out = true;
+                    break;
+                }
+            }
+        }
+        let Some(credential) = working else {
+            return;
+        };
+
+        for stream in report
+            .streams
+            .iter_mut()
+            .filter(|s| s.access == StreamAccess::Protected)
+        {
+            // Digest nonces are per request on some servers; fetch a fresh
+            // challenge for each path.
+            let Ok(challenge) = self.describe(addr, &stream.path, &[]).await else {
+                continue;
+            };
+            if let LoginOutcome::Success(response) = self
+                .try_login(addr, &stream.path, &challenge.challenges(), &credential)
+                .await
+            {
+                stream.access = StreamAccess::DefaultCredential;
+                stream.credential = Some(credential.clone());
+                stream.sdp = Some(SessionDescription::parse(&response.body));
+            }
+        }
+        report.logins.push(credential);
+    }
+
+    async fn try_login(
+        &self,
+        addr: SocketAddr,
+        path: &str,
+        challenges: &[Challenge],
+        credential: &DefaultCredential,
+    ) -> LoginOutcome {
+        let mut challenges = challenges.to_vec();
+        // One retry when the server only objected to an expired nonce.
+        for _ in 0..2 {
+            // Digest when offered: it is what clients use, and some servers
+            // list Basic without accepting it.
+            let Some(challenge) = challenges
+                .iter()
+                .find(|c| c.scheme == AuthScheme::Digest)
+                .or_else(|| challenges.first())
+            else {
+                return LoginOutcome::Refused;
+            };
+            let authorization = challenge.authorization(
+                "DESCRIBE",
+                &self.url(addr, path),
+                &credential.username,
+                &credential.password,
+            );
+            let response = match self
+                .describe(addr, path, &[("Authorization".to_string(), authorization)])
+                .await
+            {
+                Ok(response) => response,
+                Err(_) => return LoginOutcome::Refused,
+            };
+            match response.status {
+                200 => return LoginOutcome::Success(response),
+                401 => {
+                    challenges = response.challenges();
+                    if !challenges.iter().any(|c| c.stale) {
+                        return LoginOutcome::Rejected(challenges);
+                    }
+                }
+                _ => return LoginOutcome::Refused,
+            }
+        }
+        LoginOutcome::Rejected(challenges)
+    }
+
+    fn url(&self, addr: SocketAddr, path: &str) -> String {
+        format!("rtsp://{}{}", addr, path)
+    }
+
+    async fn describe(
+        &self,
+        addr: SocketAddr,
+        path: &str,
+        extra_headers: &[(String, String)],
+    ) -> Result<RtspResponse> {
+        let mut headers = vec![("Accept".to_string(), "application/sdp".to_string())];
+        headers.extend_from_slice(extra_headers);
+        self.request(addr, "DESCRIBE", path, &headers).await
+    }
+
+    /// Sends one request on a fresh connection and reads the response.
+    async fn request(
+        &self,
+        addr: SocketAddr,
+        method: &str,
+        path: &str,
+        extra_headers: &[(String, String)],
+    ) -> Result<RtspResponse> {
+        let cseq = self.cseq.fetch_add(1, Ordering::Relaxed);
+        let mut request = format!(
+            "{} {} RTSP/1.0\r\nCSeq: {}\r\nUser-Agent: {}\r\n",
+            method,
+            self.url(addr, path),
+            cseq,
+            self.config.user_agent
+        );
+        for (name, value) in extra_headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str("\r\n");
+
+        let mut stream = timeout(
+            Duration::from_millis(self.config.connect_timeout_ms),
+            TcpStream::connect(addr),
+        )
+        .await
+        .map_err(|_| anyhow!("connection to {} timed out", addr))?
+        .with_context(|| format!("failed to connect to {}", addr))?;
+        let deadline = Instant::now() + Duration::from_millis(self.config.read_timeout_ms);
+        timeout_at(deadline, stream.write_all(request.as_bytes()))
+            .await
+            .map_err(|_| anyhow!("timed out sending {} to {}", method, addr))??;
+
+        let mut raw = Vec::new();
+        let mut buf = [0u8; 4096];
+        while raw.len() < MAX_RESPONSE_BYTES {
+            match timeout_at(deadline, stream.read(&mut buf)).await {
+                Ok(Ok(0)) => break,
+                Ok(Ok(n)) => raw.extend_from_slice(&buf[..n]),
+                Ok(Err(e)) if raw.is_empty() => return Err(e.into()),
+                Err(_) if raw.is_empty() => bail!("timed out waiting for {} reply", method),
+                _ => break,
+            }
+            if response_complete(&raw) {
+                break;
+            }
+        }
+        parse_response(&raw).with_context(|| format!("invalid RTSP response from {}", addr))
+    }
+}
+
+impl Default for RtspChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn response_complete(raw: &[u8]) -> bool {
+    let Some(head_end) = raw.windows(4).position(|w| w == b"\r\n\r\n") else {
+        return false;
+    };
+    let head = String::from_utf8_lossy(&raw[..head_end]);
+    let content_length = head
+        .lines()
+        .skip(1)
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.trim()
+                .eq_ignore_ascii_case("content-length")
+                .then(|| value.trim().parse::<usize>().ok())
+                .flatten()
+        })
+        .unwrap_or(0);
+    raw.len() >= head_end + 4 + content_length
+}
+
+fn parse_response(raw: &[u8]) -> Result<RtspResponse> {
+    let text = String::from_utf8_lossy(raw);
+    let (head, body) = text
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| anyhow!("no end of headers in response"))?;
+    let mut lines = head.split("\r\n");
+    let status_line = lines.next().unwrap_or_default();
+    let mut parts = status_line.splitn(3, ' ');
+    if !parts.next().unwrap_or_default().starts_with("RTSP/") {
+        bail!("not an RTSP status line: {}", status_line);
+    }
+    let status = parts
+        .next()
+        .and_then(|s| s.parse::<u16>().ok())
+        .ok_or_else(|| anyhow!("invalid status line: {}", status_line))?;
+    let reason = parts.next().unwrap_or_default().to_string();
+    let headers = lines
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            Some((name.trim().to_string(), value.trim().to_string()))
+        })
+        .collect();
+    Ok(RtspResponse {
+        status,
+        reason,
+        headers,
+        body: body.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    const DIGEST: &str = "Digest realm=\"IPCAM\", nonce=\"5f1c0a\"";
+    const SDP: &str = "v=0\r\no=- 0 0 IN IP4 127.0.0.1\r\ns=Drone camera\r\n\
+                       m=video 0 RTP/AVP 96\r\na=rtpmap:96 H264/90000\r\na=control:track1\r\n\
+                       m=audio 0 RTP/AVP 97\r\na=rtpmap:97 PCMA/8000\r\na=control:track2\r\n";
+
+    /// In-process RTSP server answering one request per connection. Protected
+    /// paths take the one login, with Digest only.
+    #[derive(Default)]
+    struct Stub {
+        open: Vec<&'static str>,
+        protected: Vec<&'static str>,
+        login: Option<(&'static str, &'static str)>,
+        authorized: AtomicUsize,
+    }
+
+    impl Stub {
+        async fn start(self) -> (SocketAddr, Arc<Self>) {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let stub = Arc::new(self);
+            let server = stub.clone();
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(server.clone().serve(stream));
+                }
+            });
+            (addr, stub)
+        }
+
+        async fn serve(self: Arc<Self>, mut stream: TcpStream) {
+            let mut raw = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !raw.windows(4).any(|w| w == b"\r\n\r\n") {
+                match stream.read(&mut buf).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => raw.extend_from_slice(&buf[..n]),
+                }
+            }
+            let request = String::from_utf8_lossy(&raw).into_owned();
+            let mut lines = request.split("\r\n");
+            let mut parts = lines.next().unwrap_or_default().split(' ');
+            let method = parts.next().unwrap_or_default();
+            let url = parts.next().unwrap_or_default();
+            let path = url.splitn(4, '/').nth(3).map(|p| format!("/{}", p));
+            let authorization = lines
+                .filter_map(|line| line.split_once(": "))
+                .find(|(name, _)| *name == "Authorization")
+                .map(|(_, value)| value.to_string());
+
+            let (status, headers, body) = match (method, path.as_deref()) {
+                ("OPTIONS", _) => (
+                    "200 OK",
+                    "Public: OPTIONS, DESCRIBE, SETUP, PLAY, ANNOUNCE, RECORD\r\n".to_string(),
+                    "",
+                ),
+                ("DESCRIBE", Some(path)) if self.open.contains(&path) => (
+                    "200 OK",
+                    "Content-Type: application/sdp\r\n".to_string(),
+                    SDP,
+                ),
+                ("DESCRIBE", Some(path)) if self.protected.contains(&path) => {
+                    if let Some(authorization) = authorization {
+                        self.authorized.fetch_add(1, Ordering::SeqCst);
+                        if self.accepts(url, &authorization) {
+                            (
+                                "200 OK",
+                                "Content-Type: application/sdp\r\n".to_string(),
+                                SDP,
+                            )
+                        } else {
+                            ("401 Unauthorized", self.challenge(), "")
+                        }
+                    } else {
+                        ("401 Unauthorized", self.challenge(), "")
+                    }
+                }
+                _ => ("404 Not Found", String::new(), ""),
+            };
+            let response = format!(
+                "RTSP/1.0 {}\r\nCSeq: 1\r\nServer: stub\r\n{}Content-Length: {}\r\n\r\n{}",
+                status,
+                headers,
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        }
+
+        fn challenge(&self) -> String {
+            format!(
+                "WWW-Authenticate: {}\r\nWWW-Authenticate: Basic realm=\"IPCAM\"\r\n",
+                DIGEST
+            )
+        }
+
+        /// Without `qop` the Digest response is deterministic, so the
+        /// expected header can be computed the way the client does.
+        fn accepts(&self, url: &str, authorization: &str) -> bool {
+            let Some((username, password)) = self.login else {
+                return false;
+            };
+            let expected = Challenge::parse(DIGEST)
+                .unwrap()
+                .authorization("DESCRIBE", url, username, password);
+            authorization == expected
+        }
+    }
+
+    fn checker(credentials: &[(&str, &str)], extra_paths: &str) -> RtspChecker {
+        let mut list = CredentialList::empty();
+        for (username, password) in credentials.iter().rev() {
+            list.push_front(username, password, &["rtsp"]);
+        }
+        let mut checker = RtspChecker::new().with_credentials(list);
+        checker.add_paths(extra_paths);
+        checker
+    }
+
+    #[test]
+    fn finds_open_stream_and_default_digest_login() {
+        crate::test_runtime().block_on(async {
+            let (addr, _stub) = Stub {
+                open: vec!["/live"],
+                protected: vec!["/cam/protected"],
+                login: Some(("admin", "12345")),
+                ..Stub::default()
+            }
+            .start()
+            .await;
+
+            let report = checker(&[("root", "root"), ("admin", "12345")], "cam/protected")
+                .check(addr)
+                .await
+                .unwrap();
+            assert_eq!(report.server.as_deref(), Some("stub"));
+            assert!(report.methods.contains(&"DESCRIBE".to_string()));
+            assert!(!report.wildcard);
+            assert_eq!(report.auth_schemes, [AuthScheme::Digest, AuthScheme::Basic]);
+            assert_eq!(report.attempts, 2);
+
+            let live = report.streams.iter().find(|s| s.path == "/live").unwrap();
+            assert_eq!(live.access, StreamAccess::Open);
+            let sdp = live.sdp.as_ref().unwrap();
+            assert_eq!(sdp.session_name.as_deref(), Some("Drone camera"));
+            assert_eq!(sdp.media[0].codecs, ["H264/90000"]);
+            assert_eq!(sdp.media[1].codecs, ["PCMA/8000"]);
+
+            let protected = report
+                .streams
+                .iter()
+                .find(|s| s.path == "/cam/protected")
+                .unwrap();
+            assert_eq!(protected.access, StreamAccess::DefaultCredential);
+            assert_eq!(protected.credential.as_ref().unwrap().username, "admin");
+            assert!(protected.sdp.is_some());
+
+            let titles: Vec<_> = report
+                .vulnerabilities()
+                .into_iter()
+                .map(|v| v.title)
+                .collect();
+            assert_eq!(
+                titles,
+                [
+                    "Unauthenticated RTSP video stream",
+                    "Default RTSP credentials accepted",
+                    "RTSP Basic authentication over cleartext",
+                    "RTSP server advertises unauthenticated publishing",
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn stops_after_max_failed_attempts() {
+        crate::test_runtime().block_on(async {
+            let (addr, stub) = Stub {
+                protected: vec!["/live"],
+                login: Some(("admin", "unguessable")),
+                ..Stub::default()
+            }
+            .start()
+            .await;
+            let credentials = [("a", "1"), ("b", "2"), ("c", "3"), ("d", "4"), ("e", "5")];
+
+            let report = checker(&credentials, "").check(addr).await.unwrap();
+            assert_eq!(report.attempts, 3);
+            assert_eq!(stub.authorized.load(Ordering::SeqCst), 3);
+            assert!(report.stopped_early);
+            assert!(!report.locked_out);
+            assert!(report.logins.is_empty());
+            assert_eq!(report.streams[0].access, StreamAccess::Protected);
+        });
+    }
+}
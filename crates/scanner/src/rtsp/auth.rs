@@ -0,0 +1,146 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use md5::{Digest, Md5};
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum AuthScheme {
+    Basic,
+    Digest,
+}
+
+impl std::fmt::Display for AuthScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthScheme::Basic => write!(f, "Basic"),
+            AuthScheme::Digest => write!(f, "Digest"),
+        }
+    }
+}
+
+/// One challenge from a `WWW-Authenticate` header.
+#[derive(Debug, Clone)]
+pub struct Challenge {
+    pub scheme: AuthScheme,
+    pub realm: String,
+    pub nonce: String,
+    pub opaque: Option<String>,
+    pub algorithm: Option<String>,
+    pub qop: Option<String>,
+    /// The request was refused only because its nonce had expired.
+    pub stale: bool,
+}
+
+impl Challenge {
+    /// Parses one header value. Unknown schemes yield `None`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let (scheme, params) = value.split_once(' ').unwrap_or((value, ""));
+        let scheme = if scheme.eq_ignore_ascii_case("basic") {
+            AuthScheme::Basic
+        } else if scheme.eq_ignore_ascii_case("digest") {
+            AuthScheme::Digest
+        } else {
+            return None;
+        };
+        let params = parse_params(params);
+        let get = |name: &str| {
+            params
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.clone())
+        };
+        Some(Self {
+            scheme,
+            realm: get("realm").unwrap_or_default(),
+            nonce: get("nonce").unwrap_or_default(),
+            opaque: get("opaque"),
+            algorithm: get("algorithm"),
+            qop: get("qop"),
+            stale: get("stale").is_some_and(|s| s.eq_ignore_ascii_case("true")),
+        })
+    }
+
+    /// `Authorization` header value for `method` on `uri`.
+    pub fn authorization(&self, method: &str, uri: &str, username: &str, password: &str) -> String {
+        match self.scheme {
+            AuthScheme::Basic => format!(
+                "Basic {}",
+                STANDARD.encode(format!("{}:{}", username, password))
+            ),
+            AuthScheme::Digest => self.digest(method, uri, username, password),
+        }
+    }
+
+    fn digest(&self, method: &str, uri: &str, username: &str, password: &str) -> String {
+        let cnonce = format!(
+            "{:016x}",
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0)
+        );
+        let nc = "00000001";
+        let session = self
+            .algorithm
+            .as_deref()
+            .is_some_and(|a| a.eq_ignore_ascii_case("md5-sess"));
+        let qop_auth = self
+            .qop
+            .as_deref()
+            .is_some_and(|q| q.split(',').any(|q| q.trim().eq_ignore_ascii_case("auth")));
+
+        let mut ha1 = md5_hex(format!("{}:{}:{}", username, self.realm, password).as_bytes());
+        if session {
+            ha1 = md5_hex(format!("{}:{}:{}", ha1, self.nonce, cnonce).as_bytes());
+        }
+        let ha2 = md5_hex(format!("{}:{}", method, uri).as_bytes());
+        let response = if qop_auth {
+            md5_hex(format!("{}:{}:{}:{}:auth:{}", ha1, self.nonce, nc, cnonce, ha2).as_bytes())
+        } else {
+            md5_hex(format!("{}:{}:{}", ha1, self.nonce, ha2).as_bytes())
+        };
+
+        let mut header = format!(
+            "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", response=\"{}\"",
+            username, self.realm, self.nonce, uri, response
+        );
+        if let Some(algorithm) = &self.algorithm {
+            let _ = write!(header, ", algorithm={}", algorithm);
+        }
+        if let Some(opaque) = &self.opaque {
+            let _ = write!(header, ", opaque=\"{}\"", opaque);
+        }
+        if qop_auth {
+            let _ = write!(header, ", qop=auth, nc={}, cnonce=\"{}\"", nc, cnonce);
+        }
+        header
+    }
+}
+
+/// Splits `name=value, name="quoted, value"` pairs.
+fn parse_params(input: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut rest = input.trim();
+    while !rest.is_empty() {
+        let Some(eq) = rest.find('=') else {
+            break;
+        };
+        let name = rest[..eq].trim().trim_start_matches(',').trim().to_string();
+        rest = rest[eq + 1..].trim_start();
+        let value;
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            value = quoted[..end].to_string();
+            rest = quoted.get(end + 1..).unwrap_or_default();
+        } else {
+            let end = rest.find(',').unwrap_or(rest.len());
+            value = rest[..end].trim().to_string();
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start().trim_start_matches(',').trim_start();
+        params.push((name, value));
+    }
+    params
+}
+
+fn md5_hex(data: &[u8]) -> String {
+    format!("{:x}", Md5::digest(data))
+}
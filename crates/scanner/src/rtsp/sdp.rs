@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionDescription {
+    /// `s=` line.
+    pub session_name: Option<String>,
+    /// `i=` line, often the camera model or stream label.
+    pub info: Option<String>,
+    /// `a=tool:` of the streaming software.
+    pub tool: Option<String>,
+    pub media: Vec<MediaDescription>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaDescription {
+    /// `video`, `audio`, `application`, ...
+    pub kind: String,
+    pub port: u16,
+    /// Transport from the `m=` line, e.g. `RTP/AVP`.
+    pub protocol: String,
+    /// Codecs as `encoding/clock-rate[/channels]`, e.g. `H264/90000`.
+    pub codecs: Vec<String>,
+    /// `a=fmtp:` parameters by payload type.
+    pub format_parameters: Vec<String>,
+    /// `a=control:` URL of the track.
+    pub control: Option<String>,
+}
+
+impl SessionDescription {
+    pub fn parse(body: &str) -> Self {
+        let mut sdp = Self::default();
+        // RTP payload types from `m=` that have not been named by `a=rtpmap`.
+        let mut unmapped: Vec<Vec<String>> = Vec::new();
+
+        for line in body.lines().map(str::trim) {
+            let Some((kind, value)) = line.split_once('=') else {
+                continue;
+            };
+            match kind {
+                "s" if sdp.media.is_empty() => {
+                    sdp.session_name = Some(value.to_string()).filter(|s| !s.trim().is_empty())
+                }
+                "i" if sdp.media.is_empty() => sdp.info = Some(value.to_string()),
+                "m" => {
+                    let mut fields = value.split_whitespace();
+                    let kind = fields.next().unwrap_or_default().to_string();
+                    let port = fields
+                        .next()
+                        .and_then(|p| p.split('/').next())
+                        .and_then(|p| p.parse().ok())
+                        .unwrap_or(0);
+                    let protocol = fields.next().unwrap_or_default().to_string();
+                    unmapped.push(fields.map(str::to_string).collect());
+                    sdp.media.push(MediaDescription {
+                        kind,
+                        port,
+                        protocol,
+                        codecs: Vec::new(),
+                        format_parameters: Vec::new(),
+                        control: None,
+                    });
+                }
+                "a" => {
+                    let (name, arg) = value.split_once(':').unwrap_or((value, ""));
+                    let Some(media) = sdp.media.last_mut() else {
+                        if name == "tool" {
+                            sdp.tool = Some(arg.to_string());
+                        }
+                        continue;
+                    };
+                    match name {
+                        "rtpmap" => {
+                            if let Some((pt, encoding)) = arg.split_once(' ') {
+                                media.codecs.push(encoding.trim().to_string());
+                                if let Some(pending) = unmapped.last_mut() {
+                                    pending.retain(|p| p != pt);
+                                }
+                            }
+                        }
+                        "fmtp" => media.format_parameters.push(arg.to_string()),
+                        "control" => media.control = Some(arg.to_string()),
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for (media, pending) in sdp.media.iter_mut().zip(unmapped) {
+            media
+                .codecs
+                .extend(pending.iter().filter_map(|pt| static_payload(pt)));
+        }
+        sdp
+    }
+
+    /// All codecs across media sections, e.g. `video H264/90000`.
+    pub fn codecs(&self) -> Vec<String> {
+        self.media
+            .iter()
+            .flat_map(|m| m.codecs.iter().map(move |c| format!("{} {}", m.kind, c)))
+            .collect()
+    }
+}
+
+/// Static RTP payload types (RFC 3551) that need no `a=rtpmap`.
+fn static_payload(pt: &str) -> Option<String> {
+    let name = match pt.parse::<u8>().ok()? {
+        0 => "PCMU/8000",
+        3 => "GSM/8000",
+        8 => "PCMA/8000",
+        9 => "G722/8000",
+        14 => "MPA/90000",
+        26 => "JPEG/90000",
+        31 => "H261/90000",
+        32 => "MPV/90000",
+        33 => "MP2T/90000",
+        34 => "H263/90000",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
@@ -0,0 +1,320 @@
+use crate::network::port_scan::{OpenPort, Transport};
+use crate::tls;
+use anyhow::{Context, Result};
+use data::Service;
+use regex::bytes::{Regex, RegexBuilder};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Instant};
+
+const BUILTIN_SIGNATURES: &str = include_str!("../signatures/services.toml");
+
+/// Banners are truncated to this many bytes.
+const MAX_BANNER_BYTES: usize = 4096;
+
+/// After the first bytes arrive, stop reading once the peer goes quiet this long.
+const READ_IDLE: Duration = Duration::from_millis(300);
+
+const TLS_PORTS: &[u16] = &[443, 465, 636, 990, 992, 993, 995, 8443, 9443];
+
+#[derive(Debug, Clone, Deserialize)]
+struct SignatureFile {
+    #[serde(default)]
+    probes: Vec<Probe>,
+    #[serde(default)]
+    signatures: Vec<SignatureDef>,
+}
+
+/// Payload sent to a TCP service to make it reveal itself.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Probe {
+    pub name: String,
+    pub payload: String,
+    /// Ports this probe is tried on. Empty means every port.
+    #[serde(default)]
+    pub ports: Vec<u16>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SignatureDef {
+    service: String,
+    probe: Option<String>,
+    pattern: String,
+    product: Option<String>,
+    version: Option<String>,
+    info: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ServiceSignature {
+    pub service: String,
+    /// Only match replies to this probe, if set.
+    pub probe: Option<String>,
+    pub pattern: Regex,
+    pub product: Option<String>,
+    pub version: Option<String>,
+    pub info: Option<String>,
+}
+
+/// Result of matching a banner against the signature set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceMatch {
+    pub service: String,
+    pub product: Option<String>,
+    pub version: Option<String>,
+    pub info: Option<String>,
+}
+
+impl ServiceMatch {
+    /// `product version (info)`, the form stored in `Service::version`.
+    pub fn version_string(&self) -> Option<String> {
+        let mut parts: Vec<&str> = self
+            .product
+            .iter()
+            .chain(self.version.iter())
+            .map(String::as_str)
+            .collect();
+        let info = self.info.as_ref().map(|info| format!("({})", info));
+        if let Some(info) = &info {
+            parts.push(info);
+        }
+        (!parts.is_empty()).then(|| parts.join(" "))
+    }
+}
+
+/// Grabs banners from open ports and matches them against signatures loaded
+/// from `signatures/services.toml` plus any user-supplied files.
+pub struct ServiceIdentifier {
+    probes: Vec<Probe>,
+    signatures: Vec<ServiceSignature>,
+    read_timeout: Duration,
+}
+
+impl ServiceIdentifier {
+    pub fn new() -> Self {
+        let mut identifier = Self {
+            probes: Vec::new(),
+            signatures: Vec::new(),
+            read_timeout: Duration::from_secs(3),
+        };
+        identifier
+            .add_signatures(BUILTIN_SIGNATURES)
+            .expect("built-in service signatures must be valid");
+        identifier
+    }
+
+    pub fn with_read_timeout(mut self, read_timeout: Duration) -> Self {
+        self.read_timeout = read_timeout;
+        self
+    }
+
+    /// Loads extra probes and signatures from a TOML file. User signatures
+    /// take precedence over the built-in ones.
+    pub fn load_signatures(&mut self, path: &Path) -> Result<usize> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read signature file {}", path.display()))?;
+        self.add_signatures(&content)
+            .with_context(|| format!("invalid signature file {}", path.display()))
+    }
+
+    pub fn add_signatures(&mut self, content: &str) -> Result<usize> {
+        let file: SignatureFile = toml::from_str(content)?;
+
+        let mut compiled = Vec::with_capacity(file.signatures.len());
+        for def in file.signatures {
+            let pattern = RegexBuilder::new(&def.pattern)
+                .unicode(false)
+                .build()
+                .with_context(|| format!("invalid pattern for {}: {}", def.service, def.pattern))?;
+            compiled.push(ServiceSignature {
+                service: def.service,
+                probe: def.probe,
+                pattern,
+                product: def.product,
+                version: def.version,
+                info: def.info,
+            });
+        }
+
+        let count = compiled.len();
+        // Loaded later means more specific, so it is matched first.
+        compiled.append(&mut self.signatures);
+        self.signatures = compiled;
+
+        for probe in file.probes {
+            match self.probes.iter_mut().find(|p| p.name == probe.name) {
+                Some(existing) => *existing = probe,
+                None => self.probes.push(probe),
+            }
+        }
+        Ok(count)
+    }
+
+    pub fn signatures(&self) -> &[ServiceSignature] {
+        &self.signatures
+    }
+
+    /// Matches a reply against the signature set.
+    pub fn match_banner(&self, probe: Option<&str>, banner: &[u8]) -> Option<ServiceMatch> {
+        self.signatures.iter().find_map(|sig| {
+            if sig.probe.is_some() && sig.probe.as_deref() != probe {
+                return None;
+            }
+            let caps = sig.pattern.captures(banner)?;
+            let expand = |template: &Option<String>| {
+                template.as_ref().and_then(|template| {
+                    let mut out = Vec::new();
+                    caps.expand(template.as_bytes(), &mut out);
+                    let out = String::from_utf8_lossy(&out).trim().to_string();
+                    (!out.is_empty()).then_some(out)
+                })
+            };
+            Some(ServiceMatch {
+                service: sig.service.clone(),
+                product: expand(&sig.product),
+                version: expand(&sig.version),
+                info: expand(&sig.info),
+            })
+        })
+    }
+
+    /// Grabs a banner from `port` and turns it into a `Service`. UDP ports are
+    /// identified from the reply captured during the port scan.
+    pub async fn identify(&self, port: &OpenPort) -> Option<Service> {
+        let (tls, probe, banner) = match port.transport {
+            Transport::Udp => (false, None, port.response.clone()?),
+            Transport::Tcp => self.grab_tcp(port.socket_addr()).await?,
+        };
+
+        let matched = self.match_banner(probe.as_deref(), &banner);
+        let fallback = port.service_name().unwrap_or("unknown").to_string();
+        let service_name = match (&matched, tls) {
+            (Some(m), true) if m.service == "http" => "https".to_string(),
+            (Some(m), true) => format!("ssl/{}", m.service),
+            (Some(m), false) => m.service.clone(),
+            (None, _) => fallback,
+        };
+
+        Some(Service {
+            port: port.port,
+            protocol: if tls {
+                format!("{}/tls", port.transport)
+            } else {
+                port.transport.to_string()
+            },
+            service_name,
+            version: matched.as_ref().and_then(ServiceMatch::version_string),
+            banner: Some(printable_banner(&banner)),
+        })
+    }
+
+    /// Returns `(tls, probe name, reply)` for the first probe that got a
+    /// reply matching a signature, or else the first non-empty reply.
+    async fn grab_tcp(&self, addr: SocketAddr) -> Option<(bool, Option<String>, Vec<u8>)> {
+        let mut first_reply = None;
+
+        let try_tls_first = TLS_PORTS.contains(&addr.port());
+        for tls in [try_tls_first, !try_tls_first] {
+            for probe in self.probes_for(addr.port()) {
+                let Some(reply) = self.send_probe(addr, tls, probe).await else {
+                    continue;
+                };
+                if self.match_banner(Some(&probe.name), &reply).is_some() {
+                    return Some((tls, Some(probe.name.clone()), reply));
+                }
+                first_reply.get_or_insert((tls, Some(probe.name.clone()), reply));
+            }
+            // Only fall back to TLS when the plaintext attempt looked like TLS
+            // (an alert record) or got nothing at all.
+            match &first_reply {
+                Some((_, _, reply)) if !looks_like_tls(reply) => break,
+                _ => {}
+            }
+        }
+        first_reply
+    }
+
+    fn probes_for(&self, port: u16) -> impl Iterator<Item = &Probe> {
+        let specific = self.probes.iter().filter(move |p| p.ports.contains(&port));
+        let generic = self.probes.iter().filter(|p| p.ports.is_empty());
+        let (null, rest): (Vec<_>, Vec<_>) = generic.partition(|p| p.payload.is_empty());
+        null.into_iter().chain(specific).chain(rest)
+    }
+
+    async fn send_probe(&self, addr: SocketAddr, tls: bool, probe: &Probe) -> Option<Vec<u8>> {
+        let stream = timeout(self.read_timeout, TcpStream::connect(addr))
+            .await
+            .ok()?
+            .ok()?;
+        let reply = if tls {
+            let host = addr.ip().to_string();
+            let stream = timeout(self.read_timeout, tls::connect(stream, &host))
+                .await
+                .ok()?
+                .ok()?;
+            self.exchange(stream, probe.payload.as_bytes()).await
+        } else {
+            self.exchange(stream, probe.payload.as_bytes()).await
+        };
+        (!reply.is_empty()).then_some(reply)
+    }
+
+    async fn exchange<S>(&self, mut stream: S, payload: &[u8]) -> Vec<u8>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        if !payload.is_empty() && stream.write_all(payload).await.is_err() {
+            return Vec::new();
+        }
+
+        let mut received = Vec::new();
+        let mut buf = vec![0u8; 2048];
+        let deadline = Instant::now() + self.read_timeout;
+        loop {
+            let wait = if received.is_empty() {
+                deadline.saturating_duration_since(Instant::now())
+            } else {
+                READ_IDLE.min(deadline.saturating_duration_since(Instant::now()))
+            };
+            match timeout(wait, stream.read(&mut buf)).await {
+                Ok(Ok(len)) if len > 0 => {
+                    received.extend_from_slice(&buf[..len]);
+                    if received.len() >= MAX_BANNER_BYTES {
+                        received.truncate(MAX_BANNER_BYTES);
+                        break;
+                    }
+                }
+                _ => break,
+            }
+        }
+        received
+    }
+}
+
+impl Default for ServiceIdentifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// TLS record header for an alert or handshake.
+fn looks_like_tls(reply: &[u8]) -> bool {
+    matches!(reply, [0x15 | 0x16, 0x03, ..])
+}
+
+/// Escapes non-printable bytes so binary banners stay readable in the UI.
+fn printable_banner(banner: &[u8]) -> String {
+    let mut out = String::with_capacity(banner.len());
+    for &byte in banner {
+        match byte {
+            b'\r' => {}
+            b'\n' | b'\t' | 0x20..=0x7e => out.push(byte as char),
+            _ => out.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    out.trim_end().to_string()
+}
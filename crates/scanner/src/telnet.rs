@@ -0,0 +1,908 @@
+use crate::credentials::{CredentialList, DefaultCredential};
+use crate::SecurityConfig;
+use anyhow::{anyhow, Context, Result};
+use data::{AssetNode, ScanType, VulnData, VulnSeverity};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, timeout_at, Instant};
+
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+
+const OPT_ECHO: u8 = 1;
+const OPT_SGA: u8 = 3;
+
+/// Banner text kept in the report.
+const MAX_BANNER_CHARS: usize = 1024;
+
+/// Output of one prompt wait is cut at this size.
+const MAX_READ_BYTES: usize = 64 * 1024;
+
+/// Printed by the probe command only if a shell actually evaluated it; the
+/// echoed command line still contains the quotes.
+const PROBE_MARKER: &str = "UAVRED42";
+const PROBE_COMMAND: &str = "echo UAVRED''42; id";
+
+/// Mirrors the `[telnet]` section of `config.toml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelnetConfig {
+    pub connect_timeout_ms: u64,
+    /// How long to wait for a login, password or shell prompt.
+    pub prompt_timeout_ms: u64,
+    /// Output is considered complete after this long without new data.
+    pub idle_timeout_ms: u64,
+}
+
+impl Default for TelnetConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout_ms: 3000,
+            prompt_timeout_ms: 5000,
+            idle_timeout_ms: 800,
+        }
+    }
+}
+
+/// What the server is waiting for at the end of its output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Prompt {
+    Login,
+    Password,
+    Shell,
+    None,
+}
+
+/// A shell reached on the service, with or without logging in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelnetShell {
+    /// `None` for a shell that did not ask for credentials.
+    pub credential: Option<DefaultCredential>,
+    /// Output of `id`, if the probe command ran.
+    pub identity: Option<String>,
+    pub root: bool,
+    /// Session transcript, with the password masked.
+    pub transcript: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelnetReport {
+    pub target: String,
+    pub banner: String,
+    /// Options the server asked for during negotiation, e.g. `WILL ECHO`.
+    pub options: Vec<String>,
+    pub prompt: Prompt,
+    pub unauthenticated: Option<TelnetShell>,
+    pub logins: Vec<TelnetShell>,
+    pub attempts: usize,
+    /// The server reported a lockout or started refusing connections.
+    pub locked_out: bool,
+    /// Credential testing stopped at the rejected-login budget.
+    pub stopped_early: bool,
+}
+
+impl TelnetReport {
+    fn affected(&self) -> String {
+        format!("{}/tcp", self.target)
+    }
+
+    /// Findings as `VulnData`: CWE-306 for a shell without login, CWE-798
+    /// for accepted default credentials and CWE-319 for the cleartext login.
+    pub fn vulnerabilities(&self) -> Vec<VulnData> {
+        let affected = self.affected();
+        let detection_time = chrono::Utc::now().to_rfc3339();
+        let mut vulns = Vec::new();
+
+        if let Some(shell) = &self.unauthenticated {
+            let (severity, title) = if shell.root {
+                (VulnSeverity::Critical, "Unauthenticated Telnet root shell")
+            } else {
+                (VulnSeverity::High, "Unauthenticated Telnet shell")
+            };
+            let mut vuln = VulnData::new(
+                format!("TELNET-NOAUTH:{}", affected),
+                title.to_string(),
+                format!(
+                    "{} drops straight into a {}shell without asking for \
+                     credentials. Anyone on the link can reconfigure or \
+                     replace the software on the device.",
+                    affected,
+                    if shell.root { "root " } else { "" }
+                ),
+                severity,
+            );
+            vuln.cwe = Some("CWE-306".to_string());
+            vuln.references = vec!["https://cwe.mitre.org/data/definitions/306.html".to_string()];
+            vuln.remediation = Some(
+                "Disable telnetd in the firmware, or start it with a login \
+                 program and a unique password; use SSH for maintenance access."
+                    .to_string(),
+            );
+            vulns.push(self.fill(vuln, shell, &detection_time));
+        }
+
+        for shell in &self.logins {
+            let Some(credential) = &shell.credential else {
+                continue;
+            };
+            let vendor = credential
+                .vendor
+                .as_ref()
+                .map(|v| format!(" ({} default)", v))
+                .unwrap_or_default();
+            let mut vuln = VulnData::new(
+                format!("TELNET-DEFAULT-CRED:{}:{}", affected, credential.username),
+                "Default Telnet credentials accepted".to_string(),
+                format!(
+                    "{} accepts the default login {}{}, giving a {}shell.",
+                    affected,
+                    credential.display(),
+                    vendor,
+                    if shell.root { "root " } else { "" }
+                ),
+                if shell.root {
+                    VulnSeverity::Critical
+                } else {
+                    VulnSeverity::High
+                },
+            );
+            vuln.cwe = Some("CWE-798".to_string());
+            vuln.references = vec!["https://cwe.mitre.org/data/definitions/798.html".to_string()];
+            vuln.remediation = Some(
+                "Change the factory password or disable the account, and \
+                 disable Telnet where it is not needed."
+                    .to_string(),
+            );
+            vulns.push(self.fill(vuln, shell, &detection_time));
+        }
+
+        if matches!(self.prompt, Prompt::Login | Prompt::Password) {
+            let mut vuln = VulnData::new(
+                format!("TELNET-CLEARTEXT:{}", affected),
+                "Telnet login over cleartext".to_string(),
+                format!(
+                    "{} offers a Telnet login, so usernames, passwords and the \
+                     whole session cross the link unencrypted.",
+                    affected
+                ),
+                VulnSeverity::Medium,
+            );
+            vuln.cwe = Some("CWE-319".to_string());
+            vuln.affected = affected.clone();
+            vuln.detection_time = detection_time.clone();
+            vuln.detection_location.component = "telnet".to_string();
+            vuln.scan_type = ScanType::Network;
+            vuln.references = vec!["https://cwe.mitre.org/data/definitions/319.html".to_string()];
+            vuln.tags = vec!["telnet".to_string(), "cleartext".to_string()];
+            vuln.remediation = Some("Replace Telnet with SSH.".to_string());
+            vulns.push(vuln);
+        }
+        vulns
+    }
+
+    fn fill(&self, mut vuln: VulnData, shell: &TelnetShell, detection_time: &str) -> VulnData {
+        if let Some(identity) = &shell.identity {
+            vuln.description
+                .push_str(&format!(" The shell runs as: {}", identity));
+        }
+        vuln.affected = self.affected();
+        vuln.affected_systems = shell
+            .credential
+            .as_ref()
+            .and_then(|c| c.vendor.clone())
+            .into_iter()
+            .collect();
+        vuln.detection_time = detection_time.to_string();
+        vuln.detection_location.component = "telnet".to_string();
+        vuln.scan_type = ScanType::Network;
+        vuln.exploit_available = true;
+        vuln.poc_available = true;
+        vuln.tags = vec!["telnet".to_string(), "authentication".to_string()];
+        if shell.root {
+            vuln.tags.push("root".to_string());
+        }
+        vuln
+    }
+
+    /// Working logins as `data::Credential` with `auth_type = "telnet"`.
+    pub fn credentials(&self) -> Vec<data::Credential> {
+        self.logins
+            .iter()
+            .filter_map(|shell| shell.credential.as_ref())
+            .map(|credential| credential.to_credential("telnet"))
+            .collect()
+    }
+
+    /// Records working logins on `asset`, skipping ones already present.
+    pub fn apply_to(&self, asset: &mut AssetNode) {
+        for credential in self.credentials() {
+            let known = asset.credentials.iter().any(|c| {
+                c.auth_type == credential.auth_type
+                    && c.username == credential.username
+                    && c.password == credential.password
+            });
+            if !known {
+                asset.credentials.push(credential);
+            }
+        }
+    }
+}
+
+enum LoginOutcome {
+    Success(TelnetShell),
+    Rejected,
+    /// Lockout message, a refused connection or no prompt at all.
+    Refused,
+}
+
+/// Tests a Telnet service for unauthenticated shells, default credentials
+/// and cleartext logins.
+pub struct TelnetChecker {
+    config: TelnetConfig,
+    credentials: CredentialList,
+    max_failed_attempts: usize,
+}
+
+impl TelnetChecker {
+    pub fn new() -> Self {
+        Self::with_config(TelnetConfig::default())
+    }
+
+    pub fn with_config(config: TelnetConfig) -> Self {
+        Self {
+            config,
+            credentials: CredentialList::new(),
+            max_failed_attempts: SecurityConfig::default().max_failed_attempts,
+        }
+    }
+
+    /// Replaces the default-credential list, e.g. with one that also loaded
+    /// vendor-specific pairs.
+    pub fn with_credentials(mut self, credentials: CredentialList) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    /// Takes the rejected-login budget from `[security]`.
+    pub fn with_security(mut self, security: &SecurityConfig) -> Self {
+        self.max_failed_attempts = security.max_failed_attempts;
+        self
+    }
+
+    pub async fn check(&self, addr: SocketAddr) -> Result<TelnetReport> {
+        tracing::info!("Checking Telnet service on {}", addr);
+        let mut session = TelnetSession::connect(addr, &self.config).await?;
+        let (mut banner, mut prompt) = session.read_prompt().await?;
+        if prompt == Prompt::None {
+            // Some daemons only print the prompt after a keystroke.
+            session.send_line("").await?;
+            let (more, next) = session.read_prompt().await?;
+            banner.push_str(&more);
+            prompt = next;
+        }
+
+        let mut report = TelnetReport {
+            target: addr.to_string(),
+            banner: banner_text(&banner),
+            options: Vec::new(),
+            prompt,
+            unauthenticated: None,
+            logins: Vec::new(),
+            attempts: 0,
+            locked_out: false,
+            stopped_early: false,
+        };
+
+        if prompt == Prompt::Shell {
+            let (identity, root) = session.probe().await;
+            report.unauthenticated = Some(TelnetShell {
+                credential: None,
+                identity,
+                root,
+                transcript: std::mem::take(&mut session.transcript),
+            });
+        }
+        session.close().await;
+        report.options = session.options;
+
+        if matches!(prompt, Prompt::Login | Prompt::Password) {
+            self.try_credentials(addr, prompt, &mut report).await;
+        }
+
+        tracing::info!(
+            "Telnet check of {}: prompt={:?}, unauthenticated={}, {} default logins, {} attempts",
+            addr,
+            report.prompt,
+            report.unauthenticated.is_some(),
+            report.logins.len(),
+            report.attempts
+        );
+        Ok(report)
+    }
+
+    async fn try_credentials(&self, addr: SocketAddr, prompt: Prompt, report: &mut TelnetReport) {
+        if lockout_message(&report.banner) {
+            report.locked_out = true;
+            return;
+        }
+        let mut tried_passwords = HashSet::new();
+        let mut failures = 0;
+        for credential in self.credentials.for_service("telnet") {
+            // Password-only prompts ignore the username.
+            if prompt == Prompt::Password && !tried_passwords.insert(&credential.password) {
+                continue;
+            }
+            if self.max_failed_attempts > 0 && failures >= self.max_failed_attempts {
+                tracing::info!(
+                    "Stopping Telnet credential tests on {} after {} failures",
+                    addr,
+                    failures
+                );
+                report.stopped_early = true;
+                break;
+            }
+            report.attempts += 1;
+            match self.try_login(addr, credential).await {
+                LoginOutcome::Success(shell) => {
+                    report.logins.push(shell);
+                    // One working default is enough to prove the point
+                    // without hammering the device.
+                    break;
+                }
+                LoginOutcome::Rejected => failures += 1,
+                LoginOutcome::Refused => {
+                    report.locked_out = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    async fn try_login(&self, addr: SocketAddr, credential: &DefaultCredential) -> LoginOutcome {
+        let Ok(mut session) = TelnetSession::connect(addr, &self.config).await else {
+            return LoginOutcome::Refused;
+        };
+        let outcome = self.login(&mut session, credential).await;
+        session.close().await;
+        match outcome {
+            Ok(Some(shell)) => LoginOutcome::Success(shell),
+            Ok(None) => LoginOutcome::Rejected,
+            Err(e) => {
+                tracing::debug!("Telnet login on {} aborted: {}", addr, e);
+                LoginOutcome::Refused
+            }
+        }
+    }
+
+    /// Walks the login dialogue. `Ok(None)` is a rejected login, an error
+    /// means the server stopped cooperating.
+    async fn login(
+        &self,
+        session: &mut TelnetSession,
+        credential: &DefaultCredential,
+    ) -> Result<Option<TelnetShell>> {
+        let (mut output, mut prompt) = session.read_prompt().await?;
+        if prompt == Prompt::None {
+            session.send_line("").await?;
+            (output, prompt) = session.read_prompt().await?;
+        }
+        let mut sent_username = false;
+        let mut sent_password = false;
+        loop {
+            if lockout_message(&output) {
+                return Err(anyhow!("server reported a lockout"));
+            }
+            match prompt {
+                Prompt::Login if !sent_username => {
+                    session.send_line(&credential.username).await?;
+                    sent_username = true;
+                }
+                Prompt::Password if !sent_password => {
+                    session.send_secret(&credential.password).await?;
+                    sent_password = true;
+                }
+                Prompt::Shell if sent_username || sent_password => {
+                    let (identity, root) = session.probe().await;
+                    return Ok(Some(TelnetShell {
+                        credential: Some(credential.clone()),
+                        identity,
+                        root,
+                        transcript: std::mem::take(&mut session.transcript),
+                    }));
+                }
+                Prompt::None if !sent_username && !sent_password => {
+                    return Err(anyhow!("no login prompt"));
+                }
+                // Prompted again, told off, or disconnected.
+                _ => return Ok(None),
+            }
+            match session.read_prompt().await {
+                Ok(next) => (output, prompt) = next,
+                Err(_) if sent_password => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Default for TelnetChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ParseState {
+    Data,
+    Iac,
+    Command(u8),
+    Sub,
+    SubIac,
+}
+
+/// One Telnet connection. Every option the server proposes is refused except
+/// server-side echo and suppress-go-ahead, which login prompts expect.
+struct TelnetSession {
+    stream: TcpStream,
+    state: ParseState,
+    answered: HashSet<(u8, u8)>,
+    options: Vec<String>,
+    prompt_timeout: Duration,
+    idle_timeout: Duration,
+    transcript: Vec<String>,
+}
+
+impl TelnetSession {
+    async fn connect(addr: SocketAddr, config: &TelnetConfig) -> Result<Self> {
+        let stream = timeout(
+            Duration::from_millis(config.connect_timeout_ms),
+            TcpStream::connect(addr),
+        )
+        .await
+        .map_err(|_| anyhow!("connection to {} timed out", addr))?
+        .with_context(|| format!("failed to connect to {}", addr))?;
+        Ok(Self {
+            stream,
+            state: ParseState::Data,
+            answered: HashSet::new(),
+            options: Vec::new(),
+            prompt_timeout: Duration::from_millis(config.prompt_timeout_ms),
+            idle_timeout: Duration::from_millis(config.idle_timeout_ms),
+            transcript: Vec::new(),
+        })
+    }
+
+    /// Reads until the output ends in a prompt, goes quiet or the prompt
+    /// timeout passes. Fails only if the server closed without sending text.
+    async fn read_prompt(&mut self) -> Result<(String, Prompt)> {
+        let deadline = Instant::now() + self.prompt_timeout;
+        let mut text = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let wait = if text.is_empty() {
+                deadline
+            } else {
+                (Instant::now() + self.idle_timeout).min(deadline)
+            };
+            let n = match timeout_at(wait, self.stream.read(&mut buf)).await {
+                Ok(Ok(0)) | Ok(Err(_)) if text.is_empty() => {
+                    return Err(anyhow!("Telnet server closed the connection"))
+                }
+                Ok(Ok(0)) | Ok(Err(_)) | Err(_) => break,
+                Ok(Ok(n)) => n,
+            };
+            let replies = self.feed(&buf[..n], &mut text);
+            if !replies.is_empty() {
+                self.stream.write_all(&replies).await?;
+            }
+            if text.len() >= MAX_READ_BYTES || classify(&clean_text(&text)) != Prompt::None {
+                break;
+            }
+        }
+        let text = clean_text(&text);
+        for line in text.lines().filter(|l| !l.trim().is_empty()) {
+            self.transcript.push(format!("< {}", line.trim_end()));
+        }
+        let prompt = classify(&text);
+        Ok((text, prompt))
+    }
+
+    async fn send_line(&mut self, line: &str) -> Result<()> {
+        self.transcript.push(format!("> {}", line));
+        self.write_line(line).await
+    }
+
+    async fn send_secret(&mut self, secret: &str) -> Result<()> {
+        self.transcript.push("> ********".to_string());
+        self.write_line(secret).await
+    }
+
+    async fn write_line(&mut self, line: &str) -> Result<()> {
+        let mut data = Vec::with_capacity(line.len() + 2);
+        for &b in line.as_bytes() {
+            data.push(b);
+            if b == IAC {
+                data.push(IAC);
+            }
+        }
+        data.extend_from_slice(b"\r\n");
+        timeout(self.prompt_timeout, self.stream.write_all(&data))
+            .await
+            .map_err(|_| anyhow!("timed out writing to Telnet server"))??;
+        Ok(())
+    }
+
+    /// Runs the probe command in the shell just reached and returns the
+    /// `id` output and whether the shell runs as root.
+    async fn probe(&mut self) -> (Option<String>, bool) {
+        let prompt_is_root = self
+            .transcript
+            .last()
+            .is_some_and(|line| line.trim_end().ends_with('#'));
+        if self.send_line(PROBE_COMMAND).await.is_err() {
+            return (None, prompt_is_root);
+        }
+        let Ok((output, _)) = self.read_prompt().await else {
+            return (None, prompt_is_root);
+        };
+        let identity = output
+            .lines()
+            .map(str::trim)
+            .find(|line| line.starts_with("uid="))
+            .map(str::to_string);
+        let root = match &identity {
+            Some(id) => id.starts_with("uid=0("),
+            // No `id` binary: trust the prompt only if the shell evaluated
+            // the marker.
+            None => prompt_is_root && output.contains(PROBE_MARKER),
+        };
+        (identity, root)
+    }
+
+    async fn close(&mut self) {
+        let _ = self.stream.shutdown().await;
+    }
+
+    /// Strips Telnet commands from `data`, appending the text to `text` and
+    /// returning the negotiation replies to send.
+    fn feed(&mut self, data: &[u8], text: &mut Vec<u8>) -> Vec<u8> {
+        let mut replies = Vec::new();
+        for &b in data {
+            self.state = match (self.state, b) {
+                (ParseState::Data, IAC) => ParseState::Iac,
+                (ParseState::Data, 0) => ParseState::Data,
+                (ParseState::Data, b) => {
+                    text.push(b);
+                    ParseState::Data
+                }
+                (ParseState::Iac, IAC) => {
+                    text.push(IAC);
+                    ParseState::Data
+                }
+                (ParseState::Iac, DO | DONT | WILL | WONT) => ParseState::Command(b),
+                (ParseState::Iac, SB) => ParseState::Sub,
+                (ParseState::Iac, _) => ParseState::Data,
+                (ParseState::Command(command), option) => {
+                    self.negotiate(command, option, &mut replies);
+                    ParseState::Data
+                }
+                (ParseState::Sub, IAC) => ParseState::SubIac,
+                (ParseState::Sub, _) => ParseState::Sub,
+                (ParseState::SubIac, SE) => ParseState::Data,
+                (ParseState::SubIac, _) => ParseState::Sub,
+            };
+        }
+        replies
+    }
+
+    fn negotiate(&mut self, command: u8, option: u8, replies: &mut Vec<u8>) {
+        let name = format!("{} {}", command_name(command), option_name(option));
+        if !self.options.contains(&name) {
+            self.options.push(name);
+        }
+        let reply = match command {
+            WILL if matches!(option, OPT_ECHO | OPT_SGA) => DO,
+            WILL => DONT,
+            DO => WONT,
+            // DONT and WONT need no answer; answering them invites loops.
+            _ => return,
+        };
+        if self.answered.insert((command, option)) {
+            replies.extend_from_slice(&[IAC, reply, option]);
+        }
+    }
+}
+
+fn command_name(command: u8) -> &'static str {
+    match command {
+        DO => "DO",
+        DONT => "DONT",
+        WILL => "WILL",
+        _ => "WONT",
+    }
+}
+
+fn option_name(option: u8) -> String {
+    match option {
+        0 => "BINARY".to_string(),
+        1 => "ECHO".to_string(),
+        3 => "SGA".to_string(),
+        5 => "STATUS".to_string(),
+        6 => "TIMING-MARK".to_string(),
+        24 => "TTYPE".to_string(),
+        31 => "NAWS".to_string(),
+        32 => "TSPEED".to_string(),
+        33 => "LFLOW".to_string(),
+        34 => "LINEMODE".to_string(),
+        35 => "XDISPLOC".to_string(),
+        36 => "ENVIRON".to_string(),
+        39 => "NEW-ENVIRON".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Decodes received text, dropping ANSI escape sequences so coloured
+/// BusyBox prompts still classify.
+fn clean_text(raw: &[u8]) -> String {
+    let text = String::from_utf8_lossy(raw);
+    let mut clean = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            clean.push(c);
+            continue;
+        }
+        if chars.next_if_eq(&'[').is_some() {
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        } else {
+            chars.next();
+        }
+    }
+    clean
+}
+
+/// Classifies the last line of output.
+fn classify(text: &str) -> Prompt {
+    let line = text
+        .trim_end_matches([' ', '\t'])
+        .rsplit(['\r', '\n'])
+        .next()
+        .unwrap_or_default();
+    if line.is_empty() || line.len() > 120 {
+        return Prompt::None;
+    }
+    let lower = line.to_ascii_lowercase();
+    let lower = lower.trim_end_matches(' ');
+    if lower.ends_with("password:") || lower.ends_with("passwd:") {
+        Prompt::Password
+    } else if ["login:", "username:", "user name:", "user:"]
+        .iter()
+        .any(|p| lower.ends_with(p))
+    {
+        Prompt::Login
+    } else if lower.ends_with('#') || lower.ends_with('$') || lower.ends_with('>') {
+        Prompt::Shell
+    } else {
+        Prompt::None
+    }
+}
+
+fn lockout_message(text: &str) -> bool {
+    let lower = text.to_ascii_lowercase();
+    ["locked", "too many", "try again later", "blocked"]
+        .iter()
+        .any(|m| lower.contains(m))
+}
+
+fn banner_text(output: &str) -> String {
+    let banner = output
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    banner.chars().take(MAX_BANNER_CHARS).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    /// In-process Telnet server: a BusyBox-style root shell, either straight
+    /// away or behind a login that re-prompts after a wrong password.
+    #[derive(Default)]
+    struct Stub {
+        logins: Vec<(&'static str, &'static str)>,
+        attempts: AtomicUsize,
+    }
+
+    impl Stub {
+        async fn start(self) -> (SocketAddr, Arc<Self>) {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let stub = Arc::new(self);
+            let server = stub.clone();
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(server.clone().serve(stream));
+                }
+            });
+            (addr, stub)
+        }
+
+        async fn serve(self: Arc<Self>, mut stream: TcpStream) {
+            let shell = self.logins.is_empty();
+            let greeting = if shell {
+                b"\r\nBusyBox v1.30.1 built-in shell (ash)\r\n# ".to_vec()
+            } else {
+                [&[IAC, WILL, OPT_ECHO][..], b"drone login: "].concat()
+            };
+            if stream.write_all(&greeting).await.is_err() {
+                return;
+            }
+            let mut logged_in = shell;
+            let mut user: Option<String> = None;
+            while let Some(line) = read_line(&mut stream).await {
+                let reply = if logged_in {
+                    if line.contains("UAVRED") {
+                        "UAVRED42\r\nuid=0(root) gid=0(root)\r\n# ".to_string()
+                    } else {
+                        "# ".to_string()
+                    }
+                } else if let Some(username) = user.take() {
+                    self.attempts.fetch_add(1, Ordering::SeqCst);
+                    if self.logins.contains(&(username.as_str(), line.as_str())) {
+                        logged_in = true;
+                        "\r\n# ".to_string()
+                    } else {
+                        "\r\nLogin incorrect\r\ndrone login: ".to_string()
+                    }
+                } else {
+                    user = Some(line);
+                    "Password: ".to_string()
+                };
+                if stream.write_all(reply.as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Reads one line, dropping the client's option replies.
+    async fn read_line(stream: &mut TcpStream) -> Option<String> {
+        let mut line = Vec::new();
+        let mut skip = 0;
+        loop {
+            let mut byte = [0u8; 1];
+            if stream.read(&mut byte).await.ok()? == 0 {
+                return None;
+            }
+            match byte[0] {
+                _ if skip > 0 => skip -= 1,
+                IAC => skip = 2,
+                b'\n' => break,
+                b'\r' | 0 => {}
+                b => line.push(b),
+            }
+        }
+        Some(String::from_utf8_lossy(&line).into_owned())
+    }
+
+    fn checker(credentials: &[(&str, &str)]) -> TelnetChecker {
+        let mut list = CredentialList::empty();
+        for (username, password) in credentials.iter().rev() {
+            list.push_front(username, password, &["telnet"]);
+        }
+        TelnetChecker::with_config(TelnetConfig {
+            prompt_timeout_ms: 2000,
+            idle_timeout_ms: 200,
+            ..TelnetConfig::default()
+        })
+        .with_credentials(list)
+    }
+
+    fn titles(report: &TelnetReport) -> Vec<String> {
+        report
+            .vulnerabilities()
+            .into_iter()
+            .map(|v| v.title)
+            .collect()
+    }
+
+    #[test]
+    fn finds_unauthenticated_root_shell() {
+        crate::test_runtime().block_on(async {
+            let (addr, stub) = Stub::default().start().await;
+
+            let report = checker(&[("root", "root")]).check(addr).await.unwrap();
+            assert_eq!(report.prompt, Prompt::Shell);
+            let shell = report.unauthenticated.as_ref().unwrap();
+            assert!(shell.root);
+            assert_eq!(shell.identity.as_deref(), Some("uid=0(root) gid=0(root)"));
+            assert_eq!(report.attempts, 0);
+            assert_eq!(stub.attempts.load(Ordering::SeqCst), 0);
+            assert_eq!(titles(&report), ["Unauthenticated Telnet root shell"]);
+        });
+    }
+
+    #[test]
+    fn finds_default_login() {
+        crate::test_runtime().block_on(async {
+            let (addr, _stub) = Stub {
+                logins: vec![("root", "12345")],
+                ..Stub::default()
+            }
+            .start()
+            .await;
+
+            let report = checker(&[("admin", "admin"), ("root", "12345"), ("root", "root")])
+                .check(addr)
+                .await
+                .unwrap();
+            assert_eq!(report.prompt, Prompt::Login);
+            assert!(report.options.contains(&"WILL ECHO".to_string()));
+            assert_eq!(report.attempts, 2);
+            let credential = report.logins[0].credential.as_ref().unwrap();
+            assert_eq!(credential.username, "root");
+            assert!(report.logins[0].root);
+            assert!(!report.logins[0]
+                .transcript
+                .iter()
+                .any(|line| line.contains("12345")));
+            assert_eq!(
+                titles(&report),
+                [
+                    "Default Telnet credentials accepted",
+                    "Telnet login over cleartext"
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn stops_after_max_failed_attempts() {
+        crate::test_runtime().block_on(async {
+            let (addr, stub) = Stub {
+                logins: vec![("root", "unguessable")],
+                ..Stub::default()
+            }
+            .start()
+            .await;
+            let credentials = [("a", "1"), ("b", "2"), ("c", "3"), ("d", "4"), ("e", "5")];
+
+            let report = checker(&credentials).check(addr).await.unwrap();
+            assert_eq!(report.attempts, 3);
+            assert_eq!(stub.attempts.load(Ordering::SeqCst), 3);
+            assert!(report.stopped_early);
+            assert!(!report.locked_out);
+
+            let unlimited = SecurityConfig {
+                max_failed_attempts: 0,
+                ..SecurityConfig::default()
+            };
+            let report = checker(&credentials)
+                .with_security(&unlimited)
+                .check(addr)
+                .await
+                .unwrap();
+            assert_eq!(report.attempts, 5);
+            assert!(!report.stopped_early);
+        });
+    }
+}